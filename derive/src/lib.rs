@@ -0,0 +1,77 @@
+//! `#[derive(VerifiableClaims)]` for `ubl-auth`; use it through the `derive` feature of `ubl-auth`.
+//!
+//! Supported field attributes:
+//! - `#[claim(required)]` — an `Option` field must be `Some`.
+//! - `#[claim(max_age = "1h")]` — a timestamp field must be at most that old (`s`, `m`, `h`, `d` units)
+//!   and not in the future.
+//! - `#[claim(one_of("a", "b"))]` — a string field must be one of the listed values.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Token};
+
+#[proc_macro_derive(VerifiableClaims, attributes(claim))]
+pub fn derive_verifiable_claims(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "VerifiableClaims can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(&input.ident, "VerifiableClaims requires named fields"));
+    };
+
+    let mut checks = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let name = ident.to_string();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("claim")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("required") {
+                    checks.push(quote! { ::ubl_auth::validate::check_required(&self.#ident, #name)?; });
+                } else if meta.path.is_ident("max_age") {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    let secs = parse_duration(&lit.value()).ok_or_else(|| meta.error("max_age must look like \"30s\", \"5m\", \"1h\" or \"7d\""))?;
+                    checks.push(quote! { ::ubl_auth::validate::check_max_age(&self.#ident, #name, #secs, now)?; });
+                } else if meta.path.is_ident("one_of") {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    let values: Vec<String> = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?.iter().map(LitStr::value).collect();
+                    checks.push(quote! { ::ubl_auth::validate::check_one_of(&self.#ident, #name, &[#(#values),*])?; });
+                } else {
+                    return Err(meta.error("expected `required`, `max_age = \"..\"` or `one_of(..)`"));
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    let ty = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ubl_auth::validate::VerifiableClaims for #ty #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn validate_claims(&self, now: i64) -> ::core::result::Result<(), ::ubl_auth::VerifyError> {
+                #(#checks)*
+                Ok(())
+            }
+        }
+    })
+}
+
+fn parse_duration(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let unit = s.chars().last()?;
+    let mult = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        _ => return None,
+    };
+    s[..s.len() - 1].trim().parse::<i64>().ok().map(|n| n * mult)
+}
@@ -0,0 +1,7 @@
+//! Prints the JOSE conformance report as JSON; exits non-zero if any case fails.
+
+fn main() {
+    let report = ubl_auth::conformance::run();
+    println!("{}", report.to_json());
+    if !report.all_passed() { std::process::exit(1); }
+}
@@ -1,7 +1,5 @@
-
-use ubl_auth::{verify_ed25519_jwt_with_cache, VerifyOptions, JwksCache, Jwk, Jwks};
-use ed25519_dalek::{SigningKey, Signer};
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ubl_auth::{sign_ed25519_jwt, verify_ed25519_jwt_with_cache, Claims, VerifyOptions, JwksCache, Jwk, Jwks};
+use ed25519_dalek::SigningKey;
 use serde_json::json;
 use rand::{SeedableRng, rngs::StdRng};
 
@@ -10,18 +8,11 @@ fn main() -> anyhow::Result<()> {
     let sk = SigningKey::generate(&mut rng);
     let vk = sk.verifying_key();
 
-    let x = B64URL.encode(vk.to_bytes());
     let cache = JwksCache::new(3600);
-    cache.put("mem://jwks", Jwks{ keys: vec![ Jwk{ kty:"OKP".into(), crv:Some("Ed25519".into()), x:Some(x), kid:Some("demo".into()) } ]});
+    cache.put("mem://jwks", Jwks{ keys: vec![ Jwk::ed25519("demo", &vk) ]});
 
-    let now = ubl_auth::now_ts();
-    let header = json!({"alg":"EdDSA","kid":"demo","typ":"JWT"});
-    let payload = json!({ "sub":"did:key:zDemo", "iss":"issuer", "aud":"example", "iat":now, "nbf":now, "exp": now+600 });
-    let hdr = B64URL.encode(serde_json::to_string(&header)?);
-    let pld = B64URL.encode(serde_json::to_string(&payload)?);
-    let msg = format!("{}.{}", hdr, pld);
-    let sig = sk.sign(msg.as_bytes());
-    let jwt = format!("{}.{}", msg, B64URL.encode(sig.to_bytes()));
+    let claims: Claims = serde_json::from_value(json!({ "sub":"did:key:zDemo", "iss":"issuer", "aud":"example" }))?;
+    let jwt = sign_ed25519_jwt(&claims, &sk, "demo")?;
 
     let opts = VerifyOptions::default().with_issuer("issuer").with_audience("example");
     let claims = verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts)?;
@@ -0,0 +1,194 @@
+//! Opaque API keys for machine clients that cannot do OAuth (feature `apikey`).
+//!
+//! Keys look like `{prefix}_{48 hex secret}{8 hex checksum}`. Only the SHA-256 of the full key is
+//! stored; lookups go through a [`KeyStore`] and the final comparison is constant-time.
+
+use crate::{fill_random, now_ts, to_hex as hex, Claims, VerifyError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+const SECRET_BYTES: usize = 24;
+const CHECKSUM_HEX: usize = 8;
+
+/// A plaintext API key. Shown to the client once at creation, never stored.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(String);
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ApiKey").field(&self.prefix()).finish()
+    }
+}
+
+impl ApiKey {
+    /// Generates a fresh key for `subject` and the record to persist in a [`KeyStore`].
+    pub fn generate(prefix: &str, subject: &str) -> (ApiKey, ApiKeyRecord) {
+        let mut secret = [0u8; SECRET_BYTES];
+        fill_random(&mut secret);
+        let body = format!("{}_{}", prefix, hex(&secret));
+        let key = ApiKey(format!("{}{}", body, checksum(&body)));
+        let record = ApiKeyRecord {
+            id: hex(&key.hash()[..8]),
+            hash: hex(&key.hash()),
+            subject: subject.to_string(),
+            scope: None,
+            created_at: now_ts(),
+            expires_at: None,
+        };
+        (key, record)
+    }
+
+    /// Parses a presented key, validating its shape and checksum (no store access).
+    pub fn parse(s: &str) -> Option<ApiKey> {
+        let (prefix, rest) = s.rsplit_once('_')?;
+        if prefix.is_empty() || rest.len() != SECRET_BYTES * 2 + CHECKSUM_HEX || !rest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let (body, sum) = s.split_at(s.len() - CHECKSUM_HEX);
+        (checksum(body) == sum).then(|| ApiKey(s.to_string()))
+    }
+
+    pub fn prefix(&self) -> &str { self.0.rsplit_once('_').map(|(p, _)| p).unwrap_or_default() }
+    pub fn as_str(&self) -> &str { &self.0 }
+    pub fn hash(&self) -> [u8; 32] { Sha256::digest(self.0.as_bytes()).into() }
+}
+
+/// What gets persisted for an API key: the hash, never the key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    /// Hex SHA-256 of the full key.
+    pub hash: String,
+    pub subject: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    pub created_at: i64,
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+impl ApiKeyRecord {
+    pub fn with_scope(mut self, scope: &str) -> Self { self.scope = Some(scope.to_string()); self }
+    pub fn with_expiry(mut self, expires_at: i64) -> Self { self.expires_at = Some(expires_at); self }
+
+    /// Claims view of the key so downstream code sees the same shape as for JWTs.
+    pub fn to_claims(&self) -> Claims {
+        Claims {
+            sub: self.subject.clone(),
+            iss: None,
+            aud: None,
+            exp: self.expires_at,
+            nbf: None,
+            iat: Some(self.created_at),
+            jti: Some(self.id.clone()),
+            scope: self.scope.clone(),
+            extra: HashMap::new(),
+        }
+    }
+}
+
+/// Storage for API key records, indexed by the hex key hash.
+pub trait KeyStore: Send + Sync {
+    fn find_by_hash(&self, hash_hex: &str) -> Option<ApiKeyRecord>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryKeyStore { inner: Mutex<HashMap<String, ApiKeyRecord>> }
+
+impl MemoryKeyStore {
+    pub fn new() -> Self { Self::default() }
+    pub fn insert(&self, record: ApiKeyRecord) { self.inner.lock().insert(record.hash.clone(), record); }
+    pub fn revoke(&self, id: &str) { self.inner.lock().retain(|_, r| r.id != id); }
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn find_by_hash(&self, hash_hex: &str) -> Option<ApiKeyRecord> { self.inner.lock().get(hash_hex).cloned() }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ApiKeyError {
+    /// Not `{prefix}_{secret}{checksum}`, or the checksum does not match.
+    #[error("malformed api key")]
+    Malformed,
+    /// Unknown or revoked.
+    #[error("invalid api key")]
+    Invalid,
+    #[error("api key expired")]
+    Expired,
+}
+
+/// Looks up a presented key and checks it is known and unexpired.
+pub fn verify_api_key(key: &str, store: &dyn KeyStore) -> Result<ApiKeyRecord, ApiKeyError> {
+    let key = ApiKey::parse(key).ok_or(ApiKeyError::Malformed)?;
+    let hash = hex(&key.hash());
+    let record = store.find_by_hash(&hash).ok_or(ApiKeyError::Invalid)?;
+    if !bool::from(record.hash.as_bytes().ct_eq(hash.as_bytes())) { return Err(ApiKeyError::Invalid); }
+    if record.expires_at.is_some_and(|exp| now_ts() > exp) { return Err(ApiKeyError::Expired); }
+    Ok(record)
+}
+
+/// The authenticated caller, from either credential type.
+#[derive(Debug, Clone)]
+pub enum Principal {
+    Jwt(Claims),
+    ApiKey(ApiKeyRecord),
+}
+
+impl Principal {
+    pub fn claims(&self) -> Claims {
+        match self {
+            Principal::Jwt(c) => c.clone(),
+            Principal::ApiKey(r) => r.to_claims(),
+        }
+    }
+}
+
+/// Authenticates an `Authorization` header value holding either `Bearer <jwt>`,
+/// `Bearer <api key>` or `ApiKey <api key>`. `verify_jwt` is the caller's configured JWT check.
+pub fn authenticate<F>(authorization: &str, store: &dyn KeyStore, verify_jwt: F) -> Result<Principal, VerifyError>
+where
+    F: FnOnce(&str) -> Result<Claims, VerifyError>,
+{
+    let (scheme, cred) = authorization.trim().split_once(' ').ok_or(VerifyError::BadFormat)?;
+    let cred = cred.trim();
+    if scheme.eq_ignore_ascii_case("apikey") || (scheme.eq_ignore_ascii_case("bearer") && cred.matches('.').count() != 2) {
+        return Ok(Principal::ApiKey(verify_api_key(cred, store)?));
+    }
+    if !scheme.eq_ignore_ascii_case("bearer") { return Err(VerifyError::BadFormat); }
+    verify_jwt(cred).map(Principal::Jwt)
+}
+
+fn checksum(body: &str) -> String { hex(&Sha256::digest(body.as_bytes())[..CHECKSUM_HEX / 2]) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_store_and_authenticate() {
+        let store = MemoryKeyStore::new();
+        let (key, record) = ApiKey::generate("ubl_live", "did:key:zMachine");
+        store.insert(record.with_scope("ledger:write"));
+        assert_eq!(key.prefix(), "ubl_live");
+
+        let p = authenticate(&format!("ApiKey {}", key.as_str()), &store, |_| unreachable!()).expect("api key");
+        assert_eq!(p.claims().sub, "did:key:zMachine");
+        assert_eq!(p.claims().scope.as_deref(), Some("ledger:write"));
+        assert!(authenticate(&format!("Bearer {}", key.as_str()), &store, |_| unreachable!()).is_ok());
+
+        let mut tampered = key.as_str().to_string();
+        tampered.replace_range(12..13, if &tampered[12..13] == "0" { "1" } else { "0" });
+        assert!(ApiKey::parse(&tampered).is_none());
+
+        store.revoke(&p.claims().jti.unwrap());
+        assert!(matches!(verify_api_key(key.as_str(), &store), Err(ApiKeyError::Invalid)));
+        assert!(matches!(authenticate(&format!("ApiKey {}", tampered), &store, |_| unreachable!()), Err(VerifyError::ApiKey(ApiKeyError::Malformed))));
+
+        let jwt = authenticate("Bearer a.b.c", &store, |t| { assert_eq!(t, "a.b.c"); Err(VerifyError::Signature) });
+        assert!(matches!(jwt, Err(VerifyError::Signature)));
+    }
+}
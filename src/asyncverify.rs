@@ -0,0 +1,164 @@
+//! Non-blocking verification for async services (feature `async`).
+//!
+//! The sync entry points fetch keys with `ureq`, which blocks the executor thread.
+//! [`verify_ed25519_jwt_async`] instead fetches cache misses through a [`JwksFetcher`], so the
+//! HTTP client is whatever the service already uses; with feature `reqwest` a `reqwest::Client`
+//! is one. Cache hits never await. All checks are the same as in
+//! [`verify_ed25519_jwt_with_cache`](crate::verify_ed25519_jwt_with_cache).
+//!
+//! Authorization decisions get the same treatment: [`AsyncDecisionHook`] is the non-blocking
+//! [`DecisionHook`](crate::opa::DecisionHook), and with feature `reqwest` an
+//! [`OpaClient`](crate::opa::OpaClient) is one.
+
+use crate::opa::{allowed, decision_input, OpaDecision};
+use crate::{split_and_decode, verified_claims, verify_signature_with, Claims, Jwks, JwksCache, VerifyError, VerifyOptions};
+use serde_json::Value as Json;
+
+#[async_trait::async_trait]
+pub trait JwksFetcher: Send + Sync {
+    async fn fetch(&self, jwks_uri: &str) -> Result<Jwks, VerifyError>;
+}
+
+#[cfg(feature = "reqwest")]
+#[async_trait::async_trait]
+impl JwksFetcher for reqwest::Client {
+    async fn fetch(&self, jwks_uri: &str) -> Result<Jwks, VerifyError> {
+        let resp = self.get(jwks_uri).send().await.and_then(reqwest::Response::error_for_status).map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
+        let body = resp.bytes().await.map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
+        serde_json::from_slice(&body).map_err(|_| VerifyError::JwksJson)
+    }
+}
+
+/// Verifies `token` against the JWKS at `jwks_uri`, fetching it through `fetcher` when `cache`
+/// has no fresh copy, or serving a stale one within the cache's `stale_if_error_secs` if that
+/// fetch fails. An unknown key triggers the cache's rate-limited refetch (see
+/// [`JwksCache::min_refetch_secs`]). Malformed tokens are rejected before any fetch.
+pub async fn verify_ed25519_jwt_async<F: JwksFetcher + ?Sized>(token: &str, jwks_uri: &str, cache: &JwksCache, fetcher: &F, opts: &VerifyOptions) -> Result<Claims, VerifyError> {
+    let jwks = match cache.get_fresh(jwks_uri) {
+        Some(jwks) => jwks,
+        None => {
+            split_and_decode(token, opts)?;
+            match fetcher.fetch(jwks_uri).await {
+                Ok(jwks) => {
+                    cache.put(jwks_uri, jwks.clone());
+                    jwks
+                }
+                Err(e) => cache.get_stale(jwks_uri).ok_or(e)?,
+            }
+        }
+    };
+    let payload = match verify_signature_with(token, opts, || Ok(jwks)) {
+        Err(VerifyError::NoKey) if cache.claim_forced_refetch(jwks_uri) => {
+            let jwks = fetcher.fetch(jwks_uri).await.map_err(|_| VerifyError::NoKey)?;
+            cache.put(jwks_uri, jwks.clone());
+            verify_signature_with(token, opts, || Ok(jwks))?
+        }
+        res => res?,
+    };
+    verified_claims(payload, opts).map(|v| v.claims)
+}
+
+#[async_trait::async_trait]
+pub trait AsyncDecisionHook: Send + Sync {
+    async fn decide(&self, input: &Json) -> Result<OpaDecision, VerifyError>;
+}
+
+#[cfg(feature = "reqwest")]
+#[async_trait::async_trait]
+impl AsyncDecisionHook for crate::opa::OpaClient {
+    async fn decide(&self, input: &Json) -> Result<OpaDecision, VerifyError> {
+        static CLIENT: once_cell::sync::Lazy<reqwest::Client> = once_cell::sync::Lazy::new(reqwest::Client::new);
+        let engine = |e: &dyn std::fmt::Display| VerifyError::PolicyEngine(e.to_string());
+        let body = serde_json::to_vec(&serde_json::json!({ "input": input })).map_err(|e| engine(&e))?;
+        let resp = CLIENT.post(&self.url).timeout(self.timeout).header("content-type", "application/json").body(body).send().await.and_then(reqwest::Response::error_for_status).map_err(|e| engine(&e))?;
+        let body: Json = serde_json::from_slice(&resp.bytes().await.map_err(|e| engine(&e))?).map_err(|e| engine(&e))?;
+        Ok(OpaDecision::from_result(body.get("result")))
+    }
+}
+
+/// Async [`authorize`](crate::opa::authorize): asks `hook` about already-verified `claims`.
+pub async fn authorize_async<H: AsyncDecisionHook + ?Sized>(claims: &Claims, context: &Json, hook: &H) -> Result<OpaDecision, VerifyError> {
+    allowed(hook.decide(&decision_input(claims, context)).await?)
+}
+
+/// Verifies `token` as [`verify_ed25519_jwt_async`] does, then requires an allow decision from `hook`.
+pub async fn verify_and_authorize_async<F: JwksFetcher + ?Sized, H: AsyncDecisionHook + ?Sized>(
+    token: &str,
+    jwks_uri: &str,
+    cache: &JwksCache,
+    fetcher: &F,
+    opts: &VerifyOptions,
+    context: &Json,
+    hook: &H,
+) -> Result<Claims, VerifyError> {
+    let claims = verify_ed25519_jwt_async(token, jwks_uri, cache, fetcher, opts).await?;
+    authorize_async(&claims, context, hook).await?;
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sign_ed25519_jwt, Jwk};
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Fixed { jwks: Jwks, calls: AtomicUsize }
+
+    #[async_trait::async_trait]
+    impl JwksFetcher for Fixed {
+        async fn fetch(&self, _: &str) -> Result<Jwks, VerifyError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self.jwks.clone())
+        }
+    }
+
+    #[test]
+    fn fetches_once_then_verifies_from_cache() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(48));
+        let fetcher = Fixed { jwks: Jwks { keys: vec![Jwk::ed25519("k1", &key.verifying_key())] }, calls: AtomicUsize::new(0) };
+        let token = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub":"did:key:zAsync","aud":"api"})).unwrap(), &key, "k1").unwrap();
+        let cache = JwksCache::new(60);
+        let opts = VerifyOptions::default().with_audience("api");
+
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            assert!(matches!(verify_ed25519_jwt_async("not-a-token", "https://idp/jwks", &cache, &fetcher, &opts).await, Err(VerifyError::BadFormat)));
+            assert_eq!(fetcher.calls.load(Ordering::SeqCst), 0);
+            for _ in 0..3 {
+                assert_eq!(verify_ed25519_jwt_async(&token, "https://idp/jwks", &cache, &fetcher, &opts).await.unwrap().sub, "did:key:zAsync");
+            }
+            let wrong = VerifyOptions::default().with_audience("other");
+            assert!(matches!(verify_ed25519_jwt_async(&token, "https://idp/jwks", &cache, &fetcher, &wrong).await, Err(VerifyError::Audience)));
+        });
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 1);
+    }
+
+    struct ReadOnly;
+
+    #[async_trait::async_trait]
+    impl AsyncDecisionHook for ReadOnly {
+        async fn decide(&self, input: &Json) -> Result<OpaDecision, VerifyError> {
+            Ok(OpaDecision { allow: input["request"]["method"] == "GET", reasons: vec!["read-only".into()] })
+        }
+    }
+
+    #[test]
+    fn async_decisions_are_folded_into_result() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(82));
+        let fetcher = Fixed { jwks: Jwks { keys: vec![Jwk::ed25519("k1", &key.verifying_key())] }, calls: AtomicUsize::new(0) };
+        let token = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub":"did:key:zAsync"})).unwrap(), &key, "k1").unwrap();
+        let cache = JwksCache::new(60);
+        let opts = VerifyOptions::default();
+
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let get = serde_json::json!({"method":"GET"});
+            assert_eq!(verify_and_authorize_async(&token, "https://idp/jwks", &cache, &fetcher, &opts, &get, &ReadOnly).await.unwrap().sub, "did:key:zAsync");
+            let post = serde_json::json!({"method":"POST"});
+            let denied = verify_and_authorize_async(&token, "https://idp/jwks", &cache, &fetcher, &opts, &post, &ReadOnly).await;
+            assert!(matches!(denied, Err(VerifyError::Policy(why)) if why == "read-only"));
+        });
+    }
+}
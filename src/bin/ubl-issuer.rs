@@ -0,0 +1,128 @@
+//! Reference token issuer for internal environments (feature `issuer-bin`).
+//!
+//! Configuration (environment):
+//! - `UBL_ISSUER` — `iss` of minted tokens (required)
+//! - `UBL_ISSUER_KEYS` — `kid=source[,kid=source...]`; each source (a path, `docker:name`,
+//!   `systemd:name`, `vault:mount/path#field`, ... see `ubl_auth::secrets`) holds a 32-byte
+//!   Ed25519 seed. The first key signs, all are published. (required)
+//! - `UBL_ISSUER_BOOTSTRAP_SECRET` — bearer secret required on `POST /token` and
+//!   `POST /introspect` (RFC 7662, form-encoded `token=...`) (required)
+//! - `UBL_ISSUER_POLICY` — path to a `Policy` JSON document limiting minted claims
+//! - `UBL_ISSUER_MAX_TTL` — lifetime cap in seconds (default 3600)
+//! - `UBL_ISSUER_ADDR` — listen address (default `127.0.0.1:8080`)
+//!
+//! Client certificates are not handled here; run it behind an mTLS-terminating proxy if needed.
+
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tiny_http::{Header, Method, Request, Response, Server};
+use ubl_auth::introspection::{IntrospectionRequest, Introspector};
+use ubl_auth::issuer::{TokenIssuer, TokenRequest};
+use ubl_auth::policy::Policy;
+use ubl_auth::secrets::{signing_key, SecretSource};
+use ubl_auth::{SignError, VerifyOptions};
+
+fn env(name: &str) -> anyhow::Result<String> {
+    std::env::var(name).map_err(|_| anyhow::anyhow!("{} is not set", name))
+}
+
+fn load_issuer() -> anyhow::Result<TokenIssuer> {
+    let mut keys = env("UBL_ISSUER_KEYS")?
+        .split(',')
+        .map(|entry| {
+            let (kid, spec) = entry.trim().split_once('=').ok_or_else(|| anyhow::anyhow!("UBL_ISSUER_KEYS entries must be kid=source"))?;
+            Ok((kid.to_string(), signing_key(&SecretSource::parse(spec)?)?))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter();
+    let (kid, key) = keys.next().ok_or_else(|| anyhow::anyhow!("UBL_ISSUER_KEYS is empty"))?;
+    let mut issuer = TokenIssuer::new(&env("UBL_ISSUER")?, &kid, key);
+    for (kid, key) in keys { issuer = issuer.with_published_key(&kid, key); }
+    if let Ok(path) = std::env::var("UBL_ISSUER_POLICY") {
+        issuer = issuer.with_policy(Policy::from_json(&std::fs::read_to_string(path)?)?);
+    }
+    if let Ok(ttl) = std::env::var("UBL_ISSUER_MAX_TTL") {
+        issuer = issuer.with_max_ttl(ttl.parse()?);
+    }
+    Ok(issuer)
+}
+
+fn respond(req: Request, status: u16, body: serde_json::Value) {
+    let header = Header::from_bytes("content-type", "application/json").expect("static header");
+    let _ = req.respond(Response::from_string(body.to_string()).with_status_code(status).with_header(header));
+}
+
+fn authorized(req: &Request, secret: &str) -> bool {
+    req.headers()
+        .iter()
+        .find(|h| h.field.equiv("authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|presented| bool::from(presented.as_bytes().ct_eq(secret.as_bytes())))
+}
+
+fn token(issuer: &TokenIssuer, mut req: Request) -> (Request, u16, serde_json::Value) {
+    let mut body = String::new();
+    if req.as_reader().read_to_string(&mut body).is_err() { return (req, 400, json!({"error":"invalid_request"})); }
+    let parsed: TokenRequest = match serde_json::from_str(&body) {
+        Ok(r) => r,
+        Err(e) => return (req, 400, json!({"error":"invalid_request","error_description": e.to_string()})),
+    };
+    let minted = issuer.claims_for(&parsed).and_then(|c| Ok((issuer.issue(&c)?, c)));
+    match minted {
+        Ok((token, claims)) => {
+            let expires_in = claims.exp.unwrap_or_default() - claims.iat.unwrap_or_default();
+            (req, 200, json!({"access_token": token, "token_type": "Bearer", "expires_in": expires_in}))
+        }
+        Err(SignError::Policy(why)) => (req, 403, json!({"error":"access_denied","error_description": why})),
+        Err(e) => (req, 500, json!({"error":"server_error","error_description": e.to_string()})),
+    }
+}
+
+fn introspect(introspector: &Introspector, mut req: Request) -> (Request, u16, serde_json::Value) {
+    let mut body = String::new();
+    if req.as_reader().read_to_string(&mut body).is_err() { return (req, 400, json!({"error":"invalid_request"})); }
+    match IntrospectionRequest::from_form(&body) {
+        Some(parsed) => {
+            let response = serde_json::to_value(introspector.introspect(&parsed.token)).unwrap_or_default();
+            (req, 200, response)
+        }
+        None => (req, 400, json!({"error":"invalid_request"})),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let issuer = load_issuer()?;
+    let introspector = Introspector::new("self://jwks", VerifyOptions::default().with_issuer(&issuer.issuer)).with_jwks(issuer.jwks());
+    let secret = env("UBL_ISSUER_BOOTSTRAP_SECRET")?;
+    let addr = std::env::var("UBL_ISSUER_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".into());
+    let server = Server::http(&addr).map_err(|e| anyhow::anyhow!("bind {}: {}", addr, e))?;
+    eprintln!("ubl-issuer: {} listening on {}", issuer.issuer, addr);
+
+    for req in server.incoming_requests() {
+        let route = (req.method().clone(), req.url().split('?').next().unwrap_or_default().to_string());
+        match route {
+            (Method::Get, path) if path == "/.well-known/jwks.json" => {
+                let jwks = serde_json::to_value(issuer.jwks()).unwrap_or_default();
+                respond(req, 200, jwks);
+            }
+            (Method::Post, path) if path == "/token" => {
+                if !authorized(&req, &secret) {
+                    respond(req, 401, json!({"error":"invalid_client"}));
+                    continue;
+                }
+                let (req, status, body) = token(&issuer, req);
+                respond(req, status, body);
+            }
+            (Method::Post, path) if path == "/introspect" => {
+                if !authorized(&req, &secret) {
+                    respond(req, 401, json!({"error":"invalid_client"}));
+                    continue;
+                }
+                let (req, status, body) = introspect(&introspector, req);
+                respond(req, status, body);
+            }
+            _ => respond(req, 404, json!({"error":"not_found"})),
+        }
+    }
+    Ok(())
+}
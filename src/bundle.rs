@@ -0,0 +1,118 @@
+//! Offline trust bundles for air-gapped deployments.
+//!
+//! A connected host exports the JWKS of every configured issuer into a [`TrustBundle`], signs it
+//! with an ops key, and ships the JSON. The air-gapped side verifies the signature and the bundle's
+//! own `exp` before installing the keys into a [`JwksCache`]. Installed keys are pinned to the
+//! bundle's `exp`: they outlive the cache TTL without any refetch, and stop verifying at `exp`.
+
+use crate::receipts::{sign_detached, verify_detached_with, DetachedSignature};
+use crate::{load_jwks, now_ts, Jwks, JwksCache, SignError, VerifyError};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleIssuer {
+    pub issuer: String,
+    pub jwks_uri: String,
+    pub jwks: Jwks,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustBundle {
+    pub issuers: Vec<BundleIssuer>,
+    pub iat: i64,
+    /// After this the bundle is rejected on import.
+    pub exp: i64,
+}
+
+/// A bundle with its detached ops-key signature, as shipped between hosts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTrustBundle {
+    pub bundle: TrustBundle,
+    pub signature: DetachedSignature,
+}
+
+impl TrustBundle {
+    /// Snapshots the current keys of each `(issuer, jwks_uri)` source, valid for `ttl_secs`.
+    pub fn export(sources: &[(&str, &str)], cache: &JwksCache, ttl_secs: i64) -> Result<Self, VerifyError> {
+        let issuers = sources
+            .iter()
+            .map(|(issuer, uri)| Ok(BundleIssuer { issuer: issuer.to_string(), jwks_uri: uri.to_string(), jwks: load_jwks(uri, cache)? }))
+            .collect::<Result<_, VerifyError>>()?;
+        let iat = now_ts();
+        Ok(TrustBundle { issuers, iat, exp: iat + ttl_secs })
+    }
+
+    pub fn sign(self, ops_key: &SigningKey, kid: &str) -> Result<SignedTrustBundle, SignError> {
+        let signature = sign_detached(&self, ops_key, kid)?;
+        Ok(SignedTrustBundle { bundle: self, signature })
+    }
+
+    /// Pins every issuer's keys in `cache` under its `jwks_uri` until the bundle's `exp`.
+    pub fn install(&self, cache: &JwksCache) {
+        for i in &self.issuers { cache.put_pinned(&i.jwks_uri, i.jwks.clone(), self.exp); }
+    }
+}
+
+impl SignedTrustBundle {
+    /// Checks the ops signature and the bundle expiry; `now` defaults to the current time.
+    pub fn verify(&self, ops_key: &VerifyingKey, now: Option<i64>) -> Result<&TrustBundle, VerifyError> {
+        verify_detached_with(&self.bundle, &self.signature, |_| Ok(*ops_key))?;
+        if now.unwrap_or_else(now_ts) >= self.bundle.exp { return Err(VerifyError::Expired); }
+        Ok(&self.bundle)
+    }
+
+    /// Verifies the bundle and installs its keys into `cache`.
+    pub fn import(&self, ops_key: &VerifyingKey, cache: &JwksCache) -> Result<(), VerifyError> {
+        self.verify(ops_key, None)?.install(cache);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verify_ed25519_jwt_with_cache, Jwk, VerifyOptions};
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn export_sign_and_import() {
+        let idp = SigningKey::generate(&mut StdRng::seed_from_u64(23));
+        let ops = SigningKey::generate(&mut StdRng::seed_from_u64(24));
+        let online = JwksCache::new(3600);
+        online.put("https://idp/jwks", Jwks { keys: vec![Jwk::ed25519("idp-1", &idp.verifying_key())] });
+
+        let signed = TrustBundle::export(&[("https://idp", "https://idp/jwks")], &online, 86_400).unwrap().sign(&ops, "ops-1").unwrap();
+        let shipped: SignedTrustBundle = serde_json::from_str(&serde_json::to_string(&signed).unwrap()).unwrap();
+
+        let offline = JwksCache::new(3600);
+        shipped.import(&ops.verifying_key(), &offline).unwrap();
+        let token = crate::sign_compact(&json!({"sub":"did:key:zAir"}), &idp, "idp-1").unwrap();
+        assert!(verify_ed25519_jwt_with_cache(&token, "https://idp/jwks", &offline, &VerifyOptions::default()).is_ok());
+
+        assert!(matches!(shipped.verify(&ops.verifying_key(), Some(shipped.bundle.exp)), Err(VerifyError::Expired)));
+        assert!(matches!(shipped.verify(&idp.verifying_key(), None), Err(VerifyError::Signature)));
+    }
+
+    #[test]
+    fn installed_keys_live_exactly_until_bundle_exp() {
+        let idp = SigningKey::generate(&mut StdRng::seed_from_u64(79));
+        let unreachable = "http://127.0.0.1:9/idp/jwks";
+        let issuers = vec![BundleIssuer { issuer: "https://idp".into(), jwks_uri: unreachable.into(), jwks: Jwks { keys: vec![Jwk::ed25519("idp-1", &idp.verifying_key())] } }];
+        let token = crate::sign_compact(&json!({"sub":"did:key:zAir"}), &idp, "idp-1").unwrap();
+        let verify = |cache: &JwksCache| verify_ed25519_jwt_with_cache(&token, unreachable, cache, &VerifyOptions::default());
+
+        // TTL already elapsed: the pinned keys still verify and nothing is fetched.
+        let past_ttl = JwksCache::new(-1).with_refetch_on_unknown_kid(0);
+        TrustBundle { issuers: issuers.clone(), iat: now_ts(), exp: now_ts() + 3_600 }.install(&past_ttl);
+        assert!(verify(&past_ttl).is_ok());
+        assert!(!past_ttl.claim_forced_refetch(unreachable));
+
+        // Bundle expired: a long cache TTL does not keep the keys alive.
+        let long_ttl = JwksCache::new(86_400);
+        long_ttl.put(unreachable, issuers[0].jwks.clone());
+        TrustBundle { issuers, iat: now_ts() - 7_200, exp: now_ts() - 1 }.install(&long_ttl);
+        assert!(matches!(verify(&long_ttl), Err(VerifyError::JwksHttp(_))));
+    }
+}
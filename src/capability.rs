@@ -0,0 +1,121 @@
+//! Capability attenuation: derive shorter-lived, narrower tokens from a verified parent.
+//!
+//! The child keeps the parent's `sub`/`iss`, gets a fresh `jti`, and references the parent via
+//! the `prt` claim (the parent `jti`, or `sha256:<hex>` of its canonical claims when it has none).
+//! [`check_attenuation`] enforces that a child never widens its parent.
+
+use crate::{now_ts, random_id, sign_compact, to_hex, Aud, Claims, SignError, VerifyError};
+use ed25519_dalek::SigningKey;
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+
+/// Claim holding the parent reference.
+pub const PARENT_CLAIM: &str = "prt";
+
+/// How a child token is narrowed relative to its parent. `None` keeps the parent's value.
+#[derive(Debug, Clone, Default)]
+pub struct Restrictions {
+    /// Scopes to keep; each must be present in the parent.
+    pub scopes: Option<Vec<String>>,
+    /// Audience to restrict to; must be one of the parent's audiences.
+    pub audience: Option<String>,
+    /// Child lifetime; capped at the parent's `exp`.
+    pub ttl_secs: Option<i64>,
+}
+
+impl Restrictions {
+    pub fn with_scopes(mut self, scopes: &[&str]) -> Self { self.scopes = Some(scopes.iter().map(|s| s.to_string()).collect()); self }
+    pub fn with_audience(mut self, aud: &str) -> Self { self.audience = Some(aud.to_string()); self }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.ttl_secs = Some(secs); self }
+}
+
+/// Reference to `parent` as stored in the child's `prt` claim.
+pub fn parent_ref(parent: &Claims) -> Result<String, SignError> {
+    if let Some(jti) = &parent.jti { return Ok(jti.clone()); }
+    let bytes = json_atomic::canonize(parent).map_err(|e| SignError::Canonical(e.to_string()))?;
+    Ok(format!("sha256:{}", to_hex(&Sha256::digest(AsRef::<[u8]>::as_ref(&bytes)))))
+}
+
+/// Builds the narrowed child claims without signing them.
+pub fn attenuate_claims(parent: &Claims, r: &Restrictions) -> Result<Claims, SignError> {
+    let now = now_ts();
+    let mut child = parent.clone();
+    child.jti = Some(random_id());
+    child.iat = Some(now);
+    child.nbf = None;
+    child.extra.insert(PARENT_CLAIM.into(), Json::String(parent_ref(parent)?));
+
+    if let Some(scopes) = &r.scopes {
+        let held: Vec<&str> = parent.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+        if let Some(s) = scopes.iter().find(|s| !held.contains(&s.as_str())) {
+            return Err(SignError::Attenuation(format!("scope '{}' not held by parent", s)));
+        }
+        child.scope = Some(scopes.join(" "));
+    }
+    if let Some(aud) = &r.audience {
+        if parent.aud.as_ref().is_some_and(|a| !a.contains(aud)) {
+            return Err(SignError::Attenuation(format!("audience '{}' not granted to parent", aud)));
+        }
+        child.aud = Some(Aud::One(aud.clone()));
+    }
+    if let Some(ttl) = r.ttl_secs {
+        let exp = now + ttl;
+        child.exp = Some(parent.exp.map_or(exp, |p| p.min(exp)));
+    }
+    Ok(child)
+}
+
+/// Narrows `parent` and signs the result as a new token.
+pub fn attenuate(parent: &Claims, restrictions: &Restrictions, key: &SigningKey, kid: &str) -> Result<String, SignError> {
+    sign_compact(&attenuate_claims(parent, restrictions)?, key, kid)
+}
+
+/// Checks that `child` (already verified) is a monotonic narrowing of `parent`.
+pub fn check_attenuation(child: &Claims, parent: &Claims) -> Result<(), VerifyError> {
+    let fail = |why: &str| Err(VerifyError::Attenuation(why.to_string()));
+    let expected = parent_ref(parent).map_err(|_| VerifyError::Json)?;
+    if child.extra.get(PARENT_CLAIM).and_then(Json::as_str) != Some(expected.as_str()) { return fail("parent reference mismatch"); }
+    if child.sub != parent.sub || child.iss != parent.iss { return fail("subject or issuer changed"); }
+    if let Some(pexp) = parent.exp {
+        if child.exp.is_none_or(|cexp| cexp > pexp) { return fail("outlives parent"); }
+    }
+    let held: Vec<&str> = parent.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+    if child.scope.as_deref().unwrap_or_default().split_whitespace().any(|s| !held.contains(&s)) { return fail("scope widened"); }
+    if let Some(paud) = &parent.aud {
+        match &child.aud {
+            Some(caud) if caud.iter().all(|a| paud.contains(a)) => {}
+            _ => return fail("audience widened"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verify_ed25519_jwt_with_cache, Jwk, Jwks, JwksCache, VerifyOptions};
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn attenuate_and_enforce_narrowing() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(12));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("svc", &sk.verifying_key())] });
+
+        let parent: Claims = serde_json::from_value(json!({
+            "sub":"did:key:zUser","jti":"p-1","scope":"read write admin","aud":["orders","billing"],"exp": now_ts() + 3600
+        })).unwrap();
+        let r = Restrictions::default().with_scopes(&["read"]).with_audience("orders").with_ttl(60);
+        let token = attenuate(&parent, &r, &sk, "svc").unwrap();
+        let child = verify_ed25519_jwt_with_cache(&token, "mem://jwks", &cache, &VerifyOptions::default()).unwrap();
+        assert_eq!(child.scope.as_deref(), Some("read"));
+        assert!(child.exp.unwrap() <= now_ts() + 60);
+        assert!(check_attenuation(&child, &parent).is_ok());
+
+        assert!(matches!(attenuate(&parent, &Restrictions::default().with_scopes(&["root"]), &sk, "svc"), Err(SignError::Attenuation(_))));
+        let mut widened = child.clone();
+        widened.scope = Some("read delete".into());
+        assert!(matches!(check_attenuation(&widened, &parent), Err(VerifyError::Attenuation(_))));
+    }
+}
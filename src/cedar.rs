@@ -0,0 +1,128 @@
+//! Route-level authorization with Cedar policies (`cedar` feature).
+//!
+//! Verified [`Claims`] become the principal entity `User::"<sub>"`, whose attributes are the
+//! claims (strings, booleans, integers, arrays as sets, objects as records) plus `scopes`, the
+//! `scope` claim split into a set. Requests are `Action::"<action>"` on `Route::"<resource>"`.
+//!
+//! ```cedar
+//! permit(principal, action == Action::"GET", resource == Route::"/orders")
+//! when { principal.scopes.contains("orders:read") };
+//! ```
+
+use crate::{Claims, VerifyError};
+use cedar_policy::{Authorizer, Context, Decision, Entities, EntityId, EntityTypeName, EntityUid, PolicySet, Request};
+use serde_json::{json, Value as Json};
+use std::str::FromStr;
+
+/// Outcome of a Cedar evaluation, with the policies that decided it and any evaluation errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CedarDecision {
+    pub allowed: bool,
+    pub policies: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct CedarAuthorizer {
+    policies: PolicySet,
+    principal_type: EntityTypeName,
+    action_type: EntityTypeName,
+    resource_type: EntityTypeName,
+}
+
+impl CedarAuthorizer {
+    /// Parses a Cedar policy set.
+    pub fn new(policies: &str) -> Result<Self, VerifyError> {
+        let policies = PolicySet::from_str(policies).map_err(|e| VerifyError::PolicyEngine(e.to_string()))?;
+        Ok(Self { policies, principal_type: type_name("User")?, action_type: type_name("Action")?, resource_type: type_name("Route")? })
+    }
+
+    /// Overrides the `User` / `Action` / `Route` entity type names.
+    pub fn with_entity_types(mut self, principal: &str, action: &str, resource: &str) -> Result<Self, VerifyError> {
+        self.principal_type = type_name(principal)?;
+        self.action_type = type_name(action)?;
+        self.resource_type = type_name(resource)?;
+        Ok(self)
+    }
+
+    /// Evaluates `action` on `resource` for the holder of `claims`; `context` must be a JSON object (or null).
+    pub fn authorize(&self, claims: &Claims, action: &str, resource: &str, context: Json) -> Result<CedarDecision, VerifyError> {
+        let engine = |e: &dyn std::fmt::Display| VerifyError::PolicyEngine(e.to_string());
+        let principal = uid(&self.principal_type, &claims.sub)?;
+        let entities = Entities::from_json_value(json!([{
+            "uid": {"type": self.principal_type.to_string(), "id": claims.sub},
+            "attrs": principal_attrs(claims),
+            "parents": [],
+        }]), None).map_err(|e| engine(&e))?;
+        let context = if context.is_null() { Context::empty() } else { Context::from_json_value(context, None).map_err(|e| engine(&e))? };
+        let request = Request::new(Some(principal), Some(uid(&self.action_type, action)?), Some(uid(&self.resource_type, resource)?), context);
+
+        let response = Authorizer::new().is_authorized(&request, &self.policies, &entities);
+        let mut policies: Vec<String> = response.diagnostics().reason().map(|p| p.to_string()).collect();
+        let mut errors: Vec<String> = response.diagnostics().errors().map(|e| e.to_string()).collect();
+        policies.sort();
+        errors.sort();
+        Ok(CedarDecision { allowed: response.decision() == Decision::Allow, policies, errors })
+    }
+
+    /// Like [`authorize`](Self::authorize) but maps a deny to [`VerifyError::Policy`].
+    pub fn check(&self, claims: &Claims, action: &str, resource: &str, context: Json) -> Result<(), VerifyError> {
+        let decision = self.authorize(claims, action, resource, context)?;
+        if decision.allowed { return Ok(()); }
+        Err(VerifyError::Policy(format!("cedar denied {} on {}", action, resource)))
+    }
+}
+
+fn type_name(name: &str) -> Result<EntityTypeName, VerifyError> {
+    EntityTypeName::from_str(name).map_err(|e| VerifyError::PolicyEngine(e.to_string()))
+}
+
+fn uid(ty: &EntityTypeName, id: &str) -> Result<EntityUid, VerifyError> {
+    let id = EntityId::from_str(id).map_err(|e| VerifyError::PolicyEngine(e.to_string()))?;
+    Ok(EntityUid::from_type_name_and_id(ty.clone(), id))
+}
+
+fn principal_attrs(claims: &Claims) -> Json {
+    let mut attrs: serde_json::Map<String, Json> = claims.to_map().into_iter().filter_map(|(k, v)| Some((k, to_cedar(v)?))).collect();
+    let scopes: Vec<Json> = claims.scope.as_deref().unwrap_or_default().split_whitespace().map(Json::from).collect();
+    attrs.insert("scopes".into(), Json::Array(scopes));
+    Json::Object(attrs)
+}
+
+/// Keeps only values Cedar can represent: no nulls or non-integer numbers.
+fn to_cedar(v: Json) -> Option<Json> {
+    match v {
+        Json::Null => None,
+        Json::Number(n) => n.as_i64().map(Json::from),
+        Json::Array(a) => Some(Json::Array(a.into_iter().filter_map(to_cedar).collect())),
+        Json::Object(o) => Some(Json::Object(o.into_iter().filter_map(|(k, v)| Some((k, to_cedar(v)?))).collect())),
+        other => Some(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_policy_allows_and_denies() {
+        let authz = CedarAuthorizer::new(r#"
+            permit(principal, action == Action::"GET", resource == Route::"/orders")
+            when { principal.scopes.contains("orders:read") };
+            forbid(principal, action, resource) when { principal has tid && principal.tid == "suspended" };
+        "#).unwrap();
+        let claims = |v: Json| -> Claims { serde_json::from_value(v).unwrap() };
+
+        let reader = claims(json!({"sub":"did:key:zR","scope":"orders:read","tid":"acme","score":0.5}));
+        let d = authz.authorize(&reader, "GET", "/orders", Json::Null).unwrap();
+        assert!(d.allowed);
+        assert_eq!(d.policies, ["policy0"]);
+        assert!(matches!(authz.check(&reader, "DELETE", "/orders", Json::Null), Err(VerifyError::Policy(_))));
+
+        let suspended = claims(json!({"sub":"did:key:zS","scope":"orders:read","tid":"suspended"}));
+        let d = authz.authorize(&suspended, "GET", "/orders", Json::Null).unwrap();
+        assert!(!d.allowed);
+        assert_eq!(d.policies, ["policy1"]);
+        assert!(matches!(CedarAuthorizer::new("permit(principal"), Err(VerifyError::PolicyEngine(_))));
+    }
+}
@@ -0,0 +1,172 @@
+//! JOSE conformance runner (feature `conformance`).
+//!
+//! [`run`] checks the RFC 8037 Ed25519 vectors (appendix A: signature and JWK thumbprint) and then
+//! feeds a curated corpus of malformed and hostile tokens through the regular verifier, recording
+//! for every case the expected and the actual outcome. The [`Report`] serializes to JSON, for
+//! attaching to certification evidence; `cargo run --example conformance --features conformance`
+//! prints it.
+
+use crate::jsonlimits::JsonLimits;
+use crate::{encode_canonical, verify_ed25519_jwt_with_cache, Jwk, Jwks, JwksCache, VerifyError, VerifyOptions};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+
+// RFC 8037, appendix A.1 (private key) and A.4 (signature over a non-JWT payload).
+const RFC8037_D: &str = "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A";
+const RFC8037_THUMBPRINT: &str = "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k";
+const RFC8037_INPUT: &str = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc";
+const RFC8037_SIG: &str = "hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+
+const KID: &str = "rfc8037";
+const JWKS_URI: &str = "conformance://jwks";
+const FAR_FUTURE: i64 = 4_102_444_800;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub id: String,
+    pub description: String,
+    /// `ok` or the expected [`VerifyError`] variant name.
+    pub expected: String,
+    pub actual: String,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub passed: usize,
+    pub failed: usize,
+    pub cases: Vec<CaseResult>,
+}
+
+impl Report {
+    pub fn all_passed(&self) -> bool { self.failed == 0 }
+    pub fn to_json(&self) -> String { serde_json::to_string_pretty(self).expect("report serializes") }
+}
+
+/// Runs every vector and corpus case.
+pub fn run() -> Report {
+    let key = SigningKey::from_bytes(&B64URL.decode(RFC8037_D).expect("vector").try_into().expect("32-byte vector"));
+    let cache = JwksCache::new(i64::MAX);
+    cache.put(JWKS_URI, Jwks { keys: vec![Jwk::ed25519(KID, &key.verifying_key())] });
+    let mut cases = Vec::new();
+    let mut record = |id: &str, description: &str, expected: &str, actual: String| {
+        cases.push(CaseResult { id: id.into(), description: description.into(), passed: expected == actual, expected: expected.into(), actual });
+    };
+
+    let sig = key.sign(RFC8037_INPUT.as_bytes());
+    record("rfc8037-a4-sign", "RFC 8037 A.4: deterministic Ed25519 signature", RFC8037_SIG, B64URL.encode(sig.to_bytes()));
+    let verified = key.verifying_key().verify_strict(RFC8037_INPUT.as_bytes(), &sig).map_err(|_| VerifyError::Signature);
+    record("rfc8037-a5-verify", "RFC 8037 A.5: signature verifies", "ok", outcome(&verified));
+    let thumbprint = Jwk::ed25519(KID, &key.verifying_key()).thumbprint().unwrap_or_default();
+    record("rfc8037-a3-thumbprint", "RFC 8037 A.3: JWK thumbprint", RFC8037_THUMBPRINT, thumbprint);
+
+    let header = json!({"alg":"EdDSA","kid":KID});
+    let claims = json!({"sub":"did:key:zConformance","iss":"https://issuer.test","aud":"api","exp":FAR_FUTURE});
+    let valid = sign(&key, &header, &claims);
+    let strict = VerifyOptions::default();
+    let corpus: Vec<(&str, &str, String, VerifyOptions, &str)> = vec![
+        ("valid", "well-formed token", valid.clone(), strict.clone(), "ok"),
+        ("alg-none", "alg none", sign(&key, &json!({"alg":"none","kid":KID}), &claims), strict.clone(), "Alg"),
+        ("alg-none-unsigned", "alg none with an empty signature", format!("{}.", unsigned(&json!({"alg":"none"}), &claims)), strict.clone(), "Signature"),
+        ("alg-hs256", "HMAC alg (key confusion)", sign(&key, &json!({"alg":"HS256","kid":KID}), &claims), strict.clone(), "Alg"),
+        ("alg-case", "alg with the wrong case", sign(&key, &json!({"alg":"eddsa","kid":KID}), &claims), strict.clone(), "Alg"),
+        ("alg-missing", "no alg", sign(&key, &json!({"kid":KID}), &claims), strict.clone(), "Alg"),
+        ("two-segments", "missing signature segment", unsigned(&header, &claims), strict.clone(), "BadFormat"),
+        ("four-segments", "extra segment", format!("{}.e30", valid), strict.clone(), "BadFormat"),
+        ("padded-base64", "padded base64url segment", pad_signature(&valid), strict.clone(), "Base64Padding"),
+        ("header-not-json", "header is not JSON", raw(&key, b"not json", claims.to_string().as_bytes()), strict.clone(), "Json"),
+        ("payload-not-object", "payload is a JSON array", raw(&key, header.to_string().as_bytes(), b"[]"), strict.clone(), "Json"),
+        ("payload-missing-sub", "no sub claim", sign(&key, &header, &json!({"exp":FAR_FUTURE})), strict.clone(), "Json"),
+        ("payload-empty-sub", "empty sub claim", sign(&key, &header, &json!({"sub":"","exp":FAR_FUTURE})), strict.clone(), "MissingSub"),
+        ("tampered-payload", "payload changed after signing", tamper_payload(&valid), strict.clone(), "Signature"),
+        ("truncated-signature", "63-byte signature", truncate_signature(&valid), strict.clone(), "Signature"),
+        ("malleable-signature", "signature with S + L (non-canonical scalar)", malleate(&valid), strict.clone(), "Signature"),
+        ("missing-kid", "no kid in the header", sign(&key, &json!({"alg":"EdDSA"}), &claims), strict.clone(), "Kid"),
+        ("unknown-kid", "kid not in the JWKS", sign(&key, &json!({"alg":"EdDSA","kid":"other"}), &claims), strict.clone(), "NoKey"),
+        ("wrong-key", "signed by another key under a known kid", sign(&SigningKey::from_bytes(&[7u8; 32]), &header, &claims), strict.clone(), "Signature"),
+        ("expired", "exp in the past", sign(&key, &header, &json!({"sub":"s","exp":1_000_000})), strict.clone(), "Expired"),
+        ("not-yet-valid", "nbf in the future", sign(&key, &header, &json!({"sub":"s","nbf":FAR_FUTURE})), strict.clone(), "NotYetValid"),
+        ("issued-in-future", "iat in the future", sign(&key, &header, &json!({"sub":"s","iat":FAR_FUTURE})), strict.clone(), "NotYetValid"),
+        ("wrong-issuer", "iss mismatch", valid.clone(), strict.clone().with_issuer("https://other.test"), "Issuer"),
+        ("wrong-audience", "aud mismatch", valid.clone(), strict.clone().with_audience("other"), "Audience"),
+        ("zip-header", "compressed payload", sign(&key, &json!({"alg":"EdDSA","kid":KID,"zip":"DEF"}), &claims), strict.clone(), "Zip"),
+        ("duplicate-aud", "duplicate aud key (claim smuggling)", raw(&key, header.to_string().as_bytes(), br#"{"sub":"s","aud":"api","aud":"admin"}"#), strict.clone().with_json_limits(JsonLimits::default()), "JsonLimits"),
+        ("non-canonical", "JSON not in canonical form", raw(&key, br#"{ "kid":"rfc8037","alg":"EdDSA" }"#, claims.to_string().as_bytes()), strict.clone().require_canonical(), "NonCanonical"),
+    ];
+    for (id, description, token, opts, expected) in corpus {
+        record(id, description, expected, outcome(&verify_ed25519_jwt_with_cache(&token, JWKS_URI, &cache, &opts)));
+    }
+
+    let failed = cases.iter().filter(|c| !c.passed).count();
+    Report { passed: cases.len() - failed, failed, cases }
+}
+
+fn outcome<T>(result: &Result<T, VerifyError>) -> String {
+    match result {
+        Ok(_) => "ok".into(),
+        Err(e) => format!("{:?}", e).split(['(', ' ']).next().unwrap_or_default().to_string(),
+    }
+}
+
+fn unsigned(header: &Json, payload: &Json) -> String { encode_canonical(header, payload).expect("JSON values canonize").2 }
+
+fn sign(key: &SigningKey, header: &Json, payload: &Json) -> String {
+    let input = unsigned(header, payload);
+    format!("{}.{}", input, B64URL.encode(key.sign(input.as_bytes()).to_bytes()))
+}
+
+fn raw(key: &SigningKey, header: &[u8], payload: &[u8]) -> String {
+    let input = format!("{}.{}", B64URL.encode(header), B64URL.encode(payload));
+    format!("{}.{}", input, B64URL.encode(key.sign(input.as_bytes()).to_bytes()))
+}
+
+fn segments(token: &str) -> Vec<String> { token.split('.').map(str::to_string).collect() }
+
+fn pad_signature(token: &str) -> String { format!("{}==", token) }
+
+fn tamper_payload(token: &str) -> String {
+    let mut s = segments(token);
+    let mut payload: Json = serde_json::from_slice(&B64URL.decode(&s[1]).expect("own token")).expect("own token");
+    payload["sub"] = json!("did:key:zMallory");
+    s[1] = B64URL.encode(payload.to_string());
+    s.join(".")
+}
+
+fn truncate_signature(token: &str) -> String {
+    let mut s = segments(token);
+    let sig = B64URL.decode(&s[2]).expect("own token");
+    s[2] = B64URL.encode(&sig[..63]);
+    s.join(".")
+}
+
+/// Adds the group order L to the S half of the signature.
+fn malleate(token: &str) -> String {
+    const L: [u8; 32] = [
+        0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+    ];
+    let mut s = segments(token);
+    let mut sig = B64URL.decode(&s[2]).expect("own token");
+    let mut carry = 0u16;
+    for (b, l) in sig[32..].iter_mut().zip(L) {
+        let sum = *b as u16 + l as u16 + carry;
+        *b = sum as u8;
+        carry = sum >> 8;
+    }
+    s[2] = B64URL.encode(&sig);
+    s.join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_case_passes() {
+        let report = run();
+        assert!(report.all_passed(), "{}", report.to_json());
+        assert!(report.cases.len() > 20);
+    }
+}
@@ -0,0 +1,133 @@
+//! Email and phone verification tokens (feature `contactverify`).
+//!
+//! [`ContactVerifier::start`] returns a single-use token and a short numeric code: the code goes
+//! to the contact (email or SMS), the token stays with the client. The token carries a keyed hash
+//! of the normalized contact (`cth`) and of the code (`vch`), never the values themselves.
+//! [`ContactVerifier::confirm`] checks both and consumes the token; after `max_attempts` wrong codes
+//! the token is burnt.
+
+use crate::issuer::{TokenIssuer, TokenRequest};
+use crate::oneshot::{OneShotTokens, PendingStore};
+use crate::{fill_random, to_hex, Claims, SignError, VerifyError};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use serde_json::Value as Json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+pub const CONTACT_CLAIM: &str = "cth";
+pub const CODE_CLAIM: &str = "vch";
+
+pub struct ContactVerifier<S: PendingStore> {
+    tokens: OneShotTokens<S>,
+    key: Vec<u8>,
+    pub code_digits: u32,
+    pub max_attempts: u32,
+    attempts: Mutex<HashMap<String, u32>>,
+}
+
+impl<S: PendingStore> std::fmt::Debug for ContactVerifier<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContactVerifier").field("code_digits", &self.code_digits).field("max_attempts", &self.max_attempts).finish_non_exhaustive()
+    }
+}
+
+impl<S: PendingStore> ContactVerifier<S> {
+    /// `hash_key` keys the contact and code hashes (see [`crate::secrets::hmac_secret`]).
+    /// Codes have 6 digits, tokens live 15 minutes and allow 5 attempts unless changed.
+    pub fn new(issuer: TokenIssuer, store: S, hash_key: &[u8]) -> Self {
+        Self { tokens: OneShotTokens::new(issuer, store).with_ttl(900), key: hash_key.to_vec(), code_digits: 6, max_attempts: 5, attempts: Mutex::new(HashMap::new()) }
+    }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.tokens = self.tokens.with_ttl(secs); self }
+    pub fn with_code_digits(mut self, digits: u32) -> Self { self.code_digits = digits.clamp(4, 10); self }
+    pub fn with_max_attempts(mut self, n: u32) -> Self { self.max_attempts = n.max(1); self }
+
+    /// Starts verifying `contact` for `sub` under `purpose` (e.g. `verify-email`); returns `(token, code)`.
+    pub fn start(&self, sub: &str, contact: &str, purpose: &str) -> Result<(String, String), SignError> {
+        let code = format!("{:0width$}", random_below(10u64.pow(self.code_digits)), width = self.code_digits as usize);
+        let contact = normalize_contact(contact);
+        let claims = HashMap::from([
+            (crate::magiclink::PURPOSE_CLAIM.to_string(), Json::from(purpose)),
+            (CONTACT_CLAIM.to_string(), Json::from(self.mac(&["contact", &contact]))),
+            (CODE_CLAIM.to_string(), Json::from(self.mac(&["code", &contact, &code]))),
+        ]);
+        let token = self.tokens.mint(&TokenRequest { sub: sub.to_string(), aud: None, scope: None, ttl_secs: None, claims })?;
+        Ok((token, code))
+    }
+
+    /// Verifies `token` for `contact` and the `code` the user entered, consuming it on success.
+    pub fn confirm(&self, token: &str, purpose: &str, contact: &str, code: &str) -> Result<Claims, VerifyError> {
+        let contact = normalize_contact(contact);
+        let mut burn = None;
+        let result = self.tokens.redeem_with(token, |claims| {
+            let matches = |name: &str, expected: String| claims.extra.get(name).and_then(Json::as_str).is_some_and(|v| bool::from(v.as_bytes().ct_eq(expected.as_bytes())));
+            if claims.extra.get(crate::magiclink::PURPOSE_CLAIM).and_then(Json::as_str) != Some(purpose) { return Err(VerifyError::Purpose); }
+            if !matches(CONTACT_CLAIM, self.mac(&["contact", &contact])) { return Err(VerifyError::Binding); }
+            if !matches(CODE_CLAIM, self.mac(&["code", &contact, code.trim()])) {
+                let jti = claims.jti.clone().unwrap_or_default();
+                let mut attempts = self.attempts.lock();
+                let n = attempts.entry(jti.clone()).or_insert(0);
+                *n += 1;
+                if *n >= self.max_attempts { attempts.remove(&jti); burn = Some(jti); }
+                return Err(VerifyError::Code);
+            }
+            Ok(())
+        });
+        if let Some(jti) = burn { self.tokens.revoke(&jti); }
+        if let Ok(claims) = &result { self.attempts.lock().remove(claims.jti.as_deref().unwrap_or_default()); }
+        result
+    }
+
+    fn mac(&self, parts: &[&str]) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("hmac accepts any key length");
+        for p in parts { mac.update(p.as_bytes()); mac.update(&[0]); }
+        to_hex(&mac.finalize().into_bytes())
+    }
+}
+
+/// Emails are trimmed and lowercased; phone numbers keep only digits and a leading `+`.
+pub fn normalize_contact(contact: &str) -> String {
+    let contact = contact.trim();
+    if contact.contains('@') { return contact.to_lowercase(); }
+    let plus = if contact.starts_with('+') { "+" } else { "" };
+    format!("{}{}", plus, contact.chars().filter(char::is_ascii_digit).collect::<String>())
+}
+
+/// Uniform in `0..n`: draws that fall in the short last stretch of `u64` are redrawn.
+fn random_below(n: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % n;
+    loop {
+        let mut bytes = [0u8; 8];
+        fill_random(&mut bytes);
+        let x = u64::from_le_bytes(bytes);
+        if x < zone { return x % n; }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oneshot::MemoryPendingStore;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn codes_confirm_once_and_burn_after_max_attempts() {
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(36)));
+        let verifier = ContactVerifier::new(issuer, MemoryPendingStore::new(), &[7u8; 32]).with_max_attempts(2);
+
+        let (token, code) = verifier.start("user-1", "Alice@Example.com ", "verify-email").unwrap();
+        assert_eq!(code.len(), 6);
+        assert!(matches!(verifier.confirm(&token, "verify-email", "bob@example.com", &code), Err(VerifyError::Binding)));
+        assert!(matches!(verifier.confirm(&token, "verify-phone", "alice@example.com", &code), Err(VerifyError::Purpose)));
+        assert_eq!(verifier.confirm(&token, "verify-email", "alice@example.com", &code).unwrap().sub, "user-1");
+        assert!(matches!(verifier.confirm(&token, "verify-email", "alice@example.com", &code), Err(VerifyError::Redeemed)));
+
+        let (token, code) = verifier.start("user-1", "+1 (555) 010-9999", "verify-phone").unwrap();
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+        assert!(matches!(verifier.confirm(&token, "verify-phone", "+15550109999", wrong), Err(VerifyError::Code)));
+        assert!(matches!(verifier.confirm(&token, "verify-phone", "+15550109999", wrong), Err(VerifyError::Code)));
+        assert!(matches!(verifier.confirm(&token, "verify-phone", "+15550109999", &code), Err(VerifyError::Redeemed)));
+    }
+}
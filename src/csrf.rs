@@ -0,0 +1,107 @@
+//! CSRF tokens bound to a session (feature `csrf`).
+//!
+//! A token is `base64url(nonce).exp.base64url(mac)` with
+//! `mac = HMAC-SHA256(secret, sub || 0 || jti || 0 || exp || 0 || nonce)`, so it is only valid
+//! for the session (subject and `jti`) it was issued for, and only until `exp`.
+
+use crate::{fill_random, now_ts, Claims};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header conventionally carrying the token on unsafe requests.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CsrfError {
+    #[error("missing CSRF token")]
+    Missing,
+    /// Malformed, forged, or issued for another session.
+    #[error("invalid CSRF token")]
+    Invalid,
+    #[error("CSRF token expired")]
+    Expired,
+}
+
+#[derive(Clone)]
+pub struct CsrfGuard { secret: Vec<u8>, pub ttl_secs: i64 }
+
+impl std::fmt::Debug for CsrfGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CsrfGuard").field("ttl_secs", &self.ttl_secs).finish_non_exhaustive()
+    }
+}
+
+impl CsrfGuard {
+    pub fn new(secret: &[u8]) -> Self { Self { secret: secret.to_vec(), ttl_secs: 3600 } }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.ttl_secs = secs; self }
+
+    /// Issues a token for the session described by `session`.
+    pub fn issue(&self, session: &Claims) -> String {
+        let mut nonce = [0u8; 16];
+        fill_random(&mut nonce);
+        let exp = match session.exp {
+            Some(sess_exp) => sess_exp.min(now_ts() + self.ttl_secs),
+            None => now_ts() + self.ttl_secs,
+        };
+        let tag = self.mac(session, exp, &nonce).finalize().into_bytes();
+        format!("{}.{}.{}", B64URL.encode(nonce), exp, B64URL.encode(tag))
+    }
+
+    pub fn verify(&self, token: &str, session: &Claims, now: Option<i64>) -> Result<(), CsrfError> {
+        let mut parts = token.trim().split('.');
+        let (Some(nonce), Some(exp), Some(tag), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(CsrfError::Invalid);
+        };
+        let nonce = B64URL.decode(nonce).map_err(|_| CsrfError::Invalid)?;
+        let exp: i64 = exp.parse().map_err(|_| CsrfError::Invalid)?;
+        let tag = B64URL.decode(tag).map_err(|_| CsrfError::Invalid)?;
+        self.mac(session, exp, &nonce).verify_slice(&tag).map_err(|_| CsrfError::Invalid)?;
+        if now.unwrap_or_else(now_ts) > exp { return Err(CsrfError::Expired); }
+        Ok(())
+    }
+
+    /// Middleware hook: safe methods pass, anything else needs a valid token for the session.
+    pub fn check_request(&self, method: &str, token: Option<&str>, session: &Claims) -> Result<(), CsrfError> {
+        if ["GET", "HEAD", "OPTIONS", "TRACE"].iter().any(|m| m.eq_ignore_ascii_case(method)) { return Ok(()); }
+        self.verify(token.ok_or(CsrfError::Missing)?, session, None)
+    }
+
+    fn mac(&self, session: &Claims, exp: i64, nonce: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("hmac accepts any key length");
+        mac.update(session.sub.as_bytes());
+        mac.update(&[0]);
+        mac.update(session.jti.as_deref().unwrap_or_default().as_bytes());
+        mac.update(&[0]);
+        mac.update(exp.to_string().as_bytes());
+        mac.update(&[0]);
+        mac.update(nonce);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(sub: &str, jti: &str) -> Claims {
+        serde_json::from_value(serde_json::json!({"sub": sub, "jti": jti})).unwrap()
+    }
+
+    #[test]
+    fn token_is_bound_to_session() {
+        let guard = CsrfGuard::new(b"csrf-secret").with_ttl(600);
+        let s = session("did:key:zUser", "sess-1");
+        let token = guard.issue(&s);
+        assert!(guard.verify(&token, &s, None).is_ok());
+        assert!(matches!(guard.verify(&token, &session("did:key:zUser", "sess-2"), None), Err(CsrfError::Invalid)));
+        assert!(matches!(guard.verify(&token, &s, Some(now_ts() + 601)), Err(CsrfError::Expired)));
+
+        assert!(guard.check_request("GET", None, &s).is_ok());
+        assert!(matches!(guard.check_request("POST", None, &s), Err(CsrfError::Missing)));
+        assert!(guard.check_request("post", Some(&token), &s).is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+//! `did:key` subjects and `did:web` issuers, Ed25519 only (feature `did`).
+//!
+//! A `did:key:z…` identifier is the multibase base58btc encoding of the multicodec prefix
+//! `0xed 0x01` followed by the 32-byte public key. [`parse_did_key`] recovers the key and
+//! [`did_key`] builds the identifier.
+//!
+//! A `did:web` identifier names a DID document served over HTTPS ([`did_web_url`]). [`DidWeb`] is
+//! a [`KeyProvider`] over that document's Ed25519 verification methods, and `did:web:` strings are
+//! accepted wherever a JWKS URI is, so they are cached like one. [`verify_did_web`] verifies a
+//! token whose `iss` is a `did:web` with that issuer's keys, once `iss` matches the issuer (or
+//! issuer patterns) the caller trusts; no document is fetched for an untrusted DID.
+//!
+//! [`verify_self_issued`] checks a token against the key embedded in its own `sub`, for wallets
+//! and agents that sign their own tokens. Anyone can mint such a token for a fresh key, so it
+//! only proves control of the DID, never that an issuer vouches for it; it is therefore refused
+//! unless [`VerifyOptions::allow_self_issued`] is set.
+
+use crate::keyprovider::KeyProvider;
+use crate::{check_issuer, decode_unverified, verified_claims, verify_ed25519_jwt_with_cache, verify_signature_with, Claims, Jwk, Jwks, JwksCache, VerifyError, VerifyOptions};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::VerifyingKey;
+use serde_json::Value as Json;
+
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+
+/// The Ed25519 key of a `did:key` (a `#fragment` is ignored).
+pub fn parse_did_key(did: &str) -> Result<VerifyingKey, VerifyError> {
+    let fail = |why: &str| VerifyError::Did(why.to_string());
+    let id = did.split('#').next().unwrap_or_default();
+    let encoded = id.strip_prefix("did:key:z").ok_or_else(|| fail("not a base58btc did:key"))?;
+    let bytes = bs58::decode(encoded).into_vec().map_err(|_| fail("invalid base58btc"))?;
+    let key = bytes.strip_prefix(&ED25519_PUB[..]).ok_or_else(|| fail("not an Ed25519 did:key"))?;
+    VerifyingKey::from_bytes(key.try_into().map_err(|_| fail("Ed25519 key must be 32 bytes"))?).map_err(|_| fail("invalid Ed25519 key"))
+}
+
+pub fn did_key(key: &VerifyingKey) -> String {
+    format!("did:key:z{}", bs58::encode([&ED25519_PUB[..], key.as_bytes()].concat()).into_string())
+}
+
+/// Where the DID document of a `did:web` lives: `did:web:example.com` maps to
+/// `https://example.com/.well-known/did.json`, `did:web:example.com:users:alice` to
+/// `https://example.com/users/alice/did.json` (`%3A` in the host encodes a port).
+pub fn did_web_url(did: &str) -> Result<String, VerifyError> {
+    let id = did.strip_prefix("did:web:").filter(|id| !id.is_empty() && !id.contains(['/', '#', '?'])).ok_or_else(|| VerifyError::Did("not a did:web".into()))?;
+    let mut parts = id.split(':');
+    let host = parts.next().unwrap_or_default().replace("%3A", ":").replace("%3a", ":");
+    let path: Vec<&str> = parts.collect();
+    Ok(match path.is_empty() {
+        true => format!("https://{}/.well-known/did.json", host),
+        false => format!("https://{}/{}/did.json", host, path.join("/")),
+    })
+}
+
+/// Ed25519 keys of a DID document, with each verification method's absolute id as `kid`.
+/// Accepts `JsonWebKey2020` (OKP JWK), `Ed25519VerificationKey2020` / `Multikey`
+/// (`publicKeyMultibase`) and `Ed25519VerificationKey2018` (`publicKeyBase58`) methods.
+pub fn keys_from_document(did: &str, document: &Json) -> Result<Jwks, VerifyError> {
+    if document.get("id").and_then(Json::as_str) != Some(did) { return Err(VerifyError::Did("document id does not match the DID".into())); }
+    let methods = document.get("verificationMethod").and_then(Json::as_array).map(Vec::as_slice).unwrap_or_default();
+    let keys = methods
+        .iter()
+        .filter_map(|m| {
+            let id = m.get("id").and_then(Json::as_str)?;
+            let kid = if id.starts_with('#') { format!("{}{}", did, id) } else { id.to_string() };
+            let get = |name: &str| m.get(name).and_then(Json::as_str);
+            let key = match get("type")? {
+                "JsonWebKey2020" => {
+                    let jwk: Jwk = serde_json::from_value(m.get("publicKeyJwk")?.clone()).ok()?;
+                    VerifyingKey::from_bytes(B64URL.decode(jwk.x.as_ref()?).ok()?.as_slice().try_into().ok()?).ok().filter(|_| jwk.kty == "OKP" && jwk.crv.as_deref() == Some("Ed25519"))?
+                }
+                "Ed25519VerificationKey2020" | "Multikey" => parse_did_key(&format!("did:key:{}", get("publicKeyMultibase")?)).ok()?,
+                "Ed25519VerificationKey2018" => VerifyingKey::from_bytes(bs58::decode(get("publicKeyBase58")?).into_vec().ok()?.as_slice().try_into().ok()?).ok()?,
+                _ => return None,
+            };
+            Some(Jwk::ed25519(&kid, &key))
+        })
+        .collect();
+    Ok(Jwks { keys })
+}
+
+/// Keys of a `did:web` DID, fetched from its document on every call (cache through [`JwksCache`]).
+#[derive(Debug, Clone)]
+pub struct DidWeb {
+    pub did: String,
+    pub url: String,
+}
+
+impl DidWeb {
+    pub fn new(did: &str) -> Result<Self, VerifyError> { Ok(Self { did: did.to_string(), url: did_web_url(did)? }) }
+}
+
+impl KeyProvider for DidWeb {
+    fn keys(&self) -> Result<Jwks, VerifyError> {
+        let resp = ureq::get(&self.url).call().map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
+        let document: Json = serde_json::from_str(&resp.into_string().map_err(|e| VerifyError::JwksHttp(e.to_string()))?).map_err(|_| VerifyError::JwksJson)?;
+        keys_from_document(&self.did, &document)
+    }
+}
+
+/// Verifies a token issued by a `did:web` DID with the keys in that DID's document. `opts` must
+/// pin the trusted issuers (`issuer` or `issuer_patterns`); `iss` is checked before resolving.
+pub fn verify_did_web(token: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<Claims, VerifyError> {
+    if opts.issuer.is_none() && opts.issuer_patterns.is_empty() { return Err(VerifyError::Issuer); }
+    let iss = decode_unverified(token)?.1.iss.filter(|iss| iss.starts_with("did:web:")).ok_or(VerifyError::Issuer)?;
+    check_issuer(Some(&iss), opts)?;
+    verify_ed25519_jwt_with_cache(token, &iss, cache, opts)
+}
+
+/// Verifies `token` with the key named by its own `sub`; all other checks follow `opts`.
+pub fn verify_self_issued(token: &str, opts: &VerifyOptions) -> Result<Claims, VerifyError> {
+    if !opts.allow_self_issued { return Err(VerifyError::SelfIssued); }
+    let (_, unverified) = decode_unverified(token)?;
+    let key = parse_did_key(&unverified.sub)?;
+    let jwks = Jwks { keys: vec![Jwk { kid: None, ..Jwk::ed25519("", &key) }] };
+    let opts = VerifyOptions { allow_missing_kid: true, ..opts.clone() };
+    verified_claims(verify_signature_with(token, &opts, || Ok(jwks))?, &opts).map(|v| v.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sign_ed25519_jwt;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn did_web_documents_provide_issuer_keys() {
+        assert_eq!(did_web_url("did:web:w3c-ccg.github.io").unwrap(), "https://w3c-ccg.github.io/.well-known/did.json");
+        assert_eq!(did_web_url("did:web:localhost%3A8443:user:alice").unwrap(), "https://localhost:8443/user/alice/did.json");
+        assert!(matches!(did_web_url("did:key:z6Mk"), Err(VerifyError::Did(_))));
+
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(74));
+        let did = "did:web:id.ubl.agency";
+        let document = serde_json::json!({
+            "id": did,
+            "verificationMethod": [
+                {"id": "#key-1", "type": "Multikey", "controller": did, "publicKeyMultibase": did_key(&key.verifying_key())["did:key:".len()..]},
+                {"id": format!("{}#jwk", did), "type": "JsonWebKey2020", "controller": did, "publicKeyJwk": Jwk::ed25519("", &key.verifying_key())},
+                {"id": "#p256", "type": "JsonWebKey2020", "controller": did, "publicKeyJwk": {"kty":"EC","crv":"P-256","x":"AA","y":"AA"}}
+            ]
+        });
+        let jwks = keys_from_document(did, &document).unwrap();
+        assert_eq!(jwks.keys.iter().map(|k| k.kid.clone().unwrap()).collect::<Vec<_>>(), ["did:web:id.ubl.agency#key-1", "did:web:id.ubl.agency#jwk"]);
+        assert!(matches!(keys_from_document("did:web:other.example", &document), Err(VerifyError::Did(_))));
+
+        let provider = DidWeb { url: crate::tests::serve_once(document.to_string()), ..DidWeb::new(did).unwrap() };
+        let cache = JwksCache::new(300);
+        cache.put(did, provider.keys().unwrap());
+        let token = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub":"did:key:zU","iss": did})).unwrap(), &key, "did:web:id.ubl.agency#key-1").unwrap();
+        let pinned = VerifyOptions::default().with_issuer(did);
+        assert_eq!(verify_did_web(&token, &cache, &pinned).unwrap().sub, "did:key:zU");
+        assert!(matches!(verify_did_web(&token, &cache, &VerifyOptions::default()), Err(VerifyError::Issuer)));
+        let not_web = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub":"did:key:zU","iss":"https://idp"})).unwrap(), &key, "k").unwrap();
+        assert!(matches!(verify_did_web(&not_web, &cache, &pinned.clone().with_issuer("https://idp")), Err(VerifyError::Issuer)));
+
+        // A token naming another did:web is refused before its document is fetched.
+        let attacker = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub":"did:key:zU","iss":"did:web:evil.example"})).unwrap(), &key, "did:web:evil.example#key-1").unwrap();
+        assert!(matches!(verify_did_web(&attacker, &cache, &pinned), Err(VerifyError::Issuer)));
+        let patterned = VerifyOptions::default().with_issuer_pattern("did:web:*.ubl.agency");
+        assert!(matches!(verify_did_web(&attacker, &cache, &patterned), Err(VerifyError::Issuer)));
+    }
+
+    #[test]
+    fn self_issued_tokens_verify_against_their_subject() {
+        // did:key test vector from the W3C did:key specification.
+        let vector = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+        assert_eq!(did_key(&parse_did_key(vector).unwrap()), vector);
+        assert!(matches!(parse_did_key("did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme"), Err(VerifyError::Did(_))));
+
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(72));
+        let sub = did_key(&key.verifying_key());
+        let token = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub": sub, "iss": sub})).unwrap(), &key, "any").unwrap();
+        assert!(matches!(verify_self_issued(&token, &VerifyOptions::default()), Err(VerifyError::SelfIssued)));
+        let opts = VerifyOptions::default().allow_self_issued();
+        assert_eq!(verify_self_issued(&token, &opts).unwrap().sub, sub);
+
+        let other = did_key(&SigningKey::generate(&mut StdRng::seed_from_u64(73)).verifying_key());
+        let impostor = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub": other})).unwrap(), &key, "any").unwrap();
+        assert!(matches!(verify_self_issued(&impostor, &opts), Err(VerifyError::Signature)));
+    }
+}
@@ -0,0 +1,91 @@
+//! OpenID Provider discovery (`{issuer}/.well-known/openid-configuration`).
+//!
+//! [`metadata`] fetches the document once per issuer and caches it for an hour; [`parse`] checks
+//! that the advertised `issuer` is the one asked for (OIDC Discovery §4.3), which catches a
+//! mistyped or proxied issuer URL at startup rather than as `Issuer` failures on every token.
+//! [`Verifier::from_discovery`](crate::Verifier::from_discovery) wires the result into a verifier.
+
+use crate::{now_ts, VerifyError};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+
+const CACHE_TTL_SECS: i64 = 3600;
+
+static CACHE: Lazy<Mutex<HashMap<String, (ProviderMetadata, i64)>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub jwks_uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization_endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub introspection_endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Json>,
+}
+
+impl ProviderMetadata {
+    /// Whether the provider signs with `alg`; providers that do not list their algorithms are assumed to.
+    pub fn supports_alg(&self, alg: &str) -> bool {
+        self.id_token_signing_alg_values_supported.is_empty() || self.id_token_signing_alg_values_supported.iter().any(|a| a == alg)
+    }
+}
+
+pub fn discovery_url(issuer: &str) -> String { format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/')) }
+
+/// Parses a discovery document for `issuer`, rejecting it with [`VerifyError::Issuer`] when it
+/// advertises a different issuer (trailing slashes aside) and with [`VerifyError::KeySource`]
+/// when its `jwks_uri` is not `https`.
+pub fn parse(issuer: &str, body: &str) -> Result<ProviderMetadata, VerifyError> {
+    let metadata: ProviderMetadata = serde_json::from_str(body).map_err(|_| VerifyError::JwksJson)?;
+    if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') { return Err(VerifyError::Issuer); }
+    if !metadata.jwks_uri.starts_with("https://") { return Err(VerifyError::KeySource("discovery jwks_uri must use https".into())); }
+    Ok(metadata)
+}
+
+/// Fetches and checks `issuer`'s metadata, bypassing the cache.
+pub fn fetch(issuer: &str) -> Result<ProviderMetadata, VerifyError> {
+    let resp = ureq::get(&discovery_url(issuer)).call().map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
+    parse(issuer, &resp.into_string().map_err(|e| VerifyError::JwksHttp(e.to_string()))?)
+}
+
+/// Cached [`fetch`].
+pub fn metadata(issuer: &str) -> Result<ProviderMetadata, VerifyError> {
+    let key = issuer.trim_end_matches('/').to_string();
+    if let Some((m, at)) = CACHE.lock().get(&key) {
+        if now_ts() - at <= CACHE_TTL_SECS { return Ok(m.clone()); }
+    }
+    let m = fetch(issuer)?;
+    CACHE.lock().insert(key, (m.clone(), now_ts()));
+    Ok(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_must_name_the_requested_issuer() {
+        let body = r#"{"issuer":"https://id.ubl.agency/","jwks_uri":"https://id.ubl.agency/jwks.json","id_token_signing_alg_values_supported":["EdDSA"],"claims_supported":["sub"]}"#;
+        let m = parse("https://id.ubl.agency", body).unwrap();
+        assert_eq!(m.jwks_uri, "https://id.ubl.agency/jwks.json");
+        assert!(m.supports_alg("EdDSA") && !m.supports_alg("RS256"));
+        assert_eq!(m.extra["claims_supported"], serde_json::json!(["sub"]));
+        assert!(matches!(parse("https://login.ubl.agency", body), Err(VerifyError::Issuer)));
+        assert!(matches!(parse("https://id.ubl.agency", r#"{"issuer":"https://id.ubl.agency"}"#), Err(VerifyError::JwksJson)));
+        for jwks_uri in ["file:///etc/ubl/jwks.json", "env://SIGNING_KEY", "http://id.ubl.agency/jwks.json"] {
+            let body = serde_json::json!({"issuer": "https://id.ubl.agency", "jwks_uri": jwks_uri}).to_string();
+            assert!(matches!(parse("https://id.ubl.agency", &body), Err(VerifyError::KeySource(_))), "{}", jwks_uri);
+        }
+    }
+}
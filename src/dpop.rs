@@ -0,0 +1,189 @@
+//! DPoP proofs (RFC 9449) for sender-constrained access tokens.
+//!
+//! A proof is a JWT with `typ: dpop+jwt` signed by the key in its own `jwk` header. It names the
+//! request (`htm`, `htu`), is fresh (`iat`) and single-use (`jti`). [`DpopVerifier::verify`] checks
+//! one proof against the request it arrived with and returns the key's RFC 7638 thumbprint;
+//! [`DpopVerifier::verify_for_token`] additionally requires the access token's hash (`ath`) and
+//! that the token was issued to that key (`cnf.jkt`). Keys are plain [`Jwk`]s, so anything the
+//! crate verifies with (`EdDSA`, and `ES256` / `RS256` behind their features) can hold the proof key.
+
+use crate::receipts::canonical_bytes;
+use crate::replay::ReplayStore;
+use crate::{now_ts, random_id, verify_jws_signature, Claims, Jwk, Jwks, SignError, VerifyError, VerifyOptions};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::{Signer, SigningKey};
+use serde::Deserialize;
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+pub const DPOP_TYP: &str = "dpop+jwt";
+
+/// A verified proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpopProof {
+    /// RFC 7638 thumbprint of the proof key, compared with the token's `cnf.jkt`.
+    pub jkt: String,
+    pub jti: String,
+    pub htm: String,
+    pub htu: String,
+    pub iat: i64,
+    pub ath: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProofClaims {
+    jti: Option<String>,
+    htm: Option<String>,
+    htu: Option<String>,
+    iat: Option<i64>,
+    ath: Option<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DpopVerifier {
+    /// How old `iat` may be.
+    pub max_age_secs: i64,
+    pub leeway_secs: i64,
+    pub allowed_algs: Vec<String>,
+    /// Server-provided nonce the proof must echo.
+    pub nonce: Option<String>,
+    pub now: Option<i64>,
+    replay: Option<Arc<dyn ReplayStore>>,
+}
+
+impl std::fmt::Debug for DpopVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DpopVerifier").field("max_age_secs", &self.max_age_secs).field("allowed_algs", &self.allowed_algs).field("replay", &self.replay.is_some()).finish_non_exhaustive()
+    }
+}
+
+impl Default for DpopVerifier {
+    fn default() -> Self { Self { max_age_secs: 300, leeway_secs: 5, allowed_algs: vec!["EdDSA".into()], nonce: None, now: None, replay: None } }
+}
+
+impl DpopVerifier {
+    pub fn new() -> Self { Self::default() }
+    pub fn with_max_age(mut self, secs: i64) -> Self { self.max_age_secs = secs; self }
+    pub fn with_leeway(mut self, secs: i64) -> Self { self.leeway_secs = secs; self }
+    pub fn with_allowed_algs(mut self, algs: &[&str]) -> Self { self.allowed_algs = algs.iter().map(|a| a.to_string()).collect(); self }
+    pub fn with_nonce(mut self, nonce: &str) -> Self { self.nonce = Some(nonce.to_string()); self }
+    pub fn with_now(mut self, now: i64) -> Self { self.now = Some(now); self }
+    /// Rejects a second proof with the same `jti` while it is still fresh.
+    pub fn with_replay_store(mut self, store: Arc<dyn ReplayStore>) -> Self { self.replay = Some(store); self }
+
+    /// Verifies `proof` for a request with `method` to `url` (query and fragment are ignored).
+    pub fn verify(&self, proof: &str, method: &str, url: &str) -> Result<DpopProof, VerifyError> {
+        let verified = self.check(proof, method, url)?;
+        self.record(&verified)?;
+        Ok(verified)
+    }
+
+    /// [`verify`](Self::verify), then binds the proof to `access_token` (`ath`) and to the verified
+    /// `claims` of that token (`cnf.jkt`). The `jti` is only recorded once the bindings hold.
+    pub fn verify_for_token(&self, proof: &str, method: &str, url: &str, access_token: &str, claims: &Claims) -> Result<DpopProof, VerifyError> {
+        let verified = self.check(proof, method, url)?;
+        if verified.ath.as_deref() != Some(access_token_hash(access_token).as_str()) { return Err(VerifyError::Binding); }
+        let jkt = claims.extra.get("cnf").and_then(|c| c.get("jkt")).and_then(Json::as_str);
+        if jkt != Some(verified.jkt.as_str()) { return Err(VerifyError::Binding); }
+        self.record(&verified)?;
+        Ok(verified)
+    }
+
+    /// Every check but replay.
+    fn check(&self, proof: &str, method: &str, url: &str) -> Result<DpopProof, VerifyError> {
+        let fail = |why: &str| VerifyError::Dpop(why.to_string());
+        let (header_b64, payload_b64, sig_b64) = match proof.trim().split('.').collect::<Vec<_>>()[..] {
+            [h, p, s] => (h, p, s),
+            _ => return Err(VerifyError::BadFormat),
+        };
+        let mut header: Json = serde_json::from_slice(&B64URL.decode(header_b64).map_err(|_| VerifyError::Base64)?).map_err(|_| VerifyError::Json)?;
+        if header.get("typ").and_then(Json::as_str) != Some(DPOP_TYP) { return Err(VerifyError::Typ); }
+        let jwk_json = header.get("jwk").filter(|j| j.is_object()).ok_or_else(|| fail("missing jwk header"))?;
+        if jwk_json.get("d").is_some() { return Err(fail("jwk header holds a private key")); }
+        let jwk = Jwk { kid: None, ..serde_json::from_value::<Jwk>(jwk_json.clone()).map_err(|_| VerifyError::Json)? };
+        let jkt = jwk.thumbprint().ok_or_else(|| fail("unsupported jwk"))?;
+
+        // The embedded key is the only candidate, whatever `kid` / `x5t` the header also carries.
+        if let Some(h) = header.as_object_mut() {
+            for name in ["kid", "x5t", "x5t#S256"] { h.remove(name); }
+        }
+        let opts = VerifyOptions { allowed_algs: self.allowed_algs.clone(), allow_missing_kid: true, ..Default::default() };
+        let sig = B64URL.decode(sig_b64).map_err(|_| VerifyError::Base64)?;
+        verify_jws_signature(&header, format!("{}.{}", header_b64, payload_b64).as_bytes(), &sig, &opts, || Ok(Jwks { keys: vec![jwk] }))?;
+
+        let claims: ProofClaims = serde_json::from_slice(&B64URL.decode(payload_b64).map_err(|_| VerifyError::Base64)?).map_err(|_| VerifyError::Json)?;
+        let jti = claims.jti.ok_or(VerifyError::MissingClaim("jti"))?;
+        let htm = claims.htm.ok_or(VerifyError::MissingClaim("htm"))?;
+        let htu = claims.htu.ok_or(VerifyError::MissingClaim("htu"))?;
+        let iat = claims.iat.ok_or(VerifyError::MissingClaim("iat"))?;
+        if htm != method { return Err(fail("htm does not match the request method")); }
+        if without_query(&htu) != without_query(url) { return Err(fail("htu does not match the request URL")); }
+        let now = self.now.unwrap_or_else(now_ts);
+        if iat > now + self.leeway_secs { return Err(VerifyError::NotYetValid); }
+        if iat < now - self.max_age_secs - self.leeway_secs { return Err(VerifyError::Expired); }
+        if self.nonce.is_some() && claims.nonce != self.nonce { return Err(VerifyError::Nonce); }
+        Ok(DpopProof { jkt, jti, htm, htu, iat, ath: claims.ath, nonce: claims.nonce })
+    }
+
+    fn record(&self, proof: &DpopProof) -> Result<(), VerifyError> {
+        let Some(store) = &self.replay else { return Ok(()) };
+        let now = self.now.unwrap_or_else(now_ts);
+        if !store.insert_if_absent(&proof.jti, proof.iat + self.max_age_secs + self.leeway_secs, now) { return Err(VerifyError::Replayed); }
+        Ok(())
+    }
+}
+
+/// `ath`: base64url SHA-256 of the access token.
+pub fn access_token_hash(access_token: &str) -> String { B64URL.encode(Sha256::digest(access_token.as_bytes())) }
+
+fn without_query(url: &str) -> &str { url.split(['?', '#']).next().unwrap_or_default() }
+
+/// Signs an EdDSA proof for `method` `url`, with `ath` when presenting `access_token`.
+pub fn sign_dpop_proof(key: &SigningKey, method: &str, url: &str, access_token: Option<&str>, nonce: Option<&str>) -> Result<String, SignError> {
+    let jwk = serde_json::json!({"kty": "OKP", "crv": "Ed25519", "x": B64URL.encode(key.verifying_key().to_bytes())});
+    let header = serde_json::json!({"typ": DPOP_TYP, "alg": "EdDSA", "jwk": jwk});
+    let mut claims = serde_json::json!({"jti": random_id(), "htm": method, "htu": url, "iat": now_ts()});
+    if let Some(token) = access_token { claims["ath"] = access_token_hash(token).into(); }
+    if let Some(nonce) = nonce { claims["nonce"] = nonce.into(); }
+    let signing_input = format!("{}.{}", B64URL.encode(canonical_bytes(&header)?), B64URL.encode(canonical_bytes(&claims)?));
+    Ok(format!("{}.{}", signing_input, B64URL.encode(key.sign(signing_input.as_bytes()).to_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::MemoryReplayStore;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn proofs_bind_request_key_and_access_token() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(75));
+        let jkt = Jwk::ed25519("", &key.verifying_key()).thumbprint().unwrap();
+        let url = "https://api.ubl.agency/orders";
+        let verifier = DpopVerifier::new().with_replay_store(Arc::new(MemoryReplayStore::new()));
+
+        let proof = sign_dpop_proof(&key, "POST", url, None, None).unwrap();
+        assert_eq!(verifier.verify(&proof, "POST", &format!("{}?page=2", url)).unwrap().jkt, jkt);
+        assert!(matches!(verifier.verify(&proof, "POST", url), Err(VerifyError::Replayed)));
+        assert!(matches!(verifier.verify(&sign_dpop_proof(&key, "GET", url, None, None).unwrap(), "POST", url), Err(VerifyError::Dpop(_))));
+        assert!(matches!(verifier.verify(&sign_dpop_proof(&key, "POST", url, None, None).unwrap(), "POST", "https://evil.example/orders"), Err(VerifyError::Dpop(_))));
+        assert!(matches!(DpopVerifier::new().with_now(now_ts() + 3_600).verify(&proof, "POST", url), Err(VerifyError::Expired)));
+        assert!(matches!(DpopVerifier::new().with_nonce("n-1").verify(&proof, "POST", url), Err(VerifyError::Nonce)));
+
+        let bound: Claims = serde_json::from_value(serde_json::json!({"sub":"alice","cnf":{"jkt": jkt}})).unwrap();
+        let unbound: Claims = serde_json::from_value(serde_json::json!({"sub":"alice"})).unwrap();
+        let proof = sign_dpop_proof(&key, "GET", url, Some("at-1"), None).unwrap();
+        assert!(matches!(verifier.verify_for_token(&proof, "GET", url, "at-2", &bound), Err(VerifyError::Binding)));
+        assert!(matches!(verifier.verify_for_token(&proof, "GET", url, "at-1", &unbound), Err(VerifyError::Binding)));
+        verifier.verify_for_token(&proof, "GET", url, "at-1", &bound).unwrap();
+        assert!(matches!(verifier.verify_for_token(&proof, "GET", url, "at-1", &bound), Err(VerifyError::Replayed)));
+
+        let mut forged: Vec<&str> = proof.split('.').collect();
+        let other = B64URL.encode(serde_json::to_vec(&serde_json::json!({"typ": DPOP_TYP, "alg": "EdDSA", "jwk": Jwk { kid: None, ..Jwk::ed25519("", &SigningKey::generate(&mut StdRng::seed_from_u64(76)).verifying_key()) }})).unwrap());
+        forged[0] = &other;
+        assert!(matches!(DpopVerifier::new().verify(&forged.join("."), "GET", url), Err(VerifyError::Signature)));
+    }
+}
@@ -0,0 +1,206 @@
+//! Experimental FROST threshold signing for issuer keys (feature `frost`, RFC 9591
+//! FROST(Ed25519, SHA-512)).
+//!
+//! The issuer key is split into `max_signers` shares of which any `min_signers` can sign, so no
+//! single host can mint tokens alone. The aggregated signature is a plain Ed25519 signature under
+//! the original public key: verifiers are unchanged.
+//!
+//! Flow per token: each participating signer calls [`KeyShare::commit`] and sends its
+//! [`SigningCommitments`] to the coordinator; the coordinator sends all commitments and the
+//! [`signing_input`] back; each signer returns [`KeyShare::sign`]; the coordinator calls
+//! [`aggregate`] and [`assemble`]. Shares are dealt by a trusted dealer ([`split`]); distributed
+//! key generation is out of scope.
+
+use crate::{encode_canonical, fill_random, Claims, SignError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use curve25519_dalek::{edwards::CompressedEdwardsY, EdwardsPoint, Scalar};
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha512};
+
+const CONTEXT: &[u8] = b"FROST-ED25519-SHA512-v1";
+
+/// One signer's share of the issuer key.
+#[derive(Clone)]
+pub struct KeyShare {
+    pub identifier: u16,
+    pub group_key: VerifyingKey,
+    pub min_signers: u16,
+    secret: Scalar,
+}
+
+impl std::fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyShare").field("identifier", &self.identifier).field("min_signers", &self.min_signers).finish_non_exhaustive()
+    }
+}
+
+/// Round-one secret nonces; consumed by [`KeyShare::sign`] so they are never reused.
+pub struct SigningNonces { hiding: Scalar, binding: Scalar, commitments: SigningCommitments }
+
+impl std::fmt::Debug for SigningNonces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningNonces").field("commitments", &self.commitments).finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigningCommitments {
+    pub identifier: u16,
+    pub hiding: [u8; 32],
+    pub binding: [u8; 32],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureShare {
+    pub identifier: u16,
+    pub z: [u8; 32],
+}
+
+/// Trusted-dealer split of `key` into `max_signers` shares with threshold `min_signers`.
+pub fn split(key: &SigningKey, min_signers: u16, max_signers: u16) -> Result<Vec<KeyShare>, SignError> {
+    if min_signers < 2 || min_signers > max_signers { return Err(threshold("need 2 <= min_signers <= max_signers")); }
+    let coefficients: Vec<Scalar> = std::iter::once(key.to_scalar()).chain((1..min_signers).map(|_| random_scalar())).collect();
+    Ok((1..=max_signers)
+        .map(|id| {
+            let x = Scalar::from(id as u64);
+            let secret = coefficients.iter().rev().fold(Scalar::ZERO, |acc, c| acc * x + c);
+            KeyShare { identifier: id, group_key: key.verifying_key(), min_signers, secret }
+        })
+        .collect())
+}
+
+impl KeyShare {
+    /// Round one: fresh nonces and the commitments to publish.
+    pub fn commit(&self) -> (SigningNonces, SigningCommitments) {
+        let (hiding, binding) = (self.nonce(), self.nonce());
+        let commitments = SigningCommitments {
+            identifier: self.identifier,
+            hiding: EdwardsPoint::mul_base(&hiding).compress().to_bytes(),
+            binding: EdwardsPoint::mul_base(&binding).compress().to_bytes(),
+        };
+        (SigningNonces { hiding, binding, commitments }, commitments)
+    }
+
+    /// Round two: this signer's share of the signature over `message`.
+    pub fn sign(&self, nonces: SigningNonces, message: &[u8], commitments: &[SigningCommitments]) -> Result<SignatureShare, SignError> {
+        let list = sorted(commitments, self.min_signers)?;
+        if !list.contains(&nonces.commitments) { return Err(threshold("own commitments missing from the signing set")); }
+        let rhos = binding_factors(&self.group_key, message, &list);
+        let rho = rhos.iter().find(|(id, _)| *id == self.identifier).map(|(_, r)| *r).ok_or_else(|| threshold("signer not in the signing set"))?;
+        let r = group_commitment(&list, &rhos)?;
+        let c = challenge(&r, &self.group_key, message);
+        let lambda = lagrange(self.identifier, &list);
+        let z = nonces.hiding + nonces.binding * rho + lambda * self.secret * c;
+        Ok(SignatureShare { identifier: self.identifier, z: z.to_bytes() })
+    }
+
+    fn nonce(&self) -> Scalar {
+        let mut random = [0u8; 32];
+        fill_random(&mut random);
+        hash_to_scalar(&[CONTEXT, b"nonce", &random, self.secret.as_bytes()])
+    }
+}
+
+/// Combines the shares into an Ed25519 signature, checking it against `group_key`.
+pub fn aggregate(group_key: &VerifyingKey, message: &[u8], commitments: &[SigningCommitments], shares: &[SignatureShare]) -> Result<Signature, SignError> {
+    let list = sorted(commitments, 2)?;
+    if shares.len() != list.len() || list.iter().any(|c| !shares.iter().any(|s| s.identifier == c.identifier)) {
+        return Err(threshold("shares do not match the signing set"));
+    }
+    let rhos = binding_factors(group_key, message, &list);
+    let r = group_commitment(&list, &rhos)?;
+    let z = shares.iter().try_fold(Scalar::ZERO, |acc, s| {
+        Option::<Scalar>::from(Scalar::from_canonical_bytes(s.z)).map(|z| acc + z).ok_or_else(|| threshold("non-canonical share"))
+    })?;
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(r.compress().as_bytes());
+    bytes[32..].copy_from_slice(z.as_bytes());
+    let sig = Signature::from_bytes(&bytes);
+    group_key.verify_strict(message, &sig).map_err(|_| threshold("aggregate signature invalid (bad share?)"))?;
+    Ok(sig)
+}
+
+/// JWS signing input (`header.payload`) for `claims` under `kid`, as every signer must sign it.
+pub fn signing_input(claims: &Claims, kid: &str) -> Result<String, SignError> {
+    let header = serde_json::json!({"alg": "EdDSA", "kid": kid, "typ": "JWT"});
+    Ok(encode_canonical(&header, claims)?.2)
+}
+
+pub fn assemble(signing_input: &str, sig: &Signature) -> String { format!("{}.{}", signing_input, B64URL.encode(sig.to_bytes())) }
+
+fn threshold(why: &str) -> SignError { SignError::Threshold(why.to_string()) }
+
+fn random_scalar() -> Scalar {
+    let mut wide = [0u8; 64];
+    fill_random(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let digest = parts.iter().fold(Sha512::new(), |h, p| h.chain_update(p)).finalize();
+    Scalar::from_bytes_mod_order_wide(&digest.into())
+}
+
+fn sorted(commitments: &[SigningCommitments], min: u16) -> Result<Vec<SigningCommitments>, SignError> {
+    let mut list = commitments.to_vec();
+    list.sort_by_key(|c| c.identifier);
+    list.dedup_by_key(|c| c.identifier);
+    if list.len() != commitments.len() { return Err(threshold("duplicate signer")); }
+    if list.len() < min as usize || list.iter().any(|c| c.identifier == 0) { return Err(threshold("not enough signers")); }
+    Ok(list)
+}
+
+fn binding_factors(group_key: &VerifyingKey, message: &[u8], list: &[SigningCommitments]) -> Vec<(u16, Scalar)> {
+    let encoded: Vec<u8> = list.iter().flat_map(|c| [Scalar::from(c.identifier as u64).to_bytes(), c.hiding, c.binding].concat()).collect();
+    let msg_hash = Sha512::new().chain_update(CONTEXT).chain_update(b"msg").chain_update(message).finalize();
+    let com_hash = Sha512::new().chain_update(CONTEXT).chain_update(b"com").chain_update(&encoded).finalize();
+    list.iter()
+        .map(|c| (c.identifier, hash_to_scalar(&[CONTEXT, b"rho", group_key.as_bytes(), &msg_hash, &com_hash, Scalar::from(c.identifier as u64).as_bytes()])))
+        .collect()
+}
+
+fn group_commitment(list: &[SigningCommitments], rhos: &[(u16, Scalar)]) -> Result<EdwardsPoint, SignError> {
+    let point = |b: &[u8; 32]| CompressedEdwardsY(*b).decompress().ok_or_else(|| threshold("invalid commitment"));
+    list.iter().zip(rhos).try_fold(EdwardsPoint::default(), |acc, (c, (_, rho))| Ok(acc + point(&c.hiding)? + point(&c.binding)? * rho))
+}
+
+fn challenge(r: &EdwardsPoint, group_key: &VerifyingKey, message: &[u8]) -> Scalar {
+    hash_to_scalar(&[r.compress().as_bytes(), group_key.as_bytes(), message])
+}
+
+fn lagrange(identifier: u16, list: &[SigningCommitments]) -> Scalar {
+    let xi = Scalar::from(identifier as u64);
+    let (num, den) = list.iter().filter(|c| c.identifier != identifier).fold((Scalar::ONE, Scalar::ONE), |(num, den), c| {
+        let xj = Scalar::from(c.identifier as u64);
+        (num * xj, den * (xj - xi))
+    });
+    num * den.invert()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verify_ed25519_jwt_with_cache, Jwk, Jwks, JwksCache, VerifyOptions};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn two_of_three_signature_verifies_as_plain_ed25519() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(28));
+        let shares = split(&key, 2, 3).unwrap();
+        let claims: Claims = serde_json::from_value(serde_json::json!({"sub":"did:key:zSvc","iss":"https://issuer.internal"})).unwrap();
+        let input = signing_input(&claims, "frost").unwrap();
+
+        let signers = [&shares[0], &shares[2]];
+        let (nonces, commitments): (Vec<_>, Vec<_>) = signers.iter().map(|s| s.commit()).unzip();
+        let sig_shares: Vec<_> = signers.iter().zip(nonces).map(|(s, n)| s.sign(n, input.as_bytes(), &commitments).unwrap()).collect();
+        let sig = aggregate(&key.verifying_key(), input.as_bytes(), &commitments, &sig_shares).unwrap();
+
+        let cache = JwksCache::new(3600);
+        cache.put("mem://frost", Jwks { keys: vec![Jwk::ed25519("frost", &key.verifying_key())] });
+        let verified = verify_ed25519_jwt_with_cache(&assemble(&input, &sig), "mem://frost", &cache, &VerifyOptions::default()).unwrap();
+        assert_eq!(verified.sub, "did:key:zSvc");
+
+        let (lone, lone_commitment) = shares[1].commit();
+        assert!(matches!(shares[1].sign(lone, input.as_bytes(), &[lone_commitment]), Err(SignError::Threshold(_))));
+    }
+}
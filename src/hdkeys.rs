@@ -0,0 +1,105 @@
+//! Deterministic SLIP-0010 Ed25519 key derivation from one master seed (feature `hdkeys`).
+//!
+//! A fleet of issuers can be provisioned from a single backed-up secret: each service or tenant
+//! gets its own key at a hardened path such as `m/0'/3'`, or at [`path_for`] a list of names.
+//! Derivation is reproducible, and so is the `kid` ([`DerivedKey::kid`], the RFC 7638 thumbprint).
+//! Ed25519 only supports hardened derivation, so every index is hardened.
+
+use crate::keyimport::kid_for;
+use crate::SignError;
+use ed25519_dalek::SigningKey;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// A SLIP-0010 master seed (16 to 64 bytes).
+#[derive(Clone)]
+pub struct MasterSeed(Vec<u8>);
+
+impl std::fmt::Debug for MasterSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.debug_struct("MasterSeed").finish_non_exhaustive() }
+}
+
+#[derive(Debug, Clone)]
+pub struct DerivedKey {
+    pub path: String,
+    pub key: SigningKey,
+    chain_code: [u8; 32],
+}
+
+impl DerivedKey {
+    pub fn kid(&self) -> String { kid_for(&self.key.verifying_key()) }
+
+    /// Derives the hardened child `index` (values at or above 2^31 are taken as already hardened).
+    pub fn child(&self, index: u32) -> DerivedKey {
+        let index = index | HARDENED;
+        let mut data = Vec::with_capacity(37);
+        data.push(0);
+        data.extend_from_slice(self.key.as_bytes());
+        data.extend_from_slice(&index.to_be_bytes());
+        let (key, chain_code) = split(&self.chain_code, &data);
+        DerivedKey { path: format!("{}/{}'", self.path, index & !HARDENED), key, chain_code }
+    }
+}
+
+impl MasterSeed {
+    pub fn new(seed: &[u8]) -> Result<Self, SignError> {
+        if !(16..=64).contains(&seed.len()) { return Err(SignError::KeyImport("master seed must be 16 to 64 bytes".into())); }
+        Ok(Self(seed.to_vec()))
+    }
+
+    pub fn master(&self) -> DerivedKey {
+        let (key, chain_code) = split(b"ed25519 seed", &self.0);
+        DerivedKey { path: "m".into(), key, chain_code }
+    }
+
+    /// Derives `path`, e.g. `m/0'/3'` (the `'` / `h` markers are optional; all indexes are hardened).
+    pub fn derive(&self, path: &str) -> Result<DerivedKey, SignError> {
+        let bad = || SignError::KeyImport(format!("invalid derivation path '{}'", path));
+        let mut segments = path.split('/');
+        if segments.next() != Some("m") { return Err(bad()); }
+        segments.try_fold(self.master(), |key, seg| {
+            let index: u32 = seg.trim_end_matches(['\'', 'h', 'H']).parse().map_err(|_| bad())?;
+            if index >= HARDENED { return Err(bad()); }
+            Ok(key.child(index))
+        })
+    }
+}
+
+/// Path for a list of names (e.g. `["tenants", "acme"]`), each mapped to an index by hashing.
+pub fn path_for(names: &[&str]) -> String {
+    names.iter().fold("m".to_string(), |path, name| {
+        let digest = Sha256::digest(name.as_bytes());
+        let index = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) & !HARDENED;
+        format!("{}/{}'", path, index)
+    })
+}
+
+fn split(key: &[u8], data: &[u8]) -> (SigningKey, [u8; 32]) {
+    let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data);
+    let out = mac.finalize().into_bytes();
+    let (il, ir) = out.split_at(32);
+    (SigningKey::from_bytes(il.try_into().expect("32-byte half")), ir.try_into().expect("32-byte half"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_hex, to_hex};
+
+    #[test]
+    fn slip10_test_vector_1() {
+        let seed = MasterSeed::new(&from_hex("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap();
+        assert_eq!(to_hex(seed.master().key.as_bytes()), "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7");
+        let k = seed.derive("m/0'/1'").unwrap();
+        assert_eq!(to_hex(k.key.as_bytes()), "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2");
+        assert_eq!(k.path, "m/0'/1'");
+        assert_eq!(seed.derive("m/0h/1h").unwrap().kid(), k.kid());
+        assert!(seed.derive("0/1").is_err());
+
+        let tenant = seed.derive(&path_for(&["tenants", "acme"])).unwrap();
+        assert_ne!(tenant.kid(), seed.derive(&path_for(&["tenants", "globex"])).unwrap().kid());
+    }
+}
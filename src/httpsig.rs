@@ -0,0 +1,206 @@
+//! RFC 9421 HTTP Message Signatures with Ed25519 (`alg="ed25519"`).
+//!
+//! Covers the derived components `@method`, `@target-uri`, `@authority`, `@scheme`, `@path`,
+//! `@query` plus plain header fields. Verification resolves `keyid` through the JWKS cache,
+//! exactly like JWT `kid`s.
+
+use crate::{check_timestamp, now_ts, resolve_key, JwksCache, SignError, VerifyError};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+/// The parts of a request that signatures can cover.
+#[derive(Debug, Clone, Default)]
+pub struct HttpRequestParts {
+    pub method: String,
+    pub scheme: String,
+    pub authority: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpRequestParts {
+    pub fn new(method: &str, scheme: &str, authority: &str, path: &str) -> Self {
+        Self { method: method.to_string(), scheme: scheme.to_string(), authority: authority.to_string(), path: path.to_string(), ..Default::default() }
+    }
+    pub fn with_query(mut self, query: &str) -> Self { self.query = Some(query.to_string()); self }
+    pub fn with_header(mut self, name: &str, value: &str) -> Self { self.headers.push((name.to_string(), value.to_string())); self }
+
+    fn component(&self, name: &str) -> Option<String> {
+        Some(match name {
+            // As sent: RFC 9421 §2.2.1 does not normalize the method's case.
+            "@method" => self.method.clone(),
+            "@scheme" => self.scheme.to_lowercase(),
+            "@authority" => self.authority.to_lowercase(),
+            "@path" => self.path.clone(),
+            "@query" => format!("?{}", self.query.as_deref().unwrap_or_default()),
+            "@target-uri" => match &self.query {
+                Some(q) => format!("{}://{}{}?{}", self.scheme.to_lowercase(), self.authority.to_lowercase(), self.path, q),
+                None => format!("{}://{}{}", self.scheme.to_lowercase(), self.authority.to_lowercase(), self.path),
+            },
+            n if n.starts_with('@') => return None,
+            n => {
+                let values: Vec<&str> = self.headers.iter().filter(|(k, _)| k.eq_ignore_ascii_case(n)).map(|(_, v)| v.trim()).collect();
+                if values.is_empty() { return None; }
+                values.join(", ")
+            }
+        })
+    }
+}
+
+/// Options for [`verify_request`].
+#[derive(Debug, Clone)]
+pub struct HttpSigOptions {
+    /// Signature label to verify; the first one in `Signature-Input` when `None`.
+    pub label: Option<String>,
+    /// Components that must be covered by the signature.
+    pub required_components: Vec<String>,
+    /// Maximum age of `created`, in seconds.
+    pub max_age_secs: i64,
+    pub now: Option<i64>,
+}
+
+impl Default for HttpSigOptions {
+    fn default() -> Self {
+        Self { label: None, required_components: vec!["@method".into(), "@target-uri".into()], max_age_secs: 300, now: None }
+    }
+}
+
+impl HttpSigOptions {
+    pub fn with_label(mut self, label: &str) -> Self { self.label = Some(label.to_string()); self }
+    pub fn require(mut self, component: &str) -> Self { self.required_components.push(component.to_lowercase()); self }
+    pub fn with_max_age(mut self, secs: i64) -> Self { self.max_age_secs = secs; self }
+    pub fn with_now(mut self, now: i64) -> Self { self.now = Some(now); self }
+}
+
+/// A successfully verified message signature.
+#[derive(Debug, Clone)]
+pub struct VerifiedSignature {
+    pub label: String,
+    pub keyid: String,
+    pub components: Vec<String>,
+    pub created: i64,
+}
+
+/// Signs `req` over `components`, returning the `Signature-Input` and `Signature` header values.
+pub fn sign_request(req: &HttpRequestParts, components: &[&str], label: &str, key: &SigningKey, keyid: &str, created: i64) -> Result<(String, String), SignError> {
+    let list: Vec<String> = components.iter().map(|c| format!("\"{}\"", c.to_lowercase())).collect();
+    let params = format!("({});created={};keyid=\"{}\";alg=\"ed25519\"", list.join(" "), created, keyid);
+    let comps: Vec<String> = components.iter().map(|c| c.to_lowercase()).collect();
+    let base = signature_base(req, &comps, &params).map_err(SignError::MissingComponent)?;
+    let sig = key.sign(base.as_bytes());
+    Ok((format!("{}={}", label, params), format!("{}=:{}:", label, B64.encode(sig.to_bytes()))))
+}
+
+/// Verifies a request signature, resolving `keyid` from `jwks_uri`.
+pub fn verify_request(req: &HttpRequestParts, signature_input: &str, signature: &str, jwks_uri: &str, cache: &JwksCache, opts: &HttpSigOptions) -> Result<VerifiedSignature, VerifyError> {
+    verify_request_with(req, signature_input, signature, opts, |kid| resolve_key(jwks_uri, cache, kid))
+}
+
+/// Like [`verify_request`] but with a caller-supplied key lookup.
+pub fn verify_request_with<F>(req: &HttpRequestParts, signature_input: &str, signature: &str, opts: &HttpSigOptions, key_for: F) -> Result<VerifiedSignature, VerifyError>
+where
+    F: FnOnce(&str) -> Result<VerifyingKey, VerifyError>,
+{
+    let inputs = dictionary(signature_input);
+    let (label, params) = match &opts.label {
+        Some(l) => inputs.iter().find(|(k, _)| k == l).cloned(),
+        None => inputs.first().cloned(),
+    }.ok_or(VerifyError::BadFormat)?;
+    let sig_b64 = dictionary(signature).into_iter().find(|(k, _)| *k == label).map(|(_, v)| v).ok_or(VerifyError::BadFormat)?;
+    let sig_bytes = B64.decode(sig_b64.trim_matches(':')).map_err(|_| VerifyError::Base64)?;
+    let sig = Signature::from_bytes(sig_bytes[..].try_into().map_err(|_| VerifyError::Signature)?);
+
+    let (components, created, keyid, alg) = parse_params(&params)?;
+    if let Some(a) = alg.as_deref().filter(|a| *a != "ed25519") { return Err(VerifyError::alg(a, &["ed25519"])); }
+    let keyid = keyid.ok_or(VerifyError::Kid)?;
+    let created = created.ok_or(VerifyError::BadFormat)?;
+    check_timestamp(created, opts.now.unwrap_or_else(now_ts), opts.max_age_secs)?;
+    if let Some(missing) = opts.required_components.iter().find(|c| !components.contains(c)) {
+        return Err(VerifyError::MissingComponent(missing.clone()));
+    }
+
+    let base = signature_base(req, &components, &params).map_err(VerifyError::MissingComponent)?;
+    key_for(&keyid)?.verify_strict(base.as_bytes(), &sig).map_err(|_| VerifyError::Signature)?;
+    Ok(VerifiedSignature { label, keyid, components, created })
+}
+
+/// The signature base, or the first component `req` does not have.
+fn signature_base(req: &HttpRequestParts, components: &[String], params: &str) -> Result<String, String> {
+    let mut base = String::new();
+    for c in components {
+        let value = req.component(c).ok_or_else(|| c.clone())?;
+        base.push_str(&format!("\"{}\": {}\n", c, value));
+    }
+    base.push_str(&format!("\"@signature-params\": {}", params));
+    Ok(base)
+}
+
+type Params = (Vec<String>, Option<i64>, Option<String>, Option<String>);
+
+fn parse_params(params: &str) -> Result<Params, VerifyError> {
+    let rest = params.strip_prefix('(').ok_or(VerifyError::BadFormat)?;
+    let (list, rest) = rest.split_once(')').ok_or(VerifyError::BadFormat)?;
+    let components = list.split_whitespace().map(|c| c.trim_matches('"').to_string()).collect();
+    let (mut created, mut keyid, mut alg) = (None, None, None);
+    for p in rest.split(';').filter(|p| !p.is_empty()) {
+        match p.split_once('=') {
+            Some(("created", v)) => created = Some(v.parse().map_err(|_| VerifyError::BadFormat)?),
+            Some(("keyid", v)) => keyid = Some(v.trim_matches('"').to_string()),
+            Some(("alg", v)) => alg = Some(v.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+    Ok((components, created, keyid, alg))
+}
+
+/// Splits a structured-field dictionary into `(key, raw value)` pairs, respecting quotes and parentheses.
+fn dictionary(s: &str) -> Vec<(String, String)> {
+    let (mut out, mut cur, mut depth, mut quoted) = (Vec::new(), String::new(), 0, false);
+    for ch in s.chars().chain(std::iter::once(',')) {
+        match ch {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                if let Some((k, v)) = cur.trim().split_once('=') { out.push((k.to_string(), v.to_string())); }
+                cur.clear();
+                continue;
+            }
+            _ => {}
+        }
+        cur.push(ch);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn sign_and_verify_request() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(9));
+        let req = HttpRequestParts::new("post", "https", "Ledger.UBL.Agency", "/spans").with_query("v=2").with_header("Content-Digest", "sha-256=:abc=:");
+        let (input, sig) = sign_request(&req, &["@method", "@target-uri", "content-digest"], "sig1", &sk, "svc-a", 1_700_000_000).unwrap();
+        assert!(input.starts_with("sig1=(\"@method\" \"@target-uri\" \"content-digest\");created=1700000000"));
+
+        let opts = HttpSigOptions::default().require("content-digest").with_now(1_700_000_010);
+        let vk = sk.verifying_key();
+        let ok = verify_request_with(&req, &input, &sig, &opts, |kid| { assert_eq!(kid, "svc-a"); Ok(vk) }).unwrap();
+        assert_eq!(ok.components, ["@method", "@target-uri", "content-digest"]);
+
+        let tampered = req.clone().with_header("content-digest", "sha-256=:xyz=:");
+        assert!(matches!(verify_request_with(&tampered, &input, &sig, &opts, |_| Ok(vk)), Err(VerifyError::Signature)));
+        let recased = HttpRequestParts { method: "POST".into(), ..req.clone() };
+        assert!(matches!(verify_request_with(&recased, &input, &sig, &opts, |_| Ok(vk)), Err(VerifyError::Signature)));
+        let (far_past, far_sig) = sign_request(&req, &["@method"], "sig1", &sk, "svc-a", i64::MIN).unwrap();
+        assert!(matches!(verify_request_with(&req, &far_past, &far_sig, &HttpSigOptions::default(), |_| Ok(vk)), Err(VerifyError::Expired)));
+        assert!(matches!(sign_request(&req, &["authorization"], "sig1", &sk, "svc-a", 0), Err(SignError::MissingComponent(_))));
+        let stale = opts.clone().with_now(1_700_001_000);
+        assert!(matches!(verify_request_with(&req, &input, &sig, &stale, |_| Ok(vk)), Err(VerifyError::Expired)));
+        let strict = opts.require("authorization");
+        assert!(matches!(verify_request_with(&req, &input, &sig, &strict, |_| Ok(vk)), Err(VerifyError::MissingComponent(_))));
+    }
+}
@@ -0,0 +1,157 @@
+//! RFC 7662 token introspection backed by the local verifier.
+//!
+//! [`Introspector::introspect`] verifies a presented token and answers with the standard
+//! introspection response; any failure (bad signature, expired, wrong issuer, unknown key, ...)
+//! yields `{"active": false}` and nothing else, as the RFC requires. [`axum_handler`] (feature
+//! `axum`) serves it as a form-encoded `POST` endpoint; the `ubl-issuer` binary exposes it at
+//! `/introspect`. Callers of the endpoint must be authenticated (RFC 7662 §2.1): put the route
+//! behind your client-authentication layer.
+
+use crate::{verify_ed25519_jwt_with_cache, Aud, Claims, Jwks, JwksCache, VerifyOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    #[serde(default)]
+    pub token_type_hint: Option<String>,
+}
+
+impl IntrospectionRequest {
+    /// Parses an `application/x-www-form-urlencoded` body.
+    pub fn from_form(body: &str) -> Option<Self> {
+        let mut req = IntrospectionRequest { token: String::new(), token_type_hint: None };
+        for pair in body.split('&') {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            match k {
+                "token" => req.token = form_decode(v)?,
+                "token_type_hint" => req.token_type_hint = Some(form_decode(v)?),
+                _ => {}
+            }
+        }
+        (!req.token.is_empty()).then_some(req)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Aud>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+impl IntrospectionResponse {
+    pub fn inactive() -> Self { Self::default() }
+
+    pub fn from_claims(claims: &Claims) -> Self {
+        let client_id = ["client_id", "azp"].iter().find_map(|k| claims.extra.get(*k).and_then(Json::as_str)).map(str::to_string);
+        Self {
+            active: true,
+            scope: claims.scope.clone(),
+            client_id,
+            token_type: Some("Bearer".into()),
+            exp: claims.exp,
+            iat: claims.iat,
+            nbf: claims.nbf,
+            sub: Some(claims.sub.clone()),
+            aud: claims.aud.clone(),
+            iss: claims.iss.clone(),
+            jti: claims.jti.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Introspector {
+    pub jwks_uri: String,
+    pub opts: VerifyOptions,
+    cache: JwksCache,
+}
+
+impl Introspector {
+    /// Keys are fetched from `jwks_uri` and cached for five minutes.
+    pub fn new(jwks_uri: &str, opts: VerifyOptions) -> Self { Self { jwks_uri: jwks_uri.to_string(), opts, cache: JwksCache::new(300) } }
+
+    /// Uses a fixed key set (e.g. the issuer's own JWKS) instead of fetching `jwks_uri`.
+    pub fn with_jwks(mut self, jwks: Jwks) -> Self {
+        self.cache = JwksCache::new(i64::MAX);
+        self.cache.put(&self.jwks_uri, jwks);
+        self
+    }
+
+    pub fn introspect(&self, token: &str) -> IntrospectionResponse {
+        verify_ed25519_jwt_with_cache(token, &self.jwks_uri, &self.cache, &self.opts).map(|c| IntrospectionResponse::from_claims(&c)).unwrap_or_default()
+    }
+}
+
+/// Axum handler: `Router::new().route("/introspect", post(axum_handler)).with_state(Arc::new(introspector))`.
+/// JWKS cache misses fetch synchronously, so seed or warm the cache at startup.
+#[cfg(feature = "axum")]
+pub async fn axum_handler(
+    axum::extract::State(introspector): axum::extract::State<std::sync::Arc<Introspector>>,
+    axum::Form(req): axum::Form<IntrospectionRequest>,
+) -> axum::Json<IntrospectionResponse> {
+    axum::Json(introspector.introspect(&req.token))
+}
+
+fn form_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                out.push(u8::from_str_radix(s.get(i + 1..i + 3)?, 16).ok()?);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issuer::TokenIssuer;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn active_tokens_are_described_and_failures_are_inactive() {
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(40)));
+        let introspector = Introspector::new("mem://issuer", VerifyOptions::default().with_issuer("https://issuer.internal")).with_jwks(issuer.jwks());
+
+        let token = issuer.mint(&serde_json::from_value(serde_json::json!({"sub":"svc-a","scope":"orders:read","claims":{"client_id":"cli-7"}})).unwrap()).unwrap();
+        let req = IntrospectionRequest::from_form(&format!("token={}&token_type_hint=access_token", token)).unwrap();
+        let active = introspector.introspect(&req.token);
+        assert!(active.active);
+        assert_eq!((active.sub.as_deref(), active.client_id.as_deref(), active.scope.as_deref()), (Some("svc-a"), Some("cli-7"), Some("orders:read")));
+
+        let other = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(41)));
+        let forged = other.mint(&serde_json::from_value(serde_json::json!({"sub":"svc-a"})).unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(introspector.introspect(&forged)).unwrap(), serde_json::json!({"active": false}));
+        assert_eq!(IntrospectionRequest::from_form("token=a%2Bb+c").unwrap().token, "a+b c");
+    }
+}
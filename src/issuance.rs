@@ -0,0 +1,265 @@
+//! Append-only ledger of issued tokens, for proving what was (not) issued.
+//!
+//! Each issued token appends a canonical-JSON [`LedgerEntry`] that links to the hash of the
+//! previous one. Signed [`Checkpoint`]s, made every `checkpoint_every` entries or on demand,
+//! commit to the chain head and two RFC 9162 Merkle roots: one over the entries in issuance order
+//! and one over the jtis in sorted order. Against a checkpoint, [`IssuanceLedger::prove`] shows
+//! that a jti was issued (its entry and audit path) or that it was not (the two adjacent jtis
+//! that would surround it). Auditors holding a full export can also re-walk the chain with
+//! [`verify_chain`].
+
+use crate::receipts::{canonical_bytes, sign_detached, verify_detached_with, DetachedSignature};
+use crate::transparency::{audit_path, leaf, tree_hash, verify_inclusion, Hash};
+use crate::{from_hex, now_ts, to_hex, Claims, SignError, VerifyError};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub seq: u64,
+    pub jti: String,
+    pub sub: String,
+    #[serde(default)]
+    pub iss: Option<String>,
+    #[serde(default)]
+    pub iat: Option<i64>,
+    #[serde(default)]
+    pub exp: Option<i64>,
+    /// Hex SHA-256 of the canonical claims.
+    pub claims_hash: String,
+    /// Hex leaf hash of the previous entry (zeros for the first).
+    pub prev: String,
+}
+
+impl LedgerEntry {
+    /// RFC 9162 leaf hash of the canonical entry.
+    pub fn hash(&self) -> Result<Hash, SignError> { Ok(leaf(&canonical_bytes(self)?)) }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointBody {
+    pub size: u64,
+    /// Hex hash of the last entry.
+    pub head: String,
+    /// Hex Merkle root over entries in issuance order.
+    pub root: String,
+    /// Hex Merkle root over jtis in sorted order.
+    pub jti_root: String,
+    pub issued_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub body: CheckpointBody,
+    pub signature: DetachedSignature,
+}
+
+/// A sorted-order neighbour of an unissued jti.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Neighbour {
+    pub jti: String,
+    pub index: u64,
+    pub proof: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum JtiProof {
+    Issued { checkpoint: Checkpoint, entry: LedgerEntry, proof: Vec<String> },
+    NotIssued { checkpoint: Checkpoint, neighbours: Vec<Neighbour> },
+}
+
+struct State { entries: Vec<LedgerEntry>, hashes: Vec<Hash>, by_jti: HashMap<String, usize>, checkpoints: Vec<Checkpoint> }
+
+pub struct IssuanceLedger {
+    key: SigningKey,
+    kid: String,
+    pub checkpoint_every: u64,
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for IssuanceLedger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IssuanceLedger").field("kid", &self.kid).field("checkpoint_every", &self.checkpoint_every).finish_non_exhaustive()
+    }
+}
+
+impl IssuanceLedger {
+    /// Checkpoints are signed with `key`; by default one is made every 1000 entries.
+    pub fn new(key: SigningKey, kid: &str) -> Self {
+        let state = State { entries: Vec::new(), hashes: Vec::new(), by_jti: HashMap::new(), checkpoints: Vec::new() };
+        Self { key, kid: kid.to_string(), checkpoint_every: 1000, state: Mutex::new(state) }
+    }
+
+    pub fn with_checkpoint_every(mut self, n: u64) -> Self { self.checkpoint_every = n.max(1); self }
+
+    /// Records issued `claims`; each jti may only be recorded once.
+    pub fn append(&self, claims: &Claims) -> Result<LedgerEntry, SignError> { Ok(self.append_with(claims, |c| Ok((c, ())))?.0) }
+
+    /// Like [`append`](Self::append), but runs `issue` on `claims` once the jti is known to be
+    /// unused and records the claims it returns. The ledger stays locked while `issue` runs, so a
+    /// token is either issued and recorded or neither; anything `issue` does (e.g. anchoring in a
+    /// transparency log) happens only for claims the ledger will accept.
+    pub fn append_with<T>(&self, claims: &Claims, issue: impl FnOnce(Claims) -> Result<(Claims, T), SignError>) -> Result<(LedgerEntry, T), SignError> {
+        let jti = claims.jti.clone().ok_or_else(|| SignError::Policy("issuance ledger requires a jti".into()))?;
+        let mut st = self.state.lock();
+        if st.by_jti.contains_key(&jti) { return Err(SignError::Policy(format!("jti '{}' already issued", jti))); }
+        let (claims, issued) = issue(claims.clone())?;
+        if claims.jti.as_deref() != Some(jti.as_str()) { return Err(SignError::Policy("jti changed during issuance".into())); }
+        let entry = LedgerEntry {
+            seq: st.entries.len() as u64,
+            jti: jti.clone(),
+            sub: claims.sub.clone(),
+            iss: claims.iss.clone(),
+            iat: claims.iat,
+            exp: claims.exp,
+            claims_hash: to_hex(&Sha256::digest(canonical_bytes(&claims.to_map())?)),
+            prev: st.hashes.last().map(|h| to_hex(h)).unwrap_or_else(|| GENESIS.into()),
+        };
+        let hash = entry.hash()?;
+        st.hashes.push(hash);
+        st.by_jti.insert(jti, entry.seq as usize);
+        st.entries.push(entry.clone());
+        if (st.entries.len() as u64).is_multiple_of(self.checkpoint_every) { self.checkpoint_locked(&mut st)?; }
+        Ok((entry, issued))
+    }
+
+    /// Signs a checkpoint over everything appended so far.
+    pub fn checkpoint(&self) -> Result<Checkpoint, SignError> { self.checkpoint_locked(&mut self.state.lock()) }
+
+    pub fn latest_checkpoint(&self) -> Option<Checkpoint> { self.state.lock().checkpoints.last().cloned() }
+
+    pub fn checkpoints(&self) -> Vec<Checkpoint> { self.state.lock().checkpoints.clone() }
+
+    pub fn entries(&self) -> Vec<LedgerEntry> { self.state.lock().entries.clone() }
+
+    /// Proves whether `jti` was issued as of `checkpoint` (which must come from this ledger).
+    pub fn prove(&self, jti: &str, checkpoint: &Checkpoint) -> Result<JtiProof, SignError> {
+        let st = self.state.lock();
+        let size = checkpoint.body.size as usize;
+        if size > st.entries.len() { return Err(SignError::Policy("checkpoint is not from this ledger".into())); }
+        let hex = |path: Vec<Hash>| path.iter().map(|h| to_hex(h)).collect::<Vec<_>>();
+        if let Some(&i) = st.by_jti.get(jti).filter(|&&i| i < size) {
+            return Ok(JtiProof::Issued { checkpoint: checkpoint.clone(), entry: st.entries[i].clone(), proof: hex(audit_path(i, &st.hashes[..size])) });
+        }
+        let sorted = sorted_jtis(&st.entries[..size]);
+        let leaves: Vec<Hash> = sorted.iter().map(|j| leaf(j.as_bytes())).collect();
+        let at = sorted.partition_point(|j| j.as_str() < jti);
+        let neighbours = [at.checked_sub(1), Some(at).filter(|&i| i < sorted.len())]
+            .into_iter()
+            .flatten()
+            .map(|i| Neighbour { jti: sorted[i].clone(), index: i as u64, proof: hex(audit_path(i, &leaves)) })
+            .collect();
+        Ok(JtiProof::NotIssued { checkpoint: checkpoint.clone(), neighbours })
+    }
+
+    fn checkpoint_locked(&self, st: &mut State) -> Result<Checkpoint, SignError> {
+        let leaves: Vec<Hash> = sorted_jtis(&st.entries).iter().map(|j| leaf(j.as_bytes())).collect();
+        let body = CheckpointBody {
+            size: st.entries.len() as u64,
+            head: st.hashes.last().map(|h| to_hex(h)).unwrap_or_else(|| GENESIS.into()),
+            root: to_hex(&tree_hash(&st.hashes)),
+            jti_root: to_hex(&tree_hash(&leaves)),
+            issued_at: now_ts(),
+        };
+        let checkpoint = Checkpoint { signature: sign_detached(&body, &self.key, &self.kid)?, body };
+        st.checkpoints.push(checkpoint.clone());
+        Ok(checkpoint)
+    }
+}
+
+fn sorted_jtis(entries: &[LedgerEntry]) -> Vec<String> {
+    let mut jtis: Vec<String> = entries.iter().map(|e| e.jti.clone()).collect();
+    jtis.sort();
+    jtis
+}
+
+fn fail(why: &str) -> VerifyError { VerifyError::Transparency(why.to_string()) }
+
+fn hash_hex(s: &str) -> Result<Hash, VerifyError> { from_hex(s).and_then(|b| b.try_into().ok()).ok_or_else(|| fail("malformed hash")) }
+
+/// Checks the checkpoint signature and the proof; returns whether `jti` was issued.
+pub fn verify_proof(proof: &JtiProof, jti: &str, key: &VerifyingKey) -> Result<bool, VerifyError> {
+    let (JtiProof::Issued { checkpoint, .. } | JtiProof::NotIssued { checkpoint, .. }) = proof;
+    verify_detached_with(&checkpoint.body, &checkpoint.signature, |_| Ok(*key))?;
+    let size = checkpoint.body.size;
+    let path = |p: &[String]| p.iter().map(|h| hash_hex(h)).collect::<Result<Vec<Hash>, _>>();
+    match proof {
+        JtiProof::Issued { entry, proof, .. } => {
+            if entry.jti != jti { return Err(fail("entry is for another jti")); }
+            let leaf_hash = entry.hash().map_err(|_| VerifyError::Json)?;
+            if !verify_inclusion(leaf_hash, entry.seq, size, &path(proof)?, hash_hex(&checkpoint.body.root)?) { return Err(fail("entry not in checkpoint")); }
+            Ok(true)
+        }
+        JtiProof::NotIssued { neighbours, .. } => {
+            let root = hash_hex(&checkpoint.body.jti_root)?;
+            for n in neighbours {
+                if !verify_inclusion(leaf(n.jti.as_bytes()), n.index, size, &path(&n.proof)?, root) { return Err(fail("neighbour not in checkpoint")); }
+            }
+            let adjacent = match neighbours.as_slice() {
+                [] => size == 0,
+                [only] if only.jti.as_str() < jti => only.index + 1 == size,
+                [only] => only.index == 0 && jti < only.jti.as_str(),
+                [lo, hi] => lo.index + 1 == hi.index && lo.jti.as_str() < jti && jti < hi.jti.as_str(),
+                _ => false,
+            };
+            if !adjacent { return Err(fail("neighbours do not bracket the jti")); }
+            Ok(false)
+        }
+    }
+}
+
+/// Re-walks an exported chain and checks it ends at `checkpoint`'s head and root.
+pub fn verify_chain(entries: &[LedgerEntry], checkpoint: &Checkpoint, key: &VerifyingKey) -> Result<(), VerifyError> {
+    verify_detached_with(&checkpoint.body, &checkpoint.signature, |_| Ok(*key))?;
+    let mut prev = GENESIS.to_string();
+    let mut hashes = Vec::with_capacity(entries.len());
+    for (i, e) in entries.iter().take(checkpoint.body.size as usize).enumerate() {
+        if e.seq != i as u64 || e.prev != prev { return Err(fail(&format!("chain broken at entry {}", i))); }
+        let h = e.hash().map_err(|_| VerifyError::Json)?;
+        prev = to_hex(&h);
+        hashes.push(h);
+    }
+    if hashes.len() as u64 != checkpoint.body.size || prev != checkpoint.body.head || to_hex(&tree_hash(&hashes)) != checkpoint.body.root {
+        return Err(fail("chain does not match checkpoint"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn proves_issued_and_unissued_jtis() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(30));
+        let ledger = IssuanceLedger::new(key.clone(), "ledger").with_checkpoint_every(4);
+        for jti in ["b", "d", "f", "h", "j"] {
+            ledger.append(&serde_json::from_value(json!({"sub":"did:key:zSvc","jti":jti})).unwrap()).unwrap();
+        }
+        assert!(ledger.append(&serde_json::from_value(json!({"sub":"x","jti":"d"})).unwrap()).is_err());
+        let cp = ledger.latest_checkpoint().unwrap();
+        assert_eq!(cp.body.size, 4);
+
+        let vk = key.verifying_key();
+        for (jti, issued) in [("d", true), ("h", true), ("a", false), ("e", false), ("z", false), ("j", false)] {
+            assert_eq!(verify_proof(&ledger.prove(jti, &cp).unwrap(), jti, &vk).unwrap(), issued, "{}", jti);
+        }
+        let JtiProof::NotIssued { checkpoint, mut neighbours } = ledger.prove("e", &cp).unwrap() else { panic!() };
+        neighbours.remove(1);
+        assert!(verify_proof(&JtiProof::NotIssued { checkpoint, neighbours }, "e", &vk).is_err());
+
+        let latest = ledger.checkpoint().unwrap();
+        verify_chain(&ledger.entries(), &latest, &vk).unwrap();
+        let mut tampered = ledger.entries();
+        tampered[1].sub = "did:key:zMallory".into();
+        assert!(verify_chain(&tampered, &latest, &vk).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! A small reference token issuer for internal environments.
+//!
+//! [`TokenIssuer`] holds a keyring (the first key signs, all are published in its JWKS), caps
+//! lifetimes, and only mints claims its [`Policy`] accepts. With a transparency log attached, every
+//! token is anchored in it before signing; with an [`IssuanceLedger`] attached, every token is
+//! recorded in it exactly as signed, and anchored only once the ledger accepts its jti.
+//!
+//! [`TokenIssuer::mint_on_behalf_of`] is the delegation half of RFC 8693: it re-issues a verified
+//! inbound token for a calling service, recorded in the `act` claim. With a [`GuestPolicy`],
+//! [`TokenIssuer::mint_guest`] mints short-lived anonymous tokens whose `sub` is a fresh `anon:` id.
+//! The `ubl-issuer` binary (feature `issuer-bin`) serves it over HTTP.
+
+use crate::issuance::IssuanceLedger;
+use crate::policy::Policy;
+use crate::transparency::{anchor, TransparencyLog};
+use crate::{now_ts, random_id, sign_compact, Aud, Claims, GuestPolicy, Jwk, Jwks, SignError, ANONYMOUS_PREFIX};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const REGISTERED: [&str; 8] = ["sub", "iss", "aud", "exp", "nbf", "iat", "jti", "scope"];
+
+/// Body of a token request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenRequest {
+    pub sub: String,
+    #[serde(default)]
+    pub aud: Option<Aud>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub ttl_secs: Option<i64>,
+    /// Additional private claims; registered claim names are ignored here.
+    #[serde(default)]
+    pub claims: HashMap<String, Json>,
+}
+
+/// A service asking for a token on behalf of the subject of a verified inbound token.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DelegationRequest {
+    /// Subject of the calling service; becomes `act.sub`.
+    pub actor: String,
+    #[serde(default)]
+    pub aud: Option<Aud>,
+    /// Narrower scope; must be a subset of the inbound scopes (defaults to all of them).
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub ttl_secs: Option<i64>,
+}
+
+#[derive(Clone)]
+pub struct TokenIssuer {
+    pub issuer: String,
+    pub policy: Policy,
+    pub max_ttl_secs: i64,
+    keys: Vec<(String, SigningKey)>,
+    tlog: Option<Arc<dyn TransparencyLog>>,
+    ledger: Option<Arc<IssuanceLedger>>,
+    guests: Option<GuestPolicy>,
+}
+
+impl std::fmt::Debug for TokenIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenIssuer").field("issuer", &self.issuer).field("policy", &self.policy).field("max_ttl_secs", &self.max_ttl_secs).finish_non_exhaustive()
+    }
+}
+
+impl TokenIssuer {
+    /// `key` becomes the active signing key.
+    pub fn new(issuer: &str, kid: &str, key: SigningKey) -> Self {
+        Self { issuer: issuer.to_string(), policy: Policy::default(), max_ttl_secs: 3600, keys: vec![(kid.to_string(), key)], tlog: None, ledger: None, guests: None }
+    }
+
+    /// Adds a key that is published in the JWKS but not used for signing (e.g. the previous key).
+    pub fn with_published_key(mut self, kid: &str, key: SigningKey) -> Self { self.keys.push((kid.to_string(), key)); self }
+    pub fn with_policy(mut self, policy: Policy) -> Self { self.policy = policy; self }
+    pub fn with_max_ttl(mut self, secs: i64) -> Self { self.max_ttl_secs = secs; self }
+    pub fn with_transparency_log(mut self, log: Arc<dyn TransparencyLog>) -> Self { self.tlog = Some(log); self }
+    /// Enables [`TokenIssuer::mint_guest`] within `policy`.
+    pub fn with_guest_policy(mut self, policy: GuestPolicy) -> Self { self.guests = Some(policy); self }
+    pub fn with_ledger(mut self, ledger: Arc<IssuanceLedger>) -> Self { self.ledger = Some(ledger); self }
+
+    pub fn jwks(&self) -> Jwks {
+        Jwks { keys: self.keys.iter().map(|(kid, k)| Jwk { alg: Some("EdDSA".into()), use_: Some("sig".into()), ..Jwk::ed25519(kid, &k.verifying_key()) }).collect() }
+    }
+
+    /// Claims that would be minted for `req`: lifetime capped at `max_ttl_secs`, checked against the policy.
+    pub fn claims_for(&self, req: &TokenRequest) -> Result<Claims, SignError> {
+        if req.sub.starts_with(ANONYMOUS_PREFIX) { return Err(SignError::Policy("anonymous subjects are reserved for guest tokens".into())); }
+        let now = now_ts();
+        let ttl = req.ttl_secs.unwrap_or(self.max_ttl_secs).clamp(1, self.max_ttl_secs);
+        let claims = Claims {
+            sub: req.sub.clone(),
+            iss: Some(self.issuer.clone()),
+            aud: req.aud.clone(),
+            exp: Some(now + ttl),
+            nbf: None,
+            iat: Some(now),
+            jti: Some(random_id()),
+            scope: req.scope.clone(),
+            extra: req.claims.iter().filter(|(k, _)| !REGISTERED.contains(&k.as_str())).map(|(k, v)| (k.clone(), v.clone())).collect(),
+        };
+        self.policy.check(&claims).map_err(|e| SignError::Policy(e.to_string()))?;
+        Ok(claims)
+    }
+
+    /// Claims for `req.actor` acting on behalf of `inbound.sub`: scopes can only narrow, the
+    /// lifetime never outlives the inbound token, and an existing `act` chain is nested (RFC 8693 §4.1).
+    pub fn claims_on_behalf_of(&self, inbound: &Claims, req: &DelegationRequest) -> Result<Claims, SignError> {
+        let granted: Vec<&str> = inbound.scope.as_deref().unwrap_or("").split_whitespace().collect();
+        let scope = match &req.scope {
+            Some(s) => {
+                if let Some(extra) = s.split_whitespace().find(|s| !granted.contains(s)) {
+                    return Err(SignError::Policy(format!("scope '{}' not granted to the subject", extra)));
+                }
+                Some(s.clone())
+            }
+            None => inbound.scope.clone(),
+        };
+        let mut act = serde_json::Map::new();
+        act.insert("sub".into(), Json::String(req.actor.clone()));
+        if let Some(prior) = inbound.extra.get("act") { act.insert("act".into(), prior.clone()); }
+        let mut claims = self.claims_for(&TokenRequest { sub: inbound.sub.clone(), aud: req.aud.clone(), scope, ttl_secs: req.ttl_secs, claims: HashMap::new() })?;
+        claims.exp = claims.exp.min(inbound.exp).or(claims.exp);
+        claims.extra.insert("act".into(), Json::Object(act));
+        self.policy.check(&claims).map_err(|e| SignError::Policy(e.to_string()))?;
+        Ok(claims)
+    }
+
+    pub fn mint_on_behalf_of(&self, inbound: &Claims, req: &DelegationRequest) -> Result<String, SignError> { self.issue(&self.claims_on_behalf_of(inbound, req)?) }
+
+    /// Claims for a new guest: a random `anon:` subject, `scope` limited to the guest scopes, and
+    /// the guest lifetime (no longer than `max_ttl_secs`).
+    pub fn guest_claims(&self, scope: Option<&str>) -> Result<Claims, SignError> {
+        let guests = self.guests.as_ref().ok_or_else(|| SignError::Policy("guest tokens are not enabled".into()))?;
+        let now = now_ts();
+        let exp = now + guests.max_ttl_secs.min(self.max_ttl_secs);
+        if !guests.permits(scope, Some(now), Some(exp)) { return Err(SignError::Policy("scope not allowed for guests".into())); }
+        Ok(Claims {
+            sub: format!("{}{}", ANONYMOUS_PREFIX, random_id()),
+            iss: Some(self.issuer.clone()),
+            aud: None,
+            exp: Some(exp),
+            nbf: None,
+            iat: Some(now),
+            jti: Some(random_id()),
+            scope: scope.map(str::to_string),
+            extra: HashMap::new(),
+        })
+    }
+
+    pub fn mint_guest(&self, scope: Option<&str>) -> Result<String, SignError> { self.issue(&self.guest_claims(scope)?) }
+
+    pub fn issue(&self, claims: &Claims) -> Result<String, SignError> {
+        let (kid, key) = &self.keys[0];
+        let sign = |mut claims: Claims| -> Result<(Claims, String), SignError> {
+            if let Some(log) = &self.tlog { anchor(&mut claims, log.as_ref())?; }
+            let token = sign_compact(&claims, key, kid)?;
+            Ok((claims, token))
+        };
+        match &self.ledger {
+            // Anchoring runs under the ledger lock: a jti the ledger rejects never reaches the log.
+            Some(ledger) => Ok(ledger.append_with(claims, sign)?.1),
+            None => Ok(sign(claims.clone())?.1),
+        }
+    }
+
+    pub fn mint(&self, req: &TokenRequest) -> Result<String, SignError> { self.issue(&self.claims_for(req)?) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verify_ed25519_jwt_with_cache, JwksCache, VerifyOptions};
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn minted_tokens_verify_against_published_jwks() {
+        let policy = Policy::from_json(r#"{"audiences":["orders"],"scopes":{"any_of":["orders:read","orders:write"]}}"#).unwrap();
+        let issuer = TokenIssuer::new("https://issuer.internal", "k2", SigningKey::generate(&mut StdRng::seed_from_u64(26)))
+            .with_published_key("k1", SigningKey::generate(&mut StdRng::seed_from_u64(27)))
+            .with_policy(policy)
+            .with_max_ttl(300);
+        let cache = JwksCache::new(3600);
+        cache.put("mem://issuer", issuer.jwks());
+
+        let req: TokenRequest = serde_json::from_value(json!({"sub":"did:key:zSvc","aud":"orders","scope":"orders:read","ttl_secs":86400,"claims":{"tenant":"acme","iss":"spoofed"}})).unwrap();
+        let token = issuer.mint(&req).unwrap();
+        let opts = VerifyOptions::default().with_issuer("https://issuer.internal").with_audience("orders");
+        let claims = verify_ed25519_jwt_with_cache(&token, "mem://issuer", &cache, &opts).unwrap();
+        assert_eq!(claims.extra["tenant"], "acme");
+        assert!(claims.exp.unwrap() - claims.iat.unwrap() <= 300);
+
+        let wide = TokenRequest { scope: Some("admin".into()), ..req };
+        assert!(matches!(issuer.mint(&wide), Err(SignError::Policy(_))));
+        assert_eq!(issuer.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn delegated_tokens_narrow_scope_and_record_the_actor() {
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(31)));
+        let inbound: Claims = serde_json::from_value(json!({"sub":"user-7","scope":"orders:read orders:write","exp":now_ts() + 60,"act":{"sub":"gateway"}})).unwrap();
+        let req = DelegationRequest { actor: "svc-billing".into(), aud: Some(Aud::One("payments".into())), scope: Some("orders:read".into()), ttl_secs: Some(600) };
+        let claims = issuer.claims_on_behalf_of(&inbound, &req).unwrap();
+        assert_eq!(claims.sub, "user-7");
+        assert_eq!(claims.scope.as_deref(), Some("orders:read"));
+        assert_eq!(claims.extra["act"], json!({"sub":"svc-billing","act":{"sub":"gateway"}}));
+        assert_eq!(claims.exp, inbound.exp);
+
+        let wider = DelegationRequest { scope: Some("orders:read admin".into()), ..req };
+        assert!(matches!(issuer.claims_on_behalf_of(&inbound, &wider), Err(SignError::Policy(_))));
+    }
+
+    #[test]
+    fn guest_tokens_are_anonymous_and_constrained() {
+        let guests = GuestPolicy::default().with_scope("catalog:read").with_max_ttl(120);
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(32))).with_guest_policy(guests.clone());
+        let cache = JwksCache::new(3600);
+        cache.put("mem://issuer", issuer.jwks());
+        let opts = VerifyOptions::default().with_guest_policy(guests.clone());
+
+        let claims = verify_ed25519_jwt_with_cache(&issuer.mint_guest(Some("catalog:read")).unwrap(), "mem://issuer", &cache, &opts).unwrap();
+        assert!(claims.is_anonymous());
+        assert!(issuer.mint_guest(Some("orders:write")).is_err());
+        assert!(issuer.mint(&serde_json::from_value(json!({"sub":"anon:spoofed"})).unwrap()).is_err());
+
+        let wide = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(32))).with_guest_policy(guests.with_scope("orders:write"));
+        let token = wide.mint_guest(Some("orders:write")).unwrap();
+        assert!(matches!(verify_ed25519_jwt_with_cache(&token, "mem://issuer", &cache, &opts), Err(crate::VerifyError::Guest)));
+    }
+
+    #[test]
+    fn ledger_records_the_signed_claims() {
+        let log = Arc::new(crate::transparency::MemoryMerkleLog::new("log-1"));
+        let ledger = Arc::new(IssuanceLedger::new(SigningKey::generate(&mut StdRng::seed_from_u64(81)), "ledger"));
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(80))).with_transparency_log(log.clone()).with_ledger(ledger.clone());
+        let cache = JwksCache::new(3600);
+        cache.put("mem://issuer", issuer.jwks());
+
+        let token = issuer.mint(&serde_json::from_value(json!({"sub":"alice"})).unwrap()).unwrap();
+        let claims = verify_ed25519_jwt_with_cache(&token, "mem://issuer", &cache, &VerifyOptions::default()).unwrap();
+        assert!(claims.extra.contains_key("tlog"));
+        let signed = crate::receipts::canonical_bytes(&claims.to_map()).unwrap();
+        assert_eq!(ledger.entries()[0].claims_hash, crate::to_hex(&<sha2::Sha256 as sha2::Digest>::digest(signed)));
+
+        let reused: Claims = serde_json::from_value(json!({"sub":"bob","jti": claims.jti})).unwrap();
+        assert!(matches!(issuer.issue(&reused), Err(SignError::Policy(_))));
+        assert!(log.root(1).is_some() && log.root(2).is_none());
+    }
+}
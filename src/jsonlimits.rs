@@ -0,0 +1,103 @@
+//! Structural limits on token JSON, checked before it is parsed into values.
+//!
+//! `serde_json` silently keeps the last of duplicate object keys, so `{"aud":"a","aud":"b"}` can
+//! read differently here than in another JOSE library looking at the same bytes. With
+//! [`VerifyOptions::with_json_limits`](crate::VerifyOptions::with_json_limits) the header and payload
+//! are scanned first: duplicate keys, nesting beyond `max_depth` and more than `max_elements`
+//! values fail with [`VerifyError::JsonLimits`].
+
+use crate::VerifyError;
+use serde::de::{DeserializeSeed, Error as _, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonLimits {
+    pub reject_duplicate_keys: bool,
+    /// Maximum nesting of objects and arrays (the top-level object is depth 1).
+    pub max_depth: usize,
+    /// Maximum number of values, counting keys' values, array items and containers.
+    pub max_elements: usize,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self { Self { reject_duplicate_keys: true, max_depth: 16, max_elements: 1000 } }
+}
+
+impl JsonLimits {
+    pub fn with_max_depth(mut self, depth: usize) -> Self { self.max_depth = depth; self }
+    pub fn with_max_elements(mut self, n: usize) -> Self { self.max_elements = n; self }
+    pub fn allow_duplicate_keys(mut self) -> Self { self.reject_duplicate_keys = false; self }
+
+    /// Scans `json` against the limits without building a value.
+    pub fn check(&self, json: &[u8]) -> Result<(), VerifyError> {
+        let count = Cell::new(0);
+        let mut de = serde_json::Deserializer::from_slice(json);
+        Scan { limits: self, depth: 0, count: &count }.deserialize(&mut de).and_then(|_| de.end()).map_err(|e| {
+            let msg = e.to_string();
+            VerifyError::JsonLimits(msg.split(" at line").next().unwrap_or(&msg).to_string())
+        })
+    }
+}
+
+struct Scan<'a> { limits: &'a JsonLimits, depth: usize, count: &'a Cell<usize> }
+
+impl Scan<'_> {
+    fn bump<E: serde::de::Error>(&self) -> Result<(), E> {
+        self.count.set(self.count.get() + 1);
+        if self.count.get() > self.limits.max_elements { return Err(E::custom("too many JSON elements")); }
+        Ok(())
+    }
+    fn nested<E: serde::de::Error>(&self) -> Result<Self, E> {
+        self.bump()?;
+        if self.depth + 1 > self.limits.max_depth { return Err(E::custom("JSON nested too deeply")); }
+        Ok(Scan { limits: self.limits, depth: self.depth + 1, count: self.count })
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Scan<'_> {
+    type Value = ();
+    fn deserialize<D: serde::Deserializer<'de>>(self, d: D) -> Result<(), D::Error> { d.deserialize_any(self) }
+}
+
+impl<'de> Visitor<'de> for Scan<'_> {
+    type Value = ();
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { f.write_str("a JSON value") }
+    fn visit_bool<E: serde::de::Error>(self, _: bool) -> Result<(), E> { self.bump() }
+    fn visit_i64<E: serde::de::Error>(self, _: i64) -> Result<(), E> { self.bump() }
+    fn visit_u64<E: serde::de::Error>(self, _: u64) -> Result<(), E> { self.bump() }
+    fn visit_f64<E: serde::de::Error>(self, _: f64) -> Result<(), E> { self.bump() }
+    fn visit_str<E: serde::de::Error>(self, _: &str) -> Result<(), E> { self.bump() }
+    fn visit_unit<E: serde::de::Error>(self) -> Result<(), E> { self.bump() }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let inner = self.nested()?;
+        while seq.next_element_seed(Scan { ..inner })?.is_some() {}
+        Ok(())
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let inner = self.nested()?;
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if self.limits.reject_duplicate_keys && !keys.insert(key.clone()) { return Err(A::Error::custom(format!("duplicate JSON key '{}'", key))); }
+            map.next_value_seed(Scan { ..inner })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicates_depth_and_size_are_limited() {
+        let limits = JsonLimits::default().with_max_depth(3).with_max_elements(8);
+        assert!(limits.check(br#"{"sub":"a","aud":["x","y"],"ctx":{"k":1}}"#).is_ok());
+        let dup = limits.check(br#"{"sub":"a","aud":"api","aud":"admin"}"#).unwrap_err();
+        assert_eq!(dup.to_string(), "JSON rejected: duplicate JSON key 'aud'");
+        assert!(limits.allow_duplicate_keys().check(br#"{"aud":"api","aud":"admin"}"#).is_ok());
+        assert!(matches!(limits.check(br#"{"a":{"b":{"c":{}}}}"#), Err(VerifyError::JsonLimits(_))));
+        assert!(matches!(limits.check(br#"{"a":[1,2,3,4,5,6,7,8]}"#), Err(VerifyError::JsonLimits(_))));
+    }
+}
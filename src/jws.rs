@@ -0,0 +1,237 @@
+//! Detached-content JWS (RFC 7515 Appendix F), optionally with an unencoded payload (RFC 7797).
+//!
+//! A detached JWS travels as `header..signature` next to the bytes it covers, so a large canonical
+//! document is sent once instead of again inside the token. With `b64: false` (which must be
+//! listed in `crit`) the signature covers the raw payload bytes rather than their base64url form.
+//! Any other `crit` parameter is rejected. `alg`, key selection and key constraints follow the
+//! same [`VerifyOptions`] as JWT verification.
+//!
+//! [`JwsJson`] is the JSON serialization (RFC 7515 §7.2) in either its general (`signatures`) or
+//! flattened form; [`verify_json_jws`] accepts it when at least one signature verifies.
+
+use crate::receipts::canonical_bytes;
+use crate::{load_jwks, verify_jws_signature, Header, JwksCache, SignError, VerifyError, VerifyOptions, GLOBAL_JWKS};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+
+/// Header parameters this module understands when listed in `crit`.
+const UNDERSTOOD: &[&str] = &["b64"];
+
+/// Signs `payload` as a detached EdDSA JWS; `b64 = false` signs the raw bytes (RFC 7797).
+pub fn sign_detached_jws(payload: &[u8], key: &SigningKey, kid: &str, b64: bool) -> Result<String, SignError> {
+    let header = match b64 {
+        true => serde_json::json!({"alg": "EdDSA", "kid": kid}),
+        false => serde_json::json!({"alg": "EdDSA", "kid": kid, "b64": false, "crit": ["b64"]}),
+    };
+    let header = B64URL.encode(canonical_bytes(&header)?);
+    let sig = key.sign(&signing_input(&header, payload, b64));
+    Ok(format!("{}..{}", header, B64URL.encode(sig.to_bytes())))
+}
+
+/// Verifies a detached JWS over `payload` with keys from `jwks_uri` (process-wide cache) and
+/// returns its protected header.
+pub fn verify_detached_jws(signature: &str, payload: &[u8], jwks_uri: &str, opts: &VerifyOptions) -> Result<Header, VerifyError> {
+    verify_detached_jws_with_cache(signature, payload, jwks_uri, &GLOBAL_JWKS, opts)
+}
+
+pub fn verify_detached_jws_with_cache(signature: &str, payload: &[u8], jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<Header, VerifyError> {
+    let (header_b64, sig_b64) = match signature.trim().split('.').collect::<Vec<_>>()[..] {
+        [h, "", s] => (h, s),
+        _ => return Err(VerifyError::BadFormat),
+    };
+    let header: Json = serde_json::from_slice(&B64URL.decode(header_b64).map_err(|_| VerifyError::Base64)?).map_err(|_| VerifyError::Json)?;
+    let b64 = check_crit(&header)?;
+    let sig = B64URL.decode(sig_b64).map_err(|_| VerifyError::Base64)?;
+    verify_jws_signature(&header, &signing_input(header_b64, payload, b64), &sig, opts, || load_jwks(jwks_uri, cache))?;
+    serde_json::from_value(header).map_err(|_| VerifyError::Json)
+}
+
+/// Checks `crit` and returns the `b64` mode.
+fn check_crit(header: &Json) -> Result<bool, VerifyError> {
+    let crit: Vec<&str> = match header.get("crit") {
+        None => Vec::new(),
+        Some(Json::Array(names)) if !names.is_empty() => names.iter().map(|n| n.as_str().ok_or(VerifyError::Json)).collect::<Result<_, _>>()?,
+        Some(_) => return Err(VerifyError::Json),
+    };
+    if let Some(unknown) = crit.iter().find(|n| !UNDERSTOOD.contains(n)) { return Err(VerifyError::Crit(unknown.to_string())); }
+    match header.get("b64") {
+        None => Ok(true),
+        Some(Json::Bool(b64)) if crit.contains(&"b64") => Ok(*b64),
+        Some(Json::Bool(_)) => Err(VerifyError::Crit("b64".into())),
+        Some(_) => Err(VerifyError::Json),
+    }
+}
+
+/// One signature of a [`JwsJson`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JwsSignature {
+    /// Base64url protected header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protected: Option<String>,
+    /// Unprotected header parameters; must not repeat protected ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<serde_json::Map<String, Json>>,
+    pub signature: String,
+}
+
+/// A JWS in JSON serialization; serializes in the general form.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JwsJson {
+    pub payload: String,
+    pub signatures: Vec<JwsSignature>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Wire {
+    General { payload: String, signatures: Vec<JwsSignature> },
+    Flattened { payload: String, #[serde(flatten)] signature: JwsSignature },
+}
+
+impl<'de> Deserialize<'de> for JwsJson {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(match Wire::deserialize(d)? {
+            Wire::General { payload, signatures } => JwsJson { payload, signatures },
+            Wire::Flattened { payload, signature } => JwsJson { payload, signatures: vec![signature] },
+        })
+    }
+}
+
+impl JwsJson {
+    /// Parses the general or the flattened form.
+    pub fn parse(json: &str) -> Result<Self, VerifyError> { serde_json::from_str(json).map_err(|_| VerifyError::Json) }
+
+    /// The flattened form, available when there is exactly one signature.
+    pub fn to_flattened(&self) -> Option<Json> {
+        let [sig] = &self.signatures[..] else { return None };
+        let mut out = serde_json::to_value(sig).ok()?;
+        out.as_object_mut()?.insert("payload".into(), Json::String(self.payload.clone()));
+        Some(out)
+    }
+}
+
+/// A verified [`JwsJson`]: its payload, and the signature that verified.
+#[derive(Debug, Clone)]
+pub struct VerifiedJws {
+    pub payload: Vec<u8>,
+    /// Protected and unprotected parameters of the accepted signature.
+    pub header: Header,
+    pub signature_index: usize,
+}
+
+/// Signs `payload` once per `(key, kid)` with EdDSA, in the general JSON serialization.
+pub fn sign_json_jws(payload: &[u8], signers: &[(&SigningKey, &str)]) -> Result<JwsJson, SignError> {
+    let payload_b64 = B64URL.encode(payload);
+    let signatures = signers
+        .iter()
+        .map(|(key, kid)| {
+            let protected = B64URL.encode(canonical_bytes(&serde_json::json!({"alg": "EdDSA", "kid": kid}))?);
+            let sig = key.sign(format!("{}.{}", protected, payload_b64).as_bytes());
+            Ok(JwsSignature { protected: Some(protected), header: None, signature: B64URL.encode(sig.to_bytes()) })
+        })
+        .collect::<Result<_, SignError>>()?;
+    Ok(JwsJson { payload: payload_b64, signatures })
+}
+
+/// Verifies `jws` with keys from `jwks_uri`; succeeds on the first signature that verifies and
+/// otherwise fails with the first signature's error.
+pub fn verify_json_jws(jws: &JwsJson, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<VerifiedJws, VerifyError> {
+    let mut first_err = None;
+    for (i, sig) in jws.signatures.iter().enumerate() {
+        match verify_one(&jws.payload, sig, jwks_uri, cache, opts) {
+            Ok((payload, header)) => return Ok(VerifiedJws { payload, header, signature_index: i }),
+            Err(e) => { first_err.get_or_insert(e); }
+        }
+    }
+    Err(first_err.unwrap_or(VerifyError::BadFormat))
+}
+
+fn verify_one(payload: &str, sig: &JwsSignature, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<(Vec<u8>, Header), VerifyError> {
+    let protected_b64 = sig.protected.as_deref().unwrap_or_default();
+    let protected: serde_json::Map<String, Json> = match protected_b64 {
+        "" => Default::default(),
+        p => serde_json::from_slice(&B64URL.decode(p).map_err(|_| VerifyError::Base64)?).map_err(|_| VerifyError::Json)?,
+    };
+    let b64 = check_crit(&Json::Object(protected.clone()))?;
+    let mut header = protected;
+    for (name, value) in sig.header.iter().flatten() {
+        if name == "crit" || name == "b64" || header.insert(name.clone(), value.clone()).is_some() { return Err(VerifyError::Json); }
+    }
+    let header = Json::Object(header);
+    let signature = B64URL.decode(&sig.signature).map_err(|_| VerifyError::Base64)?;
+    // Encoded or not (RFC 7797 §5.2), the payload member is signed exactly as transmitted.
+    verify_jws_signature(&header, format!("{}.{}", protected_b64, payload).as_bytes(), &signature, opts, || load_jwks(jwks_uri, cache))?;
+    let payload = match b64 {
+        true => B64URL.decode(payload).map_err(|_| VerifyError::Base64)?,
+        false => payload.as_bytes().to_vec(),
+    };
+    Ok((payload, serde_json::from_value(header).map_err(|_| VerifyError::Json)?))
+}
+
+fn signing_input(header_b64: &str, payload: &[u8], b64: bool) -> Vec<u8> {
+    let mut input = format!("{}.", header_b64).into_bytes();
+    match b64 {
+        true => input.extend_from_slice(B64URL.encode(payload).as_bytes()),
+        false => input.extend_from_slice(payload),
+    }
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Jwk, Jwks};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn json_serialization_accepts_any_matching_signature() {
+        let (ours, theirs) = (SigningKey::generate(&mut StdRng::seed_from_u64(68)), SigningKey::generate(&mut StdRng::seed_from_u64(69)));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("ours", &ours.verifying_key())] });
+        let opts = VerifyOptions::default();
+        let doc = br#"{"id":"ubl-2"}"#;
+
+        let general = sign_json_jws(doc, &[(&theirs, "theirs"), (&ours, "ours")]).unwrap();
+        let parsed = JwsJson::parse(&serde_json::to_string(&general).unwrap()).unwrap();
+        let verified = verify_json_jws(&parsed, "mem://jwks", &cache, &opts).unwrap();
+        assert_eq!((verified.payload.as_slice(), verified.signature_index, verified.header.kid.as_deref()), (&doc[..], 1, Some("ours")));
+
+        let single = sign_json_jws(doc, &[(&ours, "ours")]).unwrap();
+        let flattened = JwsJson::parse(&single.to_flattened().unwrap().to_string()).unwrap();
+        assert_eq!(flattened, single);
+        assert!(verify_json_jws(&flattened, "mem://jwks", &cache, &opts).is_ok());
+        assert!(general.to_flattened().is_none());
+
+        let mut unprotected = single.clone();
+        unprotected.signatures[0].header = Some(serde_json::from_value(serde_json::json!({"kid":"theirs"})).unwrap());
+        assert!(matches!(verify_json_jws(&unprotected, "mem://jwks", &cache, &opts), Err(VerifyError::Json)));
+        let only_theirs = sign_json_jws(doc, &[(&theirs, "theirs")]).unwrap();
+        assert!(matches!(verify_json_jws(&only_theirs, "mem://jwks", &cache, &opts), Err(VerifyError::NoKey)));
+    }
+
+    #[test]
+    fn detached_signatures_cover_encoded_and_raw_payloads() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(67));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("doc", &key.verifying_key())] });
+        let doc = br#"{"amount":"12.50","id":"ubl-1"}"#;
+        let opts = VerifyOptions::default();
+
+        for b64 in [true, false] {
+            let sig = sign_detached_jws(doc, &key, "doc", b64).unwrap();
+            let header = verify_detached_jws_with_cache(&sig, doc, "mem://jwks", &cache, &opts).unwrap();
+            assert_eq!((header.kid.as_deref(), header.crit.is_empty()), (Some("doc"), b64));
+            assert!(matches!(verify_detached_jws_with_cache(&sig, br#"{"amount":"99","id":"ubl-1"}"#, "mem://jwks", &cache, &opts), Err(VerifyError::Signature)));
+        }
+
+        let attached = sign_detached_jws(doc, &key, "doc", true).unwrap().replace("..", ".e30.");
+        assert!(matches!(verify_detached_jws_with_cache(&attached, doc, "mem://jwks", &cache, &opts), Err(VerifyError::BadFormat)));
+        let header = |h: Json| format!("{}..{}", B64URL.encode(h.to_string()), B64URL.encode([0u8; 64]));
+        let uncritical = header(serde_json::json!({"alg":"EdDSA","kid":"doc","b64":false}));
+        assert!(matches!(verify_detached_jws_with_cache(&uncritical, doc, "mem://jwks", &cache, &opts), Err(VerifyError::Crit(n)) if n == "b64"));
+        let unknown = header(serde_json::json!({"alg":"EdDSA","kid":"doc","crit":["exp"],"exp":1}));
+        assert!(matches!(verify_detached_jws_with_cache(&unknown, doc, "mem://jwks", &cache, &opts), Err(VerifyError::Crit(n)) if n == "exp"));
+    }
+}
@@ -0,0 +1,98 @@
+//! Where verification keys come from.
+//!
+//! Every JWKS URI the crate is given is resolved through [`provider_for`]: `https://` and
+//! `http://` are fetched with `ureq`, and with the `did` feature a `did:web:` identifier resolves
+//! to the keys of its DID document (`did::DidWeb`). JWKS URIs can come from remote metadata, so
+//! they never reach local files or the environment. Air-gapped deployments pass [`FileJwks`] or
+//! [`EnvJwks`] explicitly, and other sources (a secrets manager, a config service) implement
+//! [`KeyProvider`]; both plug into a [`Verifier`](crate::Verifier) via
+//! [`VerifierBuilder::key_provider`](crate::VerifierBuilder::key_provider). A [`Jwks`] is itself
+//! a provider of fixed keys.
+
+use crate::{Jwk, Jwks, VerifyError};
+use std::path::PathBuf;
+
+pub trait KeyProvider: Send + Sync {
+    fn keys(&self) -> Result<Jwks, VerifyError>;
+}
+
+impl KeyProvider for Jwks {
+    fn keys(&self) -> Result<Jwks, VerifyError> { Ok(self.clone()) }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpJwks { pub uri: String }
+
+impl KeyProvider for HttpJwks {
+    fn keys(&self) -> Result<Jwks, VerifyError> {
+        if !self.uri.starts_with("https://") && !self.uri.starts_with("http://") { return Err(VerifyError::KeySource("unsupported JWKS URI scheme".into())); }
+        let resp = ureq::get(&self.uri).call().map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
+        parse(&resp.into_string().map_err(|e| VerifyError::JwksHttp(e.to_string()))?)
+    }
+}
+
+/// A JWKS document on disk, re-read on every fetch so rotated files are picked up.
+#[derive(Debug, Clone)]
+pub struct FileJwks { pub path: PathBuf }
+
+impl KeyProvider for FileJwks {
+    fn keys(&self) -> Result<Jwks, VerifyError> {
+        parse(&std::fs::read_to_string(&self.path).map_err(|e| VerifyError::KeySource(format!("{}: {}", self.path.display(), e)))?)
+    }
+}
+
+/// A JWKS document, or a single JWK, in an environment variable.
+#[derive(Debug, Clone)]
+pub struct EnvJwks { pub var: String }
+
+impl KeyProvider for EnvJwks {
+    fn keys(&self) -> Result<Jwks, VerifyError> {
+        parse(&std::env::var(&self.var).map_err(|e| VerifyError::KeySource(format!("${}: {}", self.var, e)))?)
+    }
+}
+
+/// The provider for a JWKS URI (see the module docs); schemes other than `https`, `http` and
+/// `did:web` fail with [`VerifyError::KeySource`] when fetched.
+pub fn provider_for(uri: &str) -> Box<dyn KeyProvider> {
+    #[cfg(feature = "did")]
+    if uri.starts_with("did:web:") {
+        if let Ok(did) = crate::did::DidWeb::new(uri) { return Box::new(did); }
+    }
+    Box::new(HttpJwks { uri: uri.to_string() })
+}
+
+/// Parses a JWKS document, accepting a bare JWK as a one-key set.
+fn parse(body: &str) -> Result<Jwks, VerifyError> {
+    serde_json::from_str::<Jwks>(body).or_else(|_| serde_json::from_str::<Jwk>(body).map(|k| Jwks { keys: vec![k] })).map_err(|_| VerifyError::JwksJson)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn local_sources_are_explicit_providers() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(60));
+        let jwk = Jwk::ed25519("local", &key.verifying_key());
+        let path = std::env::temp_dir().join(format!("ubl-auth-keyprovider-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_string(&Jwks { keys: vec![jwk.clone()] }).unwrap()).unwrap();
+        std::env::set_var("UBL_AUTH_TEST_JWK", serde_json::to_string(&jwk).unwrap());
+
+        let from_file = FileJwks { path: path.clone() }.keys().unwrap();
+        let from_env = EnvJwks { var: "UBL_AUTH_TEST_JWK".into() }.keys().unwrap();
+        assert_eq!((from_file.keys[0].kid.as_deref(), from_env.keys[0].kid.as_deref()), (Some("local"), Some("local")));
+
+        // A JWKS URI (possibly taken from remote metadata) cannot reach either.
+        for uri in [format!("file://{}", path.display()), "env://UBL_AUTH_TEST_JWK".to_string()] {
+            match provider_for(&uri).keys() {
+                Err(VerifyError::KeySource(msg)) => assert!(!msg.contains("UBL_AUTH_TEST") && !msg.contains("keyprovider")),
+                other => panic!("{} resolved locally: {:?}", uri, other),
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(FileJwks { path: path.clone() }.keys(), Err(VerifyError::KeySource(_))));
+        assert!(matches!(EnvJwks { var: "UBL_AUTH_TEST_UNSET".into() }.keys(), Err(VerifyError::KeySource(_))));
+    }
+}
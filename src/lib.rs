@@ -3,14 +3,94 @@
 
 /// Re-export json_atomic for LLM-first canonical JSON serialization.
 pub use json_atomic;
+pub use verifier::{MultiIssuerVerifier, Verifier, VerifierBuilder};
+#[cfg(feature = "async")]
+pub use asyncverify::{verify_ed25519_jwt_async, AsyncDecisionHook, JwksFetcher};
 
+// Lets `ubl-auth-derive` output (`::ubl_auth::...` paths) compile inside this crate too.
+#[cfg(feature = "derive")]
+extern crate self as ubl_auth;
+
+#[cfg(feature = "apikey")]
+pub mod apikey;
+#[cfg(feature = "async")]
+pub mod asyncverify;
+pub mod bundle;
+pub mod capability;
+#[cfg(feature = "cedar")]
+pub mod cedar;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+#[cfg(feature = "contactverify")]
+pub mod contactverify;
+#[cfg(feature = "csrf")]
+pub mod csrf;
+#[cfg(feature = "did")]
+pub mod did;
+pub mod discovery;
+pub mod dpop;
+#[cfg(feature = "frost")]
+pub mod frost;
+#[cfg(feature = "hdkeys")]
+pub mod hdkeys;
+pub mod httpsig;
+pub mod introspection;
+pub mod issuance;
+pub mod issuer;
+pub mod jsonlimits;
+pub mod jws;
+pub mod keyimport;
+pub mod keyprovider;
+pub mod magiclink;
+pub mod mapping;
+pub mod oneshot;
+pub mod opa;
+#[cfg(feature = "otp")]
+pub mod otp;
+#[cfg(feature = "pairwise")]
+pub mod pairwise;
+#[cfg(feature = "password")]
+pub mod password;
+pub mod pattern;
+pub mod pinning;
+pub mod policy;
+pub mod presets;
+pub mod propagation;
+pub mod receiptchain;
+pub mod receipts;
+pub mod refresh;
+pub mod replay;
+pub mod reporting;
+pub mod roles;
+pub mod secrets;
+#[cfg(feature = "session")]
+pub mod session;
+pub mod sessionstore;
+pub mod shamir;
+pub mod stepup;
+#[cfg(feature = "tsa")]
+pub mod timestamp;
+pub mod tokensource;
+pub mod toolcall;
+pub mod transparency;
+pub mod validate;
+pub mod verifier;
+#[cfg(feature = "webauthn")]
+pub mod webauthn;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+use arc_swap::ArcSwap;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
 use ed25519_dalek::{VerifyingKey, Signature};
 use once_cell::sync::Lazy;
-use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as Json;
-use std::{collections::HashMap, time::{SystemTime, UNIX_EPOCH}};
+use sha2::{Digest, Sha256};
+use std::{collections::{BTreeMap, HashMap, HashSet}, time::{SystemTime, UNIX_EPOCH}};
+
+/// `sub` prefix of anonymous (guest) tokens, e.g. `anon:3f9c...`.
+pub const ANONYMOUS_PREFIX: &str = "anon:";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
@@ -33,23 +113,408 @@ pub struct Claims {
     pub extra: HashMap<String, Json>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Aud {
     One(String),
     Many(Vec<String>),
 }
 
+impl Aud {
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        let s: &[String] = match self { Aud::One(s) => std::slice::from_ref(s), Aud::Many(v) => v };
+        s.iter().map(String::as_str)
+    }
+    pub fn contains(&self, aud: &str) -> bool { self.iter().any(|a| a == aud) }
+    pub fn as_vec(&self) -> Vec<String> { self.iter().map(str::to_string).collect() }
+    pub fn is_empty(&self) -> bool { matches!(self, Aud::Many(v) if v.is_empty()) }
+    /// Collapses a single-element `Many` into `One`; everything else is returned unchanged.
+    pub fn normalize(self) -> Self {
+        match self {
+            Aud::Many(mut v) if v.len() == 1 => Aud::One(v.remove(0)),
+            other => other,
+        }
+    }
+}
+impl From<&str> for Aud {
+    fn from(s: &str) -> Self { Aud::One(s.to_string()) }
+}
+impl From<String> for Aud {
+    fn from(s: String) -> Self { Aud::One(s) }
+}
+impl From<Vec<String>> for Aud {
+    fn from(v: Vec<String>) -> Self { Aud::Many(v).normalize() }
+}
+
+/// A JWS header as sent, e.g. from [`decode_unverified`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crit: Vec<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Json>,
+}
+
+/// Space-delimited OAuth scopes (RFC 6749 §3.3), e.g. from [`Claims::scopes`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scopes(std::collections::BTreeSet<String>);
+
+impl Scopes {
+    pub fn parse(scope: &str) -> Self { Self(scope.split_whitespace().map(str::to_string).collect()) }
+    pub fn has(&self, scope: &str) -> bool { self.0.contains(scope) }
+    pub fn has_all(&self, scopes: &[&str]) -> bool { scopes.iter().all(|s| self.has(s)) }
+    pub fn has_any(&self, scopes: &[&str]) -> bool { scopes.iter().any(|s| self.has(s)) }
+    pub fn iter(&self) -> impl Iterator<Item = &str> { self.0.iter().map(String::as_str) }
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+}
+impl std::fmt::Display for Scopes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str(&self.iter().collect::<Vec<_>>().join(" ")) }
+}
+impl<'a> FromIterator<&'a str> for Scopes {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self { Self(iter.into_iter().map(str::to_string).collect()) }
+}
+
+/// Claim-level comparison of two claim sets; names are sorted so the serialized form is canonical.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimsDiff {
+    pub retained: Vec<String>,
+    pub changed: Vec<String>,
+    pub dropped: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl ClaimsDiff {
+    pub fn is_empty(&self) -> bool { self.changed.is_empty() && self.dropped.is_empty() && self.added.is_empty() }
+}
+
+impl Claims {
+    pub(crate) fn to_map(&self) -> serde_json::Map<String, Json> {
+        match serde_json::to_value(self) {
+            Ok(Json::Object(m)) => m.into_iter().filter(|(_, v)| !v.is_null()).collect(),
+            _ => Default::default(),
+        }
+    }
+
+    /// The custom claim stored under the namespaced key `{namespace}{name}`.
+    pub fn get_custom_in(&self, namespace: &str, name: &str) -> Option<&Json> {
+        self.extra.get(&format!("{}{}", namespace, name))
+    }
+
+    /// Granted scopes: `scope`, or else an `scp` claim (array or string) as some providers send.
+    pub fn scopes(&self) -> Scopes {
+        match (&self.scope, self.extra.get("scp")) {
+            (Some(s), _) => Scopes::parse(s),
+            (None, Some(Json::Array(a))) => a.iter().filter_map(Json::as_str).collect(),
+            (None, Some(Json::String(s))) => Scopes::parse(s),
+            _ => Scopes::default(),
+        }
+    }
+
+    /// Whether this is a guest token (see [`ANONYMOUS_PREFIX`]).
+    pub fn is_anonymous(&self) -> bool { self.sub.starts_with(ANONYMOUS_PREFIX) }
+
+    /// Compares `self` (the original) with `other` (e.g. an exchanged or downscoped token).
+    pub fn diff(&self, other: &Claims) -> ClaimsDiff {
+        let (a, b) = (self.to_map(), other.to_map());
+        let mut d = ClaimsDiff::default();
+        for (k, v) in &a {
+            match b.get(k) {
+                Some(w) if w == v => d.retained.push(k.clone()),
+                Some(_) => d.changed.push(k.clone()),
+                None => d.dropped.push(k.clone()),
+            }
+        }
+        d.added = b.keys().filter(|k| !a.contains_key(*k)).cloned().collect();
+        // Sorted explicitly: map order depends on whether serde_json's `preserve_order` is enabled.
+        for names in [&mut d.retained, &mut d.changed, &mut d.dropped, &mut d.added] { names.sort(); }
+        d
+    }
+
+    /// Applies top-level `overrides` on top of `self` (issuer defaults); a `null` override removes the claim.
+    pub fn merged_with(&self, overrides: &serde_json::Map<String, Json>) -> Result<Claims, VerifyError> {
+        let mut m = self.to_map();
+        for (k, v) in overrides {
+            if v.is_null() { m.remove(k); } else { m.insert(k.clone(), v.clone()); }
+        }
+        serde_json::from_value(Json::Object(m)).map_err(|_| VerifyError::Json)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VerifyOptions {
     pub leeway_secs: i64,
     pub issuer: Option<String>,
     pub audience: Option<String>,
     pub now: Option<i64>,
+    /// Accept tokens past `exp` (signature and all other checks still apply); see [`Verified::expired`].
+    #[serde(default)]
+    pub allow_expired: bool,
+    /// Accept tokens without `kid` when the JWKS holds exactly one Ed25519 key.
+    #[serde(default)]
+    pub allow_missing_kid: bool,
+    /// When no key matches `kid`, try up to this many Ed25519 keys from the JWKS.
+    #[serde(default)]
+    pub try_all_keys: Option<usize>,
+    /// Reject tokens whose header/payload bytes are not the `json_atomic` canonical form.
+    #[serde(default)]
+    pub require_canonical: bool,
+    /// Prefix of namespaced custom claims (e.g. `https://example.com/`), used by [`VerifyOptions::get_custom`].
+    #[serde(default)]
+    pub claim_namespace: Option<String>,
+    #[serde(default)]
+    pub base64: Base64Policy,
+    #[serde(default)]
+    pub zip: ZipPolicy,
+    /// Accepted issuer patterns (see [`pattern`]); `iss` must equal `issuer` or match one of these.
+    #[serde(default)]
+    pub issuer_patterns: Vec<String>,
+    /// Accepted audience patterns (see [`pattern`]; e.g. `api://orders/*`), in addition to `audience`.
+    #[serde(default)]
+    pub audience_patterns: Vec<String>,
+    /// Allow/deny rules on `sub`, e.g. for break-glass blocking of a principal.
+    #[serde(default)]
+    pub subjects: SubjectPolicy,
+    /// Role implications used by [`VerifyOptions::has_role`].
+    #[serde(default)]
+    pub role_hierarchy: Option<std::sync::Arc<roles::RoleHierarchy>>,
+    /// Coerce float or string `exp`/`nbf`/`iat` values to integer seconds instead of failing.
+    #[serde(default)]
+    pub lenient_numeric_dates: bool,
+    /// Duplicate-key, depth and size limits applied to header and payload JSON.
+    #[serde(default)]
+    pub json_limits: Option<jsonlimits::JsonLimits>,
+    /// When set, anonymous tokens must stay within these limits.
+    #[serde(default)]
+    pub guests: Option<GuestPolicy>,
+    /// Scopes every token must hold (see [`Claims::scopes`]).
+    #[serde(default)]
+    pub required_scopes: Vec<String>,
+    /// Header `alg` values accepted; only `EdDSA` by default. `ES256` and `RS256` need features
+    /// `es256` and `rs256`.
+    #[serde(default = "default_allowed_algs")]
+    pub allowed_algs: Vec<String>,
+    #[serde(default)]
+    pub profile: Profile,
+    /// Required value of the `nonce` claim (the one sent in the authorization request).
+    #[serde(default)]
+    pub nonce: Option<String>,
+    /// Maximum age of the end-user authentication (`auth_time`), e.g. the `max_age` requested.
+    #[serde(default)]
+    pub max_auth_age_secs: Option<i64>,
+    /// Opt-in for `did::verify_self_issued` (feature `did`).
+    #[serde(default)]
+    pub allow_self_issued: bool,
+    /// Proof-of-possession the presenter has shown; the token's `cnf` must name the same key.
+    #[serde(default)]
+    pub confirmation: Option<Confirmation>,
+}
+
+fn default_allowed_algs() -> Vec<String> { vec!["EdDSA".into()] }
+
+/// Thumbprints of what the presenter holds, matched against the token's `cnf` claim: the mTLS
+/// client certificate (`x5t#S256`, RFC 8705) and/or a proof key (`jkt`, e.g. from
+/// [`dpop`]). Every member set here must be present in `cnf` and equal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Confirmation {
+    #[serde(default, rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jkt: Option<String>,
+}
+
+impl Confirmation {
+    /// Confirmation by the DER-encoded client certificate presented on the TLS connection.
+    pub fn certificate(der: &[u8]) -> Self { Self { x5t_s256: Some(cert_thumbprint(der)), jkt: None } }
+
+    fn check(&self, c: &Claims) -> Result<(), VerifyError> {
+        let cnf = c.extra.get("cnf");
+        let member = |name: &str| cnf.and_then(|m| m.get(name)).and_then(Json::as_str);
+        let pairs = [(self.x5t_s256.as_deref(), member("x5t#S256")), (self.jkt.as_deref(), member("jkt"))];
+        if pairs.iter().any(|(want, got)| want.is_some() && want != got) { return Err(VerifyError::ConfirmationMismatch); }
+        Ok(())
+    }
+}
+
+/// Base64url SHA-256 of a DER certificate: the `x5t#S256` form.
+pub fn cert_thumbprint(der: &[u8]) -> String { B64URL.encode(Sha256::digest(der)) }
+
+/// Limits for guest tokens: which scopes they may carry and how long they may live.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestPolicy {
+    #[serde(default)]
+    pub scopes: std::collections::BTreeSet<String>,
+    pub max_ttl_secs: i64,
+}
+
+impl Default for GuestPolicy {
+    fn default() -> Self { Self { scopes: Default::default(), max_ttl_secs: 900 } }
+}
+
+impl GuestPolicy {
+    pub fn with_scope(mut self, scope: &str) -> Self { self.scopes.insert(scope.to_string()); self }
+    pub fn with_max_ttl(mut self, secs: i64) -> Self { self.max_ttl_secs = secs; self }
+
+    /// Whether a guest token with `scope` and lifetime `[iat, exp]` is within the limits.
+    pub fn permits(&self, scope: Option<&str>, iat: Option<i64>, exp: Option<i64>) -> bool {
+        let scopes_ok = scope.unwrap_or("").split_whitespace().all(|s| self.scopes.contains(s));
+        let ttl_ok = matches!((iat, exp), (Some(iat), Some(exp)) if exp - iat <= self.max_ttl_secs);
+        scopes_ok && ttl_ok
+    }
+}
+
+type SubjectCheck = std::sync::Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Subject allow/deny rules, checked after the registered claims. Deny always wins; with an
+/// allow set only listed subjects pass; the callback (not serialized) can consult live state.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SubjectPolicy {
+    #[serde(default)]
+    pub allow: Option<std::collections::BTreeSet<String>>,
+    #[serde(default)]
+    pub deny: std::collections::BTreeSet<String>,
+    #[serde(skip)]
+    check: Option<SubjectCheck>,
+}
+
+impl std::fmt::Debug for SubjectPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubjectPolicy").field("allow", &self.allow).field("deny", &self.deny).field("check", &self.check.is_some()).finish()
+    }
+}
+
+impl SubjectPolicy {
+    pub fn allow(mut self, sub: &str) -> Self { self.allow.get_or_insert_with(Default::default).insert(sub.to_string()); self }
+    pub fn deny(mut self, sub: &str) -> Self { self.deny.insert(sub.to_string()); self }
+    /// Adds a callback that must return `true` for the subject to be accepted.
+    pub fn with_check<F: Fn(&str) -> bool + Send + Sync + 'static>(mut self, f: F) -> Self { self.check = Some(std::sync::Arc::new(f)); self }
+
+    pub fn permits(&self, sub: &str) -> bool {
+        !self.deny.contains(sub) && self.allow.as_ref().is_none_or(|a| a.contains(sub)) && self.check.as_ref().is_none_or(|f| f(sub))
+    }
+}
+
+/// Token profile whose header and claim rules apply on top of the generic checks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Profile {
+    /// No profile-specific rules.
+    #[default]
+    Jwt,
+    /// RFC 9068 JWT access tokens: `typ` must be `at+jwt` (or `application/at+jwt`), and `iss`,
+    /// `exp`, `aud`, `sub`, `iat` and `jti` must all be present. `alg: none` is refused outright,
+    /// whatever `allowed_algs` says.
+    OAuth2AccessToken,
+    /// OpenID Connect ID tokens (Core §3.1.3.7): `iss`, `aud`, `exp` and `iat` must be present, and
+    /// `azp` must name the configured audience when `aud` lists several. Combine with
+    /// [`VerifyOptions::with_nonce`] and [`VerifyOptions::with_max_auth_age`].
+    IdToken,
+}
+
+impl Profile {
+    fn check_header(self, header: &Json) -> Result<(), VerifyError> {
+        match self {
+            Profile::Jwt | Profile::IdToken => Ok(()),
+            Profile::OAuth2AccessToken => {
+                let get = |name: &str| header.get(name).and_then(Json::as_str).unwrap_or_default().to_ascii_lowercase();
+                if get("alg") == "none" { return Err(VerifyError::alg("none", &[] as &[&str])); }
+                if !matches!(get("typ").as_str(), "at+jwt" | "application/at+jwt") { return Err(VerifyError::Typ); }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_claims(self, c: &Claims, opts: &VerifyOptions) -> Result<(), VerifyError> {
+        let has_aud = c.aud.as_ref().is_some_and(|a| !a.is_empty());
+        let required: &[(&'static str, bool)] = match self {
+            Profile::Jwt => &[],
+            Profile::OAuth2AccessToken => &[("iss", c.iss.is_some()), ("exp", c.exp.is_some()), ("aud", has_aud), ("iat", c.iat.is_some()), ("jti", c.jti.is_some())],
+            Profile::IdToken => &[("iss", c.iss.is_some()), ("aud", has_aud), ("exp", c.exp.is_some()), ("iat", c.iat.is_some())],
+        };
+        if let Some((name, _)) = required.iter().find(|(_, ok)| !ok) { return Err(VerifyError::MissingClaim(name)); }
+        if self == Profile::IdToken {
+            let azp = c.extra.get("azp").and_then(Json::as_str);
+            if azp.is_none() && c.aud.as_ref().is_some_and(|a| a.iter().nth(1).is_some()) { return Err(VerifyError::MissingClaim("azp")); }
+            if let (Some(azp), Some(client_id)) = (azp, &opts.audience) {
+                if azp != client_id { return Err(VerifyError::Azp); }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Handling of the `zip` header parameter (DEFLATE-compressed payloads).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ZipPolicy {
+    /// Fail with [`VerifyError::Zip`] whenever `zip` is present.
+    #[default]
+    Reject,
+    /// Inflate `zip: "DEF"` payloads up to `max_decompressed` bytes (feature `zip`).
+    #[cfg(feature = "zip")]
+    Allow { max_decompressed: usize },
+}
+
+impl ZipPolicy {
+    fn inflate(&self, header: &Json, payload: Vec<u8>) -> Result<Vec<u8>, VerifyError> {
+        if header.get("zip").is_none() { return Ok(payload); }
+        match *self {
+            ZipPolicy::Reject => Err(VerifyError::Zip),
+            #[cfg(feature = "zip")]
+            ZipPolicy::Allow { max_decompressed } => {
+                if header["zip"].as_str() != Some("DEF") { return Err(VerifyError::Zip); }
+                use std::io::Read;
+                let mut out = Vec::new();
+                flate2::read::DeflateDecoder::new(&payload[..]).take(max_decompressed as u64 + 1).read_to_end(&mut out).map_err(|_| VerifyError::Zip)?;
+                if out.len() > max_decompressed { return Err(VerifyError::ZipLimit); }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Tolerance for non-canonical base64url segments. Both are rejected by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Base64Policy {
+    /// Accept trailing `=` padding.
+    #[serde(default)]
+    pub allow_padding: bool,
+    /// Accept the standard alphabet (`+`, `/`) instead of base64url.
+    #[serde(default)]
+    pub allow_standard_alphabet: bool,
+}
+
+impl Base64Policy {
+    pub fn lenient() -> Self { Self { allow_padding: true, allow_standard_alphabet: true } }
+
+    fn decode(&self, segment: &str) -> Result<Vec<u8>, VerifyError> {
+        let mut s = std::borrow::Cow::Borrowed(segment);
+        if s.ends_with('=') {
+            if !self.allow_padding { return Err(VerifyError::Base64Padding); }
+            s = std::borrow::Cow::Owned(s.trim_end_matches('=').to_string());
+        }
+        if s.contains(['+', '/']) {
+            if !self.allow_standard_alphabet { return Err(VerifyError::Base64Alphabet); }
+            s = std::borrow::Cow::Owned(s.replace('+', "-").replace('/', "_"));
+        }
+        B64URL.decode(s.as_bytes()).map_err(|_| VerifyError::Base64)
+    }
 }
 impl Default for VerifyOptions {
     fn default() -> Self {
-        Self { leeway_secs: 300, issuer: None, audience: None, now: None }
+        Self {
+            leeway_secs: 300, issuer: None, audience: None, now: None,
+            allow_expired: false, allow_missing_kid: false, try_all_keys: None, require_canonical: false,
+            claim_namespace: None, base64: Base64Policy::default(), zip: ZipPolicy::Reject,
+            issuer_patterns: Vec::new(), audience_patterns: Vec::new(), subjects: SubjectPolicy::default(),
+            role_hierarchy: None, lenient_numeric_dates: false, json_limits: None, guests: None,
+            required_scopes: Vec::new(), allowed_algs: default_allowed_algs(), profile: Profile::Jwt,
+            nonce: None, max_auth_age_secs: None, allow_self_issued: false, confirmation: None,
+        }
     }
 }
 impl VerifyOptions {
@@ -57,18 +522,92 @@ impl VerifyOptions {
     pub fn with_audience(mut self, aud: &str) -> Self { self.audience = Some(aud.to_string()); self }
     pub fn with_leeway(mut self, secs: i64) -> Self { self.leeway_secs = secs; self }
     pub fn with_now(mut self, now: i64) -> Self { self.now = Some(now); self }
+    pub fn allow_expired(mut self) -> Self { self.allow_expired = true; self }
+    pub fn allow_missing_kid(mut self) -> Self { self.allow_missing_kid = true; self }
+    pub fn with_try_all_keys(mut self, max_keys: usize) -> Self { self.try_all_keys = Some(max_keys); self }
+    pub fn require_canonical(mut self) -> Self { self.require_canonical = true; self }
+    pub fn with_claim_namespace(mut self, ns: &str) -> Self { self.claim_namespace = Some(ns.to_string()); self }
+    /// Accepts issuers matching `pattern`, e.g. `https://login.microsoftonline.com/{tid}/v2.0`.
+    pub fn with_issuer_pattern(mut self, pattern: &str) -> Self { self.issuer_patterns.push(pattern.to_string()); self }
+    pub fn with_audience_pattern(mut self, pattern: &str) -> Self { self.audience_patterns.push(pattern.to_string()); self }
+    pub fn with_subject_policy(mut self, p: SubjectPolicy) -> Self { self.subjects = p; self }
+    pub fn with_role_hierarchy(mut self, h: roles::RoleHierarchy) -> Self { self.role_hierarchy = Some(std::sync::Arc::new(h)); self }
+    pub fn with_base64_policy(mut self, policy: Base64Policy) -> Self { self.base64 = policy; self }
+    pub fn with_zip_policy(mut self, policy: ZipPolicy) -> Self { self.zip = policy; self }
+    pub fn with_guest_policy(mut self, policy: GuestPolicy) -> Self { self.guests = Some(policy); self }
+    pub fn lenient_numeric_dates(mut self) -> Self { self.lenient_numeric_dates = true; self }
+    pub fn with_json_limits(mut self, limits: jsonlimits::JsonLimits) -> Self { self.json_limits = Some(limits); self }
+    /// Replaces the accepted algorithms, e.g. `&["EdDSA", "ES256"]`.
+    pub fn with_allowed_algs(mut self, algs: &[&str]) -> Self { self.allowed_algs = algs.iter().map(|a| a.to_string()).collect(); self }
+    pub fn with_profile(mut self, profile: Profile) -> Self { self.profile = profile; self }
+    pub fn with_nonce(mut self, nonce: &str) -> Self { self.nonce = Some(nonce.to_string()); self }
+    pub fn with_max_auth_age(mut self, secs: i64) -> Self { self.max_auth_age_secs = Some(secs); self }
+    pub fn allow_self_issued(mut self) -> Self { self.allow_self_issued = true; self }
+    /// Requires `cnf.x5t#S256` to equal `thumbprint` (see [`cert_thumbprint`]).
+    pub fn with_cert_thumbprint(mut self, thumbprint: &str) -> Self { self.confirmation.get_or_insert_with(Default::default).x5t_s256 = Some(thumbprint.to_string()); self }
+    /// Requires `cnf.jkt` to equal `thumbprint` (see [`Jwk::thumbprint`]).
+    pub fn with_key_thumbprint(mut self, thumbprint: &str) -> Self { self.confirmation.get_or_insert_with(Default::default).jkt = Some(thumbprint.to_string()); self }
+    pub fn require_scopes(mut self, scopes: &[&str]) -> Self { self.required_scopes.extend(scopes.iter().map(|s| s.to_string())); self }
+
+    /// Looks up a custom claim by its short name, falling back to the namespaced key
+    /// (`{claim_namespace}{name}`, e.g. Auth0's `https://example.com/roles`) when a namespace is set.
+    pub fn get_custom<'a>(&self, claims: &'a Claims, name: &str) -> Option<&'a Json> {
+        claims.extra.get(name).or_else(|| claims.get_custom_in(self.claim_namespace.as_deref()?, name))
+    }
+
+    /// Roles from the `roles` claim (array or space-separated string, namespace-aware).
+    pub fn roles<'a>(&self, claims: &'a Claims) -> Vec<&'a str> {
+        match self.get_custom(claims, "roles") {
+            Some(Json::Array(a)) => a.iter().filter_map(Json::as_str).collect(),
+            Some(Json::String(s)) => s.split_whitespace().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether `claims` hold `role` directly or through [`role_hierarchy`](Self::role_hierarchy).
+    pub fn has_role(&self, claims: &Claims, role: &str) -> bool {
+        match &self.role_hierarchy {
+            Some(h) => h.grants(self.roles(claims), role),
+            None => self.roles(claims).contains(&role),
+        }
+    }
 }
 
+/// Outcome of a successful verification, with the claims and any conditions the options tolerated.
+#[derive(Debug, Clone)]
+pub struct Verified {
+    pub claims: Claims,
+    /// `exp` had passed; only possible when [`VerifyOptions::allow_expired`] is set.
+    pub expired: bool,
+    /// Variables captured by the issuer pattern that accepted `iss` (e.g. `tid`).
+    pub issuer_vars: BTreeMap<String, String>,
+    /// The concrete `aud` value that satisfied the audience check, if one was configured.
+    pub audience: Option<String>,
+}
+
+/// Why a token or credential was rejected. Subsystems with several failure modes of their own
+/// (API keys, sessions, passkeys, ...) report them through a nested error type.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum VerifyError {
     #[error("bad token format")]
     BadFormat,
     #[error("base64 decode failed")]
     Base64,
+    #[error("padded base64url segment not allowed")]
+    Base64Padding,
+    #[error("standard-alphabet base64 segment not allowed (expected base64url)")]
+    Base64Alphabet,
     #[error("json parse failed")]
     Json,
-    #[error("alg not allowed (expected EdDSA)")]
-    Alg,
+    #[error("JSON rejected: {0}")]
+    JsonLimits(String),
+    #[error("compressed payload (zip) not accepted")]
+    Zip,
+    #[error("decompressed payload exceeds size limit")]
+    ZipLimit,
+    #[error("alg '{alg}' not allowed (allowed: {})", .allowed.join(", "))]
+    Alg { alg: String, allowed: Vec<String> },
     #[error("missing kid in JWT header")]
     Kid,
     #[error("jwks http error: {0}")]
@@ -89,35 +628,327 @@ pub enum VerifyError {
     Audience,
     #[error("missing sub")]
     MissingSub,
+    #[error("token JSON is not in canonical form")]
+    NonCanonical,
+    #[cfg(feature = "apikey")]
+    #[error(transparent)]
+    ApiKey(#[from] apikey::ApiKeyError),
+    #[error("signature does not cover required component '{0}'")]
+    MissingComponent(String),
+    #[error("decryption failed")]
+    Decrypt,
+    #[cfg(feature = "csrf")]
+    #[error(transparent)]
+    Csrf(#[from] csrf::CsrfError),
+    #[error("arguments do not match the signed hash")]
+    ArgsMismatch,
+    #[error("replayed token or message")]
+    Replayed,
+    #[error("attenuation violated: {0}")]
+    Attenuation(String),
+    #[error("missing required claim '{0}'")]
+    MissingClaim(&'static str),
+    #[error("subject not accepted")]
+    Subject,
+    #[error("guest token exceeds the guest policy")]
+    Guest,
+    #[error("token already redeemed, expired or unknown")]
+    Redeemed,
+    #[error("token was issued for a different purpose")]
+    Purpose,
+    #[error("token is bound to a different request")]
+    Binding,
+    #[error("verification code mismatch")]
+    Code,
+    #[error("nonce mismatch")]
+    Nonce,
+    #[error("missing required role '{0}'")]
+    MissingRole(String),
+    #[error("group claims omitted (overage) and not resolved")]
+    GroupsOverage,
+    #[error("matching key is not usable for signature verification (use/key_ops)")]
+    KeyUse,
+    #[error(transparent)]
+    Pinning(#[from] pinning::PinError),
+    #[error("policy denied: {0}")]
+    Policy(String),
+    #[error("policy engine error: {0}")]
+    PolicyEngine(String),
+    #[error("claim mapping failed: {0}")]
+    Mapping(String),
+    #[error(transparent)]
+    RefreshToken(#[from] refresh::RefreshError),
+    #[error(transparent)]
+    Session(#[from] sessionstore::SessionError),
+    #[error("transparency log check failed: {0}")]
+    Transparency(String),
+    #[cfg(feature = "tsa")]
+    #[error(transparent)]
+    Timestamp(#[from] timestamp::TimestampError),
+    #[cfg(feature = "webauthn")]
+    #[error(transparent)]
+    WebAuthn(#[from] webauthn::WebAuthnError),
+    #[cfg(feature = "password")]
+    #[error(transparent)]
+    Password(#[from] password::PasswordError),
+    #[error("insufficient scope: '{0}' required")]
+    InsufficientScope(String),
+    #[error("key source unavailable: {0}")]
+    KeySource(String),
+    #[error("unexpected typ header")]
+    Typ,
+    #[error("token was issued to a different client (azp)")]
+    Azp,
+    #[error("end-user authentication is too old (auth_time)")]
+    AuthTime,
+    #[error("critical header parameter '{0}' missing or not understood")]
+    Crit(String),
+    #[error("receipt chain broken: {0}")]
+    Chain(String),
+    #[error("invalid DID: {0}")]
+    Did(String),
+    #[error("self-issued tokens are not allowed")]
+    SelfIssued,
+    #[error("invalid DPoP proof: {0}")]
+    Dpop(String),
+    #[error("token is confirmed to a different key or certificate")]
+    ConfirmationMismatch,
+}
+
+impl VerifyError {
+    pub(crate) fn alg(alg: &str, allowed: &[impl AsRef<str>]) -> Self {
+        VerifyError::Alg { alg: alg.to_owned(), allowed: allowed.iter().map(|a| a.as_ref().to_owned()).collect() }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SignError {
+    #[error("canonical encoding failed: {0}")]
+    Canonical(String),
+    #[error("encryption failed")]
+    Encrypt,
+    #[error("cannot attenuate: {0}")]
+    Attenuation(String),
+    #[error("claims not permitted by policy: {0}")]
+    Policy(String),
+    #[error("secret unavailable: {0}")]
+    Secret(String),
+    #[error("key import failed: {0}")]
+    KeyImport(String),
+    #[error("threshold signing failed: {0}")]
+    Threshold(String),
+    #[cfg(feature = "tsa")]
+    #[error(transparent)]
+    Timestamp(#[from] timestamp::TimestampError),
+    #[cfg(feature = "password")]
+    #[error(transparent)]
+    Password(#[from] password::PasswordError),
+    #[error("key share rejected: {0}")]
+    KeyShare(String),
+    #[error("missing covered component: {0}")]
+    MissingComponent(String),
+}
+
+/// A public key from a JWKS. New members are added as providers need them, so outside this crate
+/// keys are built with [`Jwk::ed25519`] (or `p256` / `rsa` behind their features) or deserialized.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub crv: Option<String>,
+    #[serde(default)]
+    pub x: Option<String>,
+    /// Second coordinate of an `EC` key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    /// Modulus and public exponent of an `RSA` key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    #[serde(default)]
+    pub kid: Option<String>,
+    /// Base64url SHA-1 / SHA-256 thumbprints of the key's certificate, for `x5t`-referenced tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x5t: Option<String>,
+    #[serde(default, rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+    /// Algorithm the key is restricted to; tokens with a different header `alg` are rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    /// Intended public-key use (`sig` or `enc`).
+    #[serde(default, rename = "use", skip_serializing_if = "Option::is_none")]
+    pub use_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_ops: Option<Vec<String>>,
+    /// Non-standard key validity window emitted by some rotation tooling; keys outside it are skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+}
+
+impl Jwk {
+    /// Public JWK (`kty: OKP`, `crv: Ed25519`) for `key`.
+    pub fn ed25519(kid: &str, key: &VerifyingKey) -> Self {
+        Jwk { kty: "OKP".into(), crv: Some("Ed25519".into()), x: Some(B64URL.encode(key.to_bytes())), kid: Some(kid.to_string()), ..Default::default() }
+    }
+
+    /// Public JWK (`kty: EC`, `crv: P-256`) for `key`.
+    #[cfg(feature = "es256")]
+    pub fn p256(kid: &str, key: &p256::ecdsa::VerifyingKey) -> Self {
+        let point = key.to_encoded_point(false);
+        let (x, y) = (point.x().map(|x| B64URL.encode(x)), point.y().map(|y| B64URL.encode(y)));
+        Jwk { kty: "EC".into(), crv: Some("P-256".into()), x, y, kid: Some(kid.to_string()), ..Default::default() }
+    }
+
+    /// Public JWK (`kty: RSA`) for `key`.
+    #[cfg(feature = "rs256")]
+    pub fn rsa(kid: &str, key: &rsa::RsaPublicKey) -> Self {
+        use rsa::traits::PublicKeyParts;
+        let (n, e) = (B64URL.encode(key.n().to_bytes_be()), B64URL.encode(key.e().to_bytes_be()));
+        Jwk { kty: "RSA".into(), n: Some(n), e: Some(e), kid: Some(kid.to_string()), ..Default::default() }
+    }
+
+    /// Whether `now` falls within the key's `nbf`/`exp` window (keys without one are always valid).
+    pub fn is_valid_at(&self, now: i64) -> bool {
+        self.nbf.is_none_or(|nbf| now >= nbf) && self.exp.is_none_or(|exp| now < exp)
+    }
+
+    /// Whether `use`/`key_ops` permit signature verification (absent members permit it). SPIFFE
+    /// bundles mark JWT-SVID signing keys with `use: jwt-svid`.
+    pub fn can_verify(&self) -> bool {
+        self.use_.as_deref().is_none_or(|u| u == "sig" || u == "jwt-svid") && self.key_ops.as_ref().is_none_or(|ops| ops.iter().any(|op| op == "verify"))
+    }
+
+    /// Whether the key may verify tokens signed with `alg` (a key without `alg` accepts any).
+    pub fn permits_alg(&self, alg: &str) -> bool {
+        self.alg.as_deref().is_none_or(|a| a == alg)
+    }
+
+    /// RFC 7638 thumbprint (base64url SHA-256 of the required members), the conventional derived `kid`.
+    pub fn thumbprint(&self) -> Option<String> {
+        let members = match self.kty.as_str() {
+            "OKP" => serde_json::json!({"crv": self.crv.as_ref()?, "kty": "OKP", "x": self.x.as_ref()?}),
+            "EC" => serde_json::json!({"crv": self.crv.as_ref()?, "kty": "EC", "x": self.x.as_ref()?, "y": self.y.as_ref()?}),
+            "RSA" => serde_json::json!({"e": self.e.as_ref()?, "kty": "RSA", "n": self.n.as_ref()?}),
+            _ => return None,
+        };
+        Some(B64URL.encode(Sha256::digest(serde_json::to_vec(&members).ok()?)))
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Jwk { pub kty:String, #[serde(default)] pub crv:Option<String>, #[serde(default)] pub x:Option<String>, #[serde(default)] pub kid:Option<String> }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Jwks { pub keys: Vec<Jwk> }
 
 #[derive(Debug, Clone)]
 pub struct JwksCacheEntry { pub jwks: Jwks, pub fetched_at: i64 }
+type JwksMap = ArcSwap<HashMap<String, std::sync::Arc<JwksCacheEntry>>>;
+
+/// Per-URI JWKS cache. Reads load an immutable snapshot of the map without taking a lock;
+/// [`JwksCache::put`] (rare: refreshes) swaps in an updated copy.
+///
+/// Past its TTL an entry can still be served: for `stale_while_revalidate_secs` while a background
+/// thread refetches it, and for `stale_if_error_secs` when a refetch fails. Both are off by default.
+///
+/// With `min_refetch_secs` set, a token whose key is not in the cached set (typically right after
+/// the issuer rotated) triggers one immediate refetch, at most once per interval and URI.
+///
+/// [`JwksCache::put_pinned`] entries ignore the TTL: they are served, and never refetched, until
+/// their own expiry, and not at all afterwards.
 #[derive(Debug)]
-pub struct JwksCache { ttl_secs: i64, inner: Mutex<HashMap<String, JwksCacheEntry>> }
+pub struct JwksCache {
+    ttl_secs: i64,
+    pub stale_while_revalidate_secs: i64,
+    pub stale_if_error_secs: i64,
+    pub min_refetch_secs: Option<i64>,
+    inner: std::sync::Arc<JwksMap>,
+    pinned: ArcSwap<HashMap<String, std::sync::Arc<(Jwks, i64)>>>,
+    refreshing: std::sync::Arc<parking_lot::Mutex<HashSet<String>>>,
+    forced: parking_lot::Mutex<HashMap<String, i64>>,
+}
 
-static GLOBAL_JWKS: Lazy<JwksCache> = Lazy::new(|| JwksCache::new(300));
+pub(crate) static GLOBAL_JWKS: Lazy<JwksCache> = Lazy::new(|| JwksCache::new(300));
 
 impl JwksCache {
-    pub fn new(ttl_secs: i64) -> Self { Self { ttl_secs, inner: Mutex::new(HashMap::new()) } }
-    pub fn put(&self, uri: &str, jwks: Jwks) {
-        let mut m = self.inner.lock();
-        m.insert(uri.to_string(), JwksCacheEntry{ jwks, fetched_at: now_ts() });
-    }
-    pub fn get_fresh(&self, uri: &str) -> Option<Jwks> {
-        let m = self.inner.lock();
-        if let Some(entry) = m.get(uri) {
-            if now_ts() - entry.fetched_at <= self.ttl_secs {
-                return Some(entry.jwks.clone());
-            }
+    pub fn new(ttl_secs: i64) -> Self {
+        Self { ttl_secs, stale_while_revalidate_secs: 0, stale_if_error_secs: 0, min_refetch_secs: None, inner: Default::default(), pinned: Default::default(), refreshing: Default::default(), forced: Default::default() }
+    }
+    pub fn with_stale_while_revalidate(mut self, secs: i64) -> Self { self.stale_while_revalidate_secs = secs; self }
+    pub fn with_stale_if_error(mut self, secs: i64) -> Self { self.stale_if_error_secs = secs; self }
+    pub fn with_refetch_on_unknown_kid(mut self, min_interval_secs: i64) -> Self { self.min_refetch_secs = Some(min_interval_secs); self }
+
+    pub fn put(&self, uri: &str, jwks: Jwks) { store_jwks(&self.inner, uri, jwks) }
+
+    /// Serves `jwks` for `uri` until `expires_at`, regardless of the TTL and without refetching.
+    /// Any regular entry for `uri` is dropped, so older keys cannot outlive the pinned ones.
+    pub fn put_pinned(&self, uri: &str, jwks: Jwks, expires_at: i64) {
+        let entry = std::sync::Arc::new((jwks, expires_at));
+        self.pinned.rcu(|m| {
+            let mut m = HashMap::clone(m);
+            m.insert(uri.to_string(), entry.clone());
+            m
+        });
+        self.inner.rcu(|m| {
+            let mut m = HashMap::clone(m);
+            m.remove(uri);
+            m
+        });
+    }
+
+    fn pinned(&self, uri: &str) -> Option<Jwks> {
+        self.pinned.load().get(uri).filter(|p| now_ts() < p.1).map(|p| p.0.clone())
+    }
+    pub fn get_fresh(&self, uri: &str) -> Option<Jwks> { self.get_within(uri, 0) }
+    /// An entry at most `stale_if_error_secs` past its TTL, for use when refetching has failed.
+    pub fn get_stale(&self, uri: &str) -> Option<Jwks> { self.get_within(uri, self.stale_if_error_secs) }
+
+    fn get_within(&self, uri: &str, stale_secs: i64) -> Option<Jwks> {
+        if let Some(jwks) = self.pinned(uri) { return Some(jwks); }
+        let entry = self.inner.load().get(uri).cloned()?;
+        (now_ts() - entry.fetched_at <= self.ttl_secs.saturating_add(stale_secs)).then(|| entry.jwks.clone())
+    }
+
+    /// A stale entry for `uris[0]` within the revalidation window, starting a background refetch
+    /// (from each of `uris` in order) unless one is already running.
+    fn revalidate(&self, uris: &[&str]) -> Option<Jwks> {
+        if self.stale_while_revalidate_secs <= 0 { return None; }
+        let primary = uris.first()?.to_string();
+        let jwks = self.get_within(&primary, self.stale_while_revalidate_secs)?;
+        if self.refreshing.lock().insert(primary.clone()) {
+            let (inner, refreshing) = (self.inner.clone(), self.refreshing.clone());
+            let uris: Vec<String> = uris.iter().map(|u| u.to_string()).collect();
+            std::thread::spawn(move || {
+                if let Some(fetched) = uris.iter().find_map(|u| fetch_jwks(u).ok()) { store_jwks(&inner, &primary, fetched); }
+                refreshing.lock().remove(&primary);
+            });
         }
-        None
+        Some(jwks)
     }
+
+    /// Whether an unknown-key refetch of `uri` may run now; records it if so.
+    pub(crate) fn claim_forced_refetch(&self, uri: &str) -> bool {
+        let Some(min) = self.min_refetch_secs else { return false };
+        if self.pinned(uri).is_some() { return false; }
+        let now = now_ts();
+        let mut forced = self.forced.lock();
+        if forced.get(uri).is_some_and(|last| now - last < min) { return false; }
+        forced.insert(uri.to_string(), now);
+        true
+    }
+}
+
+fn store_jwks(inner: &JwksMap, uri: &str, jwks: Jwks) {
+    let entry = std::sync::Arc::new(JwksCacheEntry { jwks, fetched_at: now_ts() });
+    inner.rcu(|m| {
+        let mut m = HashMap::clone(m);
+        m.insert(uri.to_string(), entry.clone());
+        m
+    });
 }
 
 pub fn verify_ed25519_jwt_with_jwks(token: &str, jwks_uri: &str, opts: &VerifyOptions) -> Result<Claims, VerifyError> {
@@ -125,72 +956,500 @@ pub fn verify_ed25519_jwt_with_jwks(token: &str, jwks_uri: &str, opts: &VerifyOp
 }
 
 pub fn verify_ed25519_jwt_with_cache(token: &str, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<Claims, VerifyError> {
-    let (header, payload, sig, signing_input) = split_and_decode(token)?;
+    verify_ed25519_jwt_detailed(token, jwks_uri, cache, opts).map(|v| v.claims)
+}
 
-    let alg = header.get("alg").and_then(|v| v.as_str()).ok_or(VerifyError::Alg)?;
-    if alg != "EdDSA" { return Err(VerifyError::Alg); }
-    let kid = header.get("kid").and_then(|v| v.as_str()).ok_or(VerifyError::Kid)?;
+pub fn verify_ed25519_jwt_detailed(token: &str, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<Verified, VerifyError> {
+    verify_ed25519_jwt_with_fallback(token, &[jwks_uri], cache, opts)
+}
 
-    let jwks = if let Some(j) = cache.get_fresh(jwks_uri) { j } else {
-        let fetched = fetch_jwks(jwks_uri)?;
-        cache.put(jwks_uri, fetched.clone());
-        fetched
-    };
-    let vk = key_by_kid(&jwks, kid).ok_or(VerifyError::NoKey)?;
+/// Like [`verify_ed25519_jwt_detailed`] with an ordered list of JWKS endpoints (primary first,
+/// then mirrors). On a cache miss each is tried in turn until one answers; the result is cached
+/// under the primary URI.
+pub fn verify_ed25519_jwt_with_fallback(token: &str, jwks_uris: &[&str], cache: &JwksCache, opts: &VerifyOptions) -> Result<Verified, VerifyError> {
+    verified_claims(verify_signature(token, jwks_uris, cache, opts)?, opts)
+}
 
-    vk.verify_strict(signing_input.as_bytes(), &sig).map_err(|_| VerifyError::Signature)?;
+/// Like [`verify_ed25519_jwt_with_cache`], deserializing the payload into `T` (e.g. a struct with
+/// typed tenant/role fields) once the registered claims have passed the usual checks.
+pub fn verify_ed25519_jwt_as<T: serde::de::DeserializeOwned>(token: &str, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<T, VerifyError> {
+    typed_claims(verify_signature(token, &[jwks_uri], cache, opts)?, opts)
+}
+
+pub(crate) fn typed_claims<T: serde::de::DeserializeOwned>(mut payload: Json, opts: &VerifyOptions) -> Result<T, VerifyError> {
+    if opts.lenient_numeric_dates { coerce_numeric_dates(&mut payload); }
+    verified_claims(payload.clone(), opts)?;
+    serde_json::from_value(payload).map_err(|_| VerifyError::Json)
+}
 
+/// Decodes `token`'s header and claims **without verifying anything**, for tooling and error
+/// pages; never base an authorization decision on the result. The signature segment may be empty.
+pub fn decode_unverified(token: &str) -> Result<(Header, Claims), VerifyError> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 { return Err(VerifyError::BadFormat); }
+    let opts = VerifyOptions::default();
+    let header: Json = serde_json::from_slice(&opts.base64.decode(parts[0])?).map_err(|_| VerifyError::Json)?;
+    let mut payload: Json = serde_json::from_slice(&opts.zip.inflate(&header, opts.base64.decode(parts[1])?)?).map_err(|_| VerifyError::Json)?;
+    coerce_numeric_dates(&mut payload);
+    let header = serde_json::from_value(header).map_err(|_| VerifyError::Json)?;
+    Ok((header, serde_json::from_value(payload).map_err(|_| VerifyError::Json)?))
+}
+
+/// Claim checks over a payload whose signature is already verified.
+pub(crate) fn verified_claims(mut payload: Json, opts: &VerifyOptions) -> Result<Verified, VerifyError> {
+    if opts.lenient_numeric_dates { coerce_numeric_dates(&mut payload); }
     let claims: Claims = serde_json::from_value(payload).map_err(|_| VerifyError::Json)?;
-    check_claims(&claims, opts)?;
-    Ok(claims)
+    let ClaimChecks { expired, issuer_vars, audience } = check_claims(&claims, opts)?;
+    Ok(Verified { claims, expired, issuer_vars, audience })
+}
+
+/// Rewrites float (`1700000000.5`) and string (`"1700000000"`) `exp`/`nbf`/`iat` values in a
+/// payload as integer seconds, rounding down. Values that are neither are left for deserialization to reject.
+pub fn coerce_numeric_dates(payload: &mut Json) {
+    for name in ["exp", "nbf", "iat"] {
+        let Some(v) = payload.get_mut(name) else { continue };
+        let secs = match v {
+            Json::Number(n) if !n.is_i64() => n.as_f64(),
+            Json::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+        if let Some(secs) = secs.filter(|f| f.is_finite() && f.abs() < i64::MAX as f64) { *v = Json::from(secs.floor() as i64); }
+    }
+}
+
+/// Checks format, `alg` and signature only, returning the raw payload without any claim checks.
+/// Meant for forensic tooling and migrations over historical tokens; never use it for access decisions.
+/// Only the key-selection options (e.g. `allow_missing_kid`) of `opts` are consulted.
+pub fn verify_ed25519_jwt_signature_only(token: &str, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<Json, VerifyError> {
+    verify_signature(token, &[jwks_uri], cache, opts)
+}
+
+pub(crate) fn verify_signature(token: &str, jwks_uris: &[&str], cache: &JwksCache, opts: &VerifyOptions) -> Result<Json, VerifyError> {
+    match verify_signature_with(token, opts, || load_jwks_any(jwks_uris, cache)) {
+        Err(VerifyError::NoKey) if jwks_uris.first().is_some_and(|p| cache.claim_forced_refetch(p)) => {
+            let jwks = refetch_jwks(jwks_uris, cache).map_err(|_| VerifyError::NoKey)?;
+            verify_signature_with(token, opts, || Ok(jwks))
+        }
+        res => res,
+    }
+}
+
+/// Format, `alg` and signature checks; `load` is only called once the token is well-formed.
+pub(crate) fn verify_signature_with<L: FnOnce() -> Result<Jwks, VerifyError>>(token: &str, opts: &VerifyOptions, load: L) -> Result<Json, VerifyError> {
+    let Decoded { header, payload, header_bytes, payload_bytes, sig, signing_input } = split_and_decode(token, opts)?;
+    if opts.require_canonical && !(is_canonical(&header, &header_bytes) && is_canonical(&payload, &payload_bytes)) {
+        return Err(VerifyError::NonCanonical);
+    }
+
+    opts.profile.check_header(&header)?;
+    verify_jws_signature(&header, signing_input.as_bytes(), &sig, opts, load)?;
+    Ok(payload)
+}
+
+/// `alg`, key selection and signature checks for a decoded JWS header over `signing_input`.
+pub(crate) fn verify_jws_signature<L: FnOnce() -> Result<Jwks, VerifyError>>(header: &Json, signing_input: &[u8], sig: &[u8], opts: &VerifyOptions, load: L) -> Result<(), VerifyError> {
+    // An unsigned token is a signature failure whatever its header claims (e.g. `alg: none`).
+    if sig.is_empty() { return Err(VerifyError::Signature); }
+    let alg = header.get("alg").and_then(|v| v.as_str()).ok_or_else(|| VerifyError::alg("", &opts.allowed_algs))?;
+    if !SUPPORTED_ALGS.contains(&alg) || !opts.allowed_algs.iter().any(|a| a == alg) { return Err(VerifyError::alg(alg, &opts.allowed_algs)); }
+    if matches!(alg, "EdDSA" | "ES256") && sig.len() != 64 { return Err(VerifyError::Signature); }
+    let key_ref = KeyRef::from_header(header);
+    if key_ref.is_none() && !opts.allow_missing_kid { return Err(VerifyError::Kid); }
+
+    let jwks = load()?;
+    let now = opts.now.unwrap_or_else(now_ts);
+    let vk = match key_ref {
+        Some(r) => key_matching(&jwks, r, alg, now)?,
+        None => Some(sole_key(&jwks, alg, now).ok_or(VerifyError::Kid)?),
+    };
+    match (vk, opts.try_all_keys) {
+        (Some(vk), _) if vk.verify(signing_input, sig) => Ok(()),
+        (Some(_), _) => Err(VerifyError::Signature),
+        (None, Some(max)) => {
+            let mut keys = usable_keys(&jwks, alg, now).map(|(_, vk)| vk).take(max).peekable();
+            if keys.peek().is_none() { return Err(VerifyError::NoKey); }
+            if !keys.any(|vk| vk.verify(signing_input, sig)) { return Err(VerifyError::Signature); }
+            Ok(())
+        }
+        (None, None) => Err(VerifyError::NoKey),
+    }
+}
+
+/// Canonically encodes a JWT header and payload with `json_atomic`.
+/// Returns `(b64_header, b64_payload, signing_input)`; sign the last one and append the signature.
+pub fn encode_canonical<H: Serialize, P: Serialize>(header: &H, payload: &P) -> Result<(String, String, String), SignError> {
+    let hdr = B64URL.encode(json_atomic::canonize(header).map_err(|e| SignError::Canonical(e.to_string()))?);
+    let pld = B64URL.encode(json_atomic::canonize(payload).map_err(|e| SignError::Canonical(e.to_string()))?);
+    let signing_input = format!("{}.{}", hdr, pld);
+    Ok((hdr, pld, signing_input))
 }
 
-fn split_and_decode(token: &str) -> Result<(Json, Json, Signature, String), VerifyError> {
+/// Signs `claims` as a compact EdDSA JWT with canonical header and payload; `iat` defaults to now
+/// and `exp` to one hour after `iat`. See [`TokenSigner`] for other defaults.
+pub fn sign_ed25519_jwt(claims: &Claims, key: &ed25519_dalek::SigningKey, kid: &str) -> Result<String, SignError> {
+    TokenSigner::new(key.clone(), kid).sign(claims)
+}
+
+/// Signs tokens under one key; unlike [`issuer::TokenIssuer`] it applies no policy, lifetime cap
+/// or `jti` of its own.
+#[derive(Clone)]
+pub struct TokenSigner {
+    key: ed25519_dalek::SigningKey,
+    pub kid: String,
+    /// Lifetime given to claims without `exp`.
+    pub default_ttl_secs: i64,
+    pub typ: String,
+}
+
+impl std::fmt::Debug for TokenSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenSigner").field("kid", &self.kid).field("default_ttl_secs", &self.default_ttl_secs).field("typ", &self.typ).finish_non_exhaustive()
+    }
+}
+
+impl TokenSigner {
+    pub fn new(key: ed25519_dalek::SigningKey, kid: &str) -> Self { Self { key, kid: kid.to_string(), default_ttl_secs: 3600, typ: "JWT".into() } }
+    pub fn with_default_ttl(mut self, secs: i64) -> Self { self.default_ttl_secs = secs; self }
+    pub fn with_typ(mut self, typ: &str) -> Self { self.typ = typ.to_string(); self }
+
+    /// The public key as a JWK, for publishing in a JWKS.
+    pub fn jwk(&self) -> Jwk { Jwk::ed25519(&self.kid, &self.key.verifying_key()) }
+
+    pub fn sign(&self, claims: &Claims) -> Result<String, SignError> {
+        use ed25519_dalek::Signer;
+        let mut claims = claims.clone();
+        let iat = *claims.iat.get_or_insert_with(now_ts);
+        claims.exp.get_or_insert(iat + self.default_ttl_secs);
+        let header = serde_json::json!({"alg": "EdDSA", "kid": self.kid, "typ": self.typ});
+        let (_, _, signing_input) = encode_canonical(&header, &claims.to_map())?;
+        let sig = self.key.sign(signing_input.as_bytes());
+        Ok(format!("{}.{}", signing_input, B64URL.encode(sig.to_bytes())))
+    }
+}
+
+/// Signs `payload` as a compact EdDSA JWT with `kid` in the header.
+pub(crate) fn sign_compact<P: Serialize>(payload: &P, key: &ed25519_dalek::SigningKey, kid: &str) -> Result<String, SignError> {
+    use ed25519_dalek::Signer;
+    let header = serde_json::json!({"alg": "EdDSA", "kid": kid, "typ": "JWT"});
+    let (_, _, signing_input) = encode_canonical(&header, payload)?;
+    let sig = key.sign(signing_input.as_bytes());
+    Ok(format!("{}.{}", signing_input, B64URL.encode(sig.to_bytes())))
+}
+
+/// Fills `buf` from the operating system's CSPRNG.
+pub(crate) fn fill_random(buf: &mut [u8]) {
+    use rand_core::RngCore;
+    rand_core::OsRng.fill_bytes(buf);
+}
+
+pub(crate) fn random_id() -> String {
+    let mut id = [0u8; 16];
+    fill_random(&mut id);
+    to_hex(&id)
+}
+
+struct Decoded {
+    header: Json,
+    payload: Json,
+    header_bytes: Vec<u8>,
+    payload_bytes: Vec<u8>,
+    sig: Vec<u8>,
+    signing_input: String,
+}
+
+fn split_and_decode(token: &str, opts: &VerifyOptions) -> Result<Decoded, VerifyError> {
     let parts: Vec<&str> = token.split('.').collect();
     if parts.len() != 3 { return Err(VerifyError::BadFormat); }
-    let header_json = String::from_utf8(B64URL.decode(parts[0].as_bytes()).map_err(|_| VerifyError::Base64)?).map_err(|_| VerifyError::Base64)?;
-    let payload_json = String::from_utf8(B64URL.decode(parts[1].as_bytes()).map_err(|_| VerifyError::Base64)?).map_err(|_| VerifyError::Base64)?;
-    let sig_bytes = B64URL.decode(parts[2].as_bytes()).map_err(|_| VerifyError::Base64)?;
-    let sig = Signature::from_bytes(sig_bytes[..].try_into().map_err(|_| VerifyError::Signature)?);
-    let header: Json = serde_json::from_str(&header_json).map_err(|_| VerifyError::Json)?;
-    let payload: Json = serde_json::from_str(&payload_json).map_err(|_| VerifyError::Json)?;
-    Ok((header, payload, sig, format!("{}.{}", parts[0], parts[1])))
-}
-
-fn fetch_jwks(uri: &str) -> Result<Jwks, VerifyError> {
-    let resp = ureq::get(uri).call().map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
-    let body = resp.into_string().map_err(|e| VerifyError::JwksHttp(e.to_string()))?;
-    serde_json::from_str(&body).map_err(|_| VerifyError::JwksJson)
-}
-
-fn key_by_kid(jwks: &Jwks, kid: &str) -> Option<VerifyingKey> {
-    for k in &jwks.keys {
-        if k.kty != "OKP" { continue; }
-        if k.crv.as_deref() != Some("Ed25519") { continue; }
-        let k_kid = k.kid.as_deref().unwrap_or_default();
-        if k_kid == kid || k_kid.is_empty() {
-            if let Some(x) = &k.x {
-                if let Ok(bytes) = B64URL.decode(x.as_bytes()) {
-                    if let Ok(vk) = VerifyingKey::from_bytes(bytes[..].try_into().ok()?) {
-                        return Some(vk);
-                    }
-                }
+    let b64 = &opts.base64;
+    let header_bytes = b64.decode(parts[0])?;
+    if let Some(limits) = &opts.json_limits { limits.check(&header_bytes)?; }
+    let header_json = std::str::from_utf8(&header_bytes).map_err(|_| VerifyError::Base64)?;
+    let header: Json = serde_json::from_str(header_json).map_err(|_| VerifyError::Json)?;
+    let payload_bytes = opts.zip.inflate(&header, b64.decode(parts[1])?)?;
+    if let Some(limits) = &opts.json_limits { limits.check(&payload_bytes)?; }
+    let payload_json = std::str::from_utf8(&payload_bytes).map_err(|_| VerifyError::Base64)?;
+    let payload: Json = serde_json::from_str(payload_json).map_err(|_| VerifyError::Json)?;
+    let sig = b64.decode(parts[2])?;
+    Ok(Decoded { header, payload, header_bytes, payload_bytes, sig, signing_input: format!("{}.{}", parts[0], parts[1]) })
+}
+
+fn is_canonical(value: &Json, bytes: &[u8]) -> bool {
+    json_atomic::canonize(value).is_ok_and(|c| AsRef::<[u8]>::as_ref(&c) == bytes)
+}
+
+pub(crate) fn load_jwks(jwks_uri: &str, cache: &JwksCache) -> Result<Jwks, VerifyError> {
+    load_jwks_any(&[jwks_uri], cache)
+}
+
+/// Cached JWKS for `uris[0]`, fetching from each of `uris` in order on a miss.
+pub(crate) fn load_jwks_any(uris: &[&str], cache: &JwksCache) -> Result<Jwks, VerifyError> {
+    let primary = *uris.first().ok_or(VerifyError::NoKey)?;
+    if let Some(j) = cache.get_fresh(primary) { return Ok(j); }
+    if let Some(j) = cache.revalidate(uris) { return Ok(j); }
+    refetch_jwks(uris, cache).or_else(|e| cache.get_stale(primary).ok_or(e))
+}
+
+/// Fetches from each of `uris` in order, caching the first answer under `uris[0]`.
+fn refetch_jwks(uris: &[&str], cache: &JwksCache) -> Result<Jwks, VerifyError> {
+    let primary = *uris.first().ok_or(VerifyError::NoKey)?;
+    let mut last_err = VerifyError::NoKey;
+    for uri in uris {
+        match fetch_jwks(uri) {
+            Ok(fetched) => {
+                cache.put(primary, fetched.clone());
+                return Ok(fetched);
             }
+            Err(e) => last_err = e,
         }
     }
-    None
+    Err(last_err)
+}
+
+/// Resolves the Ed25519 key for `kid` from a (cached) JWKS.
+pub(crate) fn resolve_key(jwks_uri: &str, cache: &JwksCache, kid: &str) -> Result<VerifyingKey, VerifyError> {
+    key_by_kid(&load_jwks(jwks_uri, cache)?, kid, "EdDSA", now_ts())?.ok_or(VerifyError::NoKey)
+}
+
+/// Result of probing one key source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceHealth {
+    pub uri: String,
+    /// Number of usable Ed25519 keys found.
+    pub keys: usize,
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl SourceHealth {
+    pub fn is_ok(&self) -> bool { self.error.is_none() && self.keys > 0 }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport { pub sources: Vec<SourceHealth> }
+
+impl HealthReport {
+    /// Every source is reachable, parseable and holds at least one usable key.
+    pub fn is_healthy(&self) -> bool { !self.sources.is_empty() && self.sources.iter().all(SourceHealth::is_ok) }
+    /// At least one source works (enough to verify when fallbacks are configured).
+    pub fn is_degraded(&self) -> bool { !self.is_healthy() && self.sources.iter().any(SourceHealth::is_ok) }
+}
+
+/// Fetches each JWKS (bypassing the cache) and reports reachability, parseability and usable key
+/// count, for readiness probes.
+pub fn health_check(jwks_uris: &[&str]) -> HealthReport {
+    let sources = jwks_uris.iter().map(|uri| {
+        let started = std::time::Instant::now();
+        let res = fetch_jwks(uri);
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match res {
+            Ok(jwks) => {
+                let keys = usable_keys(&jwks, "EdDSA", now_ts()).count();
+                let error = (keys == 0).then(|| "no usable Ed25519 keys".to_string());
+                SourceHealth { uri: uri.to_string(), keys, latency_ms, error }
+            }
+            Err(e) => SourceHealth { uri: uri.to_string(), keys: 0, latency_ms, error: Some(e.to_string()) },
+        }
+    }).collect();
+    HealthReport { sources }
+}
+
+/// Reads `jwks_uri` from `{issuer}`'s (cached) discovery document, checking the advertised issuer.
+pub(crate) fn discover_jwks_uri(issuer: &str) -> Result<String, VerifyError> {
+    discovery::metadata(issuer).map(|m| m.jwks_uri)
+}
+
+fn fetch_jwks(uri: &str) -> Result<Jwks, VerifyError> { keyprovider::provider_for(uri).keys() }
+
+/// How a token header points at its verification key.
+#[derive(Clone, Copy)]
+enum KeyRef<'a> { Kid(&'a str), X5tS256(&'a str), X5t(&'a str) }
+
+impl<'a> KeyRef<'a> {
+    /// `kid` wins; otherwise the SHA-256 certificate thumbprint, then the SHA-1 one.
+    fn from_header(header: &'a Json) -> Option<Self> {
+        let get = |name: &str| header.get(name).and_then(|v| v.as_str());
+        get("kid").map(KeyRef::Kid).or_else(|| get("x5t#S256").map(KeyRef::X5tS256)).or_else(|| get("x5t").map(KeyRef::X5t))
+    }
+
+    fn matches(self, k: &Jwk) -> bool {
+        match self {
+            KeyRef::Kid(kid) => {
+                let k_kid = k.kid.as_deref().unwrap_or_default();
+                k_kid == kid || k_kid.is_empty()
+            }
+            KeyRef::X5tS256(t) => k.x5t_s256.as_deref() == Some(t),
+            KeyRef::X5t(t) => k.x5t.as_deref() == Some(t),
+        }
+    }
+}
+
+/// JWS algorithms this build can verify.
+const SUPPORTED_ALGS: &[&str] = &["EdDSA", #[cfg(feature = "es256")] "ES256", #[cfg(feature = "rs256")] "RS256"];
+
+/// A JWK's public key, parsed for the algorithm a token names.
+#[derive(Clone)]
+enum PublicKey {
+    Ed25519(VerifyingKey),
+    #[cfg(feature = "es256")]
+    P256(p256::ecdsa::VerifyingKey),
+    #[cfg(feature = "rs256")]
+    Rsa(rsa::RsaPublicKey),
+}
+
+impl PublicKey {
+    fn from_jwk(k: &Jwk, alg: &str) -> Option<Self> {
+        match alg {
+            "EdDSA" => ed25519_key(k).map(PublicKey::Ed25519),
+            #[cfg(feature = "es256")]
+            "ES256" => p256_key(k).map(PublicKey::P256),
+            #[cfg(feature = "rs256")]
+            "RS256" => rsa_key(k).map(PublicKey::Rsa),
+            _ => None,
+        }
+    }
+
+    fn verify(&self, msg: &[u8], sig: &[u8]) -> bool {
+        match self {
+            PublicKey::Ed25519(vk) => <&[u8; 64]>::try_from(sig).is_ok_and(|s| vk.verify_strict(msg, &Signature::from_bytes(s)).is_ok()),
+            #[cfg(feature = "es256")]
+            PublicKey::P256(vk) => {
+                use p256::ecdsa::signature::Verifier;
+                p256::ecdsa::Signature::from_slice(sig).is_ok_and(|s| vk.verify(msg, &s).is_ok())
+            }
+            #[cfg(feature = "rs256")]
+            PublicKey::Rsa(key) => key.verify(rsa::Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(msg), sig).is_ok(),
+        }
+    }
+
+    #[allow(unreachable_patterns)]
+    fn into_ed25519(self) -> Option<VerifyingKey> {
+        match self {
+            PublicKey::Ed25519(vk) => Some(vk),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn key_by_kid(jwks: &Jwks, kid: &str, alg: &str, now: i64) -> Result<Option<VerifyingKey>, VerifyError> {
+    Ok(key_matching(jwks, KeyRef::Kid(kid), alg, now)?.and_then(PublicKey::into_ed25519))
+}
+
+/// Fails with [`VerifyError::KeyUse`] or [`VerifyError::Alg`] when the only keys matching `r`
+/// are restricted to another use or algorithm.
+fn key_matching(jwks: &Jwks, r: KeyRef<'_>, alg: &str, now: i64) -> Result<Option<PublicKey>, VerifyError> {
+    if let Some((_, vk)) = usable_keys(jwks, alg, now).find(|(k, _)| r.matches(k)) { return Ok(Some(vk)); }
+    let mut ineligible = jwks.keys.iter().filter(|k| r.matches(k) && k.is_valid_at(now) && PublicKey::from_jwk(k, alg).is_some());
+    match ineligible.next() {
+        Some(k) if !k.can_verify() => Err(VerifyError::KeyUse),
+        Some(k) => Err(VerifyError::alg(alg, k.alg.as_slice())),
+        None => Ok(None),
+    }
+}
+
+/// The only usable key for `alg` in the set, if there is exactly one.
+fn sole_key(jwks: &Jwks, alg: &str, now: i64) -> Option<PublicKey> {
+    let mut keys = usable_keys(jwks, alg, now);
+    let (_, vk) = keys.next()?;
+    if keys.next().is_some() { return None; }
+    Some(vk)
+}
+
+/// Verification keys for `alg` that are currently within their validity window.
+fn usable_keys<'a>(jwks: &'a Jwks, alg: &'a str, now: i64) -> impl Iterator<Item = (&'a Jwk, PublicKey)> {
+    jwks.keys.iter().filter(move |k| k.is_valid_at(now) && k.can_verify() && k.permits_alg(alg)).filter_map(move |k| Some((k, PublicKey::from_jwk(k, alg)?)))
+}
+
+fn ed25519_key(k: &Jwk) -> Option<VerifyingKey> {
+    if k.kty != "OKP" || k.crv.as_deref() != Some("Ed25519") { return None; }
+    let bytes = B64URL.decode(k.x.as_ref()?.as_bytes()).ok()?;
+    VerifyingKey::from_bytes(bytes[..].try_into().ok()?).ok()
+}
+
+#[cfg(feature = "es256")]
+fn p256_key(k: &Jwk) -> Option<p256::ecdsa::VerifyingKey> {
+    if k.kty != "EC" || k.crv.as_deref() != Some("P-256") { return None; }
+    let (x, y) = (B64URL.decode(k.x.as_ref()?.as_bytes()).ok()?, B64URL.decode(k.y.as_ref()?.as_bytes()).ok()?);
+    if x.len() != 32 || y.len() != 32 { return None; }
+    p256::ecdsa::VerifyingKey::from_sec1_bytes(&[&[4u8][..], &x, &y].concat()).ok()
+}
+
+/// RSA keys under 2048 bits are ignored.
+#[cfg(feature = "rs256")]
+fn rsa_key(k: &Jwk) -> Option<rsa::RsaPublicKey> {
+    if k.kty != "RSA" { return None; }
+    let (n, e) = (B64URL.decode(k.n.as_ref()?.as_bytes()).ok()?, B64URL.decode(k.e.as_ref()?.as_bytes()).ok()?);
+    let n = rsa::BigUint::from_bytes_be(&n);
+    if n.bits() < 2048 { return None; }
+    rsa::RsaPublicKey::new(n, rsa::BigUint::from_bytes_be(&e)).ok()
+}
+
+/// Wildcard match where `*` matches any (possibly empty) run of characters.
+pub(crate) fn glob_match(pattern: &str, s: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 { return pattern == s; }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if s.len() < first.len() + last.len() || !s.starts_with(first) || !s.ends_with(last) { return false; }
+    let mut rest = &s[first.len()..s.len() - last.len()];
+    for mid in &parts[1..parts.len() - 1] {
+        match rest.find(mid) {
+            Some(i) => rest = &rest[i + mid.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Rejects a timestamp more than `max_skew` seconds before or after `now`. `ts` may come from an
+/// attacker, so the arithmetic saturates instead of overflowing.
+pub(crate) fn check_timestamp(ts: i64, now: i64, max_skew: i64) -> Result<(), VerifyError> {
+    let age = now.saturating_sub(ts);
+    if age > max_skew { return Err(VerifyError::Expired); }
+    if age < max_skew.saturating_neg() { return Err(VerifyError::NotYetValid); }
+    Ok(())
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() }
+
+pub(crate) fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 == 1 { return None; }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
 }
 
 pub fn now_ts() -> i64 {
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
-    now
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64
+}
+
+/// What the registered-claim checks tolerated or matched.
+struct ClaimChecks {
+    expired: bool,
+    issuer_vars: BTreeMap<String, String>,
+    audience: Option<String>,
 }
 
-fn check_claims(c: &Claims, opts: &VerifyOptions) -> Result<(), VerifyError> {
+fn check_claims(c: &Claims, opts: &VerifyOptions) -> Result<ClaimChecks, VerifyError> {
     let now = opts.now.unwrap_or_else(now_ts);
     if c.sub.is_empty() { return Err(VerifyError::MissingSub); }
+    opts.profile.check_claims(c, opts)?;
+    if let Some(nonce) = &opts.nonce {
+        match c.extra.get("nonce").and_then(Json::as_str) {
+            Some(n) if n == nonce => {}
+            Some(_) => return Err(VerifyError::Nonce),
+            None => return Err(VerifyError::MissingClaim("nonce")),
+        }
+    }
+    if let Some(confirmation) = &opts.confirmation { confirmation.check(c)?; }
+    if let Some(max) = opts.max_auth_age_secs {
+        let auth_time = c.extra.get("auth_time").and_then(Json::as_i64).ok_or(VerifyError::MissingClaim("auth_time"))?;
+        let age = now.saturating_sub(auth_time);
+        if age > max.saturating_add(opts.leeway_secs) || age < opts.leeway_secs.saturating_neg() { return Err(VerifyError::AuthTime); }
+    }
+    let mut expired = false;
     if let Some(exp) = c.exp {
-        if now > exp + opts.leeway_secs { return Err(VerifyError::Expired); }
+        if now > exp + opts.leeway_secs {
+            if !opts.allow_expired { return Err(VerifyError::Expired); }
+            expired = true;
+        }
     }
     if let Some(nbf) = c.nbf {
         if now + opts.leeway_secs < nbf { return Err(VerifyError::NotYetValid); }
@@ -198,18 +1457,30 @@ fn check_claims(c: &Claims, opts: &VerifyOptions) -> Result<(), VerifyError> {
     if let Some(iat) = c.iat {
         if iat > now + opts.leeway_secs { return Err(VerifyError::NotYetValid); }
     }
-    if let Some(ref iss) = opts.issuer {
-        if c.iss.as_deref() != Some(iss) { return Err(VerifyError::Issuer); }
+    if !opts.subjects.permits(&c.sub) { return Err(VerifyError::Subject); }
+    if let Some(guests) = opts.guests.as_ref().filter(|_| c.is_anonymous()) {
+        if !guests.permits(c.scope.as_deref(), c.iat, c.exp) { return Err(VerifyError::Guest); }
     }
-    if let Some(ref aud) = opts.audience {
-        match &c.aud {
-            None => return Err(VerifyError::Audience),
-            Some(Aud::One(s)) if s != aud => return Err(VerifyError::Audience),
-            Some(Aud::Many(v)) if !v.iter().any(|x| x == aud) => return Err(VerifyError::Audience),
-            _ => {}
-        }
+    let issuer_vars = check_issuer(c.iss.as_deref(), opts)?;
+    let audience = check_audience(c.aud.as_ref(), opts)?;
+    if !opts.required_scopes.is_empty() {
+        let held = c.scopes();
+        if let Some(missing) = opts.required_scopes.iter().find(|s| !held.has(s)) { return Err(VerifyError::InsufficientScope(missing.clone())); }
     }
-    Ok(())
+    Ok(ClaimChecks { expired, issuer_vars, audience })
+}
+
+fn check_audience(aud: Option<&Aud>, opts: &VerifyOptions) -> Result<Option<String>, VerifyError> {
+    if opts.audience.is_none() && opts.audience_patterns.is_empty() { return Ok(None); }
+    let accepted = |a: &str| opts.audience.as_deref() == Some(a) || opts.audience_patterns.iter().any(|p| pattern::matches(p, a));
+    aud.and_then(|aud| aud.iter().find(|a| accepted(a))).map(|a| Some(a.to_string())).ok_or(VerifyError::Audience)
+}
+
+pub(crate) fn check_issuer(iss: Option<&str>, opts: &VerifyOptions) -> Result<BTreeMap<String, String>, VerifyError> {
+    if opts.issuer.is_none() && opts.issuer_patterns.is_empty() { return Ok(BTreeMap::new()); }
+    let iss = iss.ok_or(VerifyError::Issuer)?;
+    if opts.issuer.as_deref() == Some(iss) { return Ok(BTreeMap::new()); }
+    opts.issuer_patterns.iter().find_map(|p| pattern::captures(p, iss)).ok_or(VerifyError::Issuer)
 }
 
 #[cfg(test)]
@@ -221,6 +1492,208 @@ mod tests {
     use base64::engine::general_purpose::URL_SAFE_NO_PAD as B64URL;
     use json_atomic::canonize;
 
+    fn test_key(seed: u64) -> (SigningKey, JwksCache) {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(seed));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks{ keys: vec![ Jwk::ed25519("test", &sk.verifying_key()) ]});
+        (sk, cache)
+    }
+
+    /// Serves `body` over HTTP for a single request and returns its URL.
+    pub(crate) fn serve_once(body: String) -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            if let Ok((mut s, _)) = listener.accept() {
+                let mut buf = [0u8; 4096];
+                let _ = s.read(&mut buf);
+                let _ = write!(s, "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", body.len(), body);
+            }
+        });
+        url
+    }
+
+    fn sign_jwt(sk: &SigningKey, header: &Json, payload: &Json) -> String {
+        let (_, _, msg) = encode_canonical(header, payload).unwrap();
+        let sig = sk.sign(msg.as_bytes());
+        format!("{}.{}", msg, B64URL.encode(sig.to_bytes()))
+    }
+
+    #[test]
+    fn only_allowed_algs_are_accepted() {
+        let (sk, cache) = test_key(62);
+        let payload = json!({"sub":"did:key:zAlg"});
+        let es256 = sign_jwt(&sk, &json!({"alg":"ES256","kid":"test"}), &payload);
+        let err = verify_ed25519_jwt_with_cache(&es256, "mem://jwks", &cache, &VerifyOptions::default()).unwrap_err();
+        assert!(matches!(&err, VerifyError::Alg { alg, allowed } if alg == "ES256" && allowed == &["EdDSA"]));
+        assert_eq!(err.to_string(), "alg 'ES256' not allowed (allowed: EdDSA)");
+        let eddsa = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &payload);
+        assert!(matches!(verify_ed25519_jwt_with_cache(&eddsa, "mem://jwks", &cache, &VerifyOptions::default().with_allowed_algs(&["ES256"])), Err(VerifyError::Alg { .. })));
+    }
+
+    #[cfg(feature = "es256")]
+    #[test]
+    fn es256_tokens_verify_against_ec_keys() {
+        use p256::ecdsa::signature::Signer as _;
+        let sk = p256::ecdsa::SigningKey::random(&mut StdRng::seed_from_u64(63));
+        let jwk = Jwk::p256("ec1", sk.verifying_key());
+        assert_eq!(serde_json::from_str::<Jwk>(&serde_json::to_string(&jwk).unwrap()).unwrap().y, jwk.y);
+        let cache = JwksCache::new(3600);
+        cache.put("mem://ec", Jwks { keys: vec![jwk, Jwk::ed25519("ed1", &test_key(62).0.verifying_key())] });
+        let (_, _, msg) = encode_canonical(&json!({"alg":"ES256","kid":"ec1"}), &json!({"sub":"did:key:zEc"})).unwrap();
+        let sig: p256::ecdsa::Signature = sk.sign(msg.as_bytes());
+        let token = format!("{}.{}", msg, B64URL.encode(sig.to_bytes()));
+
+        let opts = VerifyOptions::default().with_allowed_algs(&["EdDSA", "ES256"]);
+        assert_eq!(verify_ed25519_jwt_with_cache(&token, "mem://ec", &cache, &opts).unwrap().sub, "did:key:zEc");
+        assert!(matches!(verify_ed25519_jwt_with_cache(&token, "mem://ec", &cache, &VerifyOptions::default()), Err(VerifyError::Alg { .. })));
+        let confused = token.replacen(&msg[..msg.find('.').unwrap()], &B64URL.encode(r#"{"alg":"ES256","kid":"ed1"}"#), 1);
+        assert!(matches!(verify_ed25519_jwt_with_cache(&confused, "mem://ec", &cache, &opts), Err(VerifyError::NoKey)));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn rs256_tokens_verify_alongside_eddsa() {
+        let sk = rsa::RsaPrivateKey::new(&mut StdRng::seed_from_u64(64), 2048).unwrap();
+        let (ed, _) = test_key(64);
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("test", &ed.verifying_key()), Jwk::rsa("legacy", &sk.to_public_key())] });
+        let (_, _, msg) = encode_canonical(&json!({"alg":"RS256","kid":"legacy"}), &json!({"sub":"did:key:zOld"})).unwrap();
+        let mut sig = sk.sign(rsa::Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(msg.as_bytes())).unwrap();
+        let legacy = format!("{}.{}", msg, B64URL.encode(&sig));
+        let modern = sign_jwt(&ed, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zNew"}));
+
+        let opts = VerifyOptions::default().with_allowed_algs(&["EdDSA", "RS256"]);
+        assert_eq!(verify_ed25519_jwt_with_cache(&legacy, "mem://jwks", &cache, &opts).unwrap().sub, "did:key:zOld");
+        assert_eq!(verify_ed25519_jwt_with_cache(&modern, "mem://jwks", &cache, &opts).unwrap().sub, "did:key:zNew");
+        assert!(matches!(verify_ed25519_jwt_with_cache(&legacy, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Alg { .. })));
+        sig[0] ^= 1;
+        assert!(matches!(verify_ed25519_jwt_with_cache(&format!("{}.{}", msg, B64URL.encode(sig)), "mem://jwks", &cache, &opts), Err(VerifyError::Signature)));
+    }
+
+    #[test]
+    fn access_token_profile_requires_typ_and_claims() {
+        let (sk, cache) = test_key(65);
+        let now = now_ts();
+        let claims = json!({"sub":"did:key:zAt","iss":"https://as","aud":"api","exp":now + 60,"iat":now,"jti":"at-1","client_id":"c1"});
+        let opts = VerifyOptions::default().with_profile(Profile::OAuth2AccessToken);
+        let verify = |jwt: &str| verify_ed25519_jwt_with_cache(jwt, "mem://jwks", &cache, &opts);
+
+        assert_eq!(verify(&sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test","typ":"at+jwt"}), &claims)).unwrap().sub, "did:key:zAt");
+        assert!(verify(&sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test","typ":"application/AT+JWT"}), &claims)).is_ok());
+        assert!(matches!(verify(&sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test","typ":"JWT"}), &claims)), Err(VerifyError::Typ)));
+        let mut no_jti = claims.clone();
+        no_jti.as_object_mut().unwrap().remove("jti");
+        assert!(matches!(verify(&sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test","typ":"at+jwt"}), &no_jti)), Err(VerifyError::MissingClaim("jti"))));
+        let (_, _, unsigned) = encode_canonical(&json!({"alg":"none","typ":"at+jwt"}), &claims).unwrap();
+        assert!(matches!(verify(&format!("{}.", unsigned)), Err(VerifyError::Alg { .. })));
+    }
+
+    #[test]
+    fn id_token_profile_checks_nonce_azp_and_auth_time() {
+        let (sk, cache) = test_key(66);
+        let now = now_ts();
+        let header = json!({"alg":"EdDSA","kid":"test"});
+        let token = |extra: Json| {
+            let mut c = json!({"sub":"did:key:zId","iss":"https://op","aud":["app","api"],"azp":"app","exp":now + 60,"iat":now,"nonce":"n-1","auth_time":now - 100});
+            c.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            sign_jwt(&sk, &header, &c)
+        };
+        let opts = VerifyOptions::default().with_profile(Profile::IdToken).with_audience("app").with_nonce("n-1").with_max_auth_age(600).with_leeway(0);
+        let verify = |jwt: &str, opts: &VerifyOptions| verify_ed25519_jwt_with_cache(jwt, "mem://jwks", &cache, opts);
+
+        assert_eq!(verify(&token(json!({})), &opts).unwrap().sub, "did:key:zId");
+        assert!(matches!(verify(&token(json!({"nonce":"n-2"})), &opts), Err(VerifyError::Nonce)));
+        assert!(matches!(verify(&token(json!({"azp":"api"})), &opts), Err(VerifyError::Azp)));
+        assert!(matches!(verify(&token(json!({"azp":null})), &opts), Err(VerifyError::MissingClaim("azp"))));
+        assert!(verify(&token(json!({"azp":null,"aud":"app"})), &opts).is_ok());
+        assert!(matches!(verify(&token(json!({})), &opts.clone().with_max_auth_age(60)), Err(VerifyError::AuthTime)));
+        assert!(matches!(verify(&token(json!({"auth_time": i64::MIN})), &opts), Err(VerifyError::AuthTime)));
+        assert!(matches!(verify(&token(json!({"auth_time": now + 60})), &opts), Err(VerifyError::AuthTime)));
+        assert!(matches!(verify(&token(json!({"iat":null})), &opts), Err(VerifyError::MissingClaim("iat"))));
+    }
+
+    #[test]
+    fn confirmation_binds_tokens_to_the_client_certificate() {
+        let (sk, cache) = test_key(77);
+        let cert = cert_thumbprint(b"client certificate DER");
+        let token = |cnf: Json| sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zM","cnf":cnf}));
+        let verify = |jwt: &str, opts: &VerifyOptions| verify_ed25519_jwt_with_cache(jwt, "mem://jwks", &cache, opts);
+        let opts = VerifyOptions { confirmation: Some(Confirmation::certificate(b"client certificate DER")), ..Default::default() };
+
+        assert!(verify(&token(json!({"x5t#S256": cert})), &opts).is_ok());
+        assert!(matches!(verify(&token(json!({"x5t#S256": cert_thumbprint(b"other")})), &opts), Err(VerifyError::ConfirmationMismatch)));
+        assert!(matches!(verify(&token(Json::Null), &opts), Err(VerifyError::ConfirmationMismatch)));
+        let by_key = VerifyOptions::default().with_key_thumbprint("jkt-1");
+        assert!(verify(&token(json!({"jkt":"jkt-1"})), &by_key).is_ok());
+        assert!(matches!(verify(&token(json!({"jkt":"jkt-1"})), &by_key.with_cert_thumbprint(&cert)), Err(VerifyError::ConfirmationMismatch)));
+    }
+
+    #[test]
+    fn decode_unverified_shows_header_and_claims() {
+        let (sk, _) = test_key(59);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"k9","typ":"at+jwt","crit":["b64"],"b64":true}), &json!({"sub":"did:key:zD","exp":"1700000000"}));
+        let (header, claims) = decode_unverified(&jwt).unwrap();
+        assert_eq!((header.alg.as_str(), header.kid.as_deref(), header.typ.as_deref()), ("EdDSA", Some("k9"), Some("at+jwt")));
+        assert_eq!((header.crit, &header.extra["b64"]), (vec!["b64".to_string()], &json!(true)));
+        assert_eq!((claims.sub.as_str(), claims.exp), ("did:key:zD", Some(1_700_000_000)));
+        let unsigned = jwt.rsplit_once('.').unwrap().0.to_string() + ".";
+        assert_eq!(decode_unverified(&unsigned).unwrap().1.sub, "did:key:zD");
+        assert!(matches!(decode_unverified("a.b"), Err(VerifyError::BadFormat)));
+    }
+
+    #[test]
+    fn required_scopes_are_enforced() {
+        let (sk, cache) = test_key(57);
+        let header = json!({"alg":"EdDSA","kid":"test"});
+        let jwt = sign_jwt(&sk, &header, &json!({"sub":"did:key:zS","scope":"orders:read  orders:write profile"}));
+        let claims = verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).unwrap();
+        let scopes = claims.scopes();
+        assert!(scopes.has("profile") && scopes.has_all(&["orders:read", "orders:write"]) && !scopes.has_any(&["admin", "orders"]));
+        assert_eq!(scopes.to_string(), "orders:read orders:write profile");
+
+        let opts = VerifyOptions::default().require_scopes(&["orders:read"]);
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts).is_ok());
+        let admin = opts.require_scopes(&["admin"]);
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &admin), Err(VerifyError::InsufficientScope(s)) if s == "admin"));
+        let scp = sign_jwt(&sk, &header, &json!({"sub":"did:key:zS","scp":["orders:read","admin"]}));
+        assert!(verify_ed25519_jwt_with_cache(&scp, "mem://jwks", &cache, &admin).is_ok());
+    }
+
+    #[test]
+    fn typed_claims_deserialize_after_registered_checks() {
+        #[derive(Deserialize)]
+        struct Tenant { sub: String, tenant: String, roles: Vec<String>, exp: i64 }
+        let (sk, cache) = test_key(50);
+        let opts = VerifyOptions::default().with_audience("api").lenient_numeric_dates();
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":"api","tenant":"acme","roles":["admin"],"exp":"4102444800"}));
+        let t: Tenant = verify_ed25519_jwt_as(&jwt, "mem://jwks", &cache, &opts).unwrap();
+        assert_eq!((t.sub.as_str(), t.tenant.as_str(), t.roles, t.exp), ("did:key:zT", "acme", vec!["admin".to_string()], 4_102_444_800));
+
+        let expired = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":"api","tenant":"acme","roles":[],"exp":1000}));
+        assert!(matches!(verify_ed25519_jwt_as::<Tenant>(&expired, "mem://jwks", &cache, &opts), Err(VerifyError::Expired)));
+        let untyped = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":"api"}));
+        assert!(matches!(verify_ed25519_jwt_as::<Tenant>(&untyped, "mem://jwks", &cache, &opts), Err(VerifyError::Json)));
+    }
+
+    #[test]
+    fn token_signer_fills_lifetimes_and_round_trips() {
+        let (sk, cache) = test_key(47);
+        let claims: Claims = serde_json::from_value(json!({"sub":"did:key:zSigned","aud":"demo","tier":"gold"})).unwrap();
+        let jwt = sign_ed25519_jwt(&claims, &sk, "test").unwrap();
+        let verified = verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default().with_audience("demo")).unwrap();
+        assert_eq!((verified.sub.as_str(), verified.extra["tier"].as_str()), ("did:key:zSigned", Some("gold")));
+        assert_eq!(verified.exp.unwrap() - verified.iat.unwrap(), 3600);
+
+        let signer = TokenSigner::new(sk, "test").with_default_ttl(60).with_typ("at+jwt");
+        let short = signer.sign(&Claims { exp: None, iat: Some(1_000), ..claims }).unwrap();
+        let header: Json = serde_json::from_slice(&B64URL.decode(short.split('.').next().unwrap()).unwrap()).unwrap();
+        assert_eq!(header["typ"], "at+jwt");
+        assert!(matches!(verify_ed25519_jwt_with_cache(&short, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Expired)));
+        assert_eq!(signer.jwk().x, cache.get_fresh("mem://jwks").unwrap().keys[0].x);
+    }
+
     #[test]
     fn roundtrip_sign_and_verify_with_cache() {
         let mut rng = StdRng::seed_from_u64(42);
@@ -229,7 +1702,7 @@ mod tests {
         let x = B64URL.encode(vk.to_bytes());
 
         let cache = JwksCache::new(3600);
-        cache.put("mem://jwks", Jwks{ keys: vec![ Jwk{ kty:"OKP".into(), crv:Some("Ed25519".into()), x:Some(x), kid:Some("test".into()) } ]});
+        cache.put("mem://jwks", Jwks{ keys: vec![ Jwk{ kty:"OKP".into(), crv:Some("Ed25519".into()), x:Some(x), kid:Some("test".into()), ..Default::default() } ]});
 
         let header = json!({"alg":"EdDSA","kid":"test","typ":"JWT"});
         let now = now_ts();
@@ -251,4 +1724,366 @@ mod tests {
         let claims = verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts).expect("verify");
         assert_eq!(claims.sub, "did:key:zTest");
     }
+
+    #[test]
+    fn aud_helpers_and_normalization() {
+        let one = Aud::from("a");
+        assert!(one.contains("a") && !one.contains("b"));
+        let many = Aud::Many(vec!["a".into(), "b".into()]);
+        assert_eq!(many.iter().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(many.as_vec(), vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(Aud::Many(vec!["x".into()]).normalize(), Aud::One("x".into()));
+        assert_eq!(Aud::from(vec!["x".to_string()]), Aud::One("x".into()));
+        assert!(Aud::Many(vec![]).is_empty());
+    }
+
+    #[test]
+    fn allow_expired_marks_result() {
+        let (sk, cache) = test_key(1);
+        let now = now_ts();
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest","exp": now - 3600}));
+
+        let strict = VerifyOptions::default();
+        assert!(matches!(verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &strict), Err(VerifyError::Expired)));
+
+        let lenient = VerifyOptions::default().allow_expired();
+        let v = verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &lenient).expect("verify");
+        assert!(v.expired);
+
+        let (other, _) = test_key(2);
+        let forged = sign_jwt(&other, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest","exp": now - 3600}));
+        assert!(matches!(verify_ed25519_jwt_detailed(&forged, "mem://jwks", &cache, &lenient), Err(VerifyError::Signature)));
+    }
+
+    #[test]
+    fn signature_only_skips_claim_checks() {
+        let (sk, cache) = test_key(3);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"legacy":true,"exp": 1}));
+        let raw = verify_ed25519_jwt_signature_only(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).expect("verify");
+        assert_eq!(raw["legacy"], true);
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).is_err());
+    }
+
+    #[test]
+    fn missing_kid_uses_sole_key_only_when_allowed() {
+        let (sk, cache) = test_key(4);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA"}), &json!({"sub":"did:key:zTest"}));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Kid)));
+        let opts = VerifyOptions::default().allow_missing_kid();
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts).is_ok());
+
+        let mut jwks = cache.get_fresh("mem://jwks").unwrap();
+        jwks.keys.push(Jwk{ kid: Some("other".into()), ..jwks.keys[0].clone() });
+        cache.put("mem://jwks", jwks);
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts), Err(VerifyError::Kid)));
+    }
+
+    #[test]
+    fn try_all_keys_falls_back_on_unknown_kid() {
+        let (sk, cache) = test_key(5);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"rotated"}), &json!({"sub":"did:key:zTest"}));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::NoKey)));
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default().with_try_all_keys(4)).is_ok());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default().with_try_all_keys(0)), Err(VerifyError::NoKey)));
+    }
+
+    #[test]
+    fn require_canonical_rejects_reordered_json() {
+        let (sk, cache) = test_key(6);
+        let opts = VerifyOptions::default().require_canonical();
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest"}));
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts).is_ok());
+
+        let msg = format!("{}.{}", B64URL.encode(r#"{"kid":"test","alg":"EdDSA"}"#), B64URL.encode(r#"{"sub":"did:key:zTest"}"#));
+        let jwt = format!("{}.{}", msg, B64URL.encode(sk.sign(msg.as_bytes()).to_bytes()));
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).is_ok());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts), Err(VerifyError::NonCanonical)));
+    }
+
+    #[test]
+    fn claims_diff_and_merge() {
+        let base: Claims = serde_json::from_value(json!({"sub":"did:key:zA","scope":"read write","tenant":"t1","exp":100})).unwrap();
+        let overrides = json!({"scope":"read","tenant":null,"act":{"sub":"svc"}});
+        let merged = base.merged_with(overrides.as_object().unwrap()).unwrap();
+        assert_eq!(merged.scope.as_deref(), Some("read"));
+        assert!(!merged.extra.contains_key("tenant"));
+
+        let d = base.diff(&merged);
+        assert_eq!(d.retained, ["exp", "sub"]);
+        assert_eq!(d.changed, ["scope"]);
+        assert_eq!(d.dropped, ["tenant"]);
+        assert_eq!(d.added, ["act"]);
+        assert!(base.diff(&base).is_empty());
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("ubl/*", "ubl/ledger"));
+        assert!(glob_match("a*b*c", "a-x-b-y-c"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("ab*ba", "aba"));
+        assert!(!glob_match("ubl/*", "ubl2/x"));
+        assert!(glob_match("exact", "exact") && !glob_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn namespaced_custom_claims() {
+        let (sk, cache) = test_key(7);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"auth0|1","https://ubl.agency/roles":["admin"],"plan":"pro"}));
+        let opts = VerifyOptions::default().with_claim_namespace("https://ubl.agency/");
+        let claims = verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts).unwrap();
+        assert_eq!(opts.get_custom(&claims, "roles"), Some(&json!(["admin"])));
+        assert_eq!(opts.get_custom(&claims, "plan"), Some(&json!("pro")));
+        assert!(opts.get_custom(&claims, "missing").is_none());
+        assert!(VerifyOptions::default().get_custom(&claims, "roles").is_none());
+        assert_eq!(opts.roles(&claims), ["admin"]);
+    }
+
+    #[test]
+    fn base64_policy_distinguishes_padding_and_alphabet() {
+        assert!(matches!(Base64Policy::default().decode("YQ=="), Err(VerifyError::Base64Padding)));
+        assert_eq!(Base64Policy { allow_padding: true, ..Default::default() }.decode("YQ==").unwrap(), b"a");
+        assert!(matches!(Base64Policy::default().decode("-_+/"), Err(VerifyError::Base64Alphabet)));
+        assert_eq!(Base64Policy::lenient().decode("+/8=").unwrap(), [0xfb, 0xff]);
+
+        let (sk, cache) = test_key(8);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest"}));
+        let (msg, sig) = jwt.rsplit_once('.').unwrap();
+        let padded = format!("{}.{}==", msg, sig);
+        assert!(matches!(verify_ed25519_jwt_with_cache(&padded, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Base64Padding)));
+        let opts = VerifyOptions::default().with_base64_policy(Base64Policy { allow_padding: true, ..Default::default() });
+        assert!(verify_ed25519_jwt_with_cache(&padded, "mem://jwks", &cache, &opts).is_ok());
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_policy() {
+        use std::io::Write;
+        let (sk, cache) = test_key(9);
+        let mut enc = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(br#"{"sub":"did:key:zTest"}"#).unwrap();
+        let msg = format!("{}.{}", B64URL.encode(r#"{"alg":"EdDSA","kid":"test","zip":"DEF"}"#), B64URL.encode(enc.finish().unwrap()));
+        let jwt = format!("{}.{}", msg, B64URL.encode(sk.sign(msg.as_bytes()).to_bytes()));
+
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Zip)));
+        let allow = VerifyOptions::default().with_zip_policy(ZipPolicy::Allow { max_decompressed: 1024 });
+        assert_eq!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &allow).unwrap().sub, "did:key:zTest");
+        let tiny = VerifyOptions::default().with_zip_policy(ZipPolicy::Allow { max_decompressed: 8 });
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &tiny), Err(VerifyError::ZipLimit)));
+    }
+
+    #[test]
+    fn fallback_jwks_uris() {
+        let (sk, seeded) = test_key(10);
+        let jwks = seeded.get_fresh("mem://jwks").unwrap();
+        let mirror = serve_once(serde_json::to_string(&jwks).unwrap());
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest"}));
+
+        let cache = JwksCache::new(60);
+        let primary = "http://127.0.0.1:9/down";
+        let v = verify_ed25519_jwt_with_fallback(&jwt, &[primary, &mirror], &cache, &VerifyOptions::default()).unwrap();
+        assert_eq!(v.claims.sub, "did:key:zTest");
+        assert!(cache.get_fresh(primary).is_some());
+    }
+
+    #[test]
+    fn jwks_cache_readers_see_whole_snapshots_during_updates() {
+        let cache = std::sync::Arc::new(JwksCache::new(60));
+        let keys = |n: u64| Jwks { keys: (0..n).map(|i| Jwk::ed25519(&i.to_string(), &SigningKey::generate(&mut StdRng::seed_from_u64(42 + i)).verifying_key())).collect() };
+        cache.put("mem://a", keys(1));
+        let writer = { let cache = cache.clone(); let big = keys(4); std::thread::spawn(move || for _ in 0..200 { cache.put("mem://a", big.clone()); cache.put("mem://b", big.clone()); }) };
+        for _ in 0..2000 {
+            let n = cache.get_fresh("mem://a").unwrap().keys.len();
+            assert!(n == 1 || n == 4);
+        }
+        writer.join().unwrap();
+        assert_eq!((cache.get_fresh("mem://a").unwrap().keys.len(), cache.get_fresh("mem://b").unwrap().keys.len()), (4, 4));
+    }
+
+    #[test]
+    fn stale_keys_are_served_while_revalidating_and_on_errors() {
+        let (old_sk, seeded) = test_key(51);
+        let (new_sk, rotated) = test_key(52);
+        let header = json!({"alg":"EdDSA","kid":"test"});
+        let old_jwt = sign_jwt(&old_sk, &header, &json!({"sub":"did:key:zOld"}));
+        let new_jwt = sign_jwt(&new_sk, &header, &json!({"sub":"did:key:zNew"}));
+        let opts = VerifyOptions::default();
+
+        let down = "http://127.0.0.1:9/down";
+        let strict = JwksCache::new(-1);
+        strict.put(down, seeded.get_fresh("mem://jwks").unwrap());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&old_jwt, down, &strict, &opts), Err(VerifyError::JwksHttp(_))));
+        let tolerant = JwksCache::new(-1).with_stale_if_error(60);
+        tolerant.put(down, seeded.get_fresh("mem://jwks").unwrap());
+        assert!(verify_ed25519_jwt_with_cache(&old_jwt, down, &tolerant, &opts).is_ok());
+
+        let uri = serve_once(serde_json::to_string(&rotated.get_fresh("mem://jwks").unwrap()).unwrap());
+        let cache = JwksCache::new(-1).with_stale_while_revalidate(60);
+        cache.put(&uri, seeded.get_fresh("mem://jwks").unwrap());
+        assert!(verify_ed25519_jwt_with_cache(&old_jwt, &uri, &cache, &opts).is_ok());
+        let refreshed = (0..200).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            cache.refreshing.lock().is_empty() && verify_ed25519_jwt_with_cache(&new_jwt, &uri, &cache, &opts).is_ok()
+        });
+        assert!(refreshed);
+    }
+
+    #[test]
+    fn unknown_kid_forces_one_rate_limited_refetch() {
+        let (old_sk, seeded) = test_key(53);
+        let new_sk = SigningKey::generate(&mut StdRng::seed_from_u64(54));
+        let rotated = Jwks { keys: vec![Jwk::ed25519("test", &old_sk.verifying_key()), Jwk::ed25519("k2", &new_sk.verifying_key())] };
+        let uri = serve_once(serde_json::to_string(&rotated).unwrap());
+        let new_jwt = sign_jwt(&new_sk, &json!({"alg":"EdDSA","kid":"k2"}), &json!({"sub":"did:key:zRotated"}));
+        let opts = VerifyOptions::default();
+
+        let waiting = JwksCache::new(3600);
+        waiting.put(&uri, seeded.get_fresh("mem://jwks").unwrap());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&new_jwt, &uri, &waiting, &opts), Err(VerifyError::NoKey)));
+
+        let cache = JwksCache::new(3600).with_refetch_on_unknown_kid(60);
+        cache.put(&uri, seeded.get_fresh("mem://jwks").unwrap());
+        assert_eq!(verify_ed25519_jwt_with_cache(&new_jwt, &uri, &cache, &opts).unwrap().sub, "did:key:zRotated");
+        let unknown = sign_jwt(&new_sk, &json!({"alg":"EdDSA","kid":"k3"}), &json!({"sub":"did:key:zRotated"}));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&unknown, &uri, &cache, &opts), Err(VerifyError::NoKey)));
+        assert!(!cache.claim_forced_refetch(&uri));
+    }
+
+    #[test]
+    fn health_check_reports_per_source() {
+        let (_, seeded) = test_key(11);
+        let good = serve_once(serde_json::to_string(&seeded.get_fresh("mem://jwks").unwrap()).unwrap());
+        let empty = serve_once(r#"{"keys":[]}"#.to_string());
+        let report = health_check(&[&good, &empty, "http://127.0.0.1:9/down"]);
+        assert_eq!(report.sources[0].keys, 1);
+        assert!(report.sources[0].is_ok());
+        assert!(!report.sources[1].is_ok() && report.sources[2].error.is_some());
+        assert!(report.is_degraded() && !report.is_healthy());
+    }
+
+    #[test]
+    fn keys_outside_validity_window_are_skipped() {
+        let (sk, cache) = test_key(12);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest"}));
+        let mut jwks = cache.get_fresh("mem://jwks").unwrap();
+        jwks.keys[0].exp = Some(now_ts() - 10);
+        cache.put("mem://jwks", jwks.clone());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::NoKey)));
+
+        jwks.keys[0].exp = None;
+        jwks.keys[0].nbf = Some(now_ts() + 3600);
+        cache.put("mem://jwks", jwks);
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).is_err());
+        let later = VerifyOptions::default().with_now(now_ts() + 7200);
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &later).is_ok());
+    }
+
+    #[test]
+    fn encryption_keys_are_not_used_for_verification() {
+        let (sk, cache) = test_key(13);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest"}));
+        let mut jwks = cache.get_fresh("mem://jwks").unwrap();
+        jwks.keys[0].use_ = Some("enc".into());
+        cache.put("mem://jwks", jwks.clone());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::KeyUse)));
+
+        jwks.keys[0].use_ = None;
+        jwks.keys[0].key_ops = Some(vec!["sign".into()]);
+        cache.put("mem://jwks", jwks.clone());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::KeyUse)));
+
+        jwks.keys[0].key_ops = Some(vec!["sign".into(), "verify".into()]);
+        cache.put("mem://jwks", jwks);
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn key_alg_must_match_header_alg() {
+        let (sk, cache) = test_key(14);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zTest"}));
+        let mut jwks = cache.get_fresh("mem://jwks").unwrap();
+        jwks.keys[0].alg = Some("ES256".into());
+        cache.put("mem://jwks", jwks.clone());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Alg { .. })));
+
+        jwks.keys[0].alg = Some("EdDSA".into());
+        cache.put("mem://jwks", jwks);
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn keys_can_be_referenced_by_certificate_thumbprint() {
+        let (sk, cache) = test_key(15);
+        let mut jwks = cache.get_fresh("mem://jwks").unwrap();
+        jwks.keys[0].kid = Some("other".into());
+        jwks.keys[0].x5t_s256 = Some("c2hhMjU2LXRodW1icHJpbnQ".into());
+        jwks.keys.push(Jwk { x5t: Some("c2hhMS10aHVtYnByaW50".into()), ..Jwk::ed25519("old", &SigningKey::generate(&mut StdRng::seed_from_u64(16)).verifying_key()) });
+        cache.put("mem://jwks", jwks);
+
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","x5t#S256":"c2hhMjU2LXRodW1icHJpbnQ"}), &json!({"sub":"did:key:zTest"}));
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()).is_ok());
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","x5t":"c2hhMS10aHVtYnByaW50"}), &json!({"sub":"did:key:zTest"}));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Signature)));
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","x5t":"dW5rbm93bg"}), &json!({"sub":"did:key:zTest"}));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::NoKey)));
+    }
+
+    #[test]
+    fn issuer_patterns_capture_tenant() {
+        let (sk, cache) = test_key(17);
+        let opts = VerifyOptions::default().with_issuer_pattern("https://login.microsoftonline.com/{tid}/v2.0");
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","iss":"https://login.microsoftonline.com/acme-tid/v2.0"}));
+        let v = verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &opts).unwrap();
+        assert_eq!(v.issuer_vars["tid"], "acme-tid");
+
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","iss":"https://login.example.com/acme-tid/v2.0"}));
+        assert!(matches!(verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &opts), Err(VerifyError::Issuer)));
+    }
+
+    #[test]
+    fn audience_patterns_report_matched_audience() {
+        let (sk, cache) = test_key(18);
+        let opts = VerifyOptions::default().with_audience_pattern("api://orders/*");
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":["api://billing","api://orders/items"]}));
+        let v = verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &opts).unwrap();
+        assert_eq!(v.audience.as_deref(), Some("api://orders/items"));
+
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":"api://ordersx"}));
+        assert!(matches!(verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &opts), Err(VerifyError::Audience)));
+
+        let opts = VerifyOptions::default().with_audience_pattern("https://*.example.com");
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":"https://evil.com/.example.com"}));
+        assert!(matches!(verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &opts), Err(VerifyError::Audience)));
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","aud":"https://orders.example.com"}));
+        assert_eq!(verify_ed25519_jwt_detailed(&jwt, "mem://jwks", &cache, &opts).unwrap().audience.as_deref(), Some("https://orders.example.com"));
+    }
+
+    #[test]
+    fn subject_policy_blocks_principals() {
+        let (sk, cache) = test_key(19);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zCompromised"}));
+        let deny = VerifyOptions::default().with_subject_policy(SubjectPolicy::default().deny("did:key:zCompromised"));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &deny), Err(VerifyError::Subject)));
+
+        let blocked = std::sync::Arc::new(parking_lot::Mutex::new(vec!["did:key:zOther".to_string()]));
+        let live = blocked.clone();
+        let opts = VerifyOptions::default().with_subject_policy(SubjectPolicy::default().with_check(move |s| !live.lock().iter().any(|b| b == s)));
+        assert!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts).is_ok());
+        blocked.lock().push("did:key:zCompromised".into());
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &opts), Err(VerifyError::Subject)));
+        assert!(!SubjectPolicy::default().allow("did:key:zA").permits("did:key:zB"));
+    }
+
+    #[test]
+    fn lenient_numeric_dates_are_coerced() {
+        let (sk, cache) = test_key(33);
+        let jwt = sign_jwt(&sk, &json!({"alg":"EdDSA","kid":"test"}), &json!({"sub":"did:key:zT","exp":"4102444800","iat":"1700000000"}));
+        assert!(matches!(verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default()), Err(VerifyError::Json)));
+        let claims = verify_ed25519_jwt_with_cache(&jwt, "mem://jwks", &cache, &VerifyOptions::default().lenient_numeric_dates()).unwrap();
+        assert_eq!((claims.exp, claims.iat), (Some(4102444800), Some(1700000000)));
+
+        let mut payload = json!({"exp":1700000000.9,"nbf":"soon","iat":1700000000});
+        coerce_numeric_dates(&mut payload);
+        assert_eq!(payload, json!({"exp":1700000000,"nbf":"soon","iat":1700000000}));
+    }
 }
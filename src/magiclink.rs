@@ -0,0 +1,68 @@
+//! Magic-link login tokens.
+//!
+//! A magic link is a short-lived [`oneshot`](crate::oneshot) token tagged with a `purpose`
+//! (e.g. `login`, `reset-password`) and optionally bound to a fingerprint of the request that
+//! asked for it (say, the browser's user agent and a device cookie), stored as a SHA-256 hash in
+//! `fph`. [`MagicLinks::redeem`] only consumes the token when purpose and fingerprint both match,
+//! so a link opened elsewhere fails without burning the link.
+
+use crate::issuer::{TokenIssuer, TokenRequest};
+use crate::oneshot::{OneShotTokens, PendingStore};
+use crate::{to_hex, Claims, SignError, VerifyError};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+pub const PURPOSE_CLAIM: &str = "purpose";
+pub const FINGERPRINT_CLAIM: &str = "fph";
+
+#[derive(Debug)]
+pub struct MagicLinks<S: PendingStore> { tokens: OneShotTokens<S> }
+
+impl<S: PendingStore> MagicLinks<S> {
+    /// Links live 15 minutes unless changed.
+    pub fn new(issuer: TokenIssuer, store: S) -> Self { Self { tokens: OneShotTokens::new(issuer, store).with_ttl(900) } }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.tokens = self.tokens.with_ttl(secs); self }
+
+    /// Token for `sub` (e.g. the email address) to redeem for `purpose`, bound to `fingerprint` if given.
+    pub fn mint(&self, sub: &str, purpose: &str, fingerprint: Option<&str>) -> Result<String, SignError> {
+        let mut claims = HashMap::from([(PURPOSE_CLAIM.to_string(), Json::from(purpose))]);
+        if let Some(fp) = fingerprint { claims.insert(FINGERPRINT_CLAIM.into(), Json::from(fingerprint_hash(fp))); }
+        self.tokens.mint(&TokenRequest { sub: sub.to_string(), aud: None, scope: None, ttl_secs: None, claims })
+    }
+
+    /// Verifies and consumes `token` if it was minted for `purpose` and, when bound, for `fingerprint`.
+    pub fn redeem(&self, token: &str, purpose: &str, fingerprint: Option<&str>) -> Result<Claims, VerifyError> {
+        self.tokens.redeem_with(token, |claims| {
+            if claims.extra.get(PURPOSE_CLAIM).and_then(Json::as_str) != Some(purpose) { return Err(VerifyError::Purpose); }
+            match claims.extra.get(FINGERPRINT_CLAIM).and_then(Json::as_str) {
+                Some(bound) if Some(bound) != fingerprint.map(fingerprint_hash).as_deref() => Err(VerifyError::Binding),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+fn fingerprint_hash(fingerprint: &str) -> String { to_hex(&Sha256::digest(fingerprint.as_bytes())) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oneshot::MemoryPendingStore;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn links_check_purpose_and_fingerprint_before_consuming() {
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(35)));
+        let links = MagicLinks::new(issuer, MemoryPendingStore::new());
+        let token = links.mint("alice@example.com", "login", Some("ua=Firefox;device=d1")).unwrap();
+
+        assert!(matches!(links.redeem(&token, "reset-password", Some("ua=Firefox;device=d1")), Err(VerifyError::Purpose)));
+        assert!(matches!(links.redeem(&token, "login", Some("ua=curl")), Err(VerifyError::Binding)));
+        assert!(matches!(links.redeem(&token, "login", None), Err(VerifyError::Binding)));
+        let claims = links.redeem(&token, "login", Some("ua=Firefox;device=d1")).unwrap();
+        assert_eq!(claims.sub, "alice@example.com");
+        assert!(matches!(links.redeem(&token, "login", Some("ua=Firefox;device=d1")), Err(VerifyError::Redeemed)));
+    }
+}
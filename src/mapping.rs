@@ -0,0 +1,136 @@
+//! Claims normalization after verification.
+//!
+//! A [`ClaimsMapper`] is an ordered list of steps — rename, split, coerce, derive — applied to
+//! verified claims before the application sees them, so services behind several IdPs can read
+//! one shape. Steps run on the JSON form of the claims; the result must still be valid [`Claims`].
+
+use crate::{Claims, VerifyError};
+use serde_json::{Map, Value as Json};
+use std::sync::Arc;
+
+type Step = Arc<dyn Fn(&mut Map<String, Json>) -> Result<(), VerifyError> + Send + Sync>;
+
+/// Target types for [`ClaimsMapper::coerce`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimType { String, Integer, Bool, StringArray }
+
+#[derive(Clone, Default)]
+pub struct ClaimsMapper { steps: Vec<Step> }
+
+impl std::fmt::Debug for ClaimsMapper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClaimsMapper").field("steps", &self.steps.len()).finish()
+    }
+}
+
+impl ClaimsMapper {
+    pub fn new() -> Self { Self::default() }
+
+    fn step<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut Map<String, Json>) -> Result<(), VerifyError> + Send + Sync + 'static,
+    {
+        self.steps.push(Arc::new(f));
+        self
+    }
+
+    /// Moves `from` to `to` (overwriting it) when `from` is present.
+    pub fn rename(self, from: &str, to: &str) -> Self {
+        let (from, to) = (from.to_string(), to.to_string());
+        self.step(move |m| {
+            if let Some(v) = m.remove(&from) { m.insert(to.clone(), v); }
+            Ok(())
+        })
+    }
+
+    /// Splits the string claim `from` on `sep` (whitespace when `None`) into a string array at `to`.
+    pub fn split(self, from: &str, to: &str, sep: Option<char>) -> Self {
+        let (from, to) = (from.to_string(), to.to_string());
+        self.step(move |m| {
+            let Some(s) = m.get(&from).and_then(Json::as_str) else { return Ok(()) };
+            let parts: Vec<Json> = match sep {
+                Some(c) => s.split(c).map(str::trim).filter(|p| !p.is_empty()).map(Json::from).collect(),
+                None => s.split_whitespace().map(Json::from).collect(),
+            };
+            m.insert(to.clone(), Json::Array(parts));
+            Ok(())
+        })
+    }
+
+    /// Converts `claim` to `ty` if present; values that cannot be converted fail with [`VerifyError::Mapping`].
+    pub fn coerce(self, claim: &str, ty: ClaimType) -> Self {
+        let claim = claim.to_string();
+        self.step(move |m| {
+            let Some(v) = m.get_mut(&claim) else { return Ok(()) };
+            *v = coerce(v, ty).ok_or_else(|| VerifyError::Mapping(format!("claim '{}' is not coercible to {:?}", claim, ty)))?;
+            Ok(())
+        })
+    }
+
+    /// Sets `claim` to the value computed from the claims so far; `None` removes it.
+    pub fn derive<F>(self, claim: &str, f: F) -> Self
+    where
+        F: Fn(&Map<String, Json>) -> Option<Json> + Send + Sync + 'static,
+    {
+        let claim = claim.to_string();
+        self.step(move |m| {
+            match f(m) {
+                Some(v) => { m.insert(claim.clone(), v); }
+                None => { m.remove(&claim); }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn apply(&self, claims: &Claims) -> Result<Claims, VerifyError> {
+        let mut m = claims.to_map();
+        for step in &self.steps { step(&mut m)?; }
+        serde_json::from_value(Json::Object(m)).map_err(|e| VerifyError::Mapping(e.to_string()))
+    }
+}
+
+fn coerce(v: &Json, ty: ClaimType) -> Option<Json> {
+    match (ty, v) {
+        (ClaimType::String, Json::String(_)) | (ClaimType::Integer, Json::Number(_)) | (ClaimType::Bool, Json::Bool(_)) => Some(v.clone()),
+        (ClaimType::String, Json::Number(_) | Json::Bool(_)) => Some(Json::String(v.to_string())),
+        (ClaimType::Integer, Json::String(s)) => s.trim().parse::<i64>().ok().map(Json::from),
+        (ClaimType::Bool, Json::String(s)) => match s.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" => Some(Json::Bool(true)),
+            "false" | "0" | "no" => Some(Json::Bool(false)),
+            _ => None,
+        },
+        (ClaimType::Bool, Json::Number(n)) => n.as_i64().filter(|n| *n == 0 || *n == 1).map(|n| Json::Bool(n == 1)),
+        (ClaimType::StringArray, Json::String(_)) => Some(Json::Array(vec![v.clone()])),
+        (ClaimType::StringArray, Json::Array(a)) => a.iter().map(|x| coerce(x, ClaimType::String)).collect::<Option<Vec<_>>>().map(Json::Array),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn mapper_normalizes_claims() {
+        let mapper = ClaimsMapper::new()
+            .rename("preferred_username", "username")
+            .split("scope", "scopes", None)
+            .coerce("email_verified", ClaimType::Bool)
+            .coerce("groups", ClaimType::StringArray)
+            .derive("is_admin", |m| Some(Json::Bool(m["groups"].as_array()?.iter().any(|g| g == "admins"))));
+        let claims: Claims = serde_json::from_value(json!({
+            "sub":"did:key:zUser","scope":"read write","preferred_username":"ana","email_verified":"true","groups":"admins"
+        })).unwrap();
+
+        let mapped = mapper.apply(&claims).unwrap();
+        assert_eq!(mapped.extra["username"], "ana");
+        assert!(!mapped.extra.contains_key("preferred_username"));
+        assert_eq!(mapped.extra["scopes"], json!(["read", "write"]));
+        assert_eq!(mapped.extra["email_verified"], true);
+        assert_eq!(mapped.extra["is_admin"], true);
+
+        let strict = ClaimsMapper::new().coerce("email_verified", ClaimType::Integer);
+        assert!(matches!(strict.apply(&claims), Err(VerifyError::Mapping(_))));
+    }
+}
@@ -0,0 +1,112 @@
+//! Single-use tokens for invite links, password resets and similar one-time actions.
+//!
+//! [`OneShotTokens::mint`] signs a token through a [`TokenIssuer`], marks it with the `one_shot`
+//! claim and records its jti as pending. [`OneShotTokens::redeem`] verifies the token and
+//! atomically consumes the jti, so a second redemption fails with [`VerifyError::Redeemed`].
+
+use crate::issuer::{TokenIssuer, TokenRequest};
+use crate::{now_ts, verify_ed25519_jwt_with_cache, Claims, JwksCache, SignError, VerifyError, VerifyOptions};
+use parking_lot::Mutex;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+
+pub const CLAIM: &str = "one_shot";
+
+const JWKS_URI: &str = "oneshot://issuer";
+
+pub trait PendingStore: Send + Sync {
+    fn insert(&self, jti: &str, expires_at: i64);
+    /// Atomically removes `jti`, returning whether it was pending and unexpired at `now`.
+    fn take(&self, jti: &str, now: i64) -> bool;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryPendingStore { inner: Mutex<HashMap<String, i64>> }
+
+impl MemoryPendingStore {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl PendingStore for MemoryPendingStore {
+    fn insert(&self, jti: &str, expires_at: i64) {
+        let mut m = self.inner.lock();
+        let now = now_ts();
+        m.retain(|_, exp| *exp >= now);
+        m.insert(jti.to_string(), expires_at);
+    }
+    fn take(&self, jti: &str, now: i64) -> bool { self.inner.lock().remove(jti).is_some_and(|exp| exp >= now) }
+}
+
+#[derive(Debug)]
+pub struct OneShotTokens<S: PendingStore> {
+    issuer: TokenIssuer,
+    store: S,
+    cache: JwksCache,
+    pub ttl_secs: i64,
+    pub opts: VerifyOptions,
+}
+
+impl<S: PendingStore> OneShotTokens<S> {
+    /// Tokens live at most one hour unless changed; redemption checks `iss` against `issuer`.
+    pub fn new(issuer: TokenIssuer, store: S) -> Self {
+        let cache = JwksCache::new(i64::MAX);
+        cache.put(JWKS_URI, issuer.jwks());
+        let opts = VerifyOptions::default().with_issuer(&issuer.issuer).with_leeway(0);
+        Self { issuer, store, cache, ttl_secs: 3600, opts }
+    }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.ttl_secs = secs; self }
+    /// Options for redemption (e.g. an audience); `iss` should stay pinned to the issuer.
+    pub fn with_verify_options(mut self, opts: VerifyOptions) -> Self { self.opts = opts; self }
+
+    /// Claims that [`OneShotTokens::mint`] would sign, already recorded as pending.
+    pub fn claims_for(&self, req: &TokenRequest) -> Result<Claims, SignError> {
+        let ttl = req.ttl_secs.unwrap_or(self.ttl_secs).min(self.ttl_secs);
+        let mut claims = self.issuer.claims_for(&TokenRequest { ttl_secs: Some(ttl), ..req.clone() })?;
+        claims.extra.insert(CLAIM.into(), Json::Bool(true));
+        let (Some(jti), Some(exp)) = (&claims.jti, claims.exp) else { return Err(SignError::Policy("one-shot tokens need jti and exp".into())) };
+        self.store.insert(jti, exp);
+        Ok(claims)
+    }
+
+    pub fn mint(&self, req: &TokenRequest) -> Result<String, SignError> { self.issuer.issue(&self.claims_for(req)?) }
+
+    /// Verifies `token` and consumes it; any later redemption fails.
+    pub fn redeem(&self, token: &str) -> Result<Claims, VerifyError> { self.redeem_with(token, |_| Ok(())) }
+
+    /// Like [`OneShotTokens::redeem`], running `check` on the verified claims first; a token that
+    /// fails `check` is not consumed.
+    pub fn redeem_with<F: FnOnce(&Claims) -> Result<(), VerifyError>>(&self, token: &str, check: F) -> Result<Claims, VerifyError> {
+        let claims = verify_ed25519_jwt_with_cache(token, JWKS_URI, &self.cache, &self.opts)?;
+        if claims.extra.get(CLAIM) != Some(&Json::Bool(true)) { return Err(VerifyError::MissingClaim(CLAIM)); }
+        check(&claims)?;
+        let jti = claims.jti.as_deref().ok_or(VerifyError::MissingClaim("jti"))?;
+        if !self.store.take(jti, self.opts.now.unwrap_or_else(now_ts)) { return Err(VerifyError::Redeemed); }
+        Ok(claims)
+    }
+
+    /// Drops a pending jti so its token can no longer be redeemed.
+    pub fn revoke(&self, jti: &str) { self.store.take(jti, i64::MIN); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn tokens_redeem_exactly_once() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(34));
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", key.clone());
+        let invites = OneShotTokens::new(issuer.clone(), MemoryPendingStore::new()).with_ttl(600);
+        let req: TokenRequest = serde_json::from_value(serde_json::json!({"sub":"invite:alice@example.com","claims":{"team":"blue"}})).unwrap();
+
+        let token = invites.mint(&req).unwrap();
+        let claims = invites.redeem(&token).unwrap();
+        assert_eq!(claims.extra["team"], "blue");
+        assert!(matches!(invites.redeem(&token), Err(VerifyError::Redeemed)));
+
+        let plain = issuer.mint(&req).unwrap();
+        assert!(matches!(invites.redeem(&plain), Err(VerifyError::MissingClaim(CLAIM))));
+    }
+}
@@ -0,0 +1,122 @@
+//! Post-verification authorization decisions from Open Policy Agent.
+//!
+//! After a token verifies, `{"claims": ..., "request": ...}` is handed to a [`DecisionHook`] —
+//! typically [`OpaClient`], which POSTs it as `input` to an OPA sidecar's Data API — and a deny is
+//! folded into the result as [`VerifyError::Policy`]. Like JWKS fetching, the HTTP call blocks;
+//! async services use [`AsyncDecisionHook`](crate::asyncverify::AsyncDecisionHook) (feature
+//! `async`) instead, which `OpaClient` also implements with feature `reqwest`. Embedded evaluators
+//! (e.g. a WASM-compiled policy) plug in by implementing either trait.
+
+use crate::{verify_ed25519_jwt_with_cache, Claims, JwksCache, VerifyError, VerifyOptions};
+use serde_json::{json, Value as Json};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpaDecision {
+    pub allow: bool,
+    pub reasons: Vec<String>,
+}
+
+impl OpaDecision {
+    /// Reads an OPA `result`: either a boolean or `{"allow": bool, "reasons": [..]}`.
+    /// An undefined result (no `result` member) denies.
+    pub fn from_result(result: Option<&Json>) -> Self {
+        match result {
+            Some(Json::Bool(allow)) => OpaDecision { allow: *allow, reasons: Vec::new() },
+            Some(Json::Object(o)) => OpaDecision {
+                allow: o.get("allow").and_then(Json::as_bool).unwrap_or(false),
+                reasons: o.get("reasons").and_then(Json::as_array).map(|r| r.iter().filter_map(|v| v.as_str().map(str::to_string)).collect()).unwrap_or_default(),
+            },
+            _ => OpaDecision::default(),
+        }
+    }
+}
+
+pub trait DecisionHook {
+    fn decide(&self, input: &Json) -> Result<OpaDecision, VerifyError>;
+}
+
+impl<F> DecisionHook for F
+where
+    F: Fn(&Json) -> Result<OpaDecision, VerifyError>,
+{
+    fn decide(&self, input: &Json) -> Result<OpaDecision, VerifyError> { self(input) }
+}
+
+/// Client for an OPA sidecar's Data API (`POST {base}/v1/data/{policy_path}`).
+#[derive(Debug, Clone)]
+pub struct OpaClient {
+    pub url: String,
+    pub timeout: Duration,
+}
+
+impl OpaClient {
+    /// `policy_path` is the package path with `/` separators, e.g. `httpapi/authz`.
+    pub fn new(base: &str, policy_path: &str) -> Self {
+        let url = format!("{}/v1/data/{}", base.trim_end_matches('/'), policy_path.trim_matches('/'));
+        Self { url, timeout: Duration::from_secs(2) }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self { self.timeout = timeout; self }
+}
+
+impl DecisionHook for OpaClient {
+    fn decide(&self, input: &Json) -> Result<OpaDecision, VerifyError> {
+        let engine = |e: &dyn std::fmt::Display| VerifyError::PolicyEngine(e.to_string());
+        let resp = ureq::post(&self.url).timeout(self.timeout).send_json(json!({ "input": input })).map_err(|e| engine(&e))?;
+        let body: Json = resp.into_json().map_err(|e| engine(&e))?;
+        Ok(OpaDecision::from_result(body.get("result")))
+    }
+}
+
+/// Asks `hook` about already-verified `claims` in the given request `context`.
+pub fn authorize(claims: &Claims, context: &Json, hook: &dyn DecisionHook) -> Result<OpaDecision, VerifyError> {
+    allowed(hook.decide(&decision_input(claims, context))?)
+}
+
+pub(crate) fn decision_input(claims: &Claims, context: &Json) -> Json { json!({ "claims": claims, "request": context }) }
+
+/// Folds a deny into [`VerifyError::Policy`].
+pub(crate) fn allowed(decision: OpaDecision) -> Result<OpaDecision, VerifyError> {
+    if decision.allow { return Ok(decision); }
+    let why = if decision.reasons.is_empty() { "opa denied".to_string() } else { decision.reasons.join("; ") };
+    Err(VerifyError::Policy(why))
+}
+
+/// Verifies `token`, then requires an allow decision from `hook`.
+pub fn verify_and_authorize(
+    token: &str,
+    jwks_uri: &str,
+    cache: &JwksCache,
+    opts: &VerifyOptions,
+    context: &Json,
+    hook: &dyn DecisionHook,
+) -> Result<Claims, VerifyError> {
+    let claims = verify_ed25519_jwt_with_cache(token, jwks_uri, cache, opts)?;
+    authorize(&claims, context, hook)?;
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opa_decisions_are_folded_into_result() {
+        let claims: Claims = serde_json::from_value(json!({"sub":"did:key:zUser","scope":"orders:read"})).unwrap();
+        let ctx = json!({"method":"GET","path":["orders"]});
+
+        let sidecar = crate::tests::serve_once(r#"{"result":{"allow":true}}"#.to_string());
+        let opa = OpaClient::new(sidecar.trim_end_matches("/jwks.json"), "httpapi/authz");
+        assert!(authorize(&claims, &ctx, &opa).unwrap().allow);
+
+        let embedded = |input: &Json| -> Result<OpaDecision, VerifyError> {
+            let read = input["claims"]["scope"] == "orders:read" && input["request"]["method"] == "GET";
+            Ok(OpaDecision { allow: read, reasons: vec!["read-only token".into()] })
+        };
+        assert!(authorize(&claims, &ctx, &embedded).is_ok());
+        let post = json!({"method":"POST","path":["orders"]});
+        assert!(matches!(authorize(&claims, &post, &embedded), Err(VerifyError::Policy(why)) if why == "read-only token"));
+        assert_eq!(OpaDecision::from_result(None), OpaDecision::default());
+    }
+}
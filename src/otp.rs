@@ -0,0 +1,157 @@
+//! TOTP/HOTP second factor (feature `otp`, RFC 6238 / RFC 4226).
+//!
+//! [`OtpSecret`] holds the shared secret (base32 for authenticator apps, see
+//! [`Totp::provisioning_uri`]). [`Totp::verify_at`] accepts codes within `skew` steps of the
+//! current one and returns the matched step; callers store it and pass it back as `last_step` so
+//! a code cannot be used twice. After a successful check, [`record_success`] adds `acr`, `amr` and
+//! `auth_time` to the claims of the token about to be minted, which [`crate::stepup`] checks.
+
+use crate::{fill_random, now_ts, SignError};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn name(self) -> &'static str {
+        match self { Algorithm::Sha1 => "SHA1", Algorithm::Sha256 => "SHA256", Algorithm::Sha512 => "SHA512" }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct OtpSecret(Vec<u8>);
+
+impl std::fmt::Debug for OtpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.debug_struct("OtpSecret").finish_non_exhaustive() }
+}
+
+impl OtpSecret {
+    /// A random 160-bit secret (the RFC 4226 recommendation).
+    pub fn generate() -> Self {
+        let mut bytes = vec![0u8; 20];
+        fill_random(&mut bytes);
+        Self(bytes)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Self { Self(bytes.to_vec()) }
+    /// Parses base32 as shown by authenticator apps (case, spaces and padding are ignored).
+    pub fn from_base32(s: &str) -> Result<Self, SignError> {
+        let clean: String = s.chars().filter(|c| !c.is_whitespace() && *c != '=').collect::<String>().to_uppercase();
+        BASE32_NOPAD.decode(clean.as_bytes()).map(Self).map_err(|_| SignError::Secret("invalid base32 OTP secret".into()))
+    }
+    pub fn to_base32(&self) -> String { BASE32_NOPAD.encode(&self.0) }
+    pub fn as_bytes(&self) -> &[u8] { &self.0 }
+}
+
+/// RFC 4226 HOTP value for `counter`.
+pub fn hotp(secret: &OtpSecret, counter: u64, digits: u32, algorithm: Algorithm) -> String {
+    let msg = counter.to_be_bytes();
+    let mac = match algorithm {
+        Algorithm::Sha1 => hmac_bytes::<Hmac<sha1::Sha1>>(&secret.0, &msg),
+        Algorithm::Sha256 => hmac_bytes::<Hmac<sha2::Sha256>>(&secret.0, &msg),
+        Algorithm::Sha512 => hmac_bytes::<Hmac<sha2::Sha512>>(&secret.0, &msg),
+    };
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([mac[offset] & 0x7f, mac[offset + 1], mac[offset + 2], mac[offset + 3]]);
+    format!("{:0width$}", code as u64 % 10u64.pow(digits), width = digits as usize)
+}
+
+fn hmac_bytes<M: Mac + hmac::digest::KeyInit>(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(msg);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[derive(Debug, Clone)]
+pub struct Totp {
+    pub secret: OtpSecret,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub period_secs: u64,
+    /// Steps of clock drift accepted on either side.
+    pub skew: u64,
+}
+
+impl Totp {
+    /// SHA-1, 6 digits, 30-second steps and one step of skew: what authenticator apps expect.
+    pub fn new(secret: OtpSecret) -> Self { Self { secret, algorithm: Algorithm::Sha1, digits: 6, period_secs: 30, skew: 1 } }
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self { self.algorithm = algorithm; self }
+    pub fn with_digits(mut self, digits: u32) -> Self { self.digits = digits.clamp(6, 9); self }
+    pub fn with_period(mut self, secs: u64) -> Self { self.period_secs = secs.max(1); self }
+    pub fn with_skew(mut self, steps: u64) -> Self { self.skew = steps; self }
+
+    pub fn step_at(&self, unix_secs: i64) -> u64 { unix_secs.max(0) as u64 / self.period_secs }
+    pub fn generate_at(&self, unix_secs: i64) -> String { hotp(&self.secret, self.step_at(unix_secs), self.digits, self.algorithm) }
+    pub fn generate(&self) -> String { self.generate_at(now_ts()) }
+
+    /// Checks `code` at `unix_secs`, returning the matched step if it is later than `last_step`.
+    pub fn verify_at(&self, code: &str, unix_secs: i64, last_step: Option<u64>) -> Option<u64> {
+        let code = code.trim();
+        let now = self.step_at(unix_secs);
+        (now.saturating_sub(self.skew)..=now + self.skew)
+            .filter(|step| last_step.is_none_or(|last| *step > last))
+            .find(|step| bool::from(hotp(&self.secret, *step, self.digits, self.algorithm).as_bytes().ct_eq(code.as_bytes())))
+    }
+
+    pub fn verify(&self, code: &str, last_step: Option<u64>) -> Option<u64> { self.verify_at(code, now_ts(), last_step) }
+
+    /// `otpauth://totp/...` URI for QR provisioning.
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+            encode(issuer), encode(account), self.secret.to_base32(), encode(issuer), self.algorithm.name(), self.digits, self.period_secs
+        )
+    }
+}
+
+fn encode(s: &str) -> String {
+    s.bytes().map(|b| if b.is_ascii_alphanumeric() || b"-._~@".contains(&b) { (b as char).to_string() } else { format!("%{:02X}", b) }).collect()
+}
+
+/// Records a successful OTP check in claims about to be minted (e.g. [`crate::issuer::TokenRequest::claims`]):
+/// `acr`, `otp` added to `amr` (RFC 8176) and `auth_time`.
+pub fn record_success(claims: &mut HashMap<String, Json>, acr: &str, auth_time: i64) {
+    let amr = claims.entry("amr".into()).or_insert_with(|| Json::Array(Vec::new()));
+    if let Json::Array(values) = amr {
+        if !values.iter().any(|v| v == "otp") { values.push(Json::from("otp")); }
+    }
+    claims.insert("acr".into(), Json::from(acr));
+    claims.insert("auth_time".into(), Json::from(auth_time));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc6238_vectors_and_replay_protection() {
+        let sha1 = Totp::new(OtpSecret::from_bytes(b"12345678901234567890")).with_digits(8);
+        assert_eq!(sha1.generate_at(59), "94287082");
+        assert_eq!(sha1.generate_at(1_111_111_109), "07081804");
+        let sha256 = Totp::new(OtpSecret::from_bytes(b"12345678901234567890123456789012")).with_digits(8).with_algorithm(Algorithm::Sha256);
+        assert_eq!(sha256.generate_at(59), "46119246");
+        let sha512 = Totp::new(OtpSecret::from_bytes(&[&b"1234567890".repeat(6)[..], b"1234"].concat())).with_digits(8).with_algorithm(Algorithm::Sha512);
+        assert_eq!(sha512.generate_at(59), "90693936");
+        assert_eq!(hotp(&OtpSecret::from_bytes(b"12345678901234567890"), 1, 6, Algorithm::Sha1), "287082");
+
+        let totp = Totp::new(OtpSecret::from_base32(&sha1.secret.to_base32().to_lowercase()).unwrap());
+        let code = totp.generate_at(1_000_000);
+        let step = totp.verify_at(&code, 1_000_025, None).unwrap();
+        assert_eq!(totp.verify_at(&code, 1_000_025, Some(step)), None);
+        assert_eq!(totp.verify_at(&code, 1_000_100, None), None);
+        assert!(totp.provisioning_uri("UBL Auth", "alice@example.com").starts_with("otpauth://totp/UBL%20Auth:alice@example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&"));
+
+        let mut claims = HashMap::from([("amr".to_string(), serde_json::json!(["pwd"]))]);
+        record_success(&mut claims, "mfa", 1_000_025);
+        assert_eq!(claims["amr"], serde_json::json!(["pwd", "otp"]));
+        assert_eq!(claims["acr"], "mfa");
+    }
+}
@@ -0,0 +1,80 @@
+//! Issuer-side pairwise subject identifiers (feature `pairwise`).
+//!
+//! Each sector (a relying party or group of them, e.g. the host of its redirect URIs) sees a
+//! different, stable `sub` for the same user, so audiences cannot correlate users by comparing
+//! subjects. Values are `base64url(HKDF-SHA256(ikm = sub, salt, info = sector))`; the salt is an
+//! issuer secret. Derivation is one-way, so issuers that need to map back keep a [`PairwiseStore`].
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use hkdf::Hkdf;
+use parking_lot::Mutex;
+use sha2::Sha256;
+use std::collections::HashMap;
+
+/// Derives the pairwise `sub` of `sub` for `sector`.
+pub fn pairwise_sub(sub: &str, sector: &str, salt: &[u8]) -> String {
+    let mut out = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), sub.as_bytes()).expand(sector.as_bytes(), &mut out).expect("32 bytes is a valid HKDF-SHA256 length");
+    B64URL.encode(out)
+}
+
+/// Reverse mapping from `(sector, pairwise sub)` to the local subject.
+pub trait PairwiseStore: Send + Sync {
+    fn record(&self, sector: &str, pairwise: &str, sub: &str);
+    fn resolve(&self, sector: &str, pairwise: &str) -> Option<String>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryPairwiseStore { inner: Mutex<HashMap<(String, String), String>> }
+
+impl MemoryPairwiseStore {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl PairwiseStore for MemoryPairwiseStore {
+    fn record(&self, sector: &str, pairwise: &str, sub: &str) {
+        self.inner.lock().insert((sector.to_string(), pairwise.to_string()), sub.to_string());
+    }
+    fn resolve(&self, sector: &str, pairwise: &str) -> Option<String> { self.inner.lock().get(&(sector.to_string(), pairwise.to_string())).cloned() }
+}
+
+pub struct PairwiseSubjects<S: PairwiseStore> {
+    salt: Vec<u8>,
+    store: S,
+}
+
+impl<S: PairwiseStore + std::fmt::Debug> std::fmt::Debug for PairwiseSubjects<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PairwiseSubjects").field("store", &self.store).finish_non_exhaustive()
+    }
+}
+
+impl<S: PairwiseStore> PairwiseSubjects<S> {
+    pub fn new(salt: &[u8], store: S) -> Self { Self { salt: salt.to_vec(), store } }
+
+    /// The `sub` to put in tokens for `sector`; records the reverse mapping.
+    pub fn subject_for(&self, sub: &str, sector: &str) -> String {
+        let pairwise = pairwise_sub(sub, sector, &self.salt);
+        self.store.record(sector, &pairwise, sub);
+        pairwise
+    }
+
+    pub fn resolve(&self, sector: &str, pairwise: &str) -> Option<String> { self.store.resolve(sector, pairwise) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_per_sector_and_reversible() {
+        let subjects = PairwiseSubjects::new(b"issuer-salt", MemoryPairwiseStore::new());
+        let a = subjects.subject_for("user-42", "orders.example");
+        let b = subjects.subject_for("user-42", "billing.example");
+        assert_ne!(a, b);
+        assert_eq!(a, pairwise_sub("user-42", "orders.example", b"issuer-salt"));
+        assert_ne!(a, pairwise_sub("user-42", "orders.example", b"other-salt"));
+        assert_eq!(subjects.resolve("orders.example", &a).as_deref(), Some("user-42"));
+        assert_eq!(subjects.resolve("billing.example", &a), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Argon2id password hashing for legacy password logins (feature `password`).
+//!
+//! Hashes are PHC strings (`$argon2id$v=19$m=19456,t=2,p=1$salt$hash`), so algorithm, version
+//! and cost travel with every hash. [`Passwords::verify`] reports whether a matching hash was
+//! made with weaker (or merely different) settings than the current ones, so the caller can store
+//! a fresh [`Passwords::hash`] while it still has the plaintext. An optional pepper is mixed in as
+//! the Argon2 secret and is not stored in the hash.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PasswordError {
+    /// Wrong password, or a stored hash this configuration cannot check.
+    #[error("password does not match")]
+    Mismatch,
+    #[error("password hashing failed: {0}")]
+    Hash(String),
+}
+
+/// Outcome of a successful [`Passwords::verify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordMatch {
+    /// The stored hash does not use the current algorithm, version or cost; rehash and store it.
+    pub needs_rehash: bool,
+}
+
+#[derive(Clone)]
+pub struct Passwords {
+    /// Memory cost in KiB.
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pepper: Option<Vec<u8>>,
+}
+
+impl std::fmt::Debug for Passwords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Passwords").field("m_cost", &self.m_cost).field("t_cost", &self.t_cost).field("p_cost", &self.p_cost).finish_non_exhaustive()
+    }
+}
+
+impl Default for Passwords {
+    /// The OWASP argon2id baseline: 19 MiB, 2 iterations, 1 lane.
+    fn default() -> Self { Self { m_cost: 19 * 1024, t_cost: 2, p_cost: 1, pepper: None } }
+}
+
+impl Passwords {
+    pub fn new() -> Self { Self::default() }
+    pub fn with_cost(mut self, m_cost_kib: u32, t_cost: u32, p_cost: u32) -> Self { self.m_cost = m_cost_kib; self.t_cost = t_cost; self.p_cost = p_cost; self }
+    pub fn with_pepper(mut self, pepper: &[u8]) -> Self { self.pepper = Some(pepper.to_vec()); self }
+
+    /// PHC string for `password` with a fresh 16-byte salt.
+    pub fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        let mut salt = [0u8; 16];
+        crate::fill_random(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|e| PasswordError::Hash(e.to_string()))?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, None).map_err(|e| PasswordError::Hash(e.to_string()))?;
+        let hash = self.argon2(params)?.hash_password(password.as_bytes(), &salt).map_err(|e| PasswordError::Hash(e.to_string()))?;
+        Ok(hash.to_string())
+    }
+
+    /// Checks `password` against a stored PHC string; fails with [`PasswordError::Mismatch`] on any mismatch.
+    pub fn verify(&self, password: &str, phc: &str) -> Result<PasswordMatch, PasswordError> {
+        let hash = PasswordHash::new(phc).map_err(|_| PasswordError::Mismatch)?;
+        let params = Params::try_from(&hash).map_err(|_| PasswordError::Mismatch)?;
+        let argon2 = self.argon2(params.clone()).map_err(|_| PasswordError::Mismatch)?;
+        argon2.verify_password(password.as_bytes(), &hash).map_err(|_| PasswordError::Mismatch)?;
+        let current = hash.algorithm.as_str() == Algorithm::Argon2id.ident().as_str()
+            && hash.version == Some(Version::V0x13.into())
+            && (params.m_cost(), params.t_cost(), params.p_cost()) == (self.m_cost, self.t_cost, self.p_cost);
+        Ok(PasswordMatch { needs_rehash: !current })
+    }
+
+    fn argon2(&self, params: Params) -> Result<Argon2<'_>, PasswordError> {
+        match &self.pepper {
+            Some(pepper) => Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params).map_err(|e| PasswordError::Hash(e.to_string())),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_verify_and_flag_outdated_cost() {
+        let weak = Passwords::new().with_cost(64, 1, 1).with_pepper(b"pepper");
+        let stored = weak.hash("correct horse").unwrap();
+        assert!(stored.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        assert_eq!(weak.verify("correct horse", &stored).unwrap(), PasswordMatch { needs_rehash: false });
+        assert!(matches!(weak.verify("wrong", &stored), Err(PasswordError::Mismatch)));
+        assert!(matches!(Passwords::new().with_cost(64, 1, 1).verify("correct horse", &stored), Err(PasswordError::Mismatch)));
+
+        let stronger = Passwords::new().with_cost(128, 2, 1).with_pepper(b"pepper");
+        assert!(stronger.verify("correct horse", &stored).unwrap().needs_rehash);
+        assert!(matches!(stronger.verify("correct horse", "plaintext"), Err(PasswordError::Mismatch)));
+    }
+}
@@ -0,0 +1,76 @@
+//! Issuer and audience patterns with captures.
+//!
+//! `{name}` captures a named variable and `*` an anonymous one (numbered `0`, `1`, ... from the
+//! left). Either matches one or more of `A-Z a-z 0-9 - _ .` — never `/`, `:`, `?` or `#`, so a
+//! variable cannot swallow a path or authority boundary. Everything else matches literally.
+//!
+//! `https://login.microsoftonline.com/{tid}/v2.0` and `https://*.tenants.ubl.agency` are typical.
+
+use std::collections::BTreeMap;
+
+enum Part<'a> { Lit(&'a str), Var(String) }
+
+fn parse(pattern: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let (mut rest, mut anon) = (pattern, 0);
+    while !rest.is_empty() {
+        let next = rest.find(['{', '*']).unwrap_or(rest.len());
+        if next > 0 { parts.push(Part::Lit(&rest[..next])); rest = &rest[next..]; continue; }
+        if let Some(after) = rest.strip_prefix('*') {
+            parts.push(Part::Var(anon.to_string()));
+            anon += 1;
+            rest = after;
+        } else if let Some(end) = rest.find('}') {
+            parts.push(Part::Var(rest[1..end].to_string()));
+            rest = &rest[end + 1..];
+        } else {
+            parts.push(Part::Lit(rest));
+            rest = "";
+        }
+    }
+    parts
+}
+
+fn var_char(c: char) -> bool { c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') }
+
+fn match_parts(parts: &[Part<'_>], s: &str, caps: &mut BTreeMap<String, String>) -> bool {
+    match parts.split_first() {
+        None => s.is_empty(),
+        Some((Part::Lit(lit), rest)) => s.strip_prefix(lit).is_some_and(|tail| match_parts(rest, tail, caps)),
+        Some((Part::Var(name), rest)) => {
+            let max = s.find(|c: char| !var_char(c)).unwrap_or(s.len());
+            // Longest first, so `{a}.example` prefers capturing as much as possible.
+            for end in (1..=max).rev() {
+                if match_parts(rest, &s[end..], caps) {
+                    caps.insert(name.clone(), s[..end].to_string());
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Matches `s` against `pattern`, returning the captured variables on success.
+pub fn captures(pattern: &str, s: &str) -> Option<BTreeMap<String, String>> {
+    let mut caps = BTreeMap::new();
+    match_parts(&parse(pattern), s, &mut caps).then_some(caps)
+}
+
+pub fn matches(pattern: &str, s: &str) -> bool { captures(pattern, s).is_some() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_and_wildcards_capture() {
+        let caps = captures("https://login.microsoftonline.com/{tid}/v2.0", "https://login.microsoftonline.com/9188040d-6c67/v2.0").unwrap();
+        assert_eq!(caps["tid"], "9188040d-6c67");
+        assert_eq!(captures("https://*.tenants.ubl.agency", "https://acme.tenants.ubl.agency").unwrap()["0"], "acme");
+        assert!(!matches("https://*.tenants.ubl.agency", "https://evil.com/x.tenants.ubl.agency"));
+        assert!(!matches("https://*.tenants.ubl.agency", "https://.tenants.ubl.agency"));
+        assert!(!matches("https://login.microsoftonline.com/{tid}/v2.0", "https://login.microsoftonline.com/a/b/v2.0"));
+        assert!(matches("exact", "exact") && !matches("exact", "exactly"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Trust-on-first-use key pinning for deployments without a PKI.
+//!
+//! The first keys seen for an issuer are pinned. Keys that show up in its JWKS later are held as
+//! *pending* and are not trusted until an operator calls [`KeyPins::approve`]. Keys are identified
+//! by their public key (`x`), so a re-published key under a new `kid` stays trusted.
+
+use crate::{load_jwks, verify_ed25519_jwt_detailed, Jwks, JwksCache, Verified, VerifyError, VerifyOptions};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Pinned and pending public keys (base64url `x`) for one issuer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinSet {
+    pub pinned: Vec<String>,
+    #[serde(default)]
+    pub pending: Vec<String>,
+}
+
+pub trait PinStore: Send + Sync {
+    fn load(&self, issuer: &str) -> Option<PinSet>;
+    fn save(&self, issuer: &str, pins: &PinSet) -> std::io::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryPinStore { inner: Mutex<HashMap<String, PinSet>> }
+
+impl MemoryPinStore {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl PinStore for MemoryPinStore {
+    fn load(&self, issuer: &str) -> Option<PinSet> { self.inner.lock().get(issuer).cloned() }
+    fn save(&self, issuer: &str, pins: &PinSet) -> std::io::Result<()> {
+        self.inner.lock().insert(issuer.to_string(), pins.clone());
+        Ok(())
+    }
+}
+
+/// Pins persisted as one JSON object (`issuer -> PinSet`), rewritten atomically on every change.
+#[derive(Debug)]
+pub struct FilePinStore { path: PathBuf, lock: Mutex<()> }
+
+impl FilePinStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self { Self { path: path.into(), lock: Mutex::new(()) } }
+
+    fn read_all(&self) -> std::io::Result<HashMap<String, PinSet>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl PinStore for FilePinStore {
+    fn load(&self, issuer: &str) -> Option<PinSet> {
+        let _guard = self.lock.lock();
+        self.read_all().ok()?.remove(issuer)
+    }
+
+    fn save(&self, issuer: &str, pins: &PinSet) -> std::io::Result<()> {
+        let _guard = self.lock.lock();
+        let mut all = self.read_all()?;
+        all.insert(issuer.to_string(), pins.clone());
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&all)?)?;
+        std::fs::rename(tmp, &self.path)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PinError {
+    #[error("key not pinned for issuer; operator approval required")]
+    NotPinned,
+    #[error("pin store error: {0}")]
+    Store(String),
+}
+
+/// TOFU verifier: pins on first use and only trusts pinned keys afterwards.
+#[derive(Debug)]
+pub struct KeyPins<S: PinStore> { store: S, lock: Mutex<()> }
+
+impl<S: PinStore> KeyPins<S> {
+    pub fn new(store: S) -> Self { Self { store, lock: Mutex::new(()) } }
+
+    /// Verifies `token` using only the pinned keys of its issuer (`opts.issuer`, else `jwks_uri`).
+    /// Fails with [`PinError::NotPinned`] when the token needs a key that awaits approval.
+    pub fn verify(&self, token: &str, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<Verified, VerifyError> {
+        let issuer = opts.issuer.as_deref().unwrap_or(jwks_uri);
+        let jwks = load_jwks(jwks_uri, cache)?;
+        let pins = self.observe(issuer, &jwks)?;
+        let trusted = Jwks { keys: jwks.keys.into_iter().filter(|k| k.x.as_ref().is_some_and(|x| pins.pinned.contains(x))).collect() };
+        let scratch = JwksCache::new(i64::MAX);
+        scratch.put(jwks_uri, trusted);
+        match verify_ed25519_jwt_detailed(token, jwks_uri, &scratch, opts) {
+            Err(VerifyError::NoKey | VerifyError::Kid) if !pins.pending.is_empty() => Err(PinError::NotPinned.into()),
+            other => other,
+        }
+    }
+
+    /// Keys seen for `issuer` that are waiting for approval.
+    pub fn pending(&self, issuer: &str) -> Vec<String> {
+        self.store.load(issuer).map(|p| p.pending).unwrap_or_default()
+    }
+
+    /// Operator approval: trusts the pending key `x`. Returns `false` if it was not pending.
+    pub fn approve(&self, issuer: &str, x: &str) -> Result<bool, PinError> {
+        self.update(issuer, |pins| {
+            let before = pins.pending.len();
+            pins.pending.retain(|p| p != x);
+            if pins.pending.len() == before { return false; }
+            pins.pinned.push(x.to_string());
+            true
+        })
+    }
+
+    /// Stops trusting `x` for `issuer`. Returns `false` if it was not pinned.
+    pub fn unpin(&self, issuer: &str, x: &str) -> Result<bool, PinError> {
+        self.update(issuer, |pins| {
+            let before = pins.pinned.len();
+            pins.pinned.retain(|p| p != x);
+            pins.pinned.len() != before
+        })
+    }
+
+    /// Pins everything on first sight; afterwards records unknown keys as pending.
+    fn observe(&self, issuer: &str, jwks: &Jwks) -> Result<PinSet, PinError> {
+        let _guard = self.lock.lock();
+        let seen: Vec<String> = jwks.keys.iter().filter_map(|k| k.x.clone()).collect();
+        let mut pins = match self.store.load(issuer) {
+            Some(pins) => pins,
+            None => {
+                let pins = PinSet { pinned: seen, pending: Vec::new() };
+                self.store.save(issuer, &pins).map_err(|e| PinError::Store(e.to_string()))?;
+                return Ok(pins);
+            }
+        };
+        let new: Vec<String> = seen.into_iter().filter(|x| !pins.pinned.contains(x) && !pins.pending.contains(x)).collect();
+        if !new.is_empty() {
+            pins.pending.extend(new);
+            self.store.save(issuer, &pins).map_err(|e| PinError::Store(e.to_string()))?;
+        }
+        Ok(pins)
+    }
+
+    fn update(&self, issuer: &str, f: impl FnOnce(&mut PinSet) -> bool) -> Result<bool, PinError> {
+        let _guard = self.lock.lock();
+        let mut pins = self.store.load(issuer).unwrap_or_default();
+        if !f(&mut pins) { return Ok(false); }
+        self.store.save(issuer, &pins).map_err(|e| PinError::Store(e.to_string()))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sign_compact, Jwk};
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn first_key_is_pinned_and_new_keys_need_approval() {
+        let old = SigningKey::generate(&mut StdRng::seed_from_u64(21));
+        let new = SigningKey::generate(&mut StdRng::seed_from_u64(22));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("k1", &old.verifying_key())] });
+        let path = std::env::temp_dir().join(format!("ubl-auth-pins-{}.json", std::process::id()));
+        let pins = KeyPins::new(FilePinStore::new(&path));
+        let opts = VerifyOptions::default().with_issuer("https://edge.local");
+        let claims = json!({"sub":"did:key:zEdge","iss":"https://edge.local"});
+
+        assert!(pins.verify(&sign_compact(&claims, &old, "k1").unwrap(), "mem://jwks", &cache, &opts).is_ok());
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("k1", &old.verifying_key()), Jwk::ed25519("k2", &new.verifying_key())] });
+        let token = sign_compact(&claims, &new, "k2").unwrap();
+        assert!(matches!(pins.verify(&token, "mem://jwks", &cache, &opts), Err(VerifyError::Pinning(PinError::NotPinned))));
+
+        let reloaded = KeyPins::new(FilePinStore::new(&path));
+        let pending = reloaded.pending("https://edge.local");
+        assert_eq!(pending.len(), 1);
+        assert!(reloaded.approve("https://edge.local", &pending[0]).unwrap());
+        assert!(reloaded.verify(&token, "mem://jwks", &cache, &opts).is_ok());
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,146 @@
+//! Claim policies as data.
+//!
+//! A [`Policy`] document (JSON, or TOML with the `toml` feature) captures the whole claim policy
+//! of a service — issuers, audiences, scopes, required claims, tenants — so it can be reviewed and
+//! versioned apart from code. It is evaluated after signature and registered-claim validation.
+//!
+//! ```toml
+//! issuers = ["https://login.example.com/*"]
+//! audiences = ["orders-api"]
+//! required_claims = ["jti"]
+//! scopes = { all_of = ["orders:read"] }
+//! tenant = { claim = "tid", allowed = ["acme", "globex"] }
+//! ```
+
+use crate::presets::Preset;
+use crate::{glob_match, Claims, VerifyError, VerifyOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Accepted `iss` values; `*` wildcards allowed. Empty accepts any issuer.
+    pub issuers: Vec<String>,
+    /// `aud` must contain at least one of these. Empty accepts any audience.
+    pub audiences: Vec<String>,
+    /// Claims that must be present (registered or custom, by name).
+    pub required_claims: Vec<String>,
+    pub scopes: ScopeRule,
+    pub tenant: Option<TenantRule>,
+    /// Claims that must equal one of the listed values.
+    pub claim_values: BTreeMap<String, Vec<Json>>,
+    pub leeway_secs: Option<i64>,
+    /// Namespace prefix for custom claims named in `required_claims` / `claim_values` (see
+    /// [`VerifyOptions::get_custom`]).
+    pub claim_namespace: Option<String>,
+}
+
+/// Rules over the space-separated `scope` claim.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScopeRule {
+    pub all_of: Vec<String>,
+    pub any_of: Vec<String>,
+}
+
+/// Restricts which tenants (the string value of `claim`) may call the service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantRule {
+    pub claim: String,
+    pub allowed: Vec<String>,
+}
+
+impl Policy {
+    pub fn from_json(doc: &str) -> Result<Self, serde_json::Error> { serde_json::from_str(doc) }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(doc: &str) -> Result<Self, toml::de::Error> { toml::from_str(doc) }
+
+    /// Evaluates the policy against already-verified claims.
+    pub fn check(&self, claims: &Claims) -> Result<(), VerifyError> {
+        let map = claims.to_map();
+        let claim = |name: &str| map.get(name).or_else(|| claims.get_custom_in(self.claim_namespace.as_deref()?, name));
+        let deny = |why: String| Err(VerifyError::Policy(why));
+
+        if !self.issuers.is_empty() && !claims.iss.as_deref().is_some_and(|iss| self.issuers.iter().any(|p| glob_match(p, iss))) {
+            return Err(VerifyError::Issuer);
+        }
+        if !self.audiences.is_empty() && !claims.aud.as_ref().is_some_and(|aud| self.audiences.iter().any(|a| aud.contains(a))) {
+            return Err(VerifyError::Audience);
+        }
+        if let Some(name) = self.required_claims.iter().find(|n| claim(n).is_none()) {
+            return deny(format!("missing claim '{}'", name));
+        }
+
+        let held: Vec<&str> = claims.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+        if let Some(s) = self.scopes.all_of.iter().find(|s| !held.contains(&s.as_str())) {
+            return deny(format!("missing scope '{}'", s));
+        }
+        if !self.scopes.any_of.is_empty() && !self.scopes.any_of.iter().any(|s| held.contains(&s.as_str())) {
+            return deny(format!("none of scopes {:?}", self.scopes.any_of));
+        }
+
+        if let Some(t) = &self.tenant {
+            match claim(&t.claim).and_then(Json::as_str) {
+                Some(tenant) if t.allowed.iter().any(|a| a == tenant) => {}
+                Some(tenant) => return deny(format!("tenant '{}' not allowed", tenant)),
+                None => return deny(format!("missing claim '{}'", t.claim)),
+            }
+        }
+        for (name, allowed) in &self.claim_values {
+            if !claim(name).is_some_and(|v| allowed.contains(v)) {
+                return deny(format!("claim '{}' not an allowed value", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// A [`Preset`] that verifies against `jwks_uri` and then applies this policy.
+    pub fn preset(&self, jwks_uri: &str) -> Preset {
+        let mut opts = VerifyOptions::default();
+        if let Some(leeway) = self.leeway_secs { opts = opts.with_leeway(leeway); }
+        if let Some(ns) = &self.claim_namespace { opts = opts.with_claim_namespace(ns); }
+        let policy = self.clone();
+        Preset::new("policy", jwks_uri, opts).with_check(move |c| policy.check(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn policy_document_is_enforced() {
+        let policy = Policy::from_json(r#"{
+            "issuers": ["https://login.example.com/*"],
+            "audiences": ["orders-api"],
+            "required_claims": ["jti"],
+            "scopes": {"all_of": ["orders:read"]},
+            "tenant": {"claim": "tid", "allowed": ["acme"]},
+            "claim_values": {"env": ["prod", "staging"]}
+        }"#).unwrap();
+        let claims = |extra: Json| -> Claims {
+            let mut base = json!({"sub":"did:key:zUser","iss":"https://login.example.com/t1","aud":"orders-api","jti":"j1",
+                                  "scope":"orders:read orders:write","tid":"acme","env":"prod"});
+            base.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(base).unwrap()
+        };
+
+        assert!(policy.check(&claims(json!({}))).is_ok());
+        assert!(matches!(policy.check(&claims(json!({"iss":"https://evil.example.com/"}))), Err(VerifyError::Issuer)));
+        assert!(matches!(policy.check(&claims(json!({"tid":"globex"}))), Err(VerifyError::Policy(_))));
+        assert!(matches!(policy.check(&claims(json!({"scope":"orders:write"}))), Err(VerifyError::Policy(_))));
+        assert!(matches!(policy.check(&claims(json!({"env":"dev"}))), Err(VerifyError::Policy(_))));
+        assert!(Policy::from_json(r#"{"issuer": "typo"}"#).is_err());
+        let namespaced = Policy::from_json(r#"{"required_claims": ["roles"], "claim_namespace": "https://ubl.agency/"}"#).unwrap();
+        assert!(namespaced.check(&claims(json!({"https://ubl.agency/roles": ["admin"]}))).is_ok());
+        assert!(namespaced.check(&claims(json!({}))).is_err());
+
+        #[cfg(feature = "toml")]
+        assert_eq!(Policy::from_toml("audiences = [\"orders-api\"]\nscopes = { any_of = [\"a\"] }").unwrap().scopes.any_of, vec!["a"]);
+    }
+}
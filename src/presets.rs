@@ -0,0 +1,703 @@
+//! Verification presets for well-known token profiles.
+//!
+//! A [`Preset`] bundles the JWKS location, the [`VerifyOptions`] and any profile-specific claim
+//! checks that must run after the generic ones.
+//!
+//! Third-party identity providers sign with RS256, so their presets accept RS256 (and only RS256)
+//! when the `rs256` feature is enabled; without it they can only verify EdDSA tokens.
+
+use crate::mapping::ClaimsMapper;
+use crate::{discover_jwks_uri, glob_match, verify_ed25519_jwt_with_cache, Claims, JwksCache, VerifyError, VerifyOptions};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+type Check = Arc<dyn Fn(&Claims) -> Result<(), VerifyError> + Send + Sync>;
+
+/// Options for SPIFFE JWT-SVIDs: the algorithms SPIFFE issuers sign with (ES256, RS256) whose
+/// features are enabled, or EdDSA without either.
+fn svid_options() -> VerifyOptions {
+    let algs: &[&str] = &[#[cfg(feature = "es256")] "ES256", #[cfg(feature = "rs256")] "RS256"];
+    match algs.is_empty() {
+        true => VerifyOptions::default(),
+        false => VerifyOptions::default().with_allowed_algs(algs),
+    }
+}
+
+/// Options for identity-provider presets: RS256 when the `rs256` feature is enabled.
+fn idp_options() -> VerifyOptions {
+    let opts = VerifyOptions::default();
+    #[cfg(feature = "rs256")]
+    let opts = opts.with_allowed_algs(&["RS256"]);
+    opts
+}
+
+#[derive(Clone)]
+pub struct Preset {
+    pub name: &'static str,
+    pub jwks_uri: String,
+    pub options: VerifyOptions,
+    checks: Vec<Check>,
+    mapper: Option<ClaimsMapper>,
+}
+
+impl std::fmt::Debug for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Preset")
+            .field("name", &self.name)
+            .field("jwks_uri", &self.jwks_uri)
+            .field("options", &self.options)
+            .field("checks", &self.checks.len())
+            .field("mapper", &self.mapper)
+            .finish()
+    }
+}
+
+impl Preset {
+    pub fn new(name: &'static str, jwks_uri: &str, options: VerifyOptions) -> Self {
+        Self { name, jwks_uri: jwks_uri.to_string(), options, checks: Vec::new(), mapper: None }
+    }
+
+    /// Adds a profile check run after signature and registered-claim validation.
+    pub fn with_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Claims) -> Result<(), VerifyError> + Send + Sync + 'static,
+    {
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    /// Normalizes claims with `mapper` after the checks pass; [`Preset::verify`] returns the mapped claims.
+    pub fn with_mapper(mut self, mapper: ClaimsMapper) -> Self {
+        self.mapper = Some(mapper);
+        self
+    }
+
+    pub fn verify(&self, token: &str, cache: &JwksCache) -> Result<Claims, VerifyError> {
+        let claims = verify_ed25519_jwt_with_cache(token, &self.jwks_uri, cache, &self.options)?;
+        self.check(&claims)?;
+        match &self.mapper {
+            Some(mapper) => mapper.apply(&claims),
+            None => Ok(claims),
+        }
+    }
+
+    pub fn health_check(&self) -> crate::HealthReport { crate::health_check(&[&self.jwks_uri]) }
+
+    /// Runs only the profile checks, for claims verified elsewhere.
+    pub fn check(&self, claims: &Claims) -> Result<(), VerifyError> {
+        self.checks.iter().try_for_each(|c| c(claims))
+    }
+
+    /// SPIFFE JWT-SVID: `aud` must contain `audience`, `exp` is mandatory, `sub` must be a
+    /// SPIFFE ID in `trust_domain`, and no clock leeway is granted. Keys come from the trust
+    /// domain's bundle endpoint (JWKS-shaped, keys with `use: jwt-svid`); ES256 and RS256 are
+    /// accepted when their features are enabled.
+    pub fn spiffe_jwt_svid(trust_domain: &str, bundle_endpoint: &str, audience: &str) -> Self {
+        let opts = svid_options().with_audience(audience).with_leeway(0);
+        let prefix = format!("spiffe://{}/", trust_domain.trim_end_matches('/'));
+        Preset::new("spiffe-jwt-svid", bundle_endpoint, opts)
+            .with_check(|c| if c.exp.is_some() { Ok(()) } else { Err(VerifyError::MissingClaim("exp")) })
+            .with_check(move |c| {
+                let path = c.sub.strip_prefix(&prefix).ok_or(VerifyError::Subject)?;
+                if path.is_empty() || path.split('/').any(|seg| seg.is_empty() || seg == "." || seg == "..") { return Err(VerifyError::Subject); }
+                Ok(())
+            })
+    }
+
+    /// Kubernetes projected service account tokens, with keys at the API server's default
+    /// `{issuer}/openid/v1/jwks`. The token must be bound to `audience` and carry a well-formed
+    /// `kubernetes.io` block matching its `system:serviceaccount:` subject.
+    pub fn kubernetes(issuer: &str, audience: &str) -> Self {
+        let jwks_uri = format!("{}/openid/v1/jwks", issuer.trim_end_matches('/'));
+        Self::kubernetes_with_jwks(issuer, &jwks_uri, audience)
+    }
+
+    /// Like [`Preset::kubernetes`] but locates the JWKS through the cluster issuer's OIDC discovery document.
+    pub fn kubernetes_discovered(issuer: &str, audience: &str) -> Result<Self, VerifyError> {
+        Ok(Self::kubernetes_with_jwks(issuer, &discover_jwks_uri(issuer)?, audience))
+    }
+
+    fn kubernetes_with_jwks(issuer: &str, jwks_uri: &str, audience: &str) -> Self {
+        let opts = idp_options().with_issuer(issuer).with_audience(audience);
+        Preset::new("kubernetes", jwks_uri, opts).with_check(|c| KubernetesClaims::from_claims(c).map(|_| ()))
+    }
+
+    /// Requires `iss` to be exactly one of `issuers` (for IdPs that emit more than one spelling).
+    pub fn with_issuers(self, issuers: &[&str]) -> Self {
+        let issuers: Vec<String> = issuers.iter().map(|s| s.to_string()).collect();
+        self.with_check(move |c| match &c.iss {
+            Some(iss) if issuers.contains(iss) => Ok(()),
+            _ => Err(VerifyError::Issuer),
+        })
+    }
+
+    /// Google-signed identity tokens for GCP service identities (metadata server / IAM Credentials).
+    /// Google emits `iss` both with and without the scheme; `audience` is the target audience requested.
+    pub fn gcp_identity(audience: &str) -> Self {
+        Preset::new("gcp-identity", "https://www.googleapis.com/oauth2/v3/certs", idp_options().with_audience(audience))
+            .with_issuers(&["https://accounts.google.com", "accounts.google.com"])
+    }
+
+    /// AWS Cognito user pool tokens. ID tokens carry `aud = client_id`, access tokens carry no `aud`
+    /// but a `client_id` claim instead; both are accepted, distinguished by `token_use`.
+    pub fn aws_cognito(region: &str, user_pool_id: &str, client_id: &str) -> Self {
+        let issuer = format!("https://cognito-idp.{}.amazonaws.com/{}", region, user_pool_id);
+        let jwks_uri = format!("{}/.well-known/jwks.json", issuer);
+        let client_id = client_id.to_string();
+        Preset::new("aws-cognito", &jwks_uri, idp_options().with_issuer(&issuer)).with_check(move |c| {
+            let bound = match c.extra.get("token_use").and_then(|v| v.as_str()) {
+                Some("id") => c.aud.as_ref().is_some_and(|a| a.contains(&client_id)),
+                Some("access") => c.extra.get("client_id").and_then(|v| v.as_str()) == Some(client_id.as_str()),
+                _ => return Err(VerifyError::MissingClaim("token_use")),
+            };
+            if bound { Ok(()) } else { Err(VerifyError::Audience) }
+        })
+    }
+
+    /// Azure managed identity tokens for `tenant_id`. Both the v1 (`sts.windows.net`) and v2
+    /// (`login.microsoftonline.com/.../v2.0`) issuer forms are accepted and `tid` must match.
+    pub fn azure_managed_identity(tenant_id: &str, audience: &str) -> Self {
+        let jwks_uri = format!("https://login.microsoftonline.com/{}/discovery/v2.0/keys", tenant_id);
+        let v1 = format!("https://sts.windows.net/{}/", tenant_id);
+        let v2 = format!("https://login.microsoftonline.com/{}/v2.0", tenant_id);
+        let tid = tenant_id.to_string();
+        Preset::new("azure-managed-identity", &jwks_uri, idp_options().with_audience(audience))
+            .with_issuers(&[&v1, &v2])
+            .with_check(move |c| match c.extra.get("tid").and_then(|v| v.as_str()) {
+                Some(t) if t == tid => Ok(()),
+                Some(_) => Err(VerifyError::Issuer),
+                None => Err(VerifyError::MissingClaim("tid")),
+            })
+    }
+}
+
+impl Preset {
+    /// GitHub Actions OIDC tokens. `audience` is what the workflow requested (`core.getIDToken(aud)`);
+    /// `repo_pattern` (e.g. `"org/*"`) restricts which repositories may authenticate.
+    pub fn github_actions(audience: &str, repo_pattern: &str) -> Self {
+        let issuer = "https://token.actions.githubusercontent.com";
+        let pattern = repo_pattern.to_string();
+        Preset::new("github-actions", &format!("{}/.well-known/jwks", issuer), idp_options().with_issuer(issuer).with_audience(audience))
+            .with_check(move |c| if GithubActionsClaims::from_claims(c)?.repo_matches(&pattern) { Ok(()) } else { Err(VerifyError::Subject) })
+    }
+}
+
+impl Preset {
+    /// Sign in with Apple ID tokens for `client_id` (bundle or services ID).
+    pub fn apple_sign_in(client_id: &str) -> Self {
+        let issuer = "https://appleid.apple.com";
+        Preset::new("apple-sign-in", "https://appleid.apple.com/auth/keys", idp_options().with_issuer(issuer).with_audience(client_id))
+    }
+
+    /// Google Sign-In ID tokens for `client_id`; both `iss` spellings Google uses are accepted.
+    pub fn google_sign_in(client_id: &str) -> Self {
+        Preset::new("google-sign-in", "https://www.googleapis.com/oauth2/v3/certs", idp_options().with_audience(client_id))
+            .with_issuers(&["https://accounts.google.com", "accounts.google.com"])
+    }
+
+    /// Requires the `nonce` claim to equal the value sent in the authorization request.
+    pub fn with_nonce(self, nonce: &str) -> Self {
+        let nonce = nonce.to_string();
+        self.with_check(move |c| match c.extra.get("nonce").and_then(|v| v.as_str()) {
+            Some(n) if n == nonce => Ok(()),
+            Some(_) => Err(VerifyError::Nonce),
+            None => Err(VerifyError::MissingClaim("nonce")),
+        })
+    }
+
+    /// Requires an `email` whose `email_verified` is true (see [`email_verified`]).
+    pub fn with_verified_email(self) -> Self {
+        self.with_check(|c| if c.extra.contains_key("email") && email_verified(c) { Ok(()) } else { Err(VerifyError::MissingClaim("email_verified")) })
+    }
+}
+
+/// Reads `email_verified`, which Apple sends as the string `"true"`/`"false"` and Google as a boolean.
+pub fn email_verified(c: &Claims) -> bool {
+    match c.extra.get("email_verified") {
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(serde_json::Value::String(s)) => s == "true",
+        _ => false,
+    }
+}
+
+impl Preset {
+    /// Keycloak realm tokens; keys at `{base_url}/realms/{realm}/protocol/openid-connect/certs`.
+    pub fn keycloak(base_url: &str, realm: &str, audience: &str) -> Self {
+        let issuer = format!("{}/realms/{}", base_url.trim_end_matches('/'), realm);
+        let jwks_uri = format!("{}/protocol/openid-connect/certs", issuer);
+        Preset::new("keycloak", &jwks_uri, idp_options().with_issuer(&issuer).with_audience(audience))
+    }
+
+    /// Role guard: requires a Keycloak realm role.
+    pub fn require_realm_role(self, role: &str) -> Self {
+        let role = role.to_string();
+        self.with_check(move |c| if KeycloakRoles::from_claims(c).has_realm_role(&role) { Ok(()) } else { Err(VerifyError::MissingRole(role.clone())) })
+    }
+
+    /// Role guard: requires a Keycloak client role under `resource_access.{client}`.
+    pub fn require_client_role(self, client: &str, role: &str) -> Self {
+        let (client, role) = (client.to_string(), role.to_string());
+        self.with_check(move |c| {
+            if KeycloakRoles::from_claims(c).has_client_role(&client, &role) { Ok(()) } else { Err(VerifyError::MissingRole(format!("{}:{}", client, role))) }
+        })
+    }
+}
+
+/// v2.0 issuer template; `{tenantid}` is substituted with the token's `tid`.
+pub const ENTRA_ISSUER_TEMPLATE: &str = "https://login.microsoftonline.com/{tenantid}/v2.0";
+
+impl Preset {
+    /// Microsoft Entra ID v2.0 tokens for `client_id`. `tenants` lists the accepted tenant ids;
+    /// an empty list accepts any tenant (multi-tenant apps), still requiring `iss` to equal the
+    /// issuer template with the token's own `tid` substituted.
+    pub fn entra_id(client_id: &str, tenants: &[&str]) -> Self {
+        let jwks_tenant = if tenants.len() == 1 { tenants[0] } else { "common" };
+        let jwks_uri = format!("https://login.microsoftonline.com/{}/discovery/v2.0/keys", jwks_tenant);
+        let tenants: Vec<String> = tenants.iter().map(|t| t.to_string()).collect();
+        Preset::new("entra-id", &jwks_uri, idp_options().with_audience(client_id)).with_check(move |c| {
+            let e = EntraClaims::from_claims(c)?;
+            if !tenants.is_empty() && !tenants.contains(&e.tid) { return Err(VerifyError::Issuer); }
+            if c.iss.as_deref() != Some(ENTRA_ISSUER_TEMPLATE.replace("{tenantid}", &e.tid).as_str()) { return Err(VerifyError::Issuer); }
+            Ok(())
+        })
+    }
+}
+
+/// Typed Entra ID claims. `oid` is the stable, tenant-wide user id; prefer it over `sub`,
+/// which is pairwise per application.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntraClaims {
+    pub tid: String,
+    pub oid: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+    /// Source endpoint when the token omitted `groups` because of overage (`_claim_names`/`_claim_sources`).
+    #[serde(default)]
+    pub groups_overage: Option<String>,
+}
+
+/// Resolves group membership when Entra omitted it from the token (typically a Microsoft Graph
+/// `getMemberObjects` call made by the application).
+pub trait GroupsResolver {
+    fn resolve_groups(&self, claims: &Claims, endpoint: &str) -> Result<Vec<String>, VerifyError>;
+}
+
+impl<F> GroupsResolver for F
+where
+    F: Fn(&Claims, &str) -> Result<Vec<String>, VerifyError>,
+{
+    fn resolve_groups(&self, claims: &Claims, endpoint: &str) -> Result<Vec<String>, VerifyError> { self(claims, endpoint) }
+}
+
+/// Endpoint referenced by `_claim_names.groups` in `_claim_sources`, if the token signals overage.
+pub fn groups_overage_endpoint(c: &Claims) -> Option<String> {
+    let src = c.extra.get("_claim_names")?.get("groups")?.as_str()?;
+    let endpoint = c.extra.get("_claim_sources")?.get(src)?.get("endpoint")?.as_str()?;
+    Some(endpoint.to_string())
+}
+
+impl EntraClaims {
+    pub fn from_claims(c: &Claims) -> Result<Self, VerifyError> {
+        let get = |k: &'static str| c.extra.get(k).and_then(|v| v.as_str()).map(str::to_string).ok_or(VerifyError::MissingClaim(k));
+        let list = |k: &str| -> Vec<String> { c.extra.get(k).cloned().and_then(|v| serde_json::from_value(v).ok()).unwrap_or_default() };
+        Ok(EntraClaims {
+            tid: get("tid")?,
+            oid: get("oid")?,
+            groups: list("groups"),
+            roles: list("roles"),
+            preferred_username: get("preferred_username").ok(),
+            groups_overage: groups_overage_endpoint(c),
+        })
+    }
+
+    /// Like [`EntraClaims::from_claims`], resolving overage groups through `resolver`.
+    pub fn from_claims_resolved(c: &Claims, resolver: &dyn GroupsResolver) -> Result<Self, VerifyError> {
+        let mut e = Self::from_claims(c)?;
+        if let Some(endpoint) = e.groups_overage.take() {
+            e.groups = resolver.resolve_groups(c, &endpoint)?;
+        }
+        Ok(e)
+    }
+
+    pub fn subject(&self) -> &str { &self.oid }
+
+    /// Group ids, failing with [`VerifyError::GroupsOverage`] while overage is unresolved.
+    pub fn groups(&self) -> Result<&[String], VerifyError> {
+        if self.groups_overage.is_some() { return Err(VerifyError::GroupsOverage); }
+        Ok(&self.groups)
+    }
+
+    /// Membership check; never true while overage is unresolved.
+    pub fn in_group(&self, group_id: &str) -> bool { self.groups().is_ok_and(|g| g.iter().any(|x| x == group_id)) }
+    pub fn has_role(&self, role: &str) -> bool { self.roles.iter().any(|r| r == role) }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleList {
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// Keycloak's `realm_access` / `resource_access` role structures.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeycloakRoles {
+    #[serde(default)]
+    pub realm_access: RoleList,
+    #[serde(default)]
+    pub resource_access: std::collections::BTreeMap<String, RoleList>,
+}
+
+impl KeycloakRoles {
+    /// Missing or malformed blocks yield no roles rather than an error.
+    pub fn from_claims(c: &Claims) -> Self {
+        fn get<T: serde::de::DeserializeOwned + Default>(c: &Claims, k: &str) -> T {
+            c.extra.get(k).cloned().and_then(|v| serde_json::from_value(v).ok()).unwrap_or_default()
+        }
+        KeycloakRoles { realm_access: get(c, "realm_access"), resource_access: get(c, "resource_access") }
+    }
+    pub fn has_realm_role(&self, role: &str) -> bool { self.realm_access.roles.iter().any(|r| r == role) }
+    pub fn has_client_role(&self, client: &str, role: &str) -> bool {
+        self.resource_access.get(client).is_some_and(|l| l.roles.iter().any(|r| r == role))
+    }
+}
+
+/// Typed view of GitHub Actions OIDC claims.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubActionsClaims {
+    pub repository: String,
+    pub repository_owner: String,
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    #[serde(default)]
+    pub ref_type: Option<String>,
+    pub workflow: String,
+    #[serde(default)]
+    pub job_workflow_ref: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+    #[serde(default)]
+    pub sha: Option<String>,
+    #[serde(default)]
+    pub actor: Option<String>,
+    #[serde(default)]
+    pub event_name: Option<String>,
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+impl GithubActionsClaims {
+    const REQUIRED: [&'static str; 4] = ["repository", "repository_owner", "ref", "workflow"];
+
+    /// Fails with [`VerifyError::MissingClaim`] naming the first required claim that is absent or
+    /// not a string.
+    pub fn from_claims(c: &Claims) -> Result<Self, VerifyError> {
+        if let Some(name) = Self::REQUIRED.iter().find(|n| !c.extra.get(**n).is_some_and(serde_json::Value::is_string)) {
+            return Err(VerifyError::MissingClaim(name));
+        }
+        serde_json::to_value(&c.extra).and_then(serde_json::from_value).map_err(|_| VerifyError::Json)
+    }
+    /// `*` matches any run of characters, e.g. `"ubl/*"` or `"ubl/ledger-*"`.
+    pub fn repo_matches(&self, pattern: &str) -> bool { glob_match(pattern, &self.repository) }
+    pub fn ref_matches(&self, pattern: &str) -> bool { glob_match(pattern, &self.git_ref) }
+    pub fn environment_is(&self, env: &str) -> bool { self.environment.as_deref() == Some(env) }
+}
+
+/// Name/uid pair as used in the `kubernetes.io` claim block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KubeRef {
+    pub name: String,
+    pub uid: String,
+}
+
+/// The `kubernetes.io` claim block of a projected service account token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KubernetesClaims {
+    pub namespace: String,
+    pub serviceaccount: KubeRef,
+    #[serde(default)]
+    pub pod: Option<KubeRef>,
+    #[serde(default)]
+    pub node: Option<KubeRef>,
+    #[serde(default)]
+    pub warnafter: Option<i64>,
+}
+
+impl KubernetesClaims {
+    pub fn from_claims(c: &Claims) -> Result<Self, VerifyError> {
+        let block = c.extra.get("kubernetes.io").ok_or(VerifyError::MissingClaim("kubernetes.io"))?;
+        let k: KubernetesClaims = serde_json::from_value(block.clone()).map_err(|_| VerifyError::Json)?;
+        if c.sub != format!("system:serviceaccount:{}:{}", k.namespace, k.serviceaccount.name) { return Err(VerifyError::Subject); }
+        Ok(k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(feature = "rs256")]
+    static IDP_KEY: once_cell::sync::Lazy<rsa::RsaPrivateKey> =
+        once_cell::sync::Lazy::new(|| rsa::RsaPrivateKey::new(&mut <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(78), 2048).unwrap());
+
+    /// Signs `claims` as an RS256 token and caches the public key under the preset's JWKS URI.
+    #[cfg(feature = "rs256")]
+    fn idp_token(p: &Preset, claims: serde_json::Value) -> (String, JwksCache) {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+        use sha2::{Digest, Sha256};
+        let cache = JwksCache::new(3600);
+        cache.put(&p.jwks_uri, crate::Jwks { keys: vec![crate::Jwk::rsa("idp-1", &IDP_KEY.to_public_key())] });
+        let (_, _, msg) = crate::encode_canonical(&json!({"alg":"RS256","kid":"idp-1","typ":"JWT"}), &claims).unwrap();
+        let sig = IDP_KEY.sign(rsa::Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(msg.as_bytes())).unwrap();
+        (format!("{}.{}", msg, B64URL.encode(sig)), cache)
+    }
+
+    #[test]
+    fn spiffe_checks_subject_and_exp() {
+        let p = Preset::spiffe_jwt_svid("prod.ubl", "https://bundle.prod.ubl/jwks", "ledger");
+        assert_eq!(p.options.leeway_secs, 0);
+        let ok: Claims = serde_json::from_value(json!({"sub":"spiffe://prod.ubl/ns/ledger/sa/writer","exp":1})).unwrap();
+        assert!(p.check(&ok).is_ok());
+        let foreign: Claims = serde_json::from_value(json!({"sub":"spiffe://dev.ubl/ns/x","exp":1})).unwrap();
+        assert!(matches!(p.check(&foreign), Err(VerifyError::Subject)));
+        let no_exp: Claims = serde_json::from_value(json!({"sub":"spiffe://prod.ubl/ns/x"})).unwrap();
+        assert!(matches!(p.check(&no_exp), Err(VerifyError::MissingClaim("exp"))));
+    }
+
+    #[cfg(feature = "es256")]
+    #[test]
+    fn spiffe_verifies_svids_against_a_bundle() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+        use p256::ecdsa::signature::Signer as _;
+        let p = Preset::spiffe_jwt_svid("prod.ubl", "https://bundle.prod.ubl/jwks", "ledger");
+        let sk = p256::ecdsa::SigningKey::random(&mut <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(83));
+        let mut svid_key = serde_json::to_value(crate::Jwk::p256("svid-1", sk.verifying_key())).unwrap();
+        svid_key["use"] = json!("jwt-svid");
+        let mut x509_key = svid_key.clone();
+        x509_key["kid"] = json!("x509-1");
+        x509_key["use"] = json!("x509-svid");
+        let bundle = json!({"keys": [svid_key, x509_key], "spiffe_sequence": 7, "spiffe_refresh_hint": 300});
+        let cache = JwksCache::new(3600);
+        cache.put(&p.jwks_uri, serde_json::from_value(bundle).unwrap());
+        let sign = |kid: &str, sub: &str| {
+            let claims = json!({"sub": sub, "aud": ["ledger"], "exp": crate::now_ts() + 300});
+            let (_, _, msg) = crate::encode_canonical(&json!({"alg":"ES256","kid":kid,"typ":"JWT"}), &claims).unwrap();
+            let sig: p256::ecdsa::Signature = sk.sign(msg.as_bytes());
+            format!("{}.{}", msg, B64URL.encode(sig.to_bytes()))
+        };
+
+        assert_eq!(p.verify(&sign("svid-1", "spiffe://prod.ubl/ns/ledger/sa/writer"), &cache).unwrap().sub, "spiffe://prod.ubl/ns/ledger/sa/writer");
+        assert!(matches!(p.verify(&sign("svid-1", "spiffe://dev.ubl/ns/x"), &cache), Err(VerifyError::Subject)));
+        assert!(matches!(p.verify(&sign("x509-1", "spiffe://prod.ubl/ns/x"), &cache), Err(VerifyError::KeyUse)));
+        #[cfg(feature = "rs256")]
+        {
+            let (rs256, cache) = idp_token(&p, json!({"sub":"spiffe://prod.ubl/ns/x","aud":["ledger"],"exp": crate::now_ts() + 300}));
+            assert_eq!(p.verify(&rs256, &cache).unwrap().sub, "spiffe://prod.ubl/ns/x");
+        }
+    }
+
+    #[test]
+    fn kubernetes_claim_block() {
+        let p = Preset::kubernetes("https://kubernetes.default.svc", "ledger");
+        assert_eq!(p.jwks_uri, "https://kubernetes.default.svc/openid/v1/jwks");
+        let c: Claims = serde_json::from_value(json!({
+            "sub":"system:serviceaccount:prod:writer",
+            "kubernetes.io":{"namespace":"prod","serviceaccount":{"name":"writer","uid":"u1"},"pod":{"name":"writer-0","uid":"u2"}}
+        })).unwrap();
+        let k = KubernetesClaims::from_claims(&c).unwrap();
+        assert_eq!(k.pod.unwrap().name, "writer-0");
+        assert!(p.check(&c).is_ok());
+
+        let mut spoofed = c.clone();
+        spoofed.sub = "system:serviceaccount:kube-system:admin".into();
+        assert!(matches!(p.check(&spoofed), Err(VerifyError::Subject)));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn identity_provider_presets_allow_only_rs256() {
+        let presets = [
+            Preset::kubernetes("https://k8s", "a"), Preset::gcp_identity("a"), Preset::aws_cognito("r", "p", "c"),
+            Preset::azure_managed_identity("t", "a"), Preset::github_actions("a", "*"), Preset::apple_sign_in("c"),
+            Preset::google_sign_in("c"), Preset::keycloak("https://sso", "r", "a"), Preset::entra_id("c", &[]),
+        ];
+        for p in presets { assert_eq!(p.options.allowed_algs, ["RS256"], "{}", p.name); }
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn kubernetes_verifies_rs256_service_account_tokens() {
+        let p = Preset::kubernetes("https://kubernetes.default.svc", "ledger");
+        let (token, cache) = idp_token(&p, json!({
+            "sub":"system:serviceaccount:prod:writer","iss":"https://kubernetes.default.svc","aud":["ledger"],
+            "kubernetes.io":{"namespace":"prod","serviceaccount":{"name":"writer","uid":"u1"}}
+        }));
+        assert_eq!(p.verify(&token, &cache).unwrap().sub, "system:serviceaccount:prod:writer");
+    }
+
+    #[test]
+    fn cloud_workload_presets() {
+        let gcp = Preset::gcp_identity("https://ledger.ubl.agency");
+        let c: Claims = serde_json::from_value(json!({"sub":"1234","iss":"accounts.google.com"})).unwrap();
+        assert!(gcp.check(&c).is_ok());
+
+        let cognito = Preset::aws_cognito("eu-west-1", "eu-west-1_abc", "client-1");
+        assert_eq!(cognito.options.issuer.as_deref(), Some("https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_abc"));
+        let access: Claims = serde_json::from_value(json!({"sub":"u","token_use":"access","client_id":"client-1"})).unwrap();
+        assert!(cognito.check(&access).is_ok());
+        let id: Claims = serde_json::from_value(json!({"sub":"u","token_use":"id","aud":"client-2"})).unwrap();
+        assert!(matches!(cognito.check(&id), Err(VerifyError::Audience)));
+
+        let azure = Preset::azure_managed_identity("t-1", "api://ledger");
+        let mi: Claims = serde_json::from_value(json!({"sub":"oid","iss":"https://sts.windows.net/t-1/","tid":"t-1"})).unwrap();
+        assert!(azure.check(&mi).is_ok());
+        let other: Claims = serde_json::from_value(json!({"sub":"oid","iss":"https://sts.windows.net/t-2/","tid":"t-2"})).unwrap();
+        assert!(matches!(azure.check(&other), Err(VerifyError::Issuer)));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn cloud_workload_presets_verify_rs256_tokens() {
+        let gcp = Preset::gcp_identity("https://ledger.ubl.agency");
+        let (token, cache) = idp_token(&gcp, json!({"sub":"1234","iss":"https://accounts.google.com","aud":"https://ledger.ubl.agency"}));
+        assert_eq!(gcp.verify(&token, &cache).unwrap().sub, "1234");
+
+        let cognito = Preset::aws_cognito("eu-west-1", "eu-west-1_abc", "client-1");
+        let (token, cache) = idp_token(&cognito, json!({"sub":"u","iss":"https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_abc","token_use":"access","client_id":"client-1"}));
+        assert_eq!(cognito.verify(&token, &cache).unwrap().sub, "u");
+
+        let azure = Preset::azure_managed_identity("t-1", "api://ledger");
+        let (token, cache) = idp_token(&azure, json!({"sub":"oid","iss":"https://sts.windows.net/t-1/","aud":"api://ledger","tid":"t-1"}));
+        assert_eq!(azure.verify(&token, &cache).unwrap().sub, "oid");
+    }
+
+    #[test]
+    fn github_actions_claims_and_matching() {
+        let p = Preset::github_actions("https://deploy.ubl.agency", "ubl/*");
+        let c: Claims = serde_json::from_value(json!({
+            "sub":"repo:ubl/ledger:environment:prod","repository":"ubl/ledger","repository_owner":"ubl",
+            "ref":"refs/heads/main","workflow":"deploy","environment":"prod"
+        })).unwrap();
+        let gh = GithubActionsClaims::from_claims(&c).unwrap();
+        assert!(gh.ref_matches("refs/heads/*") && gh.environment_is("prod"));
+        assert!(p.check(&c).is_ok());
+        assert!(matches!(Preset::github_actions("x", "other/*").check(&c), Err(VerifyError::Subject)));
+        let mut no_workflow = c.clone();
+        no_workflow.extra.remove("workflow");
+        assert!(matches!(GithubActionsClaims::from_claims(&no_workflow), Err(VerifyError::MissingClaim("workflow"))));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn github_actions_verifies_rs256_tokens() {
+        let p = Preset::github_actions("https://deploy.ubl.agency", "ubl/*");
+        let (token, cache) = idp_token(&p, json!({
+            "sub":"repo:ubl/ledger:ref:refs/heads/main","iss":"https://token.actions.githubusercontent.com","aud":"https://deploy.ubl.agency",
+            "repository":"ubl/ledger","repository_owner":"ubl","ref":"refs/heads/main","workflow":"deploy"
+        }));
+        assert_eq!(p.verify(&token, &cache).unwrap().sub, "repo:ubl/ledger:ref:refs/heads/main");
+    }
+
+    #[test]
+    fn sign_in_presets_nonce_and_email() {
+        let apple = Preset::apple_sign_in("agency.ubl.app").with_nonce("n-1").with_verified_email();
+        let c: Claims = serde_json::from_value(json!({"sub":"a","nonce":"n-1","email":"x@privaterelay.appleid.com","email_verified":"true"})).unwrap();
+        assert!(apple.check(&c).is_ok());
+        let replayed: Claims = serde_json::from_value(json!({"sub":"a","nonce":"n-0","email":"x@y","email_verified":"true"})).unwrap();
+        assert!(matches!(apple.check(&replayed), Err(VerifyError::Nonce)));
+
+        let google = Preset::google_sign_in("cid.apps.googleusercontent.com").with_verified_email();
+        let unverified: Claims = serde_json::from_value(json!({"sub":"g","iss":"https://accounts.google.com","email":"x@y","email_verified":false})).unwrap();
+        assert!(matches!(google.check(&unverified), Err(VerifyError::MissingClaim("email_verified"))));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn sign_in_presets_verify_rs256_id_tokens() {
+        let apple = Preset::apple_sign_in("agency.ubl.app").with_nonce("n-1").with_verified_email();
+        let (token, cache) = idp_token(&apple, json!({
+            "sub":"a","iss":"https://appleid.apple.com","aud":"agency.ubl.app","nonce":"n-1","email":"x@privaterelay.appleid.com","email_verified":"true"
+        }));
+        assert_eq!(apple.verify(&token, &cache).unwrap().sub, "a");
+
+        let google = Preset::google_sign_in("cid.apps.googleusercontent.com").with_verified_email();
+        let (token, cache) = idp_token(&google, json!({"sub":"g","iss":"accounts.google.com","aud":"cid.apps.googleusercontent.com","email":"x@y","email_verified":true}));
+        assert_eq!(google.verify(&token, &cache).unwrap().sub, "g");
+        let (unverified, cache) = idp_token(&google, json!({"sub":"g","iss":"accounts.google.com","aud":"cid.apps.googleusercontent.com","email":"x@y","email_verified":false}));
+        assert!(matches!(google.verify(&unverified, &cache), Err(VerifyError::MissingClaim("email_verified"))));
+    }
+
+    #[test]
+    fn keycloak_roles_and_guards() {
+        let c: Claims = serde_json::from_value(json!({
+            "sub":"u","realm_access":{"roles":["offline_access","auditor"]},
+            "resource_access":{"ledger":{"roles":["writer"]}}
+        })).unwrap();
+        let roles = KeycloakRoles::from_claims(&c);
+        assert!(roles.has_realm_role("auditor") && roles.has_client_role("ledger", "writer"));
+        assert!(!roles.has_client_role("billing", "writer"));
+
+        let p = Preset::keycloak("https://sso.ubl.agency/", "ubl", "ledger").require_realm_role("auditor").require_client_role("ledger", "writer");
+        assert_eq!(p.jwks_uri, "https://sso.ubl.agency/realms/ubl/protocol/openid-connect/certs");
+        assert!(p.check(&c).is_ok());
+        assert!(matches!(p.require_client_role("ledger", "admin").check(&c), Err(VerifyError::MissingRole(r)) if r == "ledger:admin"));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn keycloak_verifies_rs256_realm_tokens() {
+        let p = Preset::keycloak("https://sso.ubl.agency", "ubl", "ledger").require_realm_role("auditor");
+        let (token, cache) = idp_token(&p, json!({"sub":"u","iss":"https://sso.ubl.agency/realms/ubl","aud":"ledger","realm_access":{"roles":["auditor"]}}));
+        assert_eq!(p.verify(&token, &cache).unwrap().sub, "u");
+    }
+
+    #[test]
+    fn entra_multi_tenant_issuer_and_typed_claims() {
+        let p = Preset::entra_id("app-1", &[]);
+        assert!(p.jwks_uri.contains("/common/"));
+        let c: Claims = serde_json::from_value(json!({
+            "sub":"pairwise","iss":"https://login.microsoftonline.com/t-9/v2.0","tid":"t-9","oid":"o-1",
+            "groups":["g-1"],"roles":["Ledger.Write"]
+        })).unwrap();
+        assert!(p.check(&c).is_ok());
+        let e = EntraClaims::from_claims(&c).unwrap();
+        assert_eq!(e.subject(), "o-1");
+        assert!(e.in_group("g-1") && e.has_role("Ledger.Write"));
+
+        let mut forged = c.clone();
+        forged.iss = Some("https://login.microsoftonline.com/t-1/v2.0".into());
+        assert!(matches!(p.check(&forged), Err(VerifyError::Issuer)));
+        assert!(matches!(Preset::entra_id("app-1", &["t-1"]).check(&c), Err(VerifyError::Issuer)));
+    }
+
+    #[cfg(feature = "rs256")]
+    #[test]
+    fn entra_id_verifies_rs256_tokens() {
+        let p = Preset::entra_id("app-1", &["t-9"]);
+        let (token, cache) = idp_token(&p, json!({"sub":"pairwise","iss":"https://login.microsoftonline.com/t-9/v2.0","aud":"app-1","tid":"t-9","oid":"o-1"}));
+        assert_eq!(p.verify(&token, &cache).unwrap().sub, "pairwise");
+    }
+
+    #[test]
+    fn entra_groups_overage_resolution() {
+        let c: Claims = serde_json::from_value(json!({
+            "sub":"s","tid":"t","oid":"o",
+            "_claim_names":{"groups":"src1"},
+            "_claim_sources":{"src1":{"endpoint":"https://graph.microsoft.com/v1.0/users/o/getMemberObjects"}}
+        })).unwrap();
+        let e = EntraClaims::from_claims(&c).unwrap();
+        assert!(matches!(e.groups(), Err(VerifyError::GroupsOverage)));
+        assert!(!e.in_group("g-1"));
+
+        let resolver = |_: &Claims, endpoint: &str| -> Result<Vec<String>, VerifyError> {
+            assert!(endpoint.ends_with("getMemberObjects"));
+            Ok(vec!["g-1".into()])
+        };
+        let resolved = EntraClaims::from_claims_resolved(&c, &resolver).unwrap();
+        assert!(resolved.in_group("g-1"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Signed identity headers from a gateway to the services behind it.
+//!
+//! Instead of forwarding an unsigned `X-User-Id`, the gateway verifies the caller once and
+//! [`IdentityPropagator::header_value`] mints a short-lived token (30 seconds by default) for one
+//! downstream audience, carrying the subject, scope and a small set of summary claims. Services
+//! check the [`HEADER`] with [`IdentityHeaderVerifier::verify`] against the gateway's pinned key
+//! set, so no JWKS fetch sits on the request path. The `ubl_identity` marker keeps ordinary access
+//! tokens from the same issuer from being passed off as identity headers.
+
+use crate::issuer::{TokenIssuer, TokenRequest};
+use crate::{verify_ed25519_jwt_with_cache, Aud, Claims, Jwks, JwksCache, SignError, VerifyError, VerifyOptions};
+use serde_json::Value as Json;
+
+pub const HEADER: &str = "x-ubl-identity";
+pub const CLAIM: &str = "ubl_identity";
+
+const JWKS_URI: &str = "identity://gateway";
+
+#[derive(Debug, Clone)]
+pub struct IdentityPropagator {
+    issuer: TokenIssuer,
+    pub ttl_secs: i64,
+    /// Inbound claims copied into the header token when present.
+    pub forwarded: Vec<String>,
+}
+
+impl IdentityPropagator {
+    /// Forwards `roles`, `amr`, `acr` and `act` by default.
+    pub fn new(issuer: TokenIssuer) -> Self {
+        Self { issuer, ttl_secs: 30, forwarded: ["roles", "amr", "acr", "act"].map(String::from).to_vec() }
+    }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.ttl_secs = secs; self }
+    pub fn with_forwarded_claim(mut self, name: &str) -> Self { self.forwarded.push(name.to_string()); self }
+
+    /// Keys downstream services pin in their [`IdentityHeaderVerifier`].
+    pub fn jwks(&self) -> Jwks { self.issuer.jwks() }
+
+    /// Summary of `inbound` for `audience`; never outlives the inbound token. The original issuer
+    /// is kept as `src_iss`.
+    pub fn claims_for(&self, inbound: &Claims, audience: &str) -> Result<Claims, SignError> {
+        let mut extra: std::collections::HashMap<String, Json> =
+            self.forwarded.iter().filter_map(|k| Some((k.clone(), inbound.extra.get(k)?.clone()))).collect();
+        if let Some(iss) = &inbound.iss { extra.insert("src_iss".into(), Json::String(iss.clone())); }
+        extra.insert(CLAIM.into(), Json::Bool(true));
+        let req = TokenRequest { sub: inbound.sub.clone(), aud: Some(Aud::One(audience.to_string())), scope: inbound.scope.clone(), ttl_secs: Some(self.ttl_secs), claims: extra };
+        let mut claims = self.issuer.claims_for(&req)?;
+        claims.exp = claims.exp.min(inbound.exp).or(claims.exp);
+        Ok(claims)
+    }
+
+    /// Value for the [`HEADER`] sent to `audience`.
+    pub fn header_value(&self, inbound: &Claims, audience: &str) -> Result<String, SignError> { self.issuer.issue(&self.claims_for(inbound, audience)?) }
+}
+
+#[derive(Debug)]
+pub struct IdentityHeaderVerifier {
+    cache: JwksCache,
+    pub opts: VerifyOptions,
+}
+
+impl IdentityHeaderVerifier {
+    /// Accepts headers minted by `gateway_iss` for `audience`, signed by a key in `jwks`.
+    pub fn new(gateway_iss: &str, audience: &str, jwks: Jwks) -> Self {
+        let cache = JwksCache::new(i64::MAX);
+        cache.put(JWKS_URI, jwks);
+        Self { cache, opts: VerifyOptions::default().with_issuer(gateway_iss).with_audience(audience).with_leeway(5) }
+    }
+
+    /// Replaces the pinned keys, e.g. after the gateway rotates.
+    pub fn set_jwks(&self, jwks: Jwks) { self.cache.put(JWKS_URI, jwks); }
+
+    pub fn verify(&self, header_value: &str) -> Result<Claims, VerifyError> {
+        let claims = verify_ed25519_jwt_with_cache(header_value.trim(), JWKS_URI, &self.cache, &self.opts)?;
+        if claims.extra.get(CLAIM) != Some(&Json::Bool(true)) { return Err(VerifyError::MissingClaim(CLAIM)); }
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn gateway_headers_verify_downstream_only() {
+        let gateway = TokenIssuer::new("https://gateway.internal", "gw1", SigningKey::generate(&mut StdRng::seed_from_u64(43)));
+        let propagator = IdentityPropagator::new(gateway.clone());
+        let inbound: Claims = serde_json::from_value(serde_json::json!({
+            "sub":"user:alice","iss":"https://idp.example","scope":"orders:read","exp":4_102_444_800i64,"roles":["admin"],"email":"alice@example.com"
+        })).unwrap();
+
+        let header = propagator.header_value(&inbound, "orders").unwrap();
+        let orders = IdentityHeaderVerifier::new("https://gateway.internal", "orders", propagator.jwks());
+        let claims = orders.verify(&header).unwrap();
+        assert_eq!((claims.sub.as_str(), claims.scope.as_deref(), claims.extra["src_iss"].as_str()), ("user:alice", Some("orders:read"), Some("https://idp.example")));
+        assert_eq!(claims.extra["roles"], serde_json::json!(["admin"]));
+        assert!(!claims.extra.contains_key("email"));
+        assert!(claims.exp.unwrap() - claims.iat.unwrap() <= 30);
+
+        let billing = IdentityHeaderVerifier::new("https://gateway.internal", "billing", propagator.jwks());
+        assert!(matches!(billing.verify(&header), Err(VerifyError::Audience)));
+        let access = gateway.mint(&serde_json::from_value(serde_json::json!({"sub":"user:alice","aud":"orders"})).unwrap()).unwrap();
+        assert!(matches!(orders.verify(&access), Err(VerifyError::MissingClaim(CLAIM))));
+    }
+}
@@ -0,0 +1,129 @@
+//! Hash-linked audit receipts.
+//!
+//! Each [`ChainedReceipt`] signs its sequence number, the record and `prev_hash`: the hex SHA-256
+//! of the canonical JSON of the receipt before it (64 zeros for the first). Changing, dropping or
+//! reordering any receipt therefore breaks every link after it. [`ReceiptChain`] appends on the
+//! signing side; [`verify_link`] checks one receipt against its predecessor and [`verify_chain`]
+//! a whole chain from genesis. Signatures are the detached canonical-JSON ones of
+//! [`receipts`](crate::receipts).
+
+use crate::receipts::{canonical_bytes, sign_detached, verify_detached_with, DetachedSignature};
+use crate::{to_hex, SignError, VerifyError};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+
+/// `prev_hash` of the first receipt.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainedReceipt {
+    pub seq: u64,
+    pub prev_hash: String,
+    pub record: Json,
+    /// Over the canonical `{seq, prev_hash, record}`.
+    pub signature: DetachedSignature,
+}
+
+impl ChainedReceipt {
+    /// Hex SHA-256 of the canonical receipt, signature included: the next receipt's `prev_hash`.
+    pub fn hash(&self) -> Result<String, SignError> { Ok(to_hex(&Sha256::digest(canonical_bytes(self)?))) }
+
+    fn signed_body(&self) -> Json { signed_body(self.seq, &self.prev_hash, &self.record) }
+}
+
+fn signed_body(seq: u64, prev_hash: &str, record: &Json) -> Json { serde_json::json!({"seq": seq, "prev_hash": prev_hash, "record": record}) }
+
+/// The signing end of a chain.
+pub struct ReceiptChain {
+    key: SigningKey,
+    kid: String,
+    receipts: Vec<ChainedReceipt>,
+}
+
+impl std::fmt::Debug for ReceiptChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReceiptChain").field("kid", &self.kid).field("len", &self.receipts.len()).finish_non_exhaustive()
+    }
+}
+
+impl ReceiptChain {
+    pub fn new(key: SigningKey, kid: &str) -> Self { Self { key, kid: kid.to_string(), receipts: Vec::new() } }
+
+    /// Continues an existing chain; `receipts` must already verify.
+    pub fn resume(key: SigningKey, kid: &str, receipts: Vec<ChainedReceipt>) -> Self { Self { key, kid: kid.to_string(), receipts } }
+
+    pub fn append<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<&ChainedReceipt, SignError> {
+        let record = serde_json::to_value(record).map_err(|e| SignError::Canonical(e.to_string()))?;
+        let (seq, prev_hash) = (self.receipts.len() as u64, self.head_hash()?);
+        let signature = sign_detached(&signed_body(seq, &prev_hash, &record), &self.key, &self.kid)?;
+        self.receipts.push(ChainedReceipt { seq, prev_hash, record, signature });
+        Ok(&self.receipts[self.receipts.len() - 1])
+    }
+
+    /// Hash the next receipt will link to.
+    pub fn head_hash(&self) -> Result<String, SignError> { self.receipts.last().map_or(Ok(GENESIS_HASH.to_string()), ChainedReceipt::hash) }
+
+    pub fn receipts(&self) -> &[ChainedReceipt] { &self.receipts }
+}
+
+/// Checks that `receipt` directly follows `prev` (`None` for the first receipt) and is signed.
+pub fn verify_link<F>(prev: Option<&ChainedReceipt>, receipt: &ChainedReceipt, key_for: F) -> Result<(), VerifyError>
+where
+    F: FnOnce(&str) -> Result<VerifyingKey, VerifyError>,
+{
+    let (seq, prev_hash) = match prev {
+        None => (0, GENESIS_HASH.to_string()),
+        Some(p) => {
+            let seq = p.seq.checked_add(1).ok_or_else(|| VerifyError::Chain(format!("receipt {} has no successor", p.seq)))?;
+            (seq, p.hash().map_err(|_| VerifyError::Json)?)
+        }
+    };
+    if receipt.seq != seq { return Err(VerifyError::Chain(format!("expected seq {}, found {}", seq, receipt.seq))); }
+    if receipt.prev_hash != prev_hash { return Err(VerifyError::Chain(format!("receipt {} does not link to its predecessor", receipt.seq))); }
+    verify_detached_with(&receipt.signed_body(), &receipt.signature, key_for)
+}
+
+/// Verifies every link from genesis; returns the head hash.
+pub fn verify_chain<F>(receipts: &[ChainedReceipt], mut key_for: F) -> Result<String, VerifyError>
+where
+    F: FnMut(&str) -> Result<VerifyingKey, VerifyError>,
+{
+    let mut prev = None;
+    for receipt in receipts {
+        verify_link(prev, receipt, &mut key_for)?;
+        prev = Some(receipt);
+    }
+    prev.map_or(Ok(GENESIS_HASH.to_string()), |r| r.hash().map_err(|_| VerifyError::Json))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn chains_verify_from_genesis_and_detect_tampering() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(71));
+        let vk = sk.verifying_key();
+        let mut chain = ReceiptChain::new(sk.clone(), "audit-1");
+        for n in 0..4 { chain.append(&json!({"op":"transfer","n":n})).unwrap(); }
+        let receipts = chain.receipts().to_vec();
+        assert_eq!(verify_chain(&receipts, |_| Ok(vk)).unwrap(), chain.head_hash().unwrap());
+        verify_link(Some(&receipts[1]), &receipts[2], |_| Ok(vk)).unwrap();
+
+        let mut edited = receipts.clone();
+        edited[1].record["n"] = json!(9);
+        assert!(matches!(verify_chain(&edited, |_| Ok(vk)), Err(VerifyError::Signature)));
+        let mut resigned = ReceiptChain::resume(sk, "audit-1", receipts[..1].to_vec());
+        resigned.append(&json!({"op":"transfer","n":9})).unwrap();
+        let forked = [resigned.receipts().to_vec(), receipts[2..].to_vec()].concat();
+        assert!(matches!(verify_chain(&forked, |_| Ok(vk)), Err(VerifyError::Chain(_))));
+        let dropped = [&receipts[..1], &receipts[2..]].concat();
+        assert!(matches!(verify_chain(&dropped, |_| Ok(vk)), Err(VerifyError::Chain(_))));
+        let last = ChainedReceipt { seq: u64::MAX, ..receipts[0].clone() };
+        assert!(matches!(verify_link(Some(&last), &receipts[1], |_| Ok(vk)), Err(VerifyError::Chain(_))));
+    }
+}
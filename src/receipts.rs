@@ -0,0 +1,164 @@
+//! Detached Ed25519 signatures over canonical-JSON records, for append-only ledger entries.
+//!
+//! The signed bytes are exactly `json_atomic::canonize(record)`, so any party holding the record
+//! can re-derive them. Keys are referenced by `kid` and resolved like JWT keys. With the `tsa`
+//! feature a receipt can also carry an RFC 3161 timestamp over its [`Receipt::imprint`].
+//!
+//! [`sign_canonical`] packs a record and its signature into one [`SignedEnvelope`]
+//! (`{payload, alg, kid, sig}`); [`verify_canonical`] checks it against any
+//! [`KeyProvider`](crate::keyprovider::KeyProvider).
+
+use crate::keyprovider::KeyProvider;
+use crate::{key_by_kid, now_ts, resolve_key, JwksCache, SignError, VerifyError};
+#[cfg(feature = "tsa")]
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+
+/// A signature kept apart from the record it covers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    pub alg: String,
+    pub kid: String,
+    /// Base64url Ed25519 signature over the canonical record bytes.
+    pub sig: String,
+}
+
+/// A record together with its detached signature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub record: Json,
+    pub signature: DetachedSignature,
+    /// Base64 DER RFC 3161 `TimeStampToken` over [`Receipt::imprint`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+pub(crate) fn canonical_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SignError> {
+    let bytes = json_atomic::canonize(value).map_err(|e| SignError::Canonical(e.to_string()))?;
+    Ok(AsRef::<[u8]>::as_ref(&bytes).to_vec())
+}
+
+pub fn sign_detached<T: Serialize + ?Sized>(record: &T, key: &SigningKey, kid: &str) -> Result<DetachedSignature, SignError> {
+    let sig = key.sign(&canonical_bytes(record)?);
+    Ok(DetachedSignature { alg: "EdDSA".into(), kid: kid.to_string(), sig: B64URL.encode(sig.to_bytes()) })
+}
+
+pub fn verify_detached_with<T, F>(record: &T, signature: &DetachedSignature, key_for: F) -> Result<(), VerifyError>
+where
+    T: Serialize + ?Sized,
+    F: FnOnce(&str) -> Result<VerifyingKey, VerifyError>,
+{
+    if signature.alg != "EdDSA" { return Err(VerifyError::alg(&signature.alg, &["EdDSA"])); }
+    let bytes = B64URL.decode(signature.sig.as_bytes()).map_err(|_| VerifyError::Base64)?;
+    let sig = Signature::from_bytes(bytes[..].try_into().map_err(|_| VerifyError::Signature)?);
+    let msg = canonical_bytes(record).map_err(|_| VerifyError::Json)?;
+    key_for(&signature.kid)?.verify_strict(&msg, &sig).map_err(|_| VerifyError::Signature)
+}
+
+pub fn verify_detached<T: Serialize + ?Sized>(record: &T, signature: &DetachedSignature, jwks_uri: &str, cache: &JwksCache) -> Result<(), VerifyError> {
+    verify_detached_with(record, signature, |kid| resolve_key(jwks_uri, cache, kid))
+}
+
+/// A record with its signature inline; the signature covers the canonical `payload` only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedEnvelope {
+    pub payload: Json,
+    pub alg: String,
+    pub kid: String,
+    pub sig: String,
+}
+
+impl SignedEnvelope {
+    pub fn signature(&self) -> DetachedSignature { DetachedSignature { alg: self.alg.clone(), kid: self.kid.clone(), sig: self.sig.clone() } }
+}
+
+pub fn sign_canonical<T: Serialize + ?Sized>(value: &T, key: &SigningKey, kid: &str) -> Result<SignedEnvelope, SignError> {
+    let payload = serde_json::to_value(value).map_err(|e| SignError::Canonical(e.to_string()))?;
+    let DetachedSignature { alg, kid, sig } = sign_detached(&payload, key, kid)?;
+    Ok(SignedEnvelope { payload, alg, kid, sig })
+}
+
+/// Verifies `envelope` with the key its `kid` names in `keys` and returns the payload.
+pub fn verify_canonical<'a, K: KeyProvider + ?Sized>(envelope: &'a SignedEnvelope, keys: &K) -> Result<&'a Json, VerifyError> {
+    verify_detached_with(&envelope.payload, &envelope.signature(), |kid| key_by_kid(&keys.keys()?, kid, "EdDSA", now_ts())?.ok_or(VerifyError::NoKey))?;
+    Ok(&envelope.payload)
+}
+
+impl Receipt {
+    pub fn sign<T: Serialize + ?Sized>(record: &T, key: &SigningKey, kid: &str) -> Result<Self, SignError> {
+        let record = serde_json::to_value(record).map_err(|e| SignError::Canonical(e.to_string()))?;
+        let signature = sign_detached(&record, key, kid)?;
+        Ok(Receipt { record, signature, timestamp: None })
+    }
+
+    /// SHA-256 of the canonical `{record, signature}`: what a timestamp covers.
+    pub fn imprint(&self) -> Result<[u8; 32], SignError> {
+        let bytes = canonical_bytes(&serde_json::json!({"record": self.record, "signature": self.signature}))?;
+        Ok(Sha256::digest(bytes).into())
+    }
+
+    /// Obtains and attaches a trusted timestamp.
+    #[cfg(feature = "tsa")]
+    pub fn stamp(&mut self, tsa: &crate::timestamp::TsaClient) -> Result<(), SignError> {
+        let token = tsa.stamp(&self.imprint()?)?;
+        self.timestamp = Some(B64.encode(token));
+        Ok(())
+    }
+
+    /// Verifies the attached timestamp against `trust` (the receipt signature is checked separately).
+    #[cfg(feature = "tsa")]
+    pub fn verify_timestamp(&self, trust: &crate::timestamp::TimestampTrust) -> Result<crate::timestamp::TimestampInfo, VerifyError> {
+        let token = B64.decode(self.timestamp.as_deref().ok_or(VerifyError::MissingClaim("timestamp"))?).map_err(|_| VerifyError::Base64)?;
+        Ok(crate::timestamp::verify_token(&token, &self.imprint().map_err(|_| VerifyError::Json)?, trust)?)
+    }
+
+    pub fn verify(&self, jwks_uri: &str, cache: &JwksCache) -> Result<(), VerifyError> {
+        verify_detached(&self.record, &self.signature, jwks_uri, cache)
+    }
+
+    pub fn verify_with<F>(&self, key_for: F) -> Result<(), VerifyError>
+    where
+        F: FnOnce(&str) -> Result<VerifyingKey, VerifyError>,
+    {
+        verify_detached_with(&self.record, &self.signature, key_for)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn envelopes_verify_against_a_key_provider() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(70));
+        let jwks = crate::Jwks { keys: vec![crate::Jwk::ed25519("ledger-2", &sk.verifying_key())] };
+        let envelope = sign_canonical(&json!({"b":[1,2],"a":"x"}), &sk, "ledger-2").unwrap();
+        let wire: SignedEnvelope = serde_json::from_str(&serde_json::to_string(&envelope).unwrap()).unwrap();
+        assert_eq!(verify_canonical(&wire, &jwks).unwrap()["a"], "x");
+
+        let mut altered = wire.clone();
+        altered.payload["a"] = json!("y");
+        assert!(matches!(verify_canonical(&altered, &jwks), Err(VerifyError::Signature)));
+        let unknown = SignedEnvelope { kid: "other".into(), ..wire };
+        assert!(matches!(verify_canonical(&unknown, &jwks), Err(VerifyError::NoKey)));
+    }
+
+    #[test]
+    fn sign_and_verify_receipt() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(13));
+        let vk = sk.verifying_key();
+        let receipt = Receipt::sign(&json!({"span":"s-1","op":"append","n":3}), &sk, "ledger-1").unwrap();
+        assert!(receipt.verify_with(|kid| { assert_eq!(kid, "ledger-1"); Ok(vk) }).is_ok());
+
+        let reordered = json!({"n":3,"op":"append","span":"s-1"});
+        assert!(verify_detached_with(&reordered, &receipt.signature, |_| Ok(vk)).is_ok());
+        let altered = json!({"span":"s-1","op":"append","n":4});
+        assert!(matches!(verify_detached_with(&altered, &receipt.signature, |_| Ok(vk)), Err(VerifyError::Signature)));
+    }
+}
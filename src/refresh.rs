@@ -0,0 +1,152 @@
+//! Rotating refresh tokens with family-based reuse detection.
+//!
+//! Every refresh token belongs to a family started at login. Redeeming a token marks it used
+//! and issues its successor in the same family. Presenting an already-used token means it
+//! leaked (or a client raced itself): the whole family is revoked and the caller must log in
+//! again. Only SHA-256 hashes of tokens are stored.
+
+use crate::{fill_random, now_ts, random_id, to_hex};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// What gets persisted for a refresh token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefreshRecord {
+    /// Hex SHA-256 of the token.
+    pub hash: String,
+    pub family_id: String,
+    pub subject: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    pub issued_at: i64,
+    pub expires_at: i64,
+    /// Absolute end of the family; rotation never extends past it.
+    pub family_expires_at: i64,
+    #[serde(default)]
+    pub used: bool,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+pub trait RefreshStore: Send + Sync {
+    fn insert(&self, record: RefreshRecord);
+    /// Atomically sets `used`, returning the record as it was before (or `None` if unknown).
+    fn mark_used(&self, hash_hex: &str) -> Option<RefreshRecord>;
+    fn revoke_family(&self, family_id: &str);
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryRefreshStore { inner: Mutex<HashMap<String, RefreshRecord>> }
+
+impl MemoryRefreshStore {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl RefreshStore for MemoryRefreshStore {
+    fn insert(&self, record: RefreshRecord) { self.inner.lock().insert(record.hash.clone(), record); }
+    fn mark_used(&self, hash_hex: &str) -> Option<RefreshRecord> {
+        let mut m = self.inner.lock();
+        let rec = m.get_mut(hash_hex)?;
+        let before = rec.clone();
+        rec.used = true;
+        Some(before)
+    }
+    fn revoke_family(&self, family_id: &str) {
+        self.inner.lock().values_mut().filter(|r| r.family_id == family_id).for_each(|r| r.revoked = true);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RefreshError {
+    /// Unknown, or its family was revoked.
+    #[error("invalid or revoked refresh token")]
+    Invalid,
+    /// An already-used token was presented; its family is now revoked.
+    #[error("refresh token reused; family revoked")]
+    Reused,
+    #[error("refresh token expired")]
+    Expired,
+}
+
+#[derive(Debug)]
+pub struct RefreshTokens<S: RefreshStore> {
+    store: S,
+    pub ttl_secs: i64,
+    pub family_ttl_secs: i64,
+}
+
+impl<S: RefreshStore> RefreshTokens<S> {
+    /// Tokens live 14 days, families 90 days, unless changed.
+    pub fn new(store: S) -> Self { Self { store, ttl_secs: 14 * 86_400, family_ttl_secs: 90 * 86_400 } }
+    pub fn with_ttl(mut self, secs: i64) -> Self { self.ttl_secs = secs; self }
+    pub fn with_family_ttl(mut self, secs: i64) -> Self { self.family_ttl_secs = secs; self }
+
+    /// Starts a new family (at login) and returns its first token.
+    pub fn issue(&self, subject: &str, scope: Option<&str>) -> (String, RefreshRecord) {
+        let now = now_ts();
+        self.mint(random_id(), subject.to_string(), scope.map(str::to_string), now + self.family_ttl_secs, now)
+    }
+
+    /// Redeems `token` for its successor. Reuse of a spent token revokes the family and fails
+    /// with [`RefreshError::Reused`].
+    pub fn rotate(&self, token: &str) -> Result<(String, RefreshRecord), RefreshError> {
+        let now = now_ts();
+        let prev = self.store.mark_used(&hash(token)).ok_or(RefreshError::Invalid)?;
+        if prev.revoked { return Err(RefreshError::Invalid); }
+        if prev.used {
+            self.store.revoke_family(&prev.family_id);
+            return Err(RefreshError::Reused);
+        }
+        if now >= prev.expires_at || now >= prev.family_expires_at { return Err(RefreshError::Expired); }
+        Ok(self.mint(prev.family_id, prev.subject, prev.scope, prev.family_expires_at, now))
+    }
+
+    /// Ends a session: every token of the family stops working.
+    pub fn revoke_family(&self, family_id: &str) { self.store.revoke_family(family_id) }
+
+    fn mint(&self, family_id: String, subject: String, scope: Option<String>, family_expires_at: i64, now: i64) -> (String, RefreshRecord) {
+        let mut secret = [0u8; 32];
+        fill_random(&mut secret);
+        let token = format!("rt_{}", B64URL.encode(secret));
+        let record = RefreshRecord {
+            hash: hash(&token),
+            family_id,
+            subject,
+            scope,
+            issued_at: now,
+            expires_at: (now + self.ttl_secs).min(family_expires_at),
+            family_expires_at,
+            used: false,
+            revoked: false,
+        };
+        self.store.insert(record.clone());
+        (token, record)
+    }
+}
+
+fn hash(token: &str) -> String { to_hex(&Sha256::digest(token.as_bytes())) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_and_reuse_detection() {
+        let rt = RefreshTokens::new(MemoryRefreshStore::new());
+        let (t1, r1) = rt.issue("did:key:zUser", Some("openid"));
+        let (t2, r2) = rt.rotate(&t1).unwrap();
+        assert_eq!((r2.family_id.as_str(), r2.scope.as_deref()), (r1.family_id.as_str(), Some("openid")));
+
+        assert!(matches!(rt.rotate(&t1), Err(RefreshError::Reused)));
+        assert!(matches!(rt.rotate(&t2), Err(RefreshError::Invalid)));
+        assert!(matches!(rt.rotate("rt_unknown"), Err(RefreshError::Invalid)));
+
+        let (t3, r3) = rt.issue("did:key:zUser", None);
+        rt.revoke_family(&r3.family_id);
+        assert!(matches!(rt.rotate(&t3), Err(RefreshError::Invalid)));
+    }
+}
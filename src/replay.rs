@@ -0,0 +1,67 @@
+//! `jti` replay detection for tokens that may only be presented once.
+//!
+//! [`check`] records a verified token's `jti` until its `exp` (plus leeway) and rejects a second
+//! presentation inside that window with [`VerifyError::Replayed`]. After the window the token
+//! fails the expiry check anyway, so the store never has to remember a jti for longer. Wire a
+//! store into a [`Verifier`](crate::Verifier) with
+//! [`VerifierBuilder::replay_store`](crate::VerifierBuilder::replay_store); tokens are only
+//! recorded once every other check has passed.
+
+use crate::{now_ts, Claims, VerifyError, VerifyOptions};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+pub trait ReplayStore: Send + Sync {
+    /// Atomically records `jti` until `expires_at`, returning `false` if it was already recorded
+    /// and not yet expired at `now`.
+    fn insert_if_absent(&self, jti: &str, expires_at: i64, now: i64) -> bool;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryReplayStore { inner: Mutex<HashMap<String, i64>> }
+
+impl MemoryReplayStore {
+    pub fn new() -> Self { Self::default() }
+    pub fn len(&self) -> usize { self.inner.lock().len() }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+impl ReplayStore for MemoryReplayStore {
+    fn insert_if_absent(&self, jti: &str, expires_at: i64, now: i64) -> bool {
+        let mut m = self.inner.lock();
+        m.retain(|_, exp| *exp >= now);
+        if m.contains_key(jti) { return false; }
+        m.insert(jti.to_string(), expires_at);
+        true
+    }
+}
+
+/// Records `claims.jti` in `store`; tokens need both `jti` and `exp` so the window is bounded.
+pub fn check<S: ReplayStore + ?Sized>(store: &S, claims: &Claims, opts: &VerifyOptions) -> Result<(), VerifyError> {
+    let jti = claims.jti.as_deref().ok_or(VerifyError::MissingClaim("jti"))?;
+    let exp = claims.exp.ok_or(VerifyError::MissingClaim("exp"))?;
+    if !store.insert_if_absent(jti, exp.saturating_add(opts.leeway_secs), opts.now.unwrap_or_else(now_ts)) { return Err(VerifyError::Replayed); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jtis_are_accepted_once_per_window() {
+        let store = MemoryReplayStore::new();
+        let claims = |jti: &str, exp: i64| -> Claims { serde_json::from_value(serde_json::json!({"sub":"a","jti":jti,"exp":exp})).unwrap() };
+        let opts = VerifyOptions { now: Some(1_000), leeway_secs: 5, ..Default::default() };
+
+        check(&store, &claims("j1", 1_060), &opts).unwrap();
+        assert!(matches!(check(&store, &claims("j1", 1_060), &opts), Err(VerifyError::Replayed)));
+        check(&store, &claims("j2", 1_060), &opts).unwrap();
+
+        let later = VerifyOptions { now: Some(1_066), ..opts.clone() };
+        check(&store, &claims("j1", 1_200), &later).unwrap();
+        assert_eq!(store.len(), 1);
+        let no_jti: Claims = serde_json::from_value(serde_json::json!({"sub":"a","exp":1_060})).unwrap();
+        assert!(matches!(check(&store, &no_jti, &opts), Err(VerifyError::MissingClaim("jti"))));
+    }
+}
@@ -0,0 +1,150 @@
+//! Rate-limited, structured reporting of verification failures.
+//!
+//! Failures are grouped by `(kid, iss, error kind)`. The first failure of a group is emitted at
+//! once; repeats within the window are only counted and emitted as one summary when the group's
+//! next failure arrives after the window or on [`FailureReporter::flush`]. There is no background
+//! timer: summaries are driven by failures and flushes, so a service calls `flush` on its own
+//! schedule (e.g. once per window). A flood of identical bad tokens thus costs one line per group
+//! per window, delivered to a caller-provided sink (a logger, metrics, ...).
+//!
+//! `kid` and `iss` come from the unverified token and are attacker-controlled, so they are
+//! truncated and the number of groups is capped.
+
+use crate::{now_ts, VerifyError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const MAX_FIELD_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct FailureKey {
+    pub kid: Option<String>,
+    pub iss: Option<String>,
+    /// `VerifyError` variant name, e.g. `Signature`.
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailureSummary {
+    #[serde(flatten)]
+    pub key: FailureKey,
+    pub count: u64,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+type Sink = Arc<dyn Fn(&FailureSummary) + Send + Sync>;
+
+struct Bucket { window_start: i64, first_seen: i64, last_seen: i64, suppressed: u64 }
+
+pub struct FailureReporter {
+    pub window_secs: i64,
+    pub max_groups: usize,
+    sink: Sink,
+    groups: Mutex<HashMap<FailureKey, Bucket>>,
+}
+
+impl std::fmt::Debug for FailureReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FailureReporter").field("window_secs", &self.window_secs).field("max_groups", &self.max_groups).finish_non_exhaustive()
+    }
+}
+
+impl FailureReporter {
+    /// Emits summaries to `sink`; `FailureSummary` serializes to one structured JSON object.
+    pub fn new<F: Fn(&FailureSummary) + Send + Sync + 'static>(window_secs: i64, sink: F) -> Self {
+        Self { window_secs, max_groups: 1024, sink: Arc::new(sink), groups: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn with_max_groups(mut self, n: usize) -> Self { self.max_groups = n; self }
+
+    /// Passes `result` through, recording it if it is a failure.
+    pub fn observe<T>(&self, token: &str, result: Result<T, VerifyError>) -> Result<T, VerifyError> {
+        if let Err(e) = &result { self.record(token, e); }
+        result
+    }
+
+    pub fn record(&self, token: &str, err: &VerifyError) { self.record_at(token, err, now_ts()) }
+
+    pub fn record_at(&self, token: &str, err: &VerifyError, now: i64) {
+        let (kid, iss) = unverified_kid_iss(token);
+        let mut key = FailureKey { kid, iss, error: error_kind(err) };
+        let mut emit = Vec::new();
+        {
+            let mut groups = self.groups.lock();
+            if !groups.contains_key(&key) && groups.len() >= self.max_groups {
+                key = FailureKey { kid: None, iss: None, error: "Overflow".into() };
+            }
+            match groups.get_mut(&key) {
+                Some(b) if now - b.window_start < self.window_secs => { b.suppressed += 1; b.last_seen = now; }
+                Some(b) => {
+                    if b.suppressed > 0 { emit.push(summary(&key, b)); }
+                    *b = Bucket { window_start: now, first_seen: now, last_seen: now, suppressed: 0 };
+                    emit.push(FailureSummary { key: key.clone(), count: 1, first_seen: now, last_seen: now });
+                }
+                None => {
+                    groups.insert(key.clone(), Bucket { window_start: now, first_seen: now, last_seen: now, suppressed: 0 });
+                    emit.push(FailureSummary { key, count: 1, first_seen: now, last_seen: now });
+                }
+            }
+        }
+        emit.iter().for_each(|s| (self.sink)(s));
+    }
+
+    /// Emits summaries for all groups with suppressed failures and forgets every group.
+    /// Call it periodically (e.g. once per window) so quiet groups are reported and memory is released.
+    pub fn flush(&self) {
+        let drained: Vec<FailureSummary> = self.groups.lock().drain().filter(|(_, b)| b.suppressed > 0).map(|(k, b)| summary(&k, &b)).collect();
+        drained.iter().for_each(|s| (self.sink)(s));
+    }
+}
+
+fn summary(key: &FailureKey, b: &Bucket) -> FailureSummary {
+    FailureSummary { key: key.clone(), count: b.suppressed, first_seen: b.first_seen, last_seen: b.last_seen }
+}
+
+fn error_kind(err: &VerifyError) -> String {
+    let debug = format!("{:?}", err);
+    debug.split(['(', ' ', '{']).next().unwrap_or_default().to_string()
+}
+
+fn unverified_kid_iss(token: &str) -> (Option<String>, Option<String>) {
+    let mut parts = token.split('.');
+    let mut field = |name: &str| -> Option<String> {
+        let json: Json = serde_json::from_slice(&B64URL.decode(parts.next()?.trim_end_matches('=')).ok()?).ok()?;
+        Some(json.get(name)?.as_str()?.chars().take(MAX_FIELD_LEN).collect())
+    };
+    let kid = field("kid");
+    let iss = field("iss");
+    (kid, iss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn identical_failures_are_aggregated() {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let sink = out.clone();
+        let reporter = FailureReporter::new(60, move |s: &FailureSummary| sink.lock().push(s.clone()));
+        let seg = |v: Json| B64URL.encode(v.to_string());
+        let token = format!("{}.{}.sig", seg(json!({"alg":"EdDSA","kid":"k1"})), seg(json!({"iss":"https://idp"})));
+
+        for t in 0..100 { reporter.record_at(&token, &VerifyError::Signature, 1_000 + t % 30); }
+        reporter.record_at(&token, &VerifyError::JwksHttp("timeout".into()), 1_010);
+        assert_eq!(out.lock().len(), 2);
+        assert_eq!(out.lock()[0].key, FailureKey { kid: Some("k1".into()), iss: Some("https://idp".into()), error: "Signature".into() });
+
+        reporter.record_at(&token, &VerifyError::Signature, 1_060);
+        let emitted = out.lock().clone();
+        assert_eq!((emitted[2].count, emitted[3].count), (99, 1));
+        reporter.flush();
+        assert_eq!(out.lock().len(), 4);
+    }
+}
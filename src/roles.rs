@@ -0,0 +1,60 @@
+//! Role hierarchies: `admin ⊒ editor ⊒ viewer` declared once in the verifier configuration.
+//!
+//! With [`VerifyOptions::with_role_hierarchy`](crate::VerifyOptions::with_role_hierarchy) set,
+//! [`VerifyOptions::has_role`](crate::VerifyOptions::has_role) treats a held role as granting every role it
+//! (transitively) implies.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Maps a role to the roles it directly implies. Cycles are tolerated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RoleHierarchy { implies: BTreeMap<String, Vec<String>> }
+
+impl RoleHierarchy {
+    pub fn new() -> Self { Self::default() }
+
+    /// Declares that holding `role` grants each of `implied`.
+    pub fn with(mut self, role: &str, implied: &[&str]) -> Self {
+        self.implies.entry(role.to_string()).or_default().extend(implied.iter().map(|r| r.to_string()));
+        self
+    }
+
+    /// Every role granted by holding `held`, including the held roles themselves.
+    pub fn expand<'a>(&self, held: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        let mut granted = BTreeSet::new();
+        let mut stack: Vec<String> = held.into_iter().map(str::to_string).collect();
+        while let Some(role) = stack.pop() {
+            if let Some(implied) = self.implies.get(&role) {
+                stack.extend(implied.iter().filter(|r| !granted.contains(*r)).cloned());
+            }
+            granted.insert(role);
+        }
+        granted
+    }
+
+    pub fn grants<'a>(&self, held: impl IntoIterator<Item = &'a str>, role: &str) -> bool {
+        self.expand(held).contains(role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Claims, VerifyOptions};
+
+    #[test]
+    fn implied_roles_are_granted() {
+        let h = RoleHierarchy::new().with("admin", &["editor"]).with("editor", &["viewer"]).with("viewer", &["admin-lite"]).with("admin-lite", &["viewer"]);
+        assert!(h.grants(["admin"], "viewer"));
+        assert!(!h.grants(["viewer"], "editor"));
+        assert_eq!(h.expand(["editor"]).len(), 3);
+
+        let c: Claims = serde_json::from_value(serde_json::json!({"sub":"did:key:zA","roles":"admin"})).unwrap();
+        assert!(VerifyOptions::default().has_role(&c, "admin") && !VerifyOptions::default().has_role(&c, "viewer"));
+        assert!(VerifyOptions::default().with_role_hierarchy(h).has_role(&c, "viewer"));
+        let parsed: RoleHierarchy = serde_json::from_str(r#"{"admin":["editor"]}"#).unwrap();
+        assert!(parsed.grants(["admin"], "editor"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Loading signing keys and HMAC secrets from the usual secret channels.
+//!
+//! A [`SecretSource`] is written as a short spec, so every binary and config file accepts the same
+//! forms:
+//! - `file:/path` (or a bare path) — must not be readable by group or others
+//! - `mounted:/path` — Docker/Kubernetes secret mounts, whose modes the orchestrator controls
+//! - `docker:name` — `/run/secrets/name`
+//! - `systemd:name` — `$CREDENTIALS_DIRECTORY/name` (`LoadCredential=`)
+//! - `env:NAME`
+//! - `vault:mount/path#field` — Vault KV v2 via `VAULT_ADDR` and `VAULT_TOKEN`
+//!
+//! [`signing_key`] accepts a 32-byte Ed25519 seed as raw bytes, hex or base64url.
+
+use crate::{from_hex, SignError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::SigningKey;
+use serde_json::Value as Json;
+use std::path::{Path, PathBuf};
+
+const MIN_HMAC_SECRET_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SecretSource {
+    File(PathBuf),
+    Mounted(PathBuf),
+    Systemd(String),
+    Env(String),
+    Vault { mount: String, path: String, field: String },
+}
+
+impl SecretSource {
+    pub fn parse(spec: &str) -> Result<Self, SignError> {
+        let bad = || SignError::Secret(format!("invalid secret spec '{}'", spec));
+        let non_empty = |s: &str| if s.is_empty() { Err(bad()) } else { Ok(s.to_string()) };
+        Ok(match spec.split_once(':') {
+            Some(("file", p)) => Self::File(non_empty(p)?.into()),
+            Some(("mounted", p)) => Self::Mounted(non_empty(p)?.into()),
+            Some(("docker", name)) => Self::Mounted(Path::new("/run/secrets").join(non_empty(name)?)),
+            Some(("systemd", name)) => Self::Systemd(non_empty(name)?),
+            Some(("env", name)) => Self::Env(non_empty(name)?),
+            Some(("vault", rest)) => {
+                let (location, field) = rest.split_once('#').ok_or_else(bad)?;
+                let (mount, path) = location.split_once('/').ok_or_else(bad)?;
+                Self::Vault { mount: non_empty(mount)?, path: non_empty(path)?, field: non_empty(field)? }
+            }
+            _ => Self::File(non_empty(spec)?.into()),
+        })
+    }
+
+    /// Raw secret bytes.
+    pub fn load(&self) -> Result<Vec<u8>, SignError> {
+        match self {
+            Self::File(path) => {
+                check_permissions(path)?;
+                read(path)
+            }
+            Self::Mounted(path) => read(path),
+            Self::Systemd(name) => {
+                let dir = std::env::var_os("CREDENTIALS_DIRECTORY").ok_or_else(|| SignError::Secret("CREDENTIALS_DIRECTORY is not set".into()))?;
+                read(&Path::new(&dir).join(name))
+            }
+            Self::Env(name) => std::env::var(name).map(String::into_bytes).map_err(|_| SignError::Secret(format!("{} is not set", name))),
+            Self::Vault { mount, path, field } => vault_kv2(mount, path, field),
+        }
+    }
+}
+
+/// Ed25519 signing key from a 32-byte seed (raw, hex or base64url).
+pub fn signing_key(source: &SecretSource) -> Result<SigningKey, SignError> {
+    let raw = source.load()?;
+    let seed = match std::str::from_utf8(&raw).map(str::trim) {
+        Ok(text) if text.len() == 64 => from_hex(text),
+        Ok(text) if raw.len() != 32 => B64URL.decode(text.trim_end_matches('=')).ok(),
+        _ => Some(raw),
+    };
+    let seed: [u8; 32] = seed.and_then(|s| s.try_into().ok()).ok_or_else(|| SignError::Secret("expected a 32-byte Ed25519 seed".into()))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// HMAC key bytes (a trailing newline is dropped); at least 32 bytes.
+pub fn hmac_secret(source: &SecretSource) -> Result<Vec<u8>, SignError> {
+    let mut raw = source.load()?;
+    while raw.last().is_some_and(|b| *b == b'\n' || *b == b'\r') { raw.pop(); }
+    if raw.len() < MIN_HMAC_SECRET_LEN { return Err(SignError::Secret(format!("HMAC secret shorter than {} bytes", MIN_HMAC_SECRET_LEN))); }
+    Ok(raw)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, SignError> { std::fs::read(path).map_err(|e| SignError::Secret(format!("{}: {}", path.display(), e))) }
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<(), SignError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path).map_err(|e| SignError::Secret(format!("{}: {}", path.display(), e)))?.permissions().mode();
+    if mode & 0o077 != 0 { return Err(SignError::Secret(format!("{}: mode {:o} is accessible by group or others", path.display(), mode & 0o777))); }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<(), SignError> { Ok(()) }
+
+fn vault_kv2(mount: &str, path: &str, field: &str) -> Result<Vec<u8>, SignError> {
+    let env = |name: &str| std::env::var(name).map_err(|_| SignError::Secret(format!("{} is not set", name)));
+    let url = format!("{}/v1/{}/data/{}", env("VAULT_ADDR")?.trim_end_matches('/'), mount, path);
+    let fail = |e: &dyn std::fmt::Display| SignError::Secret(format!("vault {}/{}: {}", mount, path, e));
+    let resp = ureq::get(&url).set("X-Vault-Token", &env("VAULT_TOKEN")?).call().map_err(|e| fail(&e))?;
+    let body: Json = resp.into_json().map_err(|e| fail(&e))?;
+    body.pointer(&format!("/data/data/{}", field))
+        .and_then(Json::as_str)
+        .map(|s| s.as_bytes().to_vec())
+        .ok_or_else(|| SignError::Secret(format!("vault {}/{}: no string field '{}'", mount, path, field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_specs_and_loads_seeds() {
+        assert_eq!(SecretSource::parse("docker:issuer-key").unwrap(), SecretSource::Mounted("/run/secrets/issuer-key".into()));
+        assert_eq!(
+            SecretSource::parse("vault:secret/ubl/issuer#seed").unwrap(),
+            SecretSource::Vault { mount: "secret".into(), path: "ubl/issuer".into(), field: "seed".into() }
+        );
+        assert!(SecretSource::parse("vault:secret").is_err());
+
+        let dir = std::env::temp_dir().join(format!("ubl-secrets-{}", crate::random_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("seed");
+        std::fs::write(&path, format!("{}\n", "07".repeat(32))).unwrap();
+        let key = signing_key(&SecretSource::Mounted(path.clone())).unwrap();
+        assert_eq!(key.to_bytes(), [7u8; 32]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(matches!(signing_key(&SecretSource::File(path.clone())), Err(SignError::Secret(_))));
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+            assert!(signing_key(&SecretSource::File(path.clone())).is_ok());
+        }
+        assert_eq!(hmac_secret(&SecretSource::Mounted(path)).unwrap().len(), 64);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! Stateless encrypted session cookies (feature `session`).
+//!
+//! [`Claims`] are canonically encoded, sealed with XChaCha20-Poly1305 and carried as
+//! `base64url(version || nonce || ciphertext)`. The key version is authenticated as associated
+//! data; older versions stay openable during rotation and are reported via [`OpenedSession::stale_key`].
+
+use crate::{fill_random, now_ts, to_hex, Claims, SignError, VerifyError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use chacha20poly1305::{aead::{Aead, KeyInit, Payload}, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+const NONCE_LEN: usize = 24;
+
+/// A versioned 256-bit session key.
+#[derive(Clone)]
+pub struct SessionKey { pub version: u8, key: [u8; 32] }
+
+impl std::fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionKey").field("version", &self.version).finish_non_exhaustive()
+    }
+}
+
+impl SessionKey {
+    pub fn new(version: u8, key: [u8; 32]) -> Self { Self { version, key } }
+    pub fn generate(version: u8) -> Self {
+        let mut key = [0u8; 32];
+        fill_random(&mut key);
+        Self { version, key }
+    }
+}
+
+/// Seals with the current key and opens with current or retired keys.
+#[derive(Debug, Clone)]
+pub struct SessionSealer {
+    current: SessionKey,
+    retired: Vec<SessionKey>,
+    /// Lifetime applied to claims that carry no `exp`.
+    pub default_ttl_secs: i64,
+}
+
+/// Result of [`SessionSealer::open`].
+#[derive(Debug, Clone)]
+pub struct OpenedSession {
+    pub claims: Claims,
+    /// Sealed under a retired key; re-seal and reissue the cookie.
+    pub stale_key: bool,
+}
+
+impl SessionSealer {
+    pub fn new(current: SessionKey) -> Self { Self { current, retired: Vec::new(), default_ttl_secs: 3600 } }
+    pub fn with_retired(mut self, key: SessionKey) -> Self { self.retired.push(key); self }
+    pub fn with_default_ttl(mut self, secs: i64) -> Self { self.default_ttl_secs = secs; self }
+
+    /// Makes `key` current, keeping the previous current key openable.
+    pub fn rotate(&mut self, key: SessionKey) {
+        let old = std::mem::replace(&mut self.current, key);
+        self.retired.insert(0, old);
+    }
+
+    pub fn seal(&self, claims: &Claims) -> Result<String, SignError> {
+        let mut claims = claims.clone();
+        if claims.exp.is_none() { claims.exp = Some(now_ts() + self.default_ttl_secs); }
+        let plaintext = json_atomic::canonize(&claims).map_err(|e| SignError::Canonical(e.to_string()))?;
+        let mut out = vec![self.current.version];
+        out.extend(seal_bytes(&self.current, AsRef::<[u8]>::as_ref(&plaintext), &[self.current.version])?);
+        Ok(B64URL.encode(out))
+    }
+
+    /// Decrypts and checks `exp` (no leeway) against `now` or the current time.
+    pub fn open(&self, cookie: &str, now: Option<i64>) -> Result<OpenedSession, VerifyError> {
+        let raw = B64URL.decode(cookie.trim().as_bytes()).map_err(|_| VerifyError::Base64)?;
+        let (&version, rest) = raw.split_first().ok_or(VerifyError::BadFormat)?;
+        let key = std::iter::once(&self.current).chain(&self.retired).find(|k| k.version == version).ok_or(VerifyError::NoKey)?;
+        let plaintext = open_bytes(key, rest, &[version])?;
+        let claims: Claims = serde_json::from_slice(&plaintext).map_err(|_| VerifyError::Json)?;
+        if claims.exp.is_some_and(|exp| now.unwrap_or_else(now_ts) > exp) { return Err(VerifyError::Expired); }
+        Ok(OpenedSession { claims, stale_key: version != self.current.version })
+    }
+}
+
+/// `nonce || XChaCha20-Poly1305(msg)` under `key`, binding `aad`.
+pub(crate) fn seal_bytes(key: &SessionKey, msg: &[u8], aad: &[u8]) -> Result<Vec<u8>, SignError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    fill_random(&mut nonce);
+    let ct = XChaCha20Poly1305::new(&key.key.into()).encrypt(XNonce::from_slice(&nonce), Payload { msg, aad }).map_err(|_| SignError::Encrypt)?;
+    Ok([&nonce[..], &ct].concat())
+}
+
+pub(crate) fn open_bytes(key: &SessionKey, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    if sealed.len() < NONCE_LEN { return Err(VerifyError::BadFormat); }
+    let (nonce, ct) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(&key.key.into()).decrypt(XNonce::from_slice(nonce), Payload { msg: ct, aad }).map_err(|_| VerifyError::Decrypt)
+}
+
+/// Keyed hash of `data` under a subkey of `key`, for storage keys that must not reveal their input.
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+pub(crate) fn blind(key: &SessionKey, data: &str) -> String {
+    let mut subkey = [0u8; 32];
+    Hkdf::<Sha256>::new(None, &key.key).expand(b"ubl-auth blind index", &mut subkey).expect("32 bytes is a valid HKDF-SHA256 length");
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&subkey).expect("hmac accepts any key length");
+    mac.update(data.as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_open_expire_and_rotate() {
+        let mut sealer = SessionSealer::new(SessionKey::generate(1));
+        let claims: Claims = serde_json::from_value(serde_json::json!({"sub":"did:key:zUser","scope":"read"})).unwrap();
+        let cookie = sealer.seal(&claims).unwrap();
+        let opened = sealer.open(&cookie, None).unwrap();
+        assert_eq!(opened.claims.sub, "did:key:zUser");
+        assert!(!opened.stale_key);
+        assert!(matches!(sealer.open(&cookie, Some(now_ts() + 7200)), Err(VerifyError::Expired)));
+
+        let mut tampered = B64URL.decode(&cookie).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(sealer.open(&B64URL.encode(tampered), None), Err(VerifyError::Decrypt)));
+
+        sealer.rotate(SessionKey::generate(2));
+        assert!(sealer.open(&cookie, None).unwrap().stale_key);
+        let other = SessionSealer::new(SessionKey::generate(2));
+        assert!(matches!(other.open(&cookie, None), Err(VerifyError::NoKey)));
+    }
+}
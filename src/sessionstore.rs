@@ -0,0 +1,203 @@
+//! Server-side session state for otherwise stateless tokens.
+//!
+//! A [`SessionManager`] keys sessions by a claim (`sid`, falling back to `jti`) and enforces an
+//! idle timeout (sliding: every successful check extends it) and an absolute lifetime. Explicit
+//! termination makes "log out now" effective even while the token itself is still valid.
+//! Stores: [`MemorySessionStore`], and `RedisSessionStore` with the `redis` feature.
+
+#[cfg(feature = "redis")]
+use crate::session::{blind, open_bytes, seal_bytes, SessionKey};
+use crate::{now_ts, Claims, VerifyError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub id: String,
+    pub subject: String,
+    pub created_at: i64,
+    pub last_seen: i64,
+    /// Absolute end of the session, regardless of activity.
+    pub expires_at: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SessionError {
+    #[error("session not found, expired or terminated")]
+    Inactive,
+    #[error("session store error: {0}")]
+    Store(String),
+}
+
+pub trait SessionStore: Send + Sync {
+    fn put(&self, record: &SessionRecord, idle_timeout_secs: i64) -> Result<(), SessionError>;
+    fn get(&self, id: &str) -> Result<Option<SessionRecord>, SessionError>;
+    fn remove(&self, id: &str) -> Result<(), SessionError>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemorySessionStore { inner: Mutex<HashMap<String, SessionRecord>> }
+
+impl MemorySessionStore {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn put(&self, record: &SessionRecord, _idle_timeout_secs: i64) -> Result<(), SessionError> {
+        self.inner.lock().insert(record.id.clone(), record.clone());
+        Ok(())
+    }
+    fn get(&self, id: &str) -> Result<Option<SessionRecord>, SessionError> { Ok(self.inner.lock().get(id).cloned()) }
+    fn remove(&self, id: &str) -> Result<(), SessionError> {
+        self.inner.lock().remove(id);
+        Ok(())
+    }
+}
+
+/// Sessions as JSON under `{prefix}{id}`, with a Redis TTL tracking the idle timeout.
+///
+/// With [`RedisSessionStore::with_seal_key`], entries are encrypted and stored under a keyed hash
+/// of the session id, so a dump of the database reveals neither subjects nor session ids.
+/// Entries written under another key become unreadable (the session ends) after a key change.
+#[cfg(feature = "redis")]
+#[derive(Debug, Clone)]
+pub struct RedisSessionStore { client: redis::Client, prefix: String, seal: Option<SessionKey> }
+
+#[cfg(feature = "redis")]
+impl RedisSessionStore {
+    pub fn new(url: &str) -> Result<Self, SessionError> {
+        let client = redis::Client::open(url).map_err(|e| SessionError::Store(e.to_string()))?;
+        Ok(Self { client, prefix: "ubl:session:".into(), seal: None })
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self { self.prefix = prefix.to_string(); self }
+    pub fn with_seal_key(mut self, key: SessionKey) -> Self { self.seal = Some(key); self }
+
+    fn key(&self, id: &str) -> String {
+        match &self.seal {
+            Some(k) => format!("{}{}", self.prefix, blind(k, id)),
+            None => format!("{}{}", self.prefix, id),
+        }
+    }
+
+    fn encode(&self, record: &SessionRecord) -> Result<Vec<u8>, SessionError> {
+        let json = serde_json::to_vec(record).map_err(|e| SessionError::Store(e.to_string()))?;
+        match &self.seal {
+            Some(k) => seal_bytes(k, &json, self.key(&record.id).as_bytes()).map_err(|e| SessionError::Store(e.to_string())),
+            None => Ok(json),
+        }
+    }
+
+    fn decode(&self, id: &str, raw: &[u8]) -> Result<SessionRecord, SessionError> {
+        let json = match &self.seal {
+            Some(k) => open_bytes(k, raw, self.key(id).as_bytes()).map_err(|e| SessionError::Store(e.to_string()))?,
+            None => raw.to_vec(),
+        };
+        serde_json::from_slice(&json).map_err(|e| SessionError::Store(e.to_string()))
+    }
+
+    fn conn(&self) -> Result<redis::Connection, SessionError> { self.client.get_connection().map_err(|e| SessionError::Store(e.to_string())) }
+}
+
+#[cfg(feature = "redis")]
+impl SessionStore for RedisSessionStore {
+    fn put(&self, record: &SessionRecord, idle_timeout_secs: i64) -> Result<(), SessionError> {
+        use redis::Commands;
+        let ttl = (record.expires_at - record.last_seen).min(idle_timeout_secs).max(1) as u64;
+        self.conn()?.set_ex(self.key(&record.id), self.encode(record)?, ttl).map_err(|e| SessionError::Store(e.to_string()))
+    }
+    fn get(&self, id: &str) -> Result<Option<SessionRecord>, SessionError> {
+        use redis::Commands;
+        let raw: Option<Vec<u8>> = self.conn()?.get(self.key(id)).map_err(|e| SessionError::Store(e.to_string()))?;
+        raw.map(|r| self.decode(id, &r)).transpose()
+    }
+    fn remove(&self, id: &str) -> Result<(), SessionError> {
+        use redis::Commands;
+        self.conn()?.del(self.key(id)).map_err(|e| SessionError::Store(e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub struct SessionManager<S: SessionStore> {
+    store: S,
+    pub idle_timeout_secs: i64,
+    pub absolute_ttl_secs: i64,
+    pub id_claim: String,
+}
+
+impl<S: SessionStore> SessionManager<S> {
+    /// 30 minutes idle, 12 hours absolute, keyed by `sid`.
+    pub fn new(store: S) -> Self { Self { store, idle_timeout_secs: 1800, absolute_ttl_secs: 12 * 3600, id_claim: "sid".into() } }
+    pub fn with_idle_timeout(mut self, secs: i64) -> Self { self.idle_timeout_secs = secs; self }
+    pub fn with_absolute_ttl(mut self, secs: i64) -> Self { self.absolute_ttl_secs = secs; self }
+    pub fn with_id_claim(mut self, claim: &str) -> Self { self.id_claim = claim.to_string(); self }
+
+    /// Session id of `claims`: the configured claim, else `jti`.
+    pub fn session_id(&self, claims: &Claims) -> Result<String, VerifyError> {
+        claims.extra.get(&self.id_claim).and_then(|v| v.as_str()).map(str::to_string).or_else(|| claims.jti.clone()).ok_or(VerifyError::MissingClaim("sid"))
+    }
+
+    /// Registers the session of freshly issued `claims` (at login).
+    pub fn start(&self, claims: &Claims) -> Result<SessionRecord, VerifyError> {
+        let now = now_ts();
+        let record = SessionRecord { id: self.session_id(claims)?, subject: claims.sub.clone(), created_at: now, last_seen: now, expires_at: now + self.absolute_ttl_secs };
+        self.store.put(&record, self.idle_timeout_secs)?;
+        Ok(record)
+    }
+
+    /// Requires a live session for `claims` and slides its idle window.
+    pub fn check(&self, claims: &Claims) -> Result<SessionRecord, VerifyError> { self.check_at(claims, now_ts()) }
+
+    pub fn check_at(&self, claims: &Claims, now: i64) -> Result<SessionRecord, VerifyError> {
+        let id = self.session_id(claims)?;
+        let mut record = self.store.get(&id)?.ok_or(SessionError::Inactive)?;
+        if record.subject != claims.sub { return Err(SessionError::Inactive.into()); }
+        if now - record.last_seen > self.idle_timeout_secs || now >= record.expires_at {
+            self.store.remove(&id)?;
+            return Err(SessionError::Inactive.into());
+        }
+        record.last_seen = now;
+        self.store.put(&record, self.idle_timeout_secs)?;
+        Ok(record)
+    }
+
+    /// Logs the session out immediately.
+    pub fn terminate(&self, id: &str) -> Result<(), SessionError> { self.store.remove(id) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sliding_idle_timeout_and_termination() {
+        let sessions = SessionManager::new(MemorySessionStore::new()).with_idle_timeout(60).with_absolute_ttl(300);
+        let claims: Claims = serde_json::from_value(json!({"sub":"did:key:zUser","sid":"s-1"})).unwrap();
+        let start = sessions.start(&claims).unwrap();
+
+        assert!(sessions.check_at(&claims, start.created_at + 50).is_ok());
+        assert!(sessions.check_at(&claims, start.created_at + 100).is_ok());
+        assert!(matches!(sessions.check_at(&claims, start.created_at + 300), Err(VerifyError::Session(SessionError::Inactive))));
+
+        sessions.start(&claims).unwrap();
+        sessions.terminate("s-1").unwrap();
+        assert!(matches!(sessions.check(&claims), Err(VerifyError::Session(SessionError::Inactive))));
+        let other: Claims = serde_json::from_value(json!({"sub":"did:key:zOther","jti":"s-2"})).unwrap();
+        assert_eq!(sessions.session_id(&other).unwrap(), "s-2");
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn sealed_redis_entries_hide_ids_and_subjects() {
+        let store = RedisSessionStore::new("redis://127.0.0.1/").unwrap().with_seal_key(SessionKey::generate(1));
+        let record = SessionRecord { id: "s-1".into(), subject: "did:key:zUser".into(), created_at: 1, last_seen: 1, expires_at: 2 };
+        let raw = store.encode(&record).unwrap();
+        assert!(!store.key("s-1").contains("s-1"));
+        assert!(!String::from_utf8_lossy(&raw).contains("zUser"));
+        assert_eq!(store.decode("s-1", &raw).unwrap(), record);
+        assert!(matches!(store.decode("s-2", &raw), Err(SessionError::Store(_))));
+    }
+}
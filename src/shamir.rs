@@ -0,0 +1,169 @@
+//! Shamir secret sharing for offline backup of an issuer signing key.
+//!
+//! [`split`] deals the 32-byte Ed25519 seed into `shares` pieces over GF(2^8), any `threshold` of
+//! which recover it with [`combine`]; fewer reveal nothing about the key. Each piece travels as a
+//! [`ShareEnvelope`] in canonical JSON, carrying the key id, the public key and a SHA-256
+//! fingerprint of its own contents. The [`Ceremony`] record lists every fingerprint and can be
+//! kept in the clear, so custodians can check that the envelope they hold belongs to the ceremony.
+//! [`combine`] only returns a key whose public half matches the envelopes.
+//!
+//! Unlike [`frost`](crate::frost), reconstruction brings the whole key together on one machine:
+//! this is for backup and recovery, not day-to-day signing.
+
+use crate::receipts::canonical_bytes;
+use crate::{fill_random, to_hex, SignError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareEnvelope {
+    pub kid: String,
+    /// Base64url Ed25519 public key of the shared signing key.
+    pub public_key: String,
+    pub threshold: u8,
+    pub shares: u8,
+    /// Evaluation point, 1..=shares.
+    pub index: u8,
+    /// Base64url share bytes.
+    pub share: String,
+    /// Hex SHA-256 over the canonical JSON of the other fields.
+    pub fingerprint: String,
+}
+
+impl std::fmt::Debug for ShareEnvelope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShareEnvelope").field("kid", &self.kid).field("index", &self.index).field("fingerprint", &self.fingerprint).finish_non_exhaustive()
+    }
+}
+
+impl ShareEnvelope {
+    pub fn compute_fingerprint(&self) -> Result<String, SignError> {
+        let body = serde_json::json!({
+            "kid": self.kid, "public_key": self.public_key, "threshold": self.threshold,
+            "shares": self.shares, "index": self.index, "share": self.share,
+        });
+        Ok(to_hex(&Sha256::digest(canonical_bytes(&body)?)))
+    }
+
+    /// Whether the envelope is intact (its fingerprint matches its contents).
+    pub fn is_intact(&self) -> bool { self.compute_fingerprint().is_ok_and(|f| f == self.fingerprint) }
+
+    pub fn to_json(&self) -> Result<String, SignError> {
+        String::from_utf8(canonical_bytes(self)?).map_err(|e| SignError::Canonical(e.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SignError> {
+        serde_json::from_str(json).map_err(|e| share_error(&format!("malformed envelope: {}", e)))
+    }
+}
+
+/// Public record of a split, safe to store alongside the issuer's configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ceremony {
+    pub kid: String,
+    pub public_key: String,
+    pub threshold: u8,
+    pub fingerprints: Vec<String>,
+}
+
+impl Ceremony {
+    pub fn contains(&self, envelope: &ShareEnvelope) -> bool {
+        envelope.kid == self.kid && envelope.public_key == self.public_key && envelope.is_intact() && self.fingerprints.contains(&envelope.fingerprint)
+    }
+}
+
+/// Splits `key` into `shares` envelopes, any `threshold` of which recover it.
+pub fn split(key: &SigningKey, kid: &str, threshold: u8, shares: u8) -> Result<(Ceremony, Vec<ShareEnvelope>), SignError> {
+    if threshold < 2 || threshold > shares { return Err(share_error("need 2 <= threshold <= shares")); }
+    let coefficients: Vec<[u8; 32]> = std::iter::once(key.to_bytes()).chain((1..threshold).map(|_| random_bytes())).collect();
+    let public_key = B64URL.encode(key.verifying_key().as_bytes());
+    let envelopes = (1..=shares)
+        .map(|x| {
+            let y: Vec<u8> = (0..32).map(|i| coefficients.iter().rev().fold(0, |acc, c| gf_mul(acc, x) ^ c[i])).collect();
+            let mut envelope = ShareEnvelope { kid: kid.to_string(), public_key: public_key.clone(), threshold, shares, index: x, share: B64URL.encode(y), fingerprint: String::new() };
+            envelope.fingerprint = envelope.compute_fingerprint()?;
+            Ok(envelope)
+        })
+        .collect::<Result<Vec<_>, SignError>>()?;
+    let ceremony = Ceremony { kid: kid.to_string(), public_key, threshold, fingerprints: envelopes.iter().map(|e| e.fingerprint.clone()).collect() };
+    Ok((ceremony, envelopes))
+}
+
+/// Recovers the signing key from at least `threshold` intact envelopes of the same split.
+pub fn combine(envelopes: &[ShareEnvelope]) -> Result<SigningKey, SignError> {
+    let first = envelopes.first().ok_or_else(|| share_error("no shares"))?;
+    let mut points: Vec<(u8, Vec<u8>)> = Vec::new();
+    for e in envelopes {
+        if !e.is_intact() { return Err(share_error(&format!("share {} fails its fingerprint", e.index))); }
+        if (&e.kid, &e.public_key, e.threshold) != (&first.kid, &first.public_key, first.threshold) { return Err(share_error("shares come from different splits")); }
+        if e.index == 0 || points.iter().any(|(x, _)| *x == e.index) { return Err(share_error(&format!("invalid or repeated share index {}", e.index))); }
+        let y = B64URL.decode(&e.share).ok().filter(|y| y.len() == 32).ok_or_else(|| share_error("share must be 32 bytes"))?;
+        points.push((e.index, y));
+    }
+    if points.len() < first.threshold as usize { return Err(share_error(&format!("need {} shares, got {}", first.threshold, points.len()))); }
+
+    let mut secret = [0u8; 32];
+    for (j, (xj, yj)) in points.iter().enumerate() {
+        let basis = points.iter().enumerate().filter(|(m, _)| *m != j).fold(1, |acc, (_, (xm, _))| gf_mul(acc, gf_mul(*xm, gf_inv(xm ^ xj))));
+        secret.iter_mut().zip(yj).for_each(|(s, y)| *s ^= gf_mul(basis, *y));
+    }
+    let key = SigningKey::from_bytes(&secret);
+    if B64URL.encode(key.verifying_key().as_bytes()) != first.public_key { return Err(share_error("recovered key does not match the public key")); }
+    Ok(key)
+}
+
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    fill_random(&mut bytes);
+    bytes
+}
+
+fn share_error(why: &str) -> SignError { SignError::KeyShare(why.to_string()) }
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1, without secret-dependent branches.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0;
+    for _ in 0..8 {
+        p ^= a & (b & 1).wrapping_neg();
+        a = (a << 1) ^ (0x1b & (a >> 7).wrapping_neg());
+        b >>= 1;
+    }
+    p
+}
+
+/// a^254 = a^-1 for a != 0.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 { result = gf_mul(result, a); }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn any_threshold_subset_recovers_the_key() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(46));
+        let (ceremony, shares) = split(&key, "root-2026", 3, 5).unwrap();
+        assert!(shares.iter().all(|s| ceremony.contains(s)));
+
+        let json: Vec<String> = shares.iter().map(|s| s.to_json().unwrap()).collect();
+        let parsed: Vec<ShareEnvelope> = json.iter().map(|j| ShareEnvelope::from_json(j).unwrap()).collect();
+        for subset in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let picked: Vec<ShareEnvelope> = subset.iter().map(|&i| parsed[i].clone()).collect();
+            assert_eq!(combine(&picked).unwrap().to_bytes(), key.to_bytes());
+        }
+
+        assert!(matches!(combine(&parsed[..2]), Err(SignError::KeyShare(_))));
+        let mut tampered = parsed[..3].to_vec();
+        tampered[1].share = B64URL.encode([7u8; 32]);
+        assert!(matches!(combine(&tampered), Err(SignError::KeyShare(_))));
+        tampered[1].fingerprint = tampered[1].compute_fingerprint().unwrap();
+        assert!(!ceremony.contains(&tampered[1]));
+        assert!(matches!(combine(&tampered), Err(SignError::KeyShare(_))));
+    }
+}
@@ -0,0 +1,118 @@
+//! Step-up challenges for scope and authentication-level failures.
+//!
+//! A [`StepUp`] describes what a route needs (scopes, `acr` values, a maximum `auth_time` age).
+//! When verified claims fall short, [`StepUp::check`] returns the [`Challenge`] middleware should
+//! send: `insufficient_scope` with the missing scopes (RFC 6750, 403), or
+//! `insufficient_user_authentication` with `acr_values` / `max_age` hints (RFC 9470, 401).
+
+use crate::{now_ts, Claims};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StepUp {
+    pub realm: Option<String>,
+    pub scopes: Vec<String>,
+    /// Accepted `acr` values, most preferred first; empty accepts any.
+    pub acr_values: Vec<String>,
+    pub max_age_secs: Option<i64>,
+    /// Clock skew tolerated for an `auth_time` ahead of `now`.
+    pub leeway_secs: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChallengeKind {
+    InsufficientScope { scope: Vec<String> },
+    InsufficientUserAuthentication { acr_values: Vec<String>, max_age_secs: Option<i64> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub realm: Option<String>,
+    pub kind: ChallengeKind,
+}
+
+impl StepUp {
+    pub fn new() -> Self { Self::default() }
+    pub fn with_realm(mut self, realm: &str) -> Self { self.realm = Some(realm.to_string()); self }
+    pub fn with_scopes(mut self, scopes: &[&str]) -> Self { self.scopes = scopes.iter().map(|s| s.to_string()).collect(); self }
+    pub fn with_acr_values(mut self, acr: &[&str]) -> Self { self.acr_values = acr.iter().map(|s| s.to_string()).collect(); self }
+    pub fn with_max_age(mut self, secs: i64) -> Self { self.max_age_secs = Some(secs); self }
+    pub fn with_leeway(mut self, secs: i64) -> Self { self.leeway_secs = secs; self }
+
+    pub fn check(&self, claims: &Claims) -> Result<(), Challenge> { self.check_at(claims, now_ts()) }
+
+    /// Authentication level is checked before scopes: re-authenticating may change what is granted.
+    pub fn check_at(&self, claims: &Claims, now: i64) -> Result<(), Challenge> {
+        let acr = claims.extra.get("acr").and_then(|v| v.as_str());
+        let acr_ok = self.acr_values.is_empty() || acr.is_some_and(|a| self.acr_values.iter().any(|v| v == a));
+        let age = claims.extra.get("auth_time").and_then(|v| v.as_i64()).map(|t| now.saturating_sub(t));
+        let fresh = self.max_age_secs.is_none_or(|max| age.is_some_and(|age| age <= max && age >= self.leeway_secs.saturating_neg()));
+        if !acr_ok || !fresh {
+            let kind = ChallengeKind::InsufficientUserAuthentication { acr_values: self.acr_values.clone(), max_age_secs: self.max_age_secs };
+            return Err(Challenge { realm: self.realm.clone(), kind });
+        }
+        let held: Vec<&str> = claims.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+        if self.scopes.iter().any(|s| !held.contains(&s.as_str())) {
+            return Err(Challenge { realm: self.realm.clone(), kind: ChallengeKind::InsufficientScope { scope: self.scopes.clone() } });
+        }
+        Ok(())
+    }
+}
+
+impl Challenge {
+    pub fn error_code(&self) -> &'static str {
+        match self.kind {
+            ChallengeKind::InsufficientScope { .. } => "insufficient_scope",
+            ChallengeKind::InsufficientUserAuthentication { .. } => "insufficient_user_authentication",
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self.kind {
+            ChallengeKind::InsufficientScope { .. } => 403,
+            ChallengeKind::InsufficientUserAuthentication { .. } => 401,
+        }
+    }
+
+    /// Value for the `WWW-Authenticate` response header.
+    pub fn www_authenticate(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(realm) = &self.realm { params.push(format!("realm=\"{}\"", quote(realm))); }
+        params.push(format!("error=\"{}\"", self.error_code()));
+        match &self.kind {
+            ChallengeKind::InsufficientScope { scope } => params.push(format!("scope=\"{}\"", quote(&scope.join(" ")))),
+            ChallengeKind::InsufficientUserAuthentication { acr_values, max_age_secs } => {
+                if !acr_values.is_empty() { params.push(format!("acr_values=\"{}\"", quote(&acr_values.join(" ")))); }
+                if let Some(max) = max_age_secs { params.push(format!("max_age=\"{}\"", max)); }
+            }
+        }
+        format!("Bearer {}", params.join(", "))
+    }
+}
+
+fn quote(s: &str) -> String { s.replace('\\', "\\\\").replace('"', "\\\"") }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn challenges_name_what_is_missing() {
+        let route = StepUp::new().with_realm("orders").with_scopes(&["orders:write"]).with_acr_values(&["mfa"]).with_max_age(300);
+        let claims = |v: serde_json::Value| -> Claims { serde_json::from_value(v).unwrap() };
+
+        let weak = route.check_at(&claims(json!({"sub":"u","scope":"orders:write","acr":"pwd","auth_time":1_000})), 1_100).unwrap_err();
+        assert_eq!((weak.status(), weak.www_authenticate().as_str()), (401, r#"Bearer realm="orders", error="insufficient_user_authentication", acr_values="mfa", max_age="300""#));
+
+        let stale = route.check_at(&claims(json!({"sub":"u","scope":"orders:write","acr":"mfa","auth_time":1_000})), 2_000).unwrap_err();
+        assert_eq!(stale.error_code(), "insufficient_user_authentication");
+        assert!(route.check_at(&claims(json!({"sub":"u","scope":"orders:write","acr":"mfa","auth_time":i64::MIN})), 1_100).is_err());
+        assert!(route.check_at(&claims(json!({"sub":"u","scope":"orders:write","acr":"mfa","auth_time":1_200})), 1_100).is_err());
+        assert!(route.clone().with_leeway(120).check_at(&claims(json!({"sub":"u","scope":"orders:write","acr":"mfa","auth_time":1_200})), 1_100).is_ok());
+
+        let narrow = route.check_at(&claims(json!({"sub":"u","scope":"orders:read","acr":"mfa","auth_time":1_000})), 1_100).unwrap_err();
+        assert_eq!((narrow.status(), narrow.www_authenticate().as_str()), (403, r#"Bearer realm="orders", error="insufficient_scope", scope="orders:write""#));
+
+        assert!(route.check_at(&claims(json!({"sub":"u","scope":"orders:read orders:write","acr":"mfa","auth_time":1_000})), 1_100).is_ok());
+    }
+}
@@ -0,0 +1,349 @@
+//! RFC 3161 trusted timestamps for receipts (feature `tsa`).
+//!
+//! A time-stamping authority signs the SHA-256 imprint of a receipt together with the time, which
+//! shows the receipt existed at that moment independently of our own clocks and keys. Tokens are
+//! CMS `SignedData` over a `TSTInfo`; verification checks the imprint, the signed attributes, the
+//! signature (RSA PKCS#1 v1.5 or ECDSA P-256), the signer's `timeStamping` key purpose, and the
+//! certificate chain up to a caller-supplied [`TimestampTrust`] at the stamped time.
+
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier};
+use der::asn1::{GeneralizedTime, ObjectIdentifier as Oid, OctetString, Uint};
+use der::{Decode, Encode, Sequence};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+use std::time::Duration;
+use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage};
+use x509_cert::Certificate;
+
+const ID_SIGNED_DATA: Oid = Oid::new_unwrap("1.2.840.113549.1.7.2");
+const ID_CT_TST_INFO: Oid = Oid::new_unwrap("1.2.840.113549.1.9.16.1.4");
+const ID_CONTENT_TYPE: Oid = Oid::new_unwrap("1.2.840.113549.1.9.3");
+const ID_MESSAGE_DIGEST: Oid = Oid::new_unwrap("1.2.840.113549.1.9.4");
+const ID_SHA256: Oid = Oid::new_unwrap("2.16.840.1.101.3.4.2.1");
+const ID_SHA384: Oid = Oid::new_unwrap("2.16.840.1.101.3.4.2.2");
+const ID_SHA512: Oid = Oid::new_unwrap("2.16.840.1.101.3.4.2.3");
+const ID_RSA_ENCRYPTION: Oid = Oid::new_unwrap("1.2.840.113549.1.1.1");
+const ID_SHA256_WITH_RSA: Oid = Oid::new_unwrap("1.2.840.113549.1.1.11");
+const ID_SHA384_WITH_RSA: Oid = Oid::new_unwrap("1.2.840.113549.1.1.12");
+const ID_SHA512_WITH_RSA: Oid = Oid::new_unwrap("1.2.840.113549.1.1.13");
+const ID_EC_PUBLIC_KEY: Oid = Oid::new_unwrap("1.2.840.10045.2.1");
+const ID_ECDSA_WITH_SHA256: Oid = Oid::new_unwrap("1.2.840.10045.4.3.2");
+const ID_KP_TIME_STAMPING: Oid = Oid::new_unwrap("1.3.6.1.5.5.7.3.8");
+const ID_CE_EXT_KEY_USAGE: Oid = Oid::new_unwrap("2.5.29.37");
+const ID_CE_BASIC_CONSTRAINTS: Oid = Oid::new_unwrap("2.5.29.19");
+const MAX_CHAIN: usize = 8;
+
+#[derive(Debug, Clone, Sequence)]
+struct MessageImprint { hash_algorithm: AlgorithmIdentifierOwned, hashed_message: OctetString }
+
+#[derive(Debug, Clone, Sequence)]
+struct TimeStampReq {
+    version: u8,
+    message_imprint: MessageImprint,
+    req_policy: Option<Oid>,
+    nonce: Option<Uint>,
+    #[asn1(default = "Default::default")]
+    cert_req: bool,
+}
+
+#[derive(Debug, Clone, Sequence)]
+struct PkiStatusInfo {
+    status: u8,
+    status_string: Option<Vec<String>>,
+    fail_info: Option<der::asn1::BitString>,
+}
+
+#[derive(Debug, Clone, Sequence)]
+struct TimeStampResp { status: PkiStatusInfo, time_stamp_token: Option<ContentInfo> }
+
+#[derive(Debug, Clone, Sequence)]
+struct Accuracy {
+    seconds: Option<u32>,
+    #[asn1(context_specific = "0", tag_mode = "IMPLICIT", optional = "true")]
+    millis: Option<u16>,
+    #[asn1(context_specific = "1", tag_mode = "IMPLICIT", optional = "true")]
+    micros: Option<u16>,
+}
+
+#[derive(Debug, Clone, Sequence)]
+struct TstInfo {
+    version: u8,
+    policy: Oid,
+    message_imprint: MessageImprint,
+    serial_number: Uint,
+    gen_time: GeneralizedTime,
+    accuracy: Option<Accuracy>,
+    #[asn1(default = "Default::default")]
+    ordering: bool,
+    nonce: Option<Uint>,
+    #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+    tsa: Option<der::Any>,
+    #[asn1(context_specific = "1", tag_mode = "IMPLICIT", optional = "true")]
+    extensions: Option<x509_cert::ext::Extensions>,
+}
+
+/// What a verified token attests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampInfo {
+    /// Stamped time, Unix seconds.
+    pub gen_time: i64,
+    pub policy: String,
+    /// Hex serial number, unique per TSA.
+    pub serial: String,
+    /// Subject of the TSA certificate.
+    pub tsa: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TimestampError {
+    /// Obtaining a token from the TSA failed.
+    #[error("timestamping failed: {0}")]
+    Request(String),
+    #[error("timestamp verification failed: {0}")]
+    Invalid(String),
+}
+
+/// Trust anchors (root or TSA certificates) for timestamp verification.
+#[derive(Debug, Clone, Default)]
+pub struct TimestampTrust { anchors: Vec<Certificate> }
+
+impl TimestampTrust {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_der(mut self, der: &[u8]) -> Result<Self, TimestampError> {
+        self.anchors.push(Certificate::from_der(der).map_err(|e| fail(&e))?);
+        Ok(self)
+    }
+
+    /// Adds every certificate of a PEM bundle.
+    pub fn with_pem(mut self, pem: &str) -> Result<Self, TimestampError> {
+        self.anchors.extend(Certificate::load_pem_chain(pem.as_bytes()).map_err(|e| fail(&e))?);
+        Ok(self)
+    }
+}
+
+/// Client for a TSA speaking the RFC 3161 HTTP transport.
+#[derive(Debug, Clone)]
+pub struct TsaClient {
+    pub url: String,
+    pub timeout: Duration,
+}
+
+impl TsaClient {
+    pub fn new(url: &str) -> Self { Self { url: url.to_string(), timeout: Duration::from_secs(10) } }
+    pub fn with_timeout(mut self, timeout: Duration) -> Self { self.timeout = timeout; self }
+
+    /// Requests a token over the SHA-256 `imprint`, returning the DER `TimeStampToken`.
+    pub fn stamp(&self, imprint: &[u8; 32]) -> Result<Vec<u8>, TimestampError> {
+        let err = |e: &dyn std::fmt::Display| TimestampError::Request(e.to_string());
+        let mut nonce = [0u8; 8];
+        crate::fill_random(&mut nonce);
+        let req = TimeStampReq {
+            version: 1,
+            message_imprint: sha256_imprint(imprint).map_err(|e| err(&e))?,
+            req_policy: None,
+            nonce: Some(Uint::new(&nonce).map_err(|e| err(&e))?),
+            cert_req: true,
+        };
+        let resp = ureq::post(&self.url)
+            .timeout(self.timeout)
+            .set("content-type", "application/timestamp-query")
+            .send_bytes(&req.to_der().map_err(|e| err(&e))?)
+            .map_err(|e| err(&e))?;
+        let mut body = Vec::new();
+        std::io::Read::read_to_end(&mut resp.into_reader(), &mut body).map_err(|e| err(&e))?;
+        let resp = TimeStampResp::from_der(&body).map_err(|e| err(&e))?;
+        if resp.status.status > 1 {
+            return Err(err(&format!("TSA refused (status {}): {:?}", resp.status.status, resp.status.status_string.unwrap_or_default())));
+        }
+        let token = resp.time_stamp_token.ok_or_else(|| err(&"TSA response has no token"))?;
+        let info = tst_info(&signed_data(&token).map_err(|e| err(&e))?).map_err(|e| err(&e))?;
+        if info.nonce.as_ref().map(Uint::as_bytes) != req.nonce.as_ref().map(Uint::as_bytes) { return Err(err(&"TSA nonce mismatch")); }
+        token.to_der().map_err(|e| err(&e))
+    }
+}
+
+/// Verifies a DER `TimeStampToken` over the SHA-256 `imprint`.
+pub fn verify_token(token: &[u8], imprint: &[u8; 32], trust: &TimestampTrust) -> Result<TimestampInfo, TimestampError> {
+    let content = ContentInfo::from_der(token).map_err(|e| fail(&e))?;
+    let sd = signed_data(&content)?;
+    let info = tst_info(&sd)?;
+    if info.message_imprint.hash_algorithm.oid != ID_SHA256 || info.message_imprint.hashed_message.as_bytes() != imprint {
+        return Err(fail(&"imprint does not match"));
+    }
+    let gen_time = info.gen_time.to_unix_duration().as_secs();
+
+    let signers = sd.signer_infos.0.as_slice();
+    let [signer] = signers else { return Err(fail(&"expected exactly one signer")) };
+    let certs: Vec<&Certificate> = sd.certificates.iter().flat_map(|s| s.0.iter()).filter_map(|c| match c { CertificateChoices::Certificate(c) => Some(c), _ => None }).collect();
+    let SignerIdentifier::IssuerAndSerialNumber(sid) = &signer.sid else { return Err(fail(&"subject key identifier signers are not supported")) };
+    let tsa_cert = certs.iter().copied().chain(&trust.anchors)
+        .find(|c| c.tbs_certificate.issuer == sid.issuer && c.tbs_certificate.serial_number == sid.serial_number)
+        .ok_or_else(|| fail(&"signer certificate not found"))?;
+
+    let attrs = signer.signed_attrs.as_ref().ok_or_else(|| fail(&"missing signed attributes"))?;
+    let attr = |oid: Oid| attrs.iter().find(|a| a.oid == oid).and_then(|a| a.values.iter().next()).ok_or_else(|| fail(&format!("missing attribute {}", oid)));
+    if attr(ID_CONTENT_TYPE)?.decode_as::<Oid>().map_err(|e| fail(&e))? != ID_CT_TST_INFO { return Err(fail(&"content-type attribute mismatch")); }
+    let econtent = econtent(&sd)?;
+    let digest = hash(&signer.digest_alg.oid, &econtent).ok_or_else(|| fail(&"unsupported digest"))?;
+    if attr(ID_MESSAGE_DIGEST)?.decode_as::<OctetString>().map_err(|e| fail(&e))?.as_bytes() != digest.as_slice() {
+        return Err(fail(&"message-digest attribute mismatch"));
+    }
+    let signed = attrs.to_der().map_err(|e| fail(&e))?;
+    verify_signature(&tsa_cert.tbs_certificate.subject_public_key_info, &signer.signature_algorithm.oid, Some(&signer.digest_alg.oid), &signed, signer.signature.as_bytes())?;
+
+    let eku: ExtendedKeyUsage = extension(tsa_cert, ID_CE_EXT_KEY_USAGE)?.ok_or_else(|| fail(&"TSA certificate has no extended key usage"))?;
+    if !eku.0.contains(&ID_KP_TIME_STAMPING) { return Err(fail(&"TSA certificate lacks the timeStamping key purpose")); }
+    verify_chain(tsa_cert, &certs, trust, gen_time)?;
+
+    Ok(TimestampInfo {
+        gen_time: gen_time as i64,
+        policy: info.policy.to_string(),
+        serial: crate::to_hex(info.serial_number.as_bytes()),
+        tsa: tsa_cert.tbs_certificate.subject.to_string(),
+    })
+}
+
+fn fail(e: &dyn std::fmt::Display) -> TimestampError { TimestampError::Invalid(e.to_string()) }
+
+fn sha256_imprint(imprint: &[u8; 32]) -> der::Result<MessageImprint> {
+    Ok(MessageImprint { hash_algorithm: AlgorithmIdentifierOwned { oid: ID_SHA256, parameters: None }, hashed_message: OctetString::new(imprint.to_vec())? })
+}
+
+fn signed_data(content: &ContentInfo) -> Result<SignedData, TimestampError> {
+    if content.content_type != ID_SIGNED_DATA { return Err(fail(&"not a SignedData token")); }
+    content.content.decode_as().map_err(|e| fail(&e))
+}
+
+fn econtent(sd: &SignedData) -> Result<Vec<u8>, TimestampError> {
+    if sd.encap_content_info.econtent_type != ID_CT_TST_INFO { return Err(fail(&"content is not TSTInfo")); }
+    let any = sd.encap_content_info.econtent.as_ref().ok_or_else(|| fail(&"missing TSTInfo"))?;
+    Ok(any.decode_as::<OctetString>().map_err(|e| fail(&e))?.into_bytes())
+}
+
+fn tst_info(sd: &SignedData) -> Result<TstInfo, TimestampError> { TstInfo::from_der(&econtent(sd)?).map_err(|e| fail(&e)) }
+
+fn hash(alg: &Oid, data: &[u8]) -> Option<Vec<u8>> {
+    match *alg {
+        ID_SHA256 => Some(Sha256::digest(data).to_vec()),
+        ID_SHA384 => Some(Sha384::digest(data).to_vec()),
+        ID_SHA512 => Some(Sha512::digest(data).to_vec()),
+        _ => None,
+    }
+}
+
+fn verify_signature(spki: &SubjectPublicKeyInfoOwned, sig_alg: &Oid, digest_alg: Option<&Oid>, msg: &[u8], sig: &[u8]) -> Result<(), TimestampError> {
+    let key_bytes = spki.subject_public_key.raw_bytes();
+    match spki.algorithm.oid {
+        ID_RSA_ENCRYPTION => {
+            use rsa::{pkcs1::DecodeRsaPublicKey, Pkcs1v15Sign, RsaPublicKey};
+            let key = RsaPublicKey::from_pkcs1_der(key_bytes).map_err(|e| fail(&e))?;
+            let (scheme, digest_oid) = match (*sig_alg, digest_alg) {
+                (ID_SHA256_WITH_RSA, _) => (Pkcs1v15Sign::new::<Sha256>(), ID_SHA256),
+                (ID_SHA384_WITH_RSA, _) => (Pkcs1v15Sign::new::<Sha384>(), ID_SHA384),
+                (ID_SHA512_WITH_RSA, _) => (Pkcs1v15Sign::new::<Sha512>(), ID_SHA512),
+                (ID_RSA_ENCRYPTION, Some(&ID_SHA256)) => (Pkcs1v15Sign::new::<Sha256>(), ID_SHA256),
+                (ID_RSA_ENCRYPTION, Some(&ID_SHA384)) => (Pkcs1v15Sign::new::<Sha384>(), ID_SHA384),
+                (ID_RSA_ENCRYPTION, Some(&ID_SHA512)) => (Pkcs1v15Sign::new::<Sha512>(), ID_SHA512),
+                _ => return Err(fail(&format!("unsupported RSA signature algorithm {}", sig_alg))),
+            };
+            let hashed = hash(&digest_oid, msg).expect("supported digest");
+            key.verify(scheme, &hashed, sig).map_err(|_| fail(&"bad signature"))
+        }
+        ID_EC_PUBLIC_KEY if *sig_alg == ID_ECDSA_WITH_SHA256 => {
+            use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+            let key = VerifyingKey::from_sec1_bytes(key_bytes).map_err(|_| fail(&"unsupported EC key (P-256 only)"))?;
+            let sig = Signature::from_der(sig).map_err(|e| fail(&e))?;
+            key.verify(msg, &sig).map_err(|_| fail(&"bad signature"))
+        }
+        _ => Err(fail(&format!("unsupported signature algorithm {}", sig_alg))),
+    }
+}
+
+fn extension<T: for<'a> Decode<'a>>(cert: &Certificate, oid: Oid) -> Result<Option<T>, TimestampError> {
+    let ext = cert.tbs_certificate.extensions.iter().flatten().find(|e| e.extn_id == oid);
+    ext.map(|e| T::from_der(e.extn_value.as_bytes()).map_err(|e| fail(&e))).transpose()
+}
+
+fn valid_at(cert: &Certificate, t: u64) -> bool {
+    let v = &cert.tbs_certificate.validity;
+    v.not_before.to_unix_duration().as_secs() <= t && t <= v.not_after.to_unix_duration().as_secs()
+}
+
+fn verify_chain(leaf: &Certificate, pool: &[&Certificate], trust: &TimestampTrust, at: u64) -> Result<(), TimestampError> {
+    let mut cert = leaf;
+    for _ in 0..MAX_CHAIN {
+        if !valid_at(cert, at) { return Err(fail(&format!("certificate '{}' not valid at the stamped time", cert.tbs_certificate.subject))); }
+        if trust.anchors.contains(cert) { return Ok(()); }
+        let signed_by = |issuer: &Certificate| {
+            issuer.tbs_certificate.subject == cert.tbs_certificate.issuer
+                && cert.tbs_certificate.to_der().is_ok_and(|tbs| {
+                    verify_signature(&issuer.tbs_certificate.subject_public_key_info, &cert.signature_algorithm.oid, None, &tbs, cert.signature.raw_bytes()).is_ok()
+                })
+        };
+        if trust.anchors.iter().any(|a| signed_by(a) && valid_at(a, at)) { return Ok(()); }
+        let issuer = pool.iter().copied().find(|c| !std::ptr::eq(*c, cert) && signed_by(c)).ok_or_else(|| fail(&"certificate chain does not reach a trust anchor"))?;
+        let is_ca = extension::<BasicConstraints>(issuer, ID_CE_BASIC_CONSTRAINTS)?.is_some_and(|bc| bc.ca);
+        if !is_ca { return Err(fail(&"intermediate certificate is not a CA")); }
+        cert = issuer;
+    }
+    Err(fail(&"certificate chain too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipts::Receipt;
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    // `openssl ts -reply` over the imprint of the receipt below, from a P-256 TSA under ROOT.
+    const TOKEN: &str = concat!(
+        "MIIDhwYJKoZIhvcNAQcCoIIDeDCCA3QCAQMxDzANBglghkgBZQMEAgEFADBoBgsqhkiG9w0BCRABBKBZBFcwVQIBAQYEKgMEATAx",
+        "MA0GCWCGSAFlAwQCAQUABCCpz6l871vPmP5OL5+G395JB+F7OnI+zfTccpsPgfBX1wIBAxgPMjAyNjEwMTYxMDM2NDVaMAMCAQGg",
+        "ggGrMIIBpzCCAU6gAwIBAgIUIQVD1VqDeL3pGYRguZ3LGPmVbiwwCgYIKoZIzj0EAwIwGDEWMBQGA1UEAwwNdWJsIHRlc3Qgcm9v",
+        "dDAgFw0yNjEwMTYxMDM2MDZaGA8yMTI2MDkyMjEwMzYwNlowFzEVMBMGA1UEAwwMdWJsIHRlc3QgdHNhMFkwEwYHKoZIzj0CAQYI",
+        "KoZIzj0DAQcDQgAEcT259KpX18vpUyiDaWBGIU3UaXeKgkhZLV6gG80eyE632F4EqyHr5rVsMRhvv3qlWjDOq5lmtF5IDOJOZmoK",
+        "y6N1MHMwFgYDVR0lAQH/BAwwCgYIKwYBBQUHAwgwCQYDVR0TBAIwADAOBgNVHQ8BAf8EBAMCB4AwHQYDVR0OBBYEFAyNPtB+TWJO",
+        "KNunkwbGZlI7OfldMB8GA1UdIwQYMBaAFMzGkfk4iB9QRCVcsKAf1+B9cYxvMAoGCCqGSM49BAMCA0cAMEQCIHM4cvuzL3au2VnE",
+        "8Bp75pNuZ1yT8TDW6AoiSVRuV6aAAiAIFXCDUOEDi4/cjk4bp6nvU7uRR7B3O/odgctHUvazIDGCAUMwggE/AgEBMDAwGDEWMBQG",
+        "A1UEAwwNdWJsIHRlc3Qgcm9vdAIUIQVD1VqDeL3pGYRguZ3LGPmVbiwwDQYJYIZIAWUDBAIBBQCggaQwGgYJKoZIhvcNAQkDMQ0G",
+        "CyqGSIb3DQEJEAEEMBwGCSqGSIb3DQEJBTEPFw0yNjEwMTYxMDM2NDVaMC8GCSqGSIb3DQEJBDEiBCAa2ZeDX5tTYYI7aBg2Pz6s",
+        "kWOQxT3X5PM1nSBcZ3eFOTA3BgsqhkiG9w0BCRACLzEoMCYwJDAiBCAXw479mI8Rlgwf67/PYQKGXmXeUHS8QMZPxOJKmYFCPDAK",
+        "BggqhkjOPQQDAgRGMEQCIAFhohwMqF/XYR4+lwkUf8/crW++UuhHmfSZ8H7/OywaAiB0F6JXEij8mPy2DIp0bagADkWRYwjBw2c6",
+        "lRBsm/mgoA==",
+    );
+    const ROOT: &str = "-----BEGIN CERTIFICATE-----
+MIIBlzCCAT2gAwIBAgIUcubKJK68+VZXP2haBgYESWh9USswCgYIKoZIzj0EAwIw
+GDEWMBQGA1UEAwwNdWJsIHRlc3Qgcm9vdDAgFw0yNjEwMTYxMDM2MDZaGA8yMTI2
+MDkyMjEwMzYwNlowGDEWMBQGA1UEAwwNdWJsIHRlc3Qgcm9vdDBZMBMGByqGSM49
+AgEGCCqGSM49AwEHA0IABBvklCJ2OWKniZE8hBT/rHTooOxw+UopYXOjCIotkxfK
+o+7grGZk6F8DNItmL4+KLV7wBRSdtaS31pUGZ8nYCVWjYzBhMB0GA1UdDgQWBBTM
+xpH5OIgfUEQlXLCgH9fgfXGMbzAfBgNVHSMEGDAWgBTMxpH5OIgfUEQlXLCgH9fg
+fXGMbzAPBgNVHRMBAf8EBTADAQH/MA4GA1UdDwEB/wQEAwICBDAKBggqhkjOPQQD
+AgNIADBFAiByIvj1Vif82HFHL3knMzge0PMSXJMeqYQ1MDHV/Y6FPwIhALjqweoP
++jMNjGll/KmHV6jXrDIkM42y4hg+emeGmcAd
+-----END CERTIFICATE-----";
+
+    #[test]
+    fn stamped_receipt_verifies_against_root() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(29));
+        let mut receipt = Receipt::sign(&json!({"span":"s-9","op":"close"}), &sk, "ledger-1").unwrap();
+        receipt.timestamp = Some(TOKEN.to_string());
+        let trust = TimestampTrust::new().with_pem(ROOT).unwrap();
+
+        let info = receipt.verify_timestamp(&trust).unwrap();
+        assert_eq!((info.policy.as_str(), info.tsa.as_str()), ("1.2.3.4.1", "CN=ubl test tsa"));
+        assert!(info.gen_time > 1_700_000_000);
+
+        let token = STANDARD.decode(TOKEN).unwrap();
+        assert!(matches!(verify_token(&token, &receipt.imprint().unwrap(), &TimestampTrust::new()), Err(TimestampError::Invalid(_))));
+        receipt.record["op"] = json!("reopen");
+        assert!(matches!(receipt.verify_timestamp(&trust), Err(crate::VerifyError::Timestamp(TimestampError::Invalid(_)))));
+    }
+}
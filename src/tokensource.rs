@@ -0,0 +1,202 @@
+//! Outgoing tokens for service-to-service calls.
+//!
+//! A [`TokenSource`] produces a token (usually `move || issuer.mint(&req)`, or a call to a remote
+//! token endpoint); [`CachedToken`] hands out the same token until it is within
+//! `refresh_before_secs` of its `exp`, then fetches a new one. [`GrpcAuth`] (feature `tonic`)
+//! attaches it to every outgoing gRPC request as `authorization: Bearer ...` metadata, and
+//! [`BearerAuth`] (feature `reqwest-middleware`) does the same for HTTP clients.
+
+use crate::{now_ts, SignError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use parking_lot::Mutex;
+use serde_json::Value as Json;
+
+pub trait TokenSource: Send + Sync {
+    fn fetch(&self) -> Result<String, SignError>;
+}
+
+impl<F: Fn() -> Result<String, SignError> + Send + Sync> TokenSource for F {
+    fn fetch(&self) -> Result<String, SignError> { self() }
+}
+
+#[derive(Debug)]
+struct Current { token: String, exp: Option<i64> }
+
+pub struct CachedToken<S: TokenSource> {
+    source: S,
+    pub refresh_before_secs: i64,
+    current: Mutex<Option<Current>>,
+}
+
+impl<S: TokenSource> std::fmt::Debug for CachedToken<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedToken").field("refresh_before_secs", &self.refresh_before_secs).finish_non_exhaustive()
+    }
+}
+
+impl<S: TokenSource> CachedToken<S> {
+    /// Refreshes 30 seconds before expiry unless changed.
+    pub fn new(source: S) -> Self { Self { source, refresh_before_secs: 30, current: Mutex::new(None) } }
+    pub fn with_refresh_before(mut self, secs: i64) -> Self { self.refresh_before_secs = secs; self }
+
+    /// The cached token, fetching a new one if there is none or it is about to expire. Tokens
+    /// without a readable `exp` are kept until [`CachedToken::invalidate`].
+    pub fn token(&self) -> Result<String, SignError> {
+        let mut current = self.current.lock();
+        if let Some(c) = current.as_ref().filter(|c| c.exp.is_none_or(|exp| exp - now_ts() > self.refresh_before_secs)) {
+            return Ok(c.token.clone());
+        }
+        let token = self.source.fetch()?;
+        *current = Some(Current { exp: unverified_exp(&token), token: token.clone() });
+        Ok(token)
+    }
+
+    /// Drops the cached token, e.g. after the peer rejected it.
+    pub fn invalidate(&self) { *self.current.lock() = None; }
+}
+
+fn unverified_exp(token: &str) -> Option<i64> {
+    let payload = B64URL.decode(token.split('.').nth(1)?).ok()?;
+    serde_json::from_slice::<Json>(&payload).ok()?.get("exp")?.as_i64()
+}
+
+/// Tonic client interceptor: `Client::with_interceptor(channel, GrpcAuth::new(tokens.clone()))`.
+#[cfg(feature = "tonic")]
+pub struct GrpcAuth<S: TokenSource> { tokens: std::sync::Arc<CachedToken<S>> }
+
+#[cfg(feature = "tonic")]
+impl<S: TokenSource> Clone for GrpcAuth<S> {
+    fn clone(&self) -> Self { Self { tokens: self.tokens.clone() } }
+}
+
+#[cfg(feature = "tonic")]
+impl<S: TokenSource> GrpcAuth<S> {
+    pub fn new(tokens: std::sync::Arc<CachedToken<S>>) -> Self { Self { tokens } }
+}
+
+#[cfg(feature = "tonic")]
+impl<S: TokenSource> tonic::service::Interceptor for GrpcAuth<S> {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let token = self.tokens.token().map_err(|e| tonic::Status::unauthenticated(e.to_string()))?;
+        let value = format!("Bearer {}", token).parse().map_err(|_| tonic::Status::internal("token is not valid metadata"))?;
+        req.metadata_mut().insert("authorization", value);
+        Ok(req)
+    }
+}
+
+/// Builds a DPoP proof for `(method, url, access_token)`.
+#[cfg(feature = "reqwest-middleware")]
+type ProofFn = std::sync::Arc<dyn Fn(&str, &str, &str) -> Result<String, SignError> + Send + Sync>;
+
+/// `reqwest-middleware` layer: `ClientBuilder::new(client).with(BearerAuth::new(tokens)).build()`.
+/// Sends `authorization: Bearer ...` (or `DPoP ...` plus a `DPoP` proof header when
+/// [`BearerAuth::with_dpop`] is set); a `401` answer invalidates the cached token and the request
+/// is retried once with a fresh one. Requests with streaming bodies cannot be cloned and are not
+/// retried. Token fetches run inline, so a [`TokenSource`] that blocks blocks the task.
+#[cfg(feature = "reqwest-middleware")]
+pub struct BearerAuth<S: TokenSource> { tokens: std::sync::Arc<CachedToken<S>>, dpop: Option<ProofFn> }
+
+#[cfg(feature = "reqwest-middleware")]
+impl<S: TokenSource> BearerAuth<S> {
+    pub fn new(tokens: std::sync::Arc<CachedToken<S>>) -> Self { Self { tokens, dpop: None } }
+    pub fn with_dpop<F: Fn(&str, &str, &str) -> Result<String, SignError> + Send + Sync + 'static>(mut self, proof: F) -> Self { self.dpop = Some(std::sync::Arc::new(proof)); self }
+
+    fn authorize(&self, mut req: reqwest::Request) -> reqwest_middleware::Result<reqwest::Request> {
+        use reqwest::header::{HeaderValue, AUTHORIZATION};
+        let token = self.tokens.token().map_err(reqwest_middleware::Error::middleware)?;
+        let scheme = if self.dpop.is_some() { "DPoP" } else { "Bearer" };
+        let value = HeaderValue::from_str(&format!("{} {}", scheme, token)).map_err(reqwest_middleware::Error::middleware)?;
+        req.headers_mut().insert(AUTHORIZATION, value);
+        if let Some(proof) = &self.dpop {
+            let proof = proof(req.method().as_str(), req.url().as_str(), &token).map_err(reqwest_middleware::Error::middleware)?;
+            req.headers_mut().insert("dpop", HeaderValue::from_str(&proof).map_err(reqwest_middleware::Error::middleware)?);
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(feature = "reqwest-middleware")]
+#[async_trait::async_trait]
+impl<S: TokenSource + 'static> reqwest_middleware::Middleware for BearerAuth<S> {
+    async fn handle(&self, req: reqwest::Request, extensions: &mut http::Extensions, next: reqwest_middleware::Next<'_>) -> reqwest_middleware::Result<reqwest::Response> {
+        let retry = req.try_clone();
+        let res = next.clone().run(self.authorize(req)?, extensions).await?;
+        match retry {
+            Some(req) if res.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                self.tokens.invalidate();
+                next.run(self.authorize(req)?, extensions).await
+            }
+            _ => Ok(res),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issuer::{TokenIssuer, TokenRequest};
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn tokens_are_reused_until_close_to_expiry() {
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(44)));
+        let minted = AtomicUsize::new(0);
+        let source = |ttl: i64| {
+            let (issuer, minted) = (issuer.clone(), &minted);
+            move || {
+                minted.fetch_add(1, Ordering::SeqCst);
+                issuer.mint(&TokenRequest { sub: "svc-orders".into(), aud: None, scope: None, ttl_secs: Some(ttl), claims: Default::default() })
+            }
+        };
+
+        let long = CachedToken::new(source(600));
+        assert_eq!(long.token().unwrap(), long.token().unwrap());
+        assert_eq!(minted.load(Ordering::SeqCst), 1);
+        long.invalidate();
+        long.token().unwrap();
+        assert_eq!(minted.load(Ordering::SeqCst), 2);
+
+        let short = CachedToken::new(source(10));
+        short.token().unwrap();
+        short.token().unwrap();
+        assert_eq!(minted.load(Ordering::SeqCst), 4);
+
+        #[cfg(feature = "tonic")]
+        {
+            use tonic::service::Interceptor;
+            let mut auth = GrpcAuth::new(std::sync::Arc::new(long));
+            let req = auth.call(tonic::Request::new(())).unwrap();
+            assert!(req.metadata().get("authorization").unwrap().to_str().unwrap().starts_with("Bearer ey"));
+        }
+    }
+
+    #[cfg(feature = "reqwest-middleware")]
+    #[test]
+    fn unauthorized_responses_are_retried_with_a_fresh_token() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/orders", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            ["401 Unauthorized", "200 OK"].map(|status| {
+                let (mut s, _) = listener.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let n = s.read(&mut buf).unwrap();
+                let _ = write!(s, "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", status);
+                let req = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                req.lines().find_map(|l| l.strip_prefix("authorization: ").map(str::to_string)).unwrap()
+            })
+        });
+
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", SigningKey::generate(&mut StdRng::seed_from_u64(45)));
+        let tokens = std::sync::Arc::new(CachedToken::new(move || issuer.mint(&TokenRequest { sub: "svc-orders".into(), aud: None, scope: None, ttl_secs: Some(600), claims: Default::default() })));
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).with(BearerAuth::new(tokens)).build();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let status = rt.block_on(async { client.get(&url).send().await.unwrap().status() });
+        assert_eq!(status, reqwest::StatusCode::OK);
+        let [first, second] = server.join().unwrap();
+        assert!(first.starts_with("bearer ") && second.starts_with("bearer "));
+        assert_ne!(first, second);
+    }
+}
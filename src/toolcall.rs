@@ -0,0 +1,129 @@
+//! Signed agent-to-agent tool-call envelopes.
+//!
+//! The calling agent signs `{tool, args_sha256, caller_jti, agent, kid, iat, nonce}` as canonical
+//! JSON; the receiving service checks the signature, that the arguments it received hash to
+//! `args_sha256`, the envelope age, and that the nonce was not seen before.
+
+use crate::{check_timestamp, fill_random, now_ts, resolve_key, to_hex, JwksCache, SignError, VerifyError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCallEnvelope {
+    pub tool: String,
+    /// Hex SHA-256 of the canonical JSON arguments.
+    pub args_sha256: String,
+    /// `jti` of the token the calling agent acts under, if any.
+    #[serde(default)]
+    pub caller_jti: Option<String>,
+    /// The calling agent's identifier (typically its DID).
+    pub agent: String,
+    pub kid: String,
+    pub iat: i64,
+    pub nonce: String,
+    /// Base64url Ed25519 signature over the canonical envelope without `sig`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sig: String,
+}
+
+/// Hex SHA-256 over the canonical JSON form of `args`.
+pub fn args_hash(args: &Json) -> Result<String, SignError> {
+    let bytes = json_atomic::canonize(args).map_err(|e| SignError::Canonical(e.to_string()))?;
+    Ok(to_hex(&Sha256::digest(AsRef::<[u8]>::as_ref(&bytes))))
+}
+
+impl ToolCallEnvelope {
+    /// Builds and signs an envelope for invoking `tool` with `args`.
+    pub fn sign(tool: &str, args: &Json, agent: &str, caller_jti: Option<&str>, key: &SigningKey, kid: &str) -> Result<Self, SignError> {
+        let mut nonce = [0u8; 16];
+        fill_random(&mut nonce);
+        let mut env = ToolCallEnvelope {
+            tool: tool.to_string(),
+            args_sha256: args_hash(args)?,
+            caller_jti: caller_jti.map(str::to_string),
+            agent: agent.to_string(),
+            kid: kid.to_string(),
+            iat: now_ts(),
+            nonce: B64URL.encode(nonce),
+            sig: String::new(),
+        };
+        env.sig = B64URL.encode(key.sign(&env.signing_bytes()?).to_bytes());
+        Ok(env)
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>, SignError> {
+        let unsigned = ToolCallEnvelope { sig: String::new(), ..self.clone() };
+        let bytes = json_atomic::canonize(&unsigned).map_err(|e| SignError::Canonical(e.to_string()))?;
+        Ok(AsRef::<[u8]>::as_ref(&bytes).to_vec())
+    }
+}
+
+/// Receiving side: checks envelopes and remembers nonces for the acceptance window.
+#[derive(Debug)]
+pub struct ToolCallVerifier {
+    pub max_age_secs: i64,
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl Default for ToolCallVerifier {
+    fn default() -> Self { Self::new(60) }
+}
+
+impl ToolCallVerifier {
+    pub fn new(max_age_secs: i64) -> Self { Self { max_age_secs, seen: Mutex::new(HashMap::new()) } }
+
+    /// Verifies against the agent keys published at `jwks_uri`.
+    pub fn verify(&self, env: &ToolCallEnvelope, args: &Json, jwks_uri: &str, cache: &JwksCache) -> Result<(), VerifyError> {
+        self.verify_with(env, args, |kid| resolve_key(jwks_uri, cache, kid))
+    }
+
+    pub fn verify_with<F>(&self, env: &ToolCallEnvelope, args: &Json, key_for: F) -> Result<(), VerifyError>
+    where
+        F: FnOnce(&str) -> Result<VerifyingKey, VerifyError>,
+    {
+        let now = now_ts();
+        check_timestamp(env.iat, now, self.max_age_secs)?;
+
+        let sig_bytes = B64URL.decode(env.sig.as_bytes()).map_err(|_| VerifyError::Base64)?;
+        let sig = Signature::from_bytes(sig_bytes[..].try_into().map_err(|_| VerifyError::Signature)?);
+        let msg = env.signing_bytes().map_err(|_| VerifyError::Json)?;
+        key_for(&env.kid)?.verify_strict(&msg, &sig).map_err(|_| VerifyError::Signature)?;
+        if args_hash(args).map_err(|_| VerifyError::Json)? != env.args_sha256 { return Err(VerifyError::ArgsMismatch); }
+
+        let mut seen = self.seen.lock();
+        seen.retain(|_, iat| now - *iat <= self.max_age_secs);
+        if seen.insert(env.nonce.clone(), env.iat).is_some() { return Err(VerifyError::Replayed); }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn sign_verify_and_reject_replay() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(11));
+        let vk = sk.verifying_key();
+        let args = json!({"account":"acme","amount":42});
+        let env = ToolCallEnvelope::sign("ledger.append", &args, "did:key:zAgent", Some("jti-1"), &sk, "agent-1").unwrap();
+
+        let verifier = ToolCallVerifier::new(60);
+        assert!(verifier.verify_with(&env, &json!({"amount":42,"account":"acme"}), |_| Ok(vk)).is_ok());
+        assert!(matches!(verifier.verify_with(&env, &args, |_| Ok(vk)), Err(VerifyError::Replayed)));
+
+        let other = ToolCallEnvelope::sign("ledger.append", &args, "did:key:zAgent", None, &sk, "agent-1").unwrap();
+        assert!(matches!(verifier.verify_with(&other, &json!({"amount":43}), |_| Ok(vk)), Err(VerifyError::ArgsMismatch)));
+        let forged = ToolCallEnvelope { tool: "ledger.delete".into(), ..other };
+        assert!(matches!(verifier.verify_with(&forged, &args, |_| Ok(vk)), Err(VerifyError::Signature)));
+        let ancient = ToolCallEnvelope { iat: i64::MIN, ..forged };
+        assert!(matches!(verifier.verify_with(&ancient, &args, |_| Ok(vk)), Err(VerifyError::Expired)));
+    }
+}
@@ -0,0 +1,157 @@
+//! Anchoring issued tokens in a transparency log.
+//!
+//! The issuer appends the hash of each token's canonical claims (without the anchor itself) to
+//! an append-only Merkle log (RFC 9162 hashing) and embeds the result as the `tlog` claim: log
+//! id, leaf index, tree size and the inclusion proof. Anyone holding a trusted root for that tree
+//! size can later check that the token was logged, so a key compromise cannot mint tokens
+//! silently. [`MemoryMerkleLog`] is an in-process log; other logs implement [`TransparencyLog`].
+
+use crate::{Claims, SignError, VerifyError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const CLAIM: &str = "tlog";
+
+pub type Hash = [u8; 32];
+
+/// The `tlog` claim.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inclusion {
+    pub log_id: String,
+    pub index: u64,
+    pub tree_size: u64,
+    /// Base64url audit path, leaf to root.
+    pub proof: Vec<String>,
+}
+
+pub trait TransparencyLog: Send + Sync {
+    fn log_id(&self) -> String;
+    /// Appends a leaf hash, returning its inclusion proof in the tree that ends with it.
+    fn append(&self, leaf: Hash) -> Result<Inclusion, SignError>;
+    /// Trusted root hash at `tree_size` (e.g. from a verified signed tree head).
+    fn root(&self, tree_size: u64) -> Option<Hash>;
+}
+
+#[derive(Debug)]
+pub struct MemoryMerkleLog { id: String, leaves: Mutex<Vec<Hash>> }
+
+impl MemoryMerkleLog {
+    pub fn new(log_id: &str) -> Self { Self { id: log_id.to_string(), leaves: Mutex::new(Vec::new()) } }
+}
+
+impl TransparencyLog for MemoryMerkleLog {
+    fn log_id(&self) -> String { self.id.clone() }
+    fn append(&self, leaf: Hash) -> Result<Inclusion, SignError> {
+        let mut leaves = self.leaves.lock();
+        leaves.push(leaf);
+        let index = leaves.len() - 1;
+        let proof = audit_path(index, &leaves).iter().map(|h| B64URL.encode(h)).collect();
+        Ok(Inclusion { log_id: self.id.clone(), index: index as u64, tree_size: leaves.len() as u64, proof })
+    }
+    fn root(&self, tree_size: u64) -> Option<Hash> {
+        let leaves = self.leaves.lock();
+        let size = usize::try_from(tree_size).ok().filter(|s| (1..=leaves.len()).contains(s))?;
+        Some(tree_hash(&leaves[..size]))
+    }
+}
+
+/// Leaf hash of `claims`: canonical JSON without the `tlog` claim and without nulls.
+pub fn leaf_hash(claims: &Claims) -> Result<Hash, SignError> {
+    let mut map = claims.to_map();
+    map.remove(CLAIM);
+    let bytes = json_atomic::canonize(&map).map_err(|e| SignError::Canonical(e.to_string()))?;
+    Ok(leaf(AsRef::<[u8]>::as_ref(&bytes)))
+}
+
+/// Logs `claims` and adds the resulting `tlog` claim.
+pub fn anchor(claims: &mut Claims, log: &dyn TransparencyLog) -> Result<Inclusion, SignError> {
+    let inclusion = log.append(leaf_hash(claims)?)?;
+    claims.extra.insert(CLAIM.into(), serde_json::to_value(&inclusion).map_err(|e| SignError::Canonical(e.to_string()))?);
+    Ok(inclusion)
+}
+
+/// Checks the `tlog` claim of verified `claims` against the trusted root of `log`.
+pub fn verify_anchor(claims: &Claims, log: &dyn TransparencyLog) -> Result<Inclusion, VerifyError> {
+    let fail = |why: &str| VerifyError::Transparency(why.to_string());
+    let inclusion: Inclusion = serde_json::from_value(claims.extra.get(CLAIM).cloned().ok_or(VerifyError::MissingClaim(CLAIM))?).map_err(|_| fail("malformed tlog claim"))?;
+    if inclusion.log_id != log.log_id() { return Err(fail("unknown log")); }
+    let root = log.root(inclusion.tree_size).ok_or_else(|| fail("no trusted root for tree size"))?;
+    let proof = inclusion.proof.iter().map(|p| B64URL.decode(p).ok().and_then(|b| b.try_into().ok())).collect::<Option<Vec<Hash>>>().ok_or_else(|| fail("malformed proof"))?;
+    let leaf = leaf_hash(claims).map_err(|e| fail(&e.to_string()))?;
+    if !verify_inclusion(leaf, inclusion.index, inclusion.tree_size, &proof, root) { return Err(fail("inclusion proof does not match root")); }
+    Ok(inclusion)
+}
+
+/// RFC 9162 §2.1.3.2 inclusion proof verification.
+pub fn verify_inclusion(leaf: Hash, index: u64, tree_size: u64, proof: &[Hash], root: Hash) -> bool {
+    if index >= tree_size { return false; }
+    let (mut fnode, mut snode, mut r) = (index, tree_size - 1, leaf);
+    for p in proof {
+        if snode == 0 { return false; }
+        if fnode & 1 == 1 || fnode == snode {
+            r = node(p, &r);
+            while fnode & 1 == 0 && fnode != 0 { fnode >>= 1; snode >>= 1; }
+        } else {
+            r = node(&r, p);
+        }
+        fnode >>= 1;
+        snode >>= 1;
+    }
+    snode == 0 && r == root
+}
+
+/// RFC 9162 leaf hash of `data`.
+pub(crate) fn leaf(data: &[u8]) -> Hash { Sha256::new().chain_update([0u8]).chain_update(data).finalize().into() }
+
+fn node(left: &Hash, right: &Hash) -> Hash { Sha256::new().chain_update([1u8]).chain_update(left).chain_update(right).finalize().into() }
+
+/// Largest power of two below `n` (n >= 2).
+fn split_point(n: usize) -> usize { 1 << (usize::BITS - 1 - (n - 1).leading_zeros()) }
+
+/// RFC 9162 Merkle tree hash over leaf hashes.
+pub(crate) fn tree_hash(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node(&tree_hash(&leaves[..k]), &tree_hash(&leaves[k..]))
+        }
+    }
+}
+
+pub(crate) fn audit_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    if leaves.len() <= 1 { return Vec::new(); }
+    let k = split_point(leaves.len());
+    let (mut path, sibling) = if m < k { (audit_path(m, &leaves[..k]), tree_hash(&leaves[k..])) } else { (audit_path(m - k, &leaves[k..]), tree_hash(&leaves[..k])) };
+    path.push(sibling);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn anchored_claims_verify_and_tampering_is_detected() {
+        let log = MemoryMerkleLog::new("internal-1");
+        let mut anchored = Vec::new();
+        for i in 0..7 {
+            let mut claims: Claims = serde_json::from_value(json!({"sub": format!("did:key:z{}", i), "jti": i.to_string()})).unwrap();
+            anchor(&mut claims, &log).unwrap();
+            anchored.push(claims);
+        }
+        for claims in &anchored {
+            let inclusion = verify_anchor(claims, &log).unwrap();
+            assert_eq!(inclusion.log_id, "internal-1");
+        }
+
+        let mut forged = anchored[3].clone();
+        forged.sub = "did:key:zMallory".into();
+        assert!(matches!(verify_anchor(&forged, &log), Err(VerifyError::Transparency(_))));
+        assert!(matches!(verify_anchor(&anchored[2], &MemoryMerkleLog::new("other")), Err(VerifyError::Transparency(_))));
+    }
+}
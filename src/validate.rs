@@ -0,0 +1,124 @@
+//! Declarative validation of application claim structs.
+//!
+//! Implement [`VerifiableClaims`] by hand, or with the `derive` feature derive it:
+//!
+//! ```ignore
+//! #[derive(serde::Deserialize, VerifiableClaims)]
+//! struct AppClaims {
+//!     sub: String,
+//!     #[claim(required, max_age = "1h")]
+//!     auth_time: Option<i64>,
+//!     #[claim(one_of("acme", "globex"))]
+//!     tenant: String,
+//! }
+//! let claims: AppClaims = verify_into(&token, jwks_uri, &cache, &opts)?;
+//! ```
+
+use crate::{now_ts, verify_ed25519_jwt_with_cache, JwksCache, VerifyError, VerifyOptions};
+use serde::de::DeserializeOwned;
+use serde_json::Value as Json;
+
+#[cfg(feature = "derive")]
+pub use ubl_auth_derive::VerifiableClaims;
+
+/// Application-level claim rules, run after signature and registered-claim validation.
+pub trait VerifiableClaims {
+    fn validate_claims(&self, now: i64) -> Result<(), VerifyError>;
+}
+
+/// Verifies `token` like [`verify_ed25519_jwt_with_cache`], deserializes the payload into `T`
+/// and runs its [`VerifiableClaims`] rules.
+pub fn verify_into<T: DeserializeOwned + VerifiableClaims>(token: &str, jwks_uri: &str, cache: &JwksCache, opts: &VerifyOptions) -> Result<T, VerifyError> {
+    let claims = verify_ed25519_jwt_with_cache(token, jwks_uri, cache, opts)?;
+    let typed: T = serde_json::from_value(Json::Object(claims.to_map())).map_err(|_| VerifyError::Json)?;
+    typed.validate_claims(opts.now.unwrap_or_else(now_ts))?;
+    Ok(typed)
+}
+
+/// Field shapes the derived checks understand; absent optional values skip every rule but `required`.
+#[doc(hidden)]
+pub trait ClaimField {
+    fn is_present(&self) -> bool { true }
+    fn timestamp(&self) -> Option<i64> { None }
+    fn text(&self) -> Option<&str> { None }
+}
+
+impl ClaimField for String {
+    fn text(&self) -> Option<&str> { Some(self) }
+}
+
+impl ClaimField for i64 {
+    fn timestamp(&self) -> Option<i64> { Some(*self) }
+}
+
+impl ClaimField for Json {
+    fn is_present(&self) -> bool { !self.is_null() }
+    fn timestamp(&self) -> Option<i64> { self.as_i64() }
+    fn text(&self) -> Option<&str> { self.as_str() }
+}
+
+impl<T> ClaimField for Vec<T> {}
+
+impl<T: ClaimField> ClaimField for Option<T> {
+    fn is_present(&self) -> bool { self.as_ref().is_some_and(T::is_present) }
+    fn timestamp(&self) -> Option<i64> { self.as_ref()?.timestamp() }
+    fn text(&self) -> Option<&str> { self.as_ref()?.text() }
+}
+
+#[doc(hidden)]
+pub fn check_required<F: ClaimField>(field: &F, name: &'static str) -> Result<(), VerifyError> {
+    if field.is_present() { Ok(()) } else { Err(VerifyError::MissingClaim(name)) }
+}
+
+#[doc(hidden)]
+pub fn check_max_age<F: ClaimField>(field: &F, name: &str, max_secs: i64, now: i64) -> Result<(), VerifyError> {
+    match field.timestamp().map(|ts| now.saturating_sub(ts)) {
+        Some(age) if age > max_secs => Err(VerifyError::Policy(format!("claim '{}' older than {}s", name, max_secs))),
+        Some(age) if age < 0 => Err(VerifyError::Policy(format!("claim '{}' is in the future", name))),
+        _ => Ok(()),
+    }
+}
+
+#[doc(hidden)]
+pub fn check_one_of<F: ClaimField>(field: &F, name: &str, allowed: &[&str]) -> Result<(), VerifyError> {
+    match field.text() {
+        Some(v) if !allowed.contains(&v) => Err(VerifyError::Policy(format!("claim '{}' not one of {:?}", name, allowed))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::{sign_compact, Jwk, Jwks};
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, VerifiableClaims)]
+    struct AppClaims {
+        #[allow(dead_code)]
+        sub: String,
+        #[claim(required, max_age = "1h")]
+        auth_time: Option<i64>,
+        #[claim(one_of("acme", "globex"))]
+        tenant: String,
+    }
+
+    #[test]
+    fn derived_rules_run_after_verification() {
+        let sk = SigningKey::generate(&mut StdRng::seed_from_u64(25));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://jwks", Jwks { keys: vec![Jwk::ed25519("k", &sk.verifying_key())] });
+        let verify = |payload: Json| verify_into::<AppClaims>(&sign_compact(&payload, &sk, "k").unwrap(), "mem://jwks", &cache, &VerifyOptions::default());
+
+        let ok = verify(json!({"sub":"did:key:zU","auth_time": now_ts() - 60,"tenant":"acme"})).unwrap();
+        assert_eq!(ok.tenant, "acme");
+        assert!(matches!(verify(json!({"sub":"did:key:zU","tenant":"acme"})), Err(VerifyError::MissingClaim("auth_time"))));
+        assert!(matches!(verify(json!({"sub":"did:key:zU","auth_time": now_ts() - 7200,"tenant":"acme"})), Err(VerifyError::Policy(_))));
+        assert!(matches!(verify(json!({"sub":"did:key:zU","auth_time": i64::MIN,"tenant":"acme"})), Err(VerifyError::Policy(_))));
+        assert!(matches!(verify(json!({"sub":"did:key:zU","auth_time": now_ts() + 3600,"tenant":"acme"})), Err(VerifyError::Policy(_))));
+        assert!(matches!(verify(json!({"sub":"did:key:zU","auth_time": now_ts(),"tenant":"initech"})), Err(VerifyError::Policy(_))));
+    }
+}
@@ -0,0 +1,261 @@
+//! A configured verifier that owns its options and JWKS cache.
+//!
+//! ```no_run
+//! let verifier = ubl_auth::Verifier::builder()
+//!     .jwks_uri("https://id.ubl.agency/.well-known/jwks.json")
+//!     .issuer("https://id.ubl.agency")
+//!     .audience("api://orders")
+//!     .leeway(60)
+//!     .build();
+//! let claims = verifier.verify("eyJ...")?;
+//! # Ok::<(), ubl_auth::VerifyError>(())
+//! ```
+//!
+//! [`MultiIssuerVerifier`] holds one such verifier per accepted identity provider and picks it by
+//! the token's `iss`.
+
+use crate::discovery::{self, ProviderMetadata};
+use crate::keyprovider::KeyProvider;
+use crate::replay::{self, ReplayStore};
+use crate::{decode_unverified, typed_claims, verified_claims, verify_signature, verify_signature_with, Claims, Jwks, JwksCache, Profile, Verified, VerifyError, VerifyOptions};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct Verifier {
+    jwks_uris: Vec<String>,
+    cache: JwksCache,
+    opts: VerifyOptions,
+    replay: Option<Arc<dyn ReplayStore>>,
+    provider: Option<Arc<dyn KeyProvider>>,
+}
+
+impl std::fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Verifier").field("jwks_uris", &self.jwks_uris).field("cache", &self.cache).field("opts", &self.opts).finish_non_exhaustive()
+    }
+}
+
+impl Verifier {
+    pub fn builder() -> VerifierBuilder { VerifierBuilder::default() }
+
+    /// A verifier for `issuer` with keys from its discovery document; use
+    /// [`VerifierBuilder::discover`] to set an audience or other options too.
+    pub fn from_discovery(issuer: &str) -> Result<Self, VerifyError> { Ok(Self::builder().discover(issuer)?.build()) }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, VerifyError> { self.verify_detailed(token).map(|v| v.claims) }
+
+    pub fn verify_detailed(&self, token: &str) -> Result<Verified, VerifyError> {
+        let verified = verified_claims(self.payload(token)?, &self.opts)?;
+        if let Some(store) = &self.replay { replay::check(store.as_ref(), &verified.claims, &self.opts)?; }
+        Ok(verified)
+    }
+
+    /// Verifies `token` and deserializes its payload into `T`; see [`crate::verify_ed25519_jwt_as`].
+    pub fn verify_as<T: serde::de::DeserializeOwned>(&self, token: &str) -> Result<T, VerifyError> {
+        let payload = self.payload(token)?;
+        let typed = typed_claims(payload.clone(), &self.opts)?;
+        if let Some(store) = &self.replay { replay::check(store.as_ref(), &verified_claims(payload, &self.opts)?.claims, &self.opts)?; }
+        Ok(typed)
+    }
+
+    pub fn options(&self) -> &VerifyOptions { &self.opts }
+    pub fn jwks_uris(&self) -> &[String] { &self.jwks_uris }
+    /// The verifier's own cache, e.g. to seed it with [`JwksCache::put`].
+    pub fn cache(&self) -> &JwksCache { &self.cache }
+
+    fn uris(&self) -> Vec<&str> { self.jwks_uris.iter().map(String::as_str).collect() }
+
+    /// Signature-checked payload, with keys from the provider (cached under the first URI) if one is set.
+    fn payload(&self, token: &str) -> Result<Json, VerifyError> {
+        let Some(provider) = &self.provider else { return verify_signature(token, &self.uris(), &self.cache, &self.opts) };
+        let uri = &self.jwks_uris[0];
+        let fetch = || provider.keys().inspect(|jwks| self.cache.put(uri, jwks.clone()));
+        let load = || self.cache.get_fresh(uri).map_or_else(|| fetch().or_else(|e| self.cache.get_stale(uri).ok_or(e)), Ok);
+        match verify_signature_with(token, &self.opts, load) {
+            Err(VerifyError::NoKey) if self.cache.claim_forced_refetch(uri) => {
+                let jwks = fetch().map_err(|_| VerifyError::NoKey)?;
+                verify_signature_with(token, &self.opts, || Ok(jwks))
+            }
+            res => res,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VerifierBuilder {
+    jwks_uris: Vec<String>,
+    jwks: Option<Jwks>,
+    cache_ttl_secs: i64,
+    min_refetch_secs: Option<i64>,
+    opts: VerifyOptions,
+    replay: Option<Arc<dyn ReplayStore>>,
+    provider: Option<Arc<dyn KeyProvider>>,
+}
+
+impl Default for VerifierBuilder {
+    fn default() -> Self {
+        Self { jwks_uris: Vec::new(), jwks: None, cache_ttl_secs: 300, min_refetch_secs: None, opts: VerifyOptions::default(), replay: None, provider: None }
+    }
+}
+
+impl std::fmt::Debug for VerifierBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VerifierBuilder").field("jwks_uris", &self.jwks_uris).field("cache_ttl_secs", &self.cache_ttl_secs).field("opts", &self.opts).finish_non_exhaustive()
+    }
+}
+
+impl VerifierBuilder {
+    /// Key endpoint; call again to add mirrors, tried in order on a cache miss.
+    pub fn jwks_uri(mut self, uri: &str) -> Self { self.jwks_uris.push(uri.to_string()); self }
+    /// Fixed keys instead of (or seeded under the first of) the JWKS endpoints; never refetched.
+    pub fn jwks(mut self, jwks: Jwks) -> Self { self.jwks = Some(jwks); self }
+    /// Keys from `provider` instead of the JWKS endpoints, cached and refetched like fetched ones.
+    pub fn key_provider(mut self, provider: Arc<dyn KeyProvider>) -> Self { self.provider = Some(provider); self }
+    pub fn issuer(mut self, iss: &str) -> Self { self.opts = self.opts.with_issuer(iss); self }
+    pub fn audience(mut self, aud: &str) -> Self { self.opts = self.opts.with_audience(aud); self }
+    pub fn leeway(mut self, secs: i64) -> Self { self.opts = self.opts.with_leeway(secs); self }
+    /// Profile rules on top of the generic checks, e.g. [`Profile::OAuth2AccessToken`].
+    pub fn profile(mut self, profile: Profile) -> Self { self.opts = self.opts.with_profile(profile); self }
+    /// How long fetched keys are reused; five minutes by default.
+    pub fn cache_ttl(mut self, secs: i64) -> Self { self.cache_ttl_secs = secs; self }
+    /// Refetch right away when a token's key is unknown, at most once per `secs` (e.g. 30). Off by
+    /// default: an unknown key waits for the TTL.
+    pub fn refetch_on_unknown_kid(mut self, secs: i64) -> Self { self.min_refetch_secs = Some(secs); self }
+    /// Issuer and JWKS endpoint from `issuer`'s discovery document; fails if the document names another
+    /// issuer or the provider signs with none of the allowed algorithms.
+    pub fn discover(self, issuer: &str) -> Result<Self, VerifyError> { self.provider(&discovery::metadata(issuer)?) }
+
+    /// Issuer and JWKS endpoint from already fetched provider metadata.
+    pub fn provider(self, metadata: &ProviderMetadata) -> Result<Self, VerifyError> {
+        if !self.opts.allowed_algs.iter().any(|a| metadata.supports_alg(a)) { return Err(VerifyError::alg(&metadata.id_token_signing_alg_values_supported.join(", "), &self.opts.allowed_algs)); }
+        Ok(self.issuer(&metadata.issuer).jwks_uri(&metadata.jwks_uri))
+    }
+
+    /// Starting options for everything the builder has no method for; replaces earlier settings.
+    pub fn options(mut self, opts: VerifyOptions) -> Self { self.opts = opts; self }
+    /// Accept each `jti` only once within its token's lifetime; tokens without `jti` or `exp` are rejected.
+    pub fn replay_store(mut self, store: Arc<dyn ReplayStore>) -> Self { self.replay = Some(store); self }
+
+    pub fn build(self) -> Verifier {
+        let Self { mut jwks_uris, jwks, cache_ttl_secs, min_refetch_secs, opts, replay, provider } = self;
+        if provider.is_some() && jwks_uris.is_empty() { jwks_uris.push("provider://keys".into()); }
+        let cache = match jwks {
+            Some(jwks) => {
+                if jwks_uris.is_empty() { jwks_uris.push("static://jwks".into()); }
+                let cache = JwksCache::new(i64::MAX);
+                cache.put(&jwks_uris[0], jwks);
+                cache
+            }
+            None => JwksCache { min_refetch_secs, ..JwksCache::new(cache_ttl_secs) },
+        };
+        Verifier { jwks_uris, cache, opts, replay, provider }
+    }
+}
+
+/// Verifiers keyed by issuer. The unverified `iss` only selects keys and options; the selected
+/// verifier is pinned to that issuer, so the claim is still checked after the signature.
+#[derive(Debug, Default)]
+pub struct MultiIssuerVerifier { issuers: HashMap<String, Verifier> }
+
+impl MultiIssuerVerifier {
+    pub fn new() -> Self { Self::default() }
+
+    /// Accepts tokens from `iss`, verified with `builder`'s keys and options.
+    pub fn with_issuer(mut self, iss: &str, builder: VerifierBuilder) -> Self { self.issuers.insert(iss.to_string(), builder.issuer(iss).build()); self }
+
+    pub fn issuer(&self, iss: &str) -> Option<&Verifier> { self.issuers.get(iss) }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, VerifyError> { self.route(token)?.verify(token) }
+    pub fn verify_detailed(&self, token: &str) -> Result<Verified, VerifyError> { self.route(token)?.verify_detailed(token) }
+    pub fn verify_as<T: serde::de::DeserializeOwned>(&self, token: &str) -> Result<T, VerifyError> { self.route(token)?.verify_as(token) }
+
+    fn route(&self, token: &str) -> Result<&Verifier, VerifyError> {
+        let (_, claims) = decode_unverified(token)?;
+        claims.iss.and_then(|iss| self.issuers.get(&iss)).ok_or(VerifyError::Issuer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sign_ed25519_jwt, Jwk};
+    use ed25519_dalek::SigningKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn builder_applies_options_and_owns_keys() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(49));
+        let verifier = Verifier::builder()
+            .jwks(Jwks { keys: vec![Jwk::ed25519("k1", &key.verifying_key())] })
+            .issuer("https://id.ubl.agency")
+            .audience("api://orders")
+            .leeway(0)
+            .build();
+        let claims = |v: serde_json::Value| sign_ed25519_jwt(&serde_json::from_value(v).unwrap(), &key, "k1").unwrap();
+
+        let ok = claims(serde_json::json!({"sub":"did:key:zA","iss":"https://id.ubl.agency","aud":"api://orders"}));
+        assert_eq!(verifier.verify(&ok).unwrap().sub, "did:key:zA");
+        let other_aud = claims(serde_json::json!({"sub":"did:key:zA","iss":"https://id.ubl.agency","aud":"api://billing"}));
+        assert!(matches!(verifier.verify(&other_aud), Err(VerifyError::Audience)));
+        assert_eq!(verifier.options().leeway_secs, 0);
+        assert!(matches!(Verifier::builder().build().verify(&ok), Err(VerifyError::NoKey)));
+    }
+
+    #[test]
+    fn provider_metadata_sets_issuer_and_keys() {
+        let metadata = discovery::parse("https://id.ubl.agency", r#"{"issuer":"https://id.ubl.agency","jwks_uri":"https://id.ubl.agency/jwks.json"}"#).unwrap();
+        let verifier = Verifier::builder().provider(&metadata).unwrap().audience("api").build();
+        assert_eq!((verifier.options().issuer.as_deref(), verifier.jwks_uris()), (Some("https://id.ubl.agency"), &["https://id.ubl.agency/jwks.json".to_string()][..]));
+        let rsa_only = ProviderMetadata { id_token_signing_alg_values_supported: vec!["RS256".into()], ..metadata };
+        assert!(matches!(Verifier::builder().provider(&rsa_only), Err(VerifyError::Alg { .. })));
+    }
+
+    #[test]
+    fn replay_store_rejects_reused_jti() {
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(58));
+        let verifier = Verifier::builder().jwks(Jwks { keys: vec![Jwk::ed25519("k1", &key.verifying_key())] }).replay_store(Arc::new(replay::MemoryReplayStore::new())).build();
+        let exp = crate::now_ts() + 60;
+        let sign = |v: serde_json::Value| sign_ed25519_jwt(&serde_json::from_value(v).unwrap(), &key, "k1").unwrap();
+
+        let once = sign(serde_json::json!({"sub":"a","jti":"j1","exp":exp}));
+        assert_eq!(verifier.verify(&once).unwrap().sub, "a");
+        assert!(matches!(verifier.verify(&once), Err(VerifyError::Replayed)));
+        assert!(matches!(verifier.verify_as::<Claims>(&sign(serde_json::json!({"sub":"a","jti":"j1","exp":exp}))), Err(VerifyError::Replayed)));
+        assert!(matches!(verifier.verify(&sign(serde_json::json!({"sub":"a","exp":exp}))), Err(VerifyError::MissingClaim("jti"))));
+    }
+
+    #[test]
+    fn custom_key_providers_are_cached() {
+        struct Counting(Jwks, std::sync::atomic::AtomicUsize);
+        impl KeyProvider for Counting {
+            fn keys(&self) -> Result<Jwks, VerifyError> {
+                self.1.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(self.0.clone())
+            }
+        }
+        let key = SigningKey::generate(&mut StdRng::seed_from_u64(61));
+        let provider = Arc::new(Counting(Jwks { keys: vec![Jwk::ed25519("k1", &key.verifying_key())] }, Default::default()));
+        let verifier = Verifier::builder().key_provider(provider.clone()).build();
+        let token = sign_ed25519_jwt(&serde_json::from_value(serde_json::json!({"sub":"did:key:zP"})).unwrap(), &key, "k1").unwrap();
+        for _ in 0..3 { assert_eq!(verifier.verify(&token).unwrap().sub, "did:key:zP"); }
+        assert_eq!(provider.1.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn tokens_route_by_issuer() {
+        let (a, b) = (SigningKey::generate(&mut StdRng::seed_from_u64(55)), SigningKey::generate(&mut StdRng::seed_from_u64(56)));
+        let jwks = |k: &SigningKey| Jwks { keys: vec![Jwk::ed25519("k1", &k.verifying_key())] };
+        let multi = MultiIssuerVerifier::new()
+            .with_issuer("https://idp-a.example", Verifier::builder().jwks(jwks(&a)).audience("orders"))
+            .with_issuer("https://idp-b.example", Verifier::builder().jwks(jwks(&b)).audience("api://orders"));
+        let sign = |k: &SigningKey, v: serde_json::Value| sign_ed25519_jwt(&serde_json::from_value(v).unwrap(), k, "k1").unwrap();
+
+        assert_eq!(multi.verify(&sign(&a, serde_json::json!({"sub":"a-user","iss":"https://idp-a.example","aud":"orders"}))).unwrap().sub, "a-user");
+        assert_eq!(multi.verify(&sign(&b, serde_json::json!({"sub":"b-user","iss":"https://idp-b.example","aud":"api://orders"}))).unwrap().sub, "b-user");
+        let crossed = sign(&a, serde_json::json!({"sub":"a-user","iss":"https://idp-b.example","aud":"api://orders"}));
+        assert!(matches!(multi.verify(&crossed), Err(VerifyError::Signature)));
+        let unknown = sign(&a, serde_json::json!({"sub":"a-user","iss":"https://idp-c.example"}));
+        assert!(matches!(multi.verify(&unknown), Err(VerifyError::Issuer)));
+    }
+}
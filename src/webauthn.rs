@@ -0,0 +1,261 @@
+//! WebAuthn / passkey assertion verification (feature `webauthn`).
+//!
+//! The browser's `navigator.credentials.get()` result ([`Assertion`], in its JSON form) is checked
+//! against the [`RelyingParty`]: client data type, challenge and origin, the RP ID hash and the
+//! user-present / user-verified flags in the authenticator data, the signature under the stored
+//! COSE public key (ES256 or EdDSA) and the signature counter. [`Passkeys::login`] then mints a
+//! token for the credential's user with `amr: ["webauthn"]`, so passkey login ends in the same
+//! token pipeline as everything else. Registration (attestation) is out of scope: store the
+//! credential id and COSE key your registration flow produced as a [`PasskeyCredential`].
+
+use crate::issuer::{TokenIssuer, TokenRequest};
+use crate::{fill_random, now_ts, SignError, VerifyError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine as _};
+use ciborium::value::{Integer, Value as Cbor};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum WebAuthnError {
+    #[error("webauthn assertion rejected: {0}")]
+    Assertion(String),
+    #[error("unknown passkey credential")]
+    UnknownCredential,
+    #[error("passkey login token not issued: {0}")]
+    Issue(#[from] SignError),
+}
+
+/// A registered passkey.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasskeyCredential {
+    /// Base64url credential id.
+    pub id: String,
+    /// Subject to mint tokens for.
+    pub user: String,
+    /// Base64url COSE_Key from registration.
+    pub public_key: String,
+    #[serde(default)]
+    pub sign_count: u32,
+}
+
+pub trait CredentialStore: Send + Sync {
+    fn get(&self, id: &str) -> Option<PasskeyCredential>;
+    fn set_sign_count(&self, id: &str, count: u32);
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryCredentialStore { inner: Mutex<HashMap<String, PasskeyCredential>> }
+
+impl MemoryCredentialStore {
+    pub fn new() -> Self { Self::default() }
+    pub fn insert(&self, credential: PasskeyCredential) { self.inner.lock().insert(credential.id.clone(), credential); }
+}
+
+impl CredentialStore for MemoryCredentialStore {
+    fn get(&self, id: &str) -> Option<PasskeyCredential> { self.inner.lock().get(id).cloned() }
+    fn set_sign_count(&self, id: &str, count: u32) {
+        if let Some(c) = self.inner.lock().get_mut(id) { c.sign_count = count; }
+    }
+}
+
+/// `PublicKeyCredential.toJSON()` of an authentication ceremony.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assertion {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(default)]
+    pub user_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub rp_id: String,
+    /// Accepted client origins, e.g. `https://app.example.com`.
+    pub origins: Vec<String>,
+    pub require_user_verification: bool,
+}
+
+impl RelyingParty {
+    /// Requires user verification unless changed.
+    pub fn new(rp_id: &str, origin: &str) -> Self { Self { rp_id: rp_id.to_string(), origins: vec![origin.to_string()], require_user_verification: true } }
+    pub fn with_origin(mut self, origin: &str) -> Self { self.origins.push(origin.to_string()); self }
+    pub fn with_user_verification(mut self, required: bool) -> Self { self.require_user_verification = required; self }
+
+    /// Verifies `assertion` for `credential` and the `challenge` issued for this ceremony;
+    /// returns the new signature counter.
+    pub fn verify(&self, assertion: &Assertion, challenge: &[u8], credential: &PasskeyCredential) -> Result<u32, VerifyError> {
+        if assertion.id != credential.id { return Err(fail("credential id mismatch")); }
+        let decode = |s: &str| B64URL.decode(s.trim_end_matches('=')).map_err(|_| VerifyError::Base64);
+        let client_data_json = decode(&assertion.response.client_data_json)?;
+        let auth_data = decode(&assertion.response.authenticator_data)?;
+        let signature = decode(&assertion.response.signature)?;
+
+        let client_data: Json = serde_json::from_slice(&client_data_json).map_err(|_| VerifyError::Json)?;
+        if client_data["type"] != "webauthn.get" { return Err(fail("not an authentication ceremony")); }
+        let sent = client_data["challenge"].as_str().and_then(|c| B64URL.decode(c).ok());
+        if sent.as_deref() != Some(challenge) { return Err(fail("challenge mismatch")); }
+        if !client_data["origin"].as_str().is_some_and(|o| self.origins.iter().any(|a| a == o)) { return Err(fail("origin not allowed")); }
+
+        if auth_data.len() < 37 { return Err(fail("authenticator data too short")); }
+        if auth_data[..32] != Sha256::digest(self.rp_id.as_bytes())[..] { return Err(fail("rp id mismatch")); }
+        let flags = auth_data[32];
+        if flags & FLAG_USER_PRESENT == 0 { return Err(fail("user not present")); }
+        if self.require_user_verification && flags & FLAG_USER_VERIFIED == 0 { return Err(fail("user not verified")); }
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data_json));
+        CoseKey::parse(&decode(&credential.public_key)?)?.verify(&signed, &signature)?;
+
+        let count = u32::from_be_bytes([auth_data[33], auth_data[34], auth_data[35], auth_data[36]]);
+        if (count != 0 || credential.sign_count != 0) && count <= credential.sign_count { return Err(fail("signature counter did not increase (cloned authenticator?)")); }
+        Ok(count)
+    }
+}
+
+/// A fresh random challenge for `navigator.credentials.get()`.
+pub fn new_challenge() -> Vec<u8> {
+    let mut challenge = vec![0u8; 32];
+    fill_random(&mut challenge);
+    challenge
+}
+
+enum CoseKey {
+    Es256(p256::ecdsa::VerifyingKey),
+    EdDsa(ed25519_dalek::VerifyingKey),
+}
+
+impl CoseKey {
+    fn parse(bytes: &[u8]) -> Result<Self, VerifyError> {
+        let map = match ciborium::de::from_reader(bytes).map_err(|_| fail("malformed COSE key"))? {
+            Cbor::Map(m) => m,
+            _ => return Err(fail("malformed COSE key")),
+        };
+        let get = |label: i64| map.iter().find(|(k, _)| k.as_integer() == Some(Integer::from(label))).map(|(_, v)| v);
+        let int = |label: i64| get(label).and_then(Cbor::as_integer).and_then(|i| i64::try_from(i).ok());
+        let bytes = |label: i64| get(label).and_then(Cbor::as_bytes).ok_or_else(|| fail("COSE key coordinate missing"));
+        match (int(1), int(3), int(-1)) {
+            (Some(2), Some(-7), Some(1)) => {
+                let point = [&[0x04][..], bytes(-2)?, bytes(-3)?].concat();
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(&point).map(CoseKey::Es256).map_err(|_| fail("invalid P-256 key"))
+            }
+            (Some(1), Some(-8), Some(6)) => {
+                let x: [u8; 32] = bytes(-2)?.as_slice().try_into().map_err(|_| fail("invalid Ed25519 key"))?;
+                ed25519_dalek::VerifyingKey::from_bytes(&x).map(CoseKey::EdDsa).map_err(|_| fail("invalid Ed25519 key"))
+            }
+            _ => Err(fail("unsupported COSE key (expected ES256 or EdDSA)")),
+        }
+    }
+
+    fn verify(&self, msg: &[u8], sig: &[u8]) -> Result<(), VerifyError> {
+        match self {
+            CoseKey::Es256(key) => {
+                use p256::ecdsa::signature::Verifier;
+                let sig = p256::ecdsa::Signature::from_der(sig).map_err(|_| VerifyError::Signature)?;
+                key.verify(msg, &sig).map_err(|_| VerifyError::Signature)
+            }
+            CoseKey::EdDsa(key) => {
+                let sig = ed25519_dalek::Signature::from_slice(sig).map_err(|_| VerifyError::Signature)?;
+                key.verify_strict(msg, &sig).map_err(|_| VerifyError::Signature)
+            }
+        }
+    }
+}
+
+/// Passkey login: verification against a [`CredentialStore`] plus token minting.
+#[derive(Debug)]
+pub struct Passkeys<S: CredentialStore> {
+    pub rp: RelyingParty,
+    store: S,
+    issuer: TokenIssuer,
+}
+
+impl<S: CredentialStore> Passkeys<S> {
+    pub fn new(rp: RelyingParty, store: S, issuer: TokenIssuer) -> Self { Self { rp, store, issuer } }
+
+    /// Verifies `assertion`, advances the stored counter and returns the credential.
+    pub fn authenticate(&self, assertion: &Assertion, challenge: &[u8]) -> Result<PasskeyCredential, VerifyError> {
+        let credential = self.store.get(&assertion.id).ok_or(WebAuthnError::UnknownCredential)?;
+        let count = self.rp.verify(assertion, challenge, &credential)?;
+        self.store.set_sign_count(&credential.id, count);
+        Ok(credential)
+    }
+
+    /// Authenticates and mints a token for the credential's user with `amr: ["webauthn"]`.
+    pub fn login(&self, assertion: &Assertion, challenge: &[u8], scope: Option<&str>) -> Result<String, VerifyError> {
+        let credential = self.authenticate(assertion, challenge)?;
+        let claims = HashMap::from([("amr".to_string(), serde_json::json!(["webauthn"])), ("auth_time".to_string(), Json::from(now_ts()))]);
+        let req = TokenRequest { sub: credential.user, aud: None, scope: scope.map(str::to_string), ttl_secs: None, claims };
+        Ok(self.issuer.mint(&req).map_err(WebAuthnError::Issue)?)
+    }
+}
+
+fn fail(why: &str) -> VerifyError { WebAuthnError::Assertion(why.to_string()).into() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verify_ed25519_jwt_with_cache, JwksCache, VerifyOptions};
+    use p256::ecdsa::{signature::Signer, DerSignature, SigningKey};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn cose_es256(key: &SigningKey) -> String {
+        let point = key.verifying_key().to_encoded_point(false);
+        let entries = [(1, Cbor::from(2)), (3, Cbor::from(-7)), (-1, Cbor::from(1)), (-2, Cbor::Bytes(point.x().unwrap().to_vec())), (-3, Cbor::Bytes(point.y().unwrap().to_vec()))];
+        let mut out = Vec::new();
+        ciborium::ser::into_writer(&Cbor::Map(entries.into_iter().map(|(k, v)| (Cbor::from(k), v)).collect()), &mut out).unwrap();
+        B64URL.encode(out)
+    }
+
+    fn assertion(key: &SigningKey, challenge: &[u8], origin: &str, count: u32) -> Assertion {
+        let client_data = serde_json::json!({"type":"webauthn.get","challenge":B64URL.encode(challenge),"origin":origin}).to_string();
+        let mut auth_data = Sha256::digest(b"example.com").to_vec();
+        auth_data.push(FLAG_USER_PRESENT | FLAG_USER_VERIFIED);
+        auth_data.extend_from_slice(&count.to_be_bytes());
+        let signed = [&auth_data[..], &Sha256::digest(client_data.as_bytes())[..]].concat();
+        let sig: DerSignature = key.sign(&signed);
+        let response = AssertionResponse {
+            client_data_json: B64URL.encode(&client_data),
+            authenticator_data: B64URL.encode(&auth_data),
+            signature: B64URL.encode(sig.as_bytes()),
+            user_handle: None,
+        };
+        Assertion { id: "cred-1".into(), response }
+    }
+
+    #[test]
+    fn es256_assertion_logs_in_and_rejects_replays() {
+        let key = SigningKey::random(&mut StdRng::seed_from_u64(37));
+        let store = MemoryCredentialStore::new();
+        store.insert(PasskeyCredential { id: "cred-1".into(), user: "user-42".into(), public_key: cose_es256(&key), sign_count: 4 });
+        let issuer = TokenIssuer::new("https://issuer.internal", "k1", ed25519_dalek::SigningKey::generate(&mut StdRng::seed_from_u64(38)));
+        let cache = JwksCache::new(3600);
+        cache.put("mem://issuer", issuer.jwks());
+        let passkeys = Passkeys::new(RelyingParty::new("example.com", "https://app.example.com"), store, issuer);
+
+        let challenge = new_challenge();
+        let ok = assertion(&key, &challenge, "https://app.example.com", 5);
+        let claims = verify_ed25519_jwt_with_cache(&passkeys.login(&ok, &challenge, None).unwrap(), "mem://issuer", &cache, &VerifyOptions::default()).unwrap();
+        assert_eq!((claims.sub.as_str(), &claims.extra["amr"]), ("user-42", &serde_json::json!(["webauthn"])));
+
+        assert!(matches!(passkeys.authenticate(&ok, &challenge), Err(VerifyError::WebAuthn(_))));
+        assert!(matches!(passkeys.authenticate(&assertion(&key, &challenge, "https://evil.example", 6), &challenge), Err(VerifyError::WebAuthn(_))));
+        assert!(matches!(passkeys.authenticate(&assertion(&key, &challenge, "https://app.example.com", 6), b"other"), Err(VerifyError::WebAuthn(_))));
+        let forged = assertion(&SigningKey::random(&mut StdRng::seed_from_u64(39)), &challenge, "https://app.example.com", 7);
+        assert!(matches!(passkeys.authenticate(&forged, &challenge), Err(VerifyError::Signature)));
+    }
+}
@@ -0,0 +1,70 @@
+//! Stripe-compatible HMAC-SHA256 webhook signatures in the `t=<unix>,v1=<hex>` header style (feature `webhook`).
+//!
+//! The signed message is `"{t}.{body}"`. Several `v1` entries may be present during secret rotation;
+//! any one matching is enough. Comparison is constant-time.
+
+use crate::{check_timestamp, from_hex, now_ts, to_hex, VerifyError};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Default tolerance between the header timestamp and now.
+pub const DEFAULT_TOLERANCE_SECS: i64 = 300;
+
+/// Produces the signature header value for `body` at `timestamp`.
+pub fn sign_webhook(secret: &[u8], body: &[u8], timestamp: i64) -> String {
+    let tag = mac_for(secret, body, timestamp).finalize().into_bytes();
+    format!("t={},v1={}", timestamp, to_hex(&tag))
+}
+
+/// Verifies a signature header against `body`, rejecting timestamps further than `tolerance_secs` from `now`.
+pub fn verify_webhook(secret: &[u8], header: &str, body: &[u8], tolerance_secs: i64, now: Option<i64>) -> Result<i64, VerifyError> {
+    let mut timestamp = None;
+    let mut candidates = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", v)) => timestamp = Some(v.parse::<i64>().map_err(|_| VerifyError::BadFormat)?),
+            Some(("v1", v)) => candidates.push(v),
+            _ => {}
+        }
+    }
+    let t = timestamp.ok_or(VerifyError::BadFormat)?;
+    if candidates.is_empty() { return Err(VerifyError::BadFormat); }
+
+    // MAC first: the timestamp is only meaningful once it is known to be the sender's.
+    let matched = candidates.iter().filter_map(|c| from_hex(c)).any(|tag| mac_for(secret, body, t).verify_slice(&tag).is_ok());
+    if !matched { return Err(VerifyError::Signature); }
+    check_timestamp(t, now.unwrap_or_else(now_ts), tolerance_secs)?;
+    Ok(t)
+}
+
+fn mac_for(secret: &[u8], body: &[u8], timestamp: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("hmac accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify_with_tolerance_and_rotation() {
+        let body = br#"{"event":"ledger.append"}"#;
+        let header = sign_webhook(b"whsec_new", body, 1_700_000_000);
+        assert_eq!(verify_webhook(b"whsec_new", &header, body, 300, Some(1_700_000_100)).unwrap(), 1_700_000_000);
+        assert!(matches!(verify_webhook(b"whsec_new", &header, b"{}", 300, Some(1_700_000_100)), Err(VerifyError::Signature)));
+        assert!(matches!(verify_webhook(b"whsec_new", &header, body, 300, Some(1_700_001_000)), Err(VerifyError::Expired)));
+
+        let old = sign_webhook(b"whsec_old", body, 1_700_000_000);
+        let rotated = format!("{},{}", header, old.split(',').nth(1).unwrap());
+        assert!(verify_webhook(b"whsec_old", &rotated, body, 300, Some(1_700_000_000)).is_ok());
+        assert!(matches!(verify_webhook(b"k", "v1=00", body, 300, None), Err(VerifyError::BadFormat)));
+        assert!(matches!(verify_webhook(b"whsec_new", "t=-9223372036854775808,v1=00", body, 300, None), Err(VerifyError::Signature)));
+        let ancient = sign_webhook(b"whsec_new", body, i64::MIN);
+        assert!(matches!(verify_webhook(b"whsec_new", &ancient, body, 300, Some(1_700_000_000)), Err(VerifyError::Expired)));
+    }
+}